@@ -0,0 +1,29 @@
+#!/usr/bin/env bash
+
+# Script to check the crank on a local solana-test-validator, given the stake
+# pool keyfile and a path to append the crank's health metrics to. It waits
+# for the next epoch, then runs one crank round, which fails if the pool is
+# not updated.
+
+cd "$(dirname "$0")" || exit
+stake_pool_keyfile=$1
+metrics_file=$2
+
+spl_stake_pool=spl-stake-pool
+# Uncomment to use a local build
+#spl_stake_pool=../../../target/debug/spl-stake-pool
+
+wait_for_next_epoch () {
+  current_epoch=$(solana epoch)
+  echo "Waiting for the end of epoch $current_epoch"
+  while test "$(solana epoch)" = "$current_epoch"
+  do
+    sleep 2
+  done
+}
+
+stake_pool_pubkey=$(solana-keygen pubkey "$stake_pool_keyfile")
+wait_for_next_epoch
+echo "Running one crank round on the stake pool"
+$spl_stake_pool crank --once --metrics-file "$metrics_file" "$stake_pool_pubkey" || exit
+tail -n 1 "$metrics_file"
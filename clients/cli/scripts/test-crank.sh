@@ -0,0 +1,85 @@
+#!/usr/bin/env bash
+
+# Script to test the crank end to end with the locally built program and CLI.
+# It starts a local solana-test-validator, creates a stake pool delegating to
+# the test validator, then runs crank.sh, which fails unless one crank round
+# updates the pool after the next epoch starts.
+
+cd "$(dirname "$0")" || exit
+set -e
+
+keys_dir=keys
+ledger_dir=$keys_dir/test-ledger
+metrics_file=$keys_dir/crank-metrics.jsonl
+# crank.sh runs whatever spl-stake-pool comes first in the path
+PATH="$(readlink -f ../../../target/debug):$PATH"
+export PATH
+
+create_keypair () {
+  if test ! -f "$1"
+  then
+    solana-keygen new --no-passphrase -s -o "$1"
+  fi
+}
+
+setup_test_validator () {
+  solana-test-validator \
+    --bpf-program SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy ../../../target/deploy/spl_stake_pool.so \
+    --slots-per-epoch 32 \
+    --ledger "$ledger_dir" \
+    --quiet --reset &
+  pid=$!
+  trap 'kill $pid' EXIT
+  solana config set --url http://127.0.0.1:8899
+  solana config set --commitment confirmed
+  echo "waiting for solana-test-validator, pid: $pid"
+  until solana slot > /dev/null 2>&1
+  do
+    sleep 1
+  done
+}
+
+mkdir -p $keys_dir
+rm -f "$metrics_file"
+create_keypair "$HOME/.config/solana/id.json"
+
+echo "Setting up local test validator"
+setup_test_validator
+solana airdrop 100
+
+echo "Creating pool"
+stake_pool_keyfile=$keys_dir/stake-pool.json
+validator_list_keyfile=$keys_dir/validator-list.json
+mint_keyfile=$keys_dir/mint.json
+reserve_keyfile=$keys_dir/reserve.json
+rm -f "$stake_pool_keyfile" "$validator_list_keyfile" "$mint_keyfile" "$reserve_keyfile"
+create_keypair $stake_pool_keyfile
+create_keypair $validator_list_keyfile
+create_keypair $mint_keyfile
+create_keypair $reserve_keyfile
+spl-stake-pool \
+  create-pool \
+  --epoch-fee-numerator 1 \
+  --epoch-fee-denominator 100 \
+  --withdrawal-fee-numerator 2 \
+  --withdrawal-fee-denominator 100 \
+  --max-validators 10 \
+  --pool-keypair "$stake_pool_keyfile" \
+  --validator-list-keypair "$validator_list_keyfile" \
+  --mint-keypair "$mint_keyfile" \
+  --reserve-keypair "$reserve_keyfile"
+stake_pool_pubkey=$(solana-keygen pubkey "$stake_pool_keyfile")
+
+echo "Delegating part of the pool to the test validator"
+vote_pubkey=$(solana-keygen pubkey "$ledger_dir/vote-account-keypair.json")
+spl-stake-pool add-validator "$stake_pool_pubkey" "$vote_pubkey"
+spl-stake-pool deposit-sol "$stake_pool_pubkey" 10
+spl-stake-pool increase-validator-stake "$stake_pool_pubkey" "$vote_pubkey" 5
+
+./crank.sh "$stake_pool_keyfile" "$metrics_file"
+if ! tail -n 1 "$metrics_file" | grep -q '"healthy":true'
+then
+  echo "The crank did not report the stake pool as healthy"
+  exit 1
+fi
+echo "The crank updated the stake pool after the epoch rollover"
@@ -1,25 +1,24 @@
 use {
-    bincode::deserialize,
     solana_account_decoder::UiAccountEncoding,
     solana_client::{
         client_error::ClientError,
         rpc_client::RpcClient,
         rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
-        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+        rpc_filter::{Memcmp, RpcFilterType},
     },
     solana_compute_budget_interface::ComputeBudgetInstruction,
     solana_message::Message,
     solana_program::{
-        borsh1::try_from_slice_unchecked, hash::Hash, instruction::Instruction, program_pack::Pack,
-        pubkey::Pubkey,
+        borsh1::try_from_slice_unchecked, hash::Hash, instruction::Instruction, pubkey::Pubkey,
     },
     solana_sdk::transaction::Transaction,
     solana_stake_interface as stake,
     spl_stake_pool::{
         find_withdraw_authority_program_address,
-        state::{StakePool, ValidatorList},
+        state::{AccountType, StakePool, ValidatorList},
     },
-    std::collections::HashSet,
+    spl_stake_pool_client::accounts,
+    std::{collections::HashSet, future::Future},
 };
 
 pub(crate) type Error = Box<dyn std::error::Error>;
@@ -28,20 +27,17 @@ pub fn get_stake_pool(
     rpc_client: &RpcClient,
     stake_pool_address: &Pubkey,
 ) -> Result<StakePool, Error> {
-    let account_data = rpc_client.get_account_data(stake_pool_address)?;
-    let stake_pool = try_from_slice_unchecked::<StakePool>(account_data.as_slice())
-        .map_err(|err| format!("Invalid stake pool {}: {}", stake_pool_address, err))?;
-    Ok(stake_pool)
+    block_on(accounts::get_stake_pool(rpc_client, stake_pool_address))
 }
 
 pub fn get_validator_list(
     rpc_client: &RpcClient,
     validator_list_address: &Pubkey,
 ) -> Result<ValidatorList, Error> {
-    let account_data = rpc_client.get_account_data(validator_list_address)?;
-    let validator_list = try_from_slice_unchecked::<ValidatorList>(account_data.as_slice())
-        .map_err(|err| format!("Invalid validator list {}: {}", validator_list_address, err))?;
-    Ok(validator_list)
+    block_on(accounts::get_validator_list(
+        rpc_client,
+        validator_list_address,
+    ))
 }
 
 pub fn get_token_account(
@@ -49,42 +45,25 @@ pub fn get_token_account(
     token_account_address: &Pubkey,
     expected_token_mint: &Pubkey,
 ) -> Result<spl_token_2022_interface::state::Account, Error> {
-    let account_data = rpc_client.get_account_data(token_account_address)?;
-    let token_account =
-        spl_token_2022_interface::state::Account::unpack_from_slice(account_data.as_slice())
-            .map_err(|err| format!("Invalid token account {}: {}", token_account_address, err))?;
-
-    if token_account.mint != *expected_token_mint {
-        Err(format!(
-            "Invalid token mint for {}, expected mint is {}",
-            token_account_address, expected_token_mint
-        )
-        .into())
-    } else {
-        Ok(token_account)
-    }
+    block_on(accounts::get_token_account(
+        rpc_client,
+        token_account_address,
+        expected_token_mint,
+    ))
 }
 
 pub fn get_token_mint(
     rpc_client: &RpcClient,
     token_mint_address: &Pubkey,
 ) -> Result<spl_token_2022_interface::state::Mint, Error> {
-    let account_data = rpc_client.get_account_data(token_mint_address)?;
-    let token_mint =
-        spl_token_2022_interface::state::Mint::unpack_from_slice(account_data.as_slice())
-            .map_err(|err| format!("Invalid token mint {}: {}", token_mint_address, err))?;
-
-    Ok(token_mint)
+    block_on(accounts::get_token_mint(rpc_client, token_mint_address))
 }
 
 pub(crate) fn get_stake_state(
     rpc_client: &RpcClient,
     stake_address: &Pubkey,
 ) -> Result<stake::state::StakeStateV2, Error> {
-    let account_data = rpc_client.get_account_data(stake_address)?;
-    let stake_state = deserialize(account_data.as_slice())
-        .map_err(|err| format!("Invalid stake account {}: {}", stake_address, err))?;
-    Ok(stake_state)
+    block_on(accounts::get_stake_state(rpc_client, stake_address))
 }
 
 #[allow(clippy::result_large_err)]
@@ -92,14 +71,15 @@ pub(crate) fn get_stake_pools(
     rpc_client: &RpcClient,
     stake_pool_program_id: &Pubkey,
 ) -> Result<Vec<(Pubkey, StakePool, ValidatorList, Pubkey)>, ClientError> {
-    rpc_client
-        .get_program_ui_accounts_with_config(
+    let mut stake_pools = vec![];
+    for account_type in [AccountType::StakePool, AccountType::StakePoolV2] {
+        let accounts = rpc_client.get_program_ui_accounts_with_config(
             stake_pool_program_id,
             RpcProgramAccountsConfig {
                 // 0 is the account type
-                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new(
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
                     0,
-                    MemcmpEncodedBytes::Base58("2".to_string()),
+                    vec![account_type as u8],
                 ))]),
                 account_config: RpcAccountInfoConfig {
                     encoding: Some(UiAccountEncoding::Base64),
@@ -107,30 +87,25 @@ pub(crate) fn get_stake_pools(
                 },
                 ..RpcProgramAccountsConfig::default()
             },
-        )
-        .map(|accounts| {
-            accounts
-                .into_iter()
-                .filter_map(|(address, account)| {
-                    let pool_withdraw_authority =
-                        find_withdraw_authority_program_address(stake_pool_program_id, &address).0;
-                    let data = account.data.decode()?;
-                    match try_from_slice_unchecked::<StakePool>(&data) {
-                        Ok(stake_pool) => {
-                            get_validator_list(rpc_client, &stake_pool.validator_list)
-                                .map(|validator_list| {
-                                    (address, stake_pool, validator_list, pool_withdraw_authority)
-                                })
-                                .ok()
-                        }
-                        Err(err) => {
-                            eprintln!("Invalid stake pool data for {}: {}", address, err);
-                            None
-                        }
-                    }
-                })
-                .collect()
-        })
+        )?;
+        stake_pools.extend(accounts.into_iter().filter_map(|(address, account)| {
+            let pool_withdraw_authority =
+                find_withdraw_authority_program_address(stake_pool_program_id, &address).0;
+            let data = account.data.decode()?;
+            match try_from_slice_unchecked::<StakePool>(&data) {
+                Ok(stake_pool) => get_validator_list(rpc_client, &stake_pool.validator_list)
+                    .map(|validator_list| {
+                        (address, stake_pool, validator_list, pool_withdraw_authority)
+                    })
+                    .ok(),
+                Err(err) => {
+                    eprintln!("Invalid stake pool data for {}: {}", address, err);
+                    None
+                }
+            }
+        }));
+    }
+    Ok(stake_pools)
 }
 
 #[allow(clippy::result_large_err)]
@@ -191,3 +166,11 @@ pub(crate) fn add_compute_unit_limit_from_simulation(
         .data = ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit).data;
     Ok(())
 }
+
+/// Wait for the result of the stake pool client, which runs on top of the
+/// blocking RPC client
+pub(crate) fn block_on<T>(
+    future: impl Future<Output = Result<T, spl_stake_pool_client::Error>>,
+) -> Result<T, Error> {
+    futures::executor::block_on(future).map_err(|err| err as Error)
+}
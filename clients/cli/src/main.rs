@@ -1,12 +1,19 @@
 #![allow(clippy::arithmetic_side_effects)]
 mod client;
 mod output;
+mod pool_config;
 
 use {
     crate::{
         client::*,
-        output::{CliStakePool, CliStakePoolDetails, CliStakePoolStakeAccountInfo, CliStakePools},
+        output::{
+            CliAudit, CliAuditIssue, CliCrankMetrics, CliPoolChange, CliPoolChanges, CliQuote,
+            CliQuoteStakeAccount, CliStakePool, CliStakePoolDetails, CliStakePoolStakeAccountInfo,
+            CliStakePools, CliValidatorChange, CliValidatorChanges, CliValidatorExport,
+        },
+        pool_config::read_pool_config_file,
     },
+    base64::{prelude::BASE64_STANDARD, Engine},
     bincode::deserialize,
     clap::{
         crate_description, crate_name, crate_version, value_t, value_t_or_exit, App, AppSettings,
@@ -14,20 +21,25 @@ use {
     },
     solana_clap_utils::{
         compute_unit_price::{compute_unit_price_arg, COMPUTE_UNIT_PRICE_ARG},
-        input_parsers::{keypair_of, pubkey_of},
+        input_parsers::{keypair_of, pubkey_of, pubkeys_of, pubkeys_sigs_of, value_of},
         input_validators::{
-            is_amount, is_keypair_or_ask_keyword, is_parsable, is_pubkey, is_url,
+            is_amount, is_keypair_or_ask_keyword, is_parsable, is_pubkey, is_pubkey_sig, is_url,
             is_valid_percentage, is_valid_pubkey, is_valid_signer,
         },
         keypair::{signer_from_path_with_config, SignerFromPathConfig},
+        offline::{
+            blockhash_arg, dump_transaction_message, sign_only_arg, BLOCKHASH_ARG,
+            DUMP_TRANSACTION_MESSAGE, SIGNER_ARG, SIGN_ONLY_ARG,
+        },
         ArgConstant,
     },
-    solana_cli_output::OutputFormat,
+    solana_cli_output::{return_signers_data, OutputFormat, ReturnSignersConfig},
     solana_client::rpc_client::RpcClient,
     solana_commitment_config::CommitmentConfig,
     solana_compute_budget_interface::ComputeBudgetInstruction,
+    solana_packet::PACKET_DATA_SIZE,
     solana_program::{
-        borsh1::{get_instance_packed_len, get_packed_len},
+        borsh1::{get_instance_packed_len, try_from_slice_unchecked},
         instruction::Instruction,
         program_pack::Pack,
         pubkey::Pubkey,
@@ -37,28 +49,48 @@ use {
         hash::Hash,
         message::Message,
         native_token::{self, Sol},
-        signature::{Keypair, Signer},
+        signature::{Keypair, Signature, Signer},
         signers::Signers,
         transaction::Transaction,
     },
     solana_stake_interface as stake,
     solana_system_interface::instruction as system_instruction,
-    spl_associated_token_account_interface::{
-        address::get_associated_token_address_with_program_id,
-        instruction::create_associated_token_account,
-    },
+    spl_associated_token_account_interface::address::get_associated_token_address_with_program_id,
     spl_stake_pool::{
-        self, find_stake_program_address, find_transient_stake_program_address,
-        find_withdraw_authority_program_address,
+        self,
+        error::StakePoolError,
+        find_deposit_authority_program_address, find_stake_program_address,
+        find_transient_stake_program_address, find_withdraw_authority_program_address,
         instruction::{FundingType, PreferredValidatorType},
-        minimum_delegation,
-        state::{Fee, FeeType, StakePool, ValidatorList, ValidatorStakeInfo},
-        MINIMUM_RESERVE_LAMPORTS,
+        merkle,
+        state::{AccountType, Fee, FeeType, StakePool, StakeStatus, ValidatorList},
+        MAX_TRANSFER_HOOK_PROGRAMS, MAX_VALIDATORS_IN_POOL, MINIMUM_RESERVE_LAMPORTS,
+    },
+    spl_stake_pool_client::{
+        audit::AuditIssue,
+        depositor_merkle_tree,
+        pool_config::{configured_fee, PoolChange, PoolConfig},
+        rebalance::{RebalanceAction, SkipReason},
+        withdraw::{PoolStakeAccount, WithdrawSource},
+        StakePoolClient, UpdateInstructions,
     },
     spl_token_2022_interface::{
-        check_spl_token_program_account, extension::StateWithExtensions, state::Mint,
+        check_spl_token_program_account,
+        extension::{
+            metadata_pointer::MetadataPointer, BaseStateWithExtensions, StateWithExtensions,
+        },
+        state::Mint,
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        fs::OpenOptions,
+        io::Write,
+        num::NonZeroU32,
+        process::exit,
+        rc::Rc,
+        thread,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     },
-    std::{cmp::Ordering, num::NonZeroU32, process::exit, rc::Rc},
 };
 
 pub(crate) struct Config {
@@ -75,12 +107,44 @@ pub(crate) struct Config {
     no_update: bool,
     compute_unit_price: Option<u64>,
     compute_unit_limit: ComputeUnitLimit,
+    sign_only: bool,
+    blockhash: Option<Hash>,
+    dump_transaction_message: Option<MessageEncoding>,
+    presigners: Vec<(Pubkey, Signature)>,
+}
+
+/// Encoding of the transaction messages printed in sign-only mode
+#[derive(Clone, Copy)]
+enum MessageEncoding {
+    Base58,
+    Base64,
+}
+
+impl MessageEncoding {
+    fn encode(self, message_data: &[u8]) -> String {
+        match self {
+            Self::Base58 => bs58::encode(message_data).into_string(),
+            Self::Base64 => BASE64_STANDARD.encode(message_data),
+        }
+    }
+}
+
+impl Config {
+    fn stake_pool_client(&self) -> StakePoolClient<&RpcClient> {
+        StakePoolClient::new(&self.rpc_client, self.stake_pool_program_id)
+    }
 }
 
 type CommandResult = Result<(), Error>;
 
 const STAKE_STATE_LEN: usize = 200;
 
+/// Number of `ResizeValidatorList` instructions packed into one transaction
+const RESIZE_INSTRUCTIONS_PER_TRANSACTION: usize = 8;
+
+/// How long the crank waits for its update transactions to be confirmed
+const CRANK_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+
 macro_rules! unique_signers {
     ($vec:ident) => {
         $vec.sort_by_key(|l| l.pubkey());
@@ -203,12 +267,29 @@ fn get_latest_blockhash(client: &RpcClient) -> Result<Hash, Error> {
         .0)
 }
 
+/// Print the signatures of a transaction, and its message if requested,
+/// instead of sending it
+fn print_sign_only_transaction(config: &Config, transaction: &Transaction) {
+    let mut sign_only_data = return_signers_data(
+        transaction,
+        &ReturnSignersConfig {
+            dump_transaction_message: false,
+        },
+    );
+    sign_only_data.message = config
+        .dump_transaction_message
+        .map(|encoding| encoding.encode(&transaction.message_data()));
+    println!("{}", config.output_format.formatted_string(&sign_only_data));
+}
+
 #[allow(clippy::result_large_err)]
 fn send_transaction_no_wait(
     config: &Config,
     transaction: Transaction,
 ) -> solana_client::client_error::Result<()> {
-    if config.dry_run {
+    if config.sign_only {
+        print_sign_only_transaction(config, &transaction);
+    } else if config.dry_run {
         let result = config.rpc_client.simulate_transaction(&transaction)?;
         println!("Simulate result: {:?}", result);
     } else {
@@ -223,7 +304,9 @@ fn send_transaction(
     config: &Config,
     transaction: Transaction,
 ) -> solana_client::client_error::Result<()> {
-    if config.dry_run {
+    if config.sign_only {
+        print_sign_only_transaction(config, &transaction);
+    } else if config.dry_run {
         let result = config.rpc_client.simulate_transaction(&transaction)?;
         println!("Simulate result: {:?}", result);
     } else {
@@ -241,7 +324,10 @@ fn checked_transaction_with_signers_and_additional_fee<T: Signers>(
     signers: &T,
     additional_fee: u64,
 ) -> Result<Transaction, Error> {
-    let recent_blockhash = get_latest_blockhash(&config.rpc_client)?;
+    let recent_blockhash = match config.blockhash {
+        Some(blockhash) => blockhash,
+        None => get_latest_blockhash(&config.rpc_client)?,
+    };
     let mut instructions = instructions.to_vec();
     if let Some(compute_unit_price) = config.compute_unit_price {
         instructions.push(ComputeBudgetInstruction::set_compute_unit_price(
@@ -269,14 +355,43 @@ fn checked_transaction_with_signers_and_additional_fee<T: Signers>(
         Some(&config.fee_payer.pubkey()),
         &recent_blockhash,
     );
-    check_fee_payer_balance(
-        config,
-        additional_fee.saturating_add(config.rpc_client.get_fee_for_message(&message)?),
-    )?;
-    let transaction = Transaction::new(signers, message, recent_blockhash);
+    let mut transaction = Transaction::new_unsigned(message);
+    if !config.sign_only {
+        check_fee_payer_balance(
+            config,
+            additional_fee.saturating_add(
+                config
+                    .rpc_client
+                    .get_fee_for_message(&transaction.message)?,
+            ),
+        )?;
+    }
+    transaction.try_partial_sign(signers, recent_blockhash)?;
+    add_presigner_signatures(config, &mut transaction)?;
+    // in sign-only mode, signers missing from this side are left for others
+    // to sign
+    if !config.sign_only && !transaction.is_signed() {
+        return Err("Transaction is missing signatures, pass them with --signer".into());
+    }
     Ok(transaction)
 }
 
+/// Add the signatures given with `--signer` to a transaction, for the signers
+/// among them that it requires
+fn add_presigner_signatures(config: &Config, transaction: &mut Transaction) -> Result<(), Error> {
+    let message_data = transaction.message_data();
+    for (pubkey, signature) in &config.presigners {
+        let Some(index) = transaction.get_signing_keypair_positions(&[*pubkey])?[0] else {
+            continue;
+        };
+        if !signature.verify(pubkey.as_ref(), &message_data) {
+            return Err(format!("Invalid signature from --signer {}", pubkey).into());
+        }
+        transaction.signatures[index] = *signature;
+    }
+    Ok(())
+}
+
 fn checked_transaction_with_signers<T: Signers>(
     config: &Config,
     instructions: &[Instruction],
@@ -481,7 +596,7 @@ fn setup_pool_fee_account(
         &config.manager.pubkey(),
         &mut instructions,
         total_rent_free_balances,
-    );
+    )?;
 
     println!("Creating pool fee collection account {}", pool_fee_account);
 
@@ -518,7 +633,7 @@ fn setup_and_initialize_validator_list_with_stake_pool(
 
     let stake_pool_account_lamports = config
         .rpc_client
-        .get_minimum_balance_for_rent_exemption(get_packed_len::<StakePool>())?;
+        .get_minimum_balance_for_rent_exemption(StakePool::account_len())?;
 
     let mut instructions = vec![];
     let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
@@ -569,7 +684,7 @@ fn setup_and_initialize_validator_list_with_stake_pool(
             &config.fee_payer.pubkey(),
             &stake_pool_keypair.pubkey(),
             stake_pool_account_lamports,
-            get_packed_len::<StakePool>() as u64,
+            StakePool::account_len() as u64,
             &config.stake_pool_program_id,
         ));
     }
@@ -650,7 +765,7 @@ fn command_create_pool(
         .get_minimum_balance_for_rent_exemption(spl_token_2022_interface::state::Account::LEN)?;
     let stake_pool_account_lamports = config
         .rpc_client
-        .get_minimum_balance_for_rent_exemption(get_packed_len::<StakePool>())?;
+        .get_minimum_balance_for_rent_exemption(StakePool::account_len())?;
     let empty_validator_list = ValidatorList::new(max_validators);
     let validator_list_size = get_instance_packed_len(&empty_validator_list)?;
     let validator_list_balance = config
@@ -722,6 +837,23 @@ fn command_create_pool(
     Ok(())
 }
 
+/// Check if the pool mint is a Token-2022 mint whose metadata pointer points
+/// to itself, in which case it holds its own metadata instead of using the
+/// metaplex program
+fn pool_mint_holds_metadata(config: &Config, stake_pool: &StakePool) -> Result<bool, Error> {
+    if stake_pool.token_program_id != spl_token_2022_interface::id() {
+        return Ok(false);
+    }
+    let mint_account = config.rpc_client.get_account(&stake_pool.pool_mint)?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_account.data)?;
+    Ok(mint
+        .get_extension::<MetadataPointer>()
+        .map(|metadata_pointer| {
+            Option::<Pubkey>::from(metadata_pointer.metadata_address) == Some(stake_pool.pool_mint)
+        })
+        .unwrap_or(false))
+}
+
 fn create_token_metadata(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -732,16 +864,30 @@ fn create_token_metadata(
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
 
     let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
-    let instructions = vec![spl_stake_pool::instruction::create_token_metadata(
-        &config.stake_pool_program_id,
-        stake_pool_address,
-        &stake_pool.manager,
-        &stake_pool.pool_mint,
-        &config.fee_payer.pubkey(),
-        name,
-        symbol,
-        uri,
-    )];
+    let instruction = if pool_mint_holds_metadata(config, &stake_pool)? {
+        spl_stake_pool::instruction::create_token_2022_metadata(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &stake_pool.manager,
+            &stake_pool.pool_mint,
+            &config.fee_payer.pubkey(),
+            name,
+            symbol,
+            uri,
+        )
+    } else {
+        spl_stake_pool::instruction::create_token_metadata(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &stake_pool.manager,
+            &stake_pool.pool_mint,
+            &config.fee_payer.pubkey(),
+            name,
+            symbol,
+            uri,
+        )
+    };
+    let instructions = vec![instruction];
     unique_signers!(signers);
     let transaction = checked_transaction_with_signers(config, &instructions, &signers)?;
     send_transaction(config, transaction)?;
@@ -758,15 +904,29 @@ fn update_token_metadata(
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
 
     let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
-    let instructions = vec![spl_stake_pool::instruction::update_token_metadata(
-        &config.stake_pool_program_id,
-        stake_pool_address,
-        &stake_pool.manager,
-        &stake_pool.pool_mint,
-        name,
-        symbol,
-        uri,
-    )];
+    let instruction = if pool_mint_holds_metadata(config, &stake_pool)? {
+        spl_stake_pool::instruction::update_token_2022_metadata(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &stake_pool.manager,
+            &stake_pool.pool_mint,
+            Some(&config.fee_payer.pubkey()),
+            name,
+            symbol,
+            uri,
+        )
+    } else {
+        spl_stake_pool::instruction::update_token_metadata(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &stake_pool.manager,
+            &stake_pool.pool_mint,
+            name,
+            symbol,
+            uri,
+        )
+    };
+    let instructions = vec![instruction];
     unique_signers!(signers);
     let transaction = checked_transaction_with_signers(config, &instructions, &signers)?;
     send_transaction(config, transaction)?;
@@ -886,6 +1046,249 @@ fn command_vsa_remove(
     Ok(())
 }
 
+/// Read vote account addresses from a JSON file holding an array of
+/// addresses, or a CSV file with an address in the first column of each line,
+/// below an optional header
+fn read_vote_accounts_file(path: &str) -> Result<Vec<Pubkey>, Error> {
+    let contents = std::fs::read_to_string(path)?;
+    if path.ends_with(".json") {
+        let addresses: Vec<String> = serde_json::from_str(&contents)
+            .map_err(|err| format!("{}: invalid vote account list: {}", path, err))?;
+        return addresses
+            .iter()
+            .map(|address| {
+                address
+                    .parse::<Pubkey>()
+                    .map_err(|_| format!("{}: invalid vote account \"{}\"", path, address).into())
+            })
+            .collect();
+    }
+
+    let mut vote_accounts = vec![];
+    let mut header = true;
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let address = line.split(',').next().unwrap_or_default().trim();
+        match address.parse::<Pubkey>() {
+            Ok(vote_account) => vote_accounts.push(vote_account),
+            Err(_) if header => {}
+            Err(_) => {
+                return Err(
+                    format!("{}:{}: invalid line \"{}\"", path, line_number + 1, line).into(),
+                )
+            }
+        }
+        header = false;
+    }
+    Ok(vote_accounts)
+}
+
+/// Check that `instructions` fit in one transaction, along with the compute
+/// budget instructions added when signing it
+fn fits_in_transaction(config: &Config, instructions: &[Instruction]) -> bool {
+    let mut instructions = instructions.to_vec();
+    instructions.push(ComputeBudgetInstruction::set_compute_unit_price(0));
+    instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(0));
+    let message = Message::new(&instructions, Some(&config.fee_payer.pubkey()));
+    bincode::serialized_size(&Transaction::new_unsigned(message))
+        .is_ok_and(|size| size <= PACKET_DATA_SIZE as u64)
+}
+
+/// Send `instructions` in order, as many per transaction as fit, returning
+/// the signature of the transaction of each instruction or why it failed
+fn send_packed(
+    config: &Config,
+    signers: &Vec<&dyn Signer>,
+    instructions: Vec<Instruction>,
+) -> Vec<Result<Signature, String>> {
+    let send = |instructions: &[Instruction]| -> Result<Signature, Error> {
+        let transaction = checked_transaction_with_signers(config, instructions, signers)?;
+        let signature = transaction.signatures[0];
+        send_transaction(config, transaction)?;
+        Ok(signature)
+    };
+    let mut results = vec![];
+    let mut pending = instructions.into_iter().peekable();
+    while let Some(instruction) = pending.next() {
+        let mut batch = vec![instruction];
+        while let Some(instruction) = pending.peek() {
+            batch.push(instruction.clone());
+            if !fits_in_transaction(config, &batch) {
+                batch.pop();
+                break;
+            }
+            pending.next();
+        }
+        match send(&batch) {
+            Ok(signature) => results.extend(batch.iter().map(|_| Ok(signature))),
+            Err(err) if batch.len() == 1 => results.push(Err(err.to_string())),
+            Err(_) => {
+                // find out which instructions fail by sending them one at a time
+                for instruction in batch {
+                    results.push(send(&[instruction]).map_err(|err| err.to_string()));
+                }
+            }
+        }
+    }
+    results
+}
+
+/// Send the instruction of every validator change, as many per transaction
+/// as fit, recording the outcome of each change. In sign-only mode, changes
+/// are only signed, so they are reported as not sent.
+fn send_validator_changes(
+    config: &Config,
+    pending: Vec<(usize, Instruction)>,
+    changes: &mut [CliValidatorChange],
+    done: &str,
+) {
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    let (indexes, instructions): (Vec<_>, Vec<_>) = pending.into_iter().unzip();
+    for (index, result) in indexes
+        .into_iter()
+        .zip(send_packed(config, &signers, instructions))
+    {
+        match result {
+            Ok(_) if config.sign_only => {
+                changes[index].status = "signed, not sent".to_string();
+            }
+            Ok(signature) => {
+                changes[index].status = done.to_string();
+                changes[index].signature = Some(signature.to_string());
+            }
+            Err(err) => changes[index].status = format!("failed: {}", err),
+        }
+    }
+}
+
+/// Print the outcome of every validator change, failing if any of them failed
+fn print_validator_changes(
+    config: &Config,
+    changes: Vec<CliValidatorChange>,
+    action: &str,
+) -> CommandResult {
+    let failed = changes
+        .iter()
+        .filter(|change| change.status.starts_with("failed"))
+        .count();
+    println!(
+        "{}",
+        config
+            .output_format
+            .formatted_string(&CliValidatorChanges { changes })
+    );
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("Failed to {} {} validator(s)", action, failed).into()),
+    }
+}
+
+fn command_vsa_add_from_file(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_accounts_file: &str,
+) -> CommandResult {
+    let vote_accounts = read_vote_accounts_file(vote_accounts_file)?;
+    if !config.no_update && !config.dry_run {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let mut free_entries = (validator_list.header.max_validators as usize)
+        .saturating_sub(validator_list.validators.len());
+    let mut seen = HashSet::new();
+    let mut changes = vec![];
+    let mut pending = vec![];
+    for vote_account in vote_accounts {
+        let status = if !seen.insert(vote_account) {
+            "duplicate"
+        } else if validator_list.contains(&vote_account) {
+            "already in the pool"
+        } else if free_entries == 0 {
+            "validator list is full"
+        } else {
+            free_entries -= 1;
+            pending.push((
+                changes.len(),
+                spl_stake_pool::instruction::add_validator_to_pool_with_vote(
+                    &config.stake_pool_program_id,
+                    &stake_pool,
+                    stake_pool_address,
+                    &vote_account,
+                    None,
+                ),
+            ));
+            "to add"
+        };
+        changes.push(CliValidatorChange {
+            vote_account_address: vote_account.to_string(),
+            status: status.to_string(),
+            signature: None,
+        });
+    }
+
+    if !config.dry_run {
+        send_validator_changes(config, pending, &mut changes, "added");
+    }
+    print_validator_changes(config, changes, "add")
+}
+
+fn command_vsa_remove_from_file(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_accounts_file: &str,
+) -> CommandResult {
+    let vote_accounts = read_vote_accounts_file(vote_accounts_file)?;
+    if !config.no_update && !config.dry_run {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let mut seen = HashSet::new();
+    let mut changes = vec![];
+    let mut pending = vec![];
+    for vote_account in vote_accounts {
+        let status = if !seen.insert(vote_account) {
+            "duplicate"
+        } else {
+            match validator_list.find(&vote_account) {
+                None => "not in the pool",
+                Some(info) if info.status != StakeStatus::Active.into() => "already being removed",
+                Some(info) => {
+                    pending.push((
+                        changes.len(),
+                        spl_stake_pool::instruction::remove_validator_from_pool_with_vote(
+                            &config.stake_pool_program_id,
+                            &stake_pool,
+                            stake_pool_address,
+                            &vote_account,
+                            NonZeroU32::new(info.validator_seed_suffix.into()),
+                            info.transient_seed_suffix.into(),
+                        ),
+                    ));
+                    "to remove"
+                }
+            }
+        };
+        changes.push(CliValidatorChange {
+            vote_account_address: vote_account.to_string(),
+            status: status.to_string(),
+            signature: None,
+        });
+    }
+
+    if !config.dry_run {
+        send_validator_changes(config, pending, &mut changes, "removed");
+    }
+    print_validator_changes(config, changes, "remove")
+}
+
 fn command_increase_validator_stake(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -962,6 +1365,145 @@ fn command_decrease_validator_stake(
     Ok(())
 }
 
+/// Target allocation of the stake of a pool between its validators
+enum Allocation {
+    /// The same stake for every validator
+    Equal,
+    /// Stake proportional to the activated stake of each validator on the
+    /// network
+    StakeWeighted,
+    /// Stake proportional to the given weights, by vote account
+    Weights(Vec<(Pubkey, u64)>),
+}
+
+/// Read a weights file: one vote account address per line, followed by a
+/// comma and its weight
+fn read_weights_file(path: &str) -> Result<Vec<(Pubkey, u64)>, Error> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut weights = vec![];
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid_line = || format!("{}:{}: invalid line \"{}\"", path, line_number + 1, line);
+        let mut fields = line.split(',').map(str::trim);
+        let vote_account = fields
+            .next()
+            .and_then(|address| address.parse::<Pubkey>().ok())
+            .ok_or_else(invalid_line)?;
+        let weight = fields
+            .next()
+            .and_then(|weight| weight.parse::<u64>().ok())
+            .ok_or_else(invalid_line)?;
+        if fields.next().is_some() {
+            return Err(invalid_line().into());
+        }
+        weights.push((vote_account, weight));
+    }
+    Ok(weights)
+}
+
+fn command_rebalance(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    allocation: Allocation,
+    retained_reserve: u64,
+    execute: bool,
+) -> CommandResult {
+    if execute && !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool_client = config.stake_pool_client();
+    let rebalance_state = block_on(stake_pool_client.rebalance_state(stake_pool_address))?;
+    if block_on(stake_pool_client.is_update_needed(&rebalance_state.stake_pool))? {
+        println!("Stake pool not updated in the current epoch, the plan may change once it is");
+    }
+    let active_validators = rebalance_state
+        .validator_list
+        .validators
+        .iter()
+        .filter(|info| info.status == StakeStatus::Active.into())
+        .map(|info| info.vote_account_address);
+    let weights = match allocation {
+        Allocation::Equal => active_validators
+            .map(|vote_account| (vote_account, 1))
+            .collect(),
+        Allocation::StakeWeighted => {
+            let vote_accounts = config.rpc_client.get_vote_accounts()?;
+            let activated_stakes = vote_accounts
+                .current
+                .iter()
+                .chain(vote_accounts.delinquent.iter())
+                .map(|vote_account| {
+                    (
+                        vote_account.vote_pubkey.clone(),
+                        vote_account.activated_stake,
+                    )
+                })
+                .collect::<HashMap<_, _>>();
+            active_validators
+                .map(|vote_account| {
+                    let activated_stake = activated_stakes
+                        .get(&vote_account.to_string())
+                        .copied()
+                        .unwrap_or(0);
+                    (vote_account, activated_stake)
+                })
+                .collect()
+        }
+        Allocation::Weights(weights) => weights,
+    };
+    let plan = rebalance_state
+        .plan(&weights, retained_reserve)
+        .map_err(|err| err as Error)?;
+
+    println!("Reserve stake: {}", Sol(rebalance_state.reserve_lamports));
+    for validator in &plan.validators {
+        let action = match validator.action {
+            RebalanceAction::None => "none".to_string(),
+            RebalanceAction::Increase(lamports) => format!("increase by {}", Sol(lamports)),
+            RebalanceAction::Decrease(lamports) => format!("decrease by {}", Sol(lamports)),
+            RebalanceAction::Skip(SkipReason::TransientStakeInUse) => {
+                "skipped, transient stake in use".to_string()
+            }
+            RebalanceAction::Skip(SkipReason::MinimumStake) => {
+                "skipped, at the minimum stake".to_string()
+            }
+            RebalanceAction::Skip(SkipReason::ReserveDepleted) => {
+                "skipped, not enough in the reserve".to_string()
+            }
+        };
+        println!(
+            "{}: {} staked, target {}, {}",
+            validator.vote_account_address,
+            Sol(validator.current_lamports),
+            Sol(validator.target_lamports),
+            action
+        );
+    }
+    println!("Reserve stake after moves: {}", Sol(plan.reserve_lamports));
+
+    let instructions = rebalance_state
+        .instructions(&config.stake_pool_program_id, stake_pool_address, &plan)
+        .map_err(|err| err as Error)?;
+    if !execute {
+        if !instructions.is_empty() {
+            println!("Run again with --execute to move the stake");
+        }
+        return Ok(());
+    }
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    for instruction in instructions {
+        let transaction = checked_transaction_with_signers(config, &[instruction], &signers)?;
+        send_transaction(config, transaction)?;
+    }
+    Ok(())
+}
+
 fn command_set_preferred_validator(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -994,30 +1536,76 @@ fn add_associated_token_account(
     owner: &Pubkey,
     instructions: &mut Vec<Instruction>,
     rent_free_balances: &mut u64,
-) -> Pubkey {
+) -> Result<Pubkey, Error> {
     // Account for tokens not specified, creating one
-    let account = get_associated_token_address_with_program_id(owner, mint, token_program_id);
-    if get_token_account(&config.rpc_client, &account, mint).is_err() {
-        println!("Creating associated token account {} to receive stake pool tokens of mint {}, owned by {}", account, mint, owner);
-
-        let min_account_balance = config
-            .rpc_client
-            .get_minimum_balance_for_rent_exemption(spl_token_2022_interface::state::Account::LEN)
-            .unwrap();
+    let account = block_on(config.stake_pool_client().associated_token_account(
+        &config.fee_payer.pubkey(),
+        owner,
+        mint,
+        token_program_id,
+    ))?;
+    if let Some(create_instruction) = account.create_instruction {
+        println!("Creating associated token account {} to receive stake pool tokens of mint {}, owned by {}", account.address, mint, owner);
 
-        instructions.push(create_associated_token_account(
-            &config.fee_payer.pubkey(),
-            owner,
-            mint,
-            token_program_id,
-        ));
+        instructions.push(create_instruction);
 
-        *rent_free_balances += min_account_balance;
+        *rent_free_balances += account.rent;
     } else {
-        println!("Using existing associated token account {} to receive stake pool tokens of mint {}, owned by {}", account, mint, owner);
+        println!("Using existing associated token account {} to receive stake pool tokens of mint {}, owned by {}", account.address, mint, owner);
     }
 
-    account
+    Ok(account.address)
+}
+
+/// Add the depositor allowlist entry or proof of the owner of the account
+/// receiving the pool tokens to the deposit instruction at the end of
+/// `instructions`, if the pool restricts its depositors, creating their
+/// depositor record if their cap needs it
+fn add_depositor_accounts(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    pool_token_receiver_account: &Pubkey,
+    depositors: &Option<Vec<(Pubkey, u64)>>,
+    instructions: &mut Vec<Instruction>,
+    total_rent_free_balances: &mut u64,
+) -> Result<(), Error> {
+    *total_rent_free_balances += block_on(config.stake_pool_client().add_depositor_accounts(
+        stake_pool_address,
+        pool_token_receiver_account,
+        &config.token_owner.pubkey(),
+        &config.fee_payer.pubkey(),
+        depositors.as_deref(),
+        instructions,
+    ))?;
+    Ok(())
+}
+
+/// Read a depositors file: one depositor address per line, optionally
+/// followed by a comma and the most SOL it may deposit in total
+fn read_depositors_file(path: &str) -> Result<Vec<(Pubkey, u64)>, Error> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut depositors = vec![];
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid_line = || format!("{}:{}: invalid line \"{}\"", path, line_number + 1, line);
+        let mut fields = line.split(',').map(str::trim);
+        let depositor = fields
+            .next()
+            .and_then(|address| address.parse::<Pubkey>().ok())
+            .ok_or_else(invalid_line)?;
+        let cap = match fields.next() {
+            Some(cap) => native_token::sol_str_to_lamports(cap).ok_or_else(invalid_line)?,
+            None => merkle::NO_CAP,
+        };
+        if fields.next().is_some() {
+            return Err(invalid_line().into());
+        }
+        depositors.push((depositor, cap));
+    }
+    Ok(depositors)
 }
 
 fn command_deposit_stake(
@@ -1027,6 +1615,7 @@ fn command_deposit_stake(
     withdraw_authority: Box<dyn Signer>,
     pool_token_receiver_account: &Option<Pubkey>,
     referrer_token_account: &Option<Pubkey>,
+    depositors: &Option<Vec<(Pubkey, u64)>>,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address, false, false, false)?;
@@ -1081,7 +1670,7 @@ fn command_deposit_stake(
             &config.token_owner.pubkey(),
             &mut instructions,
             &mut total_rent_free_balances,
-        ));
+        )?);
 
     let referrer_token_account = referrer_token_account.unwrap_or(pool_token_receiver_account);
 
@@ -1135,6 +1724,14 @@ fn command_deposit_stake(
             )
         };
 
+    add_depositor_accounts(
+        config,
+        stake_pool_address,
+        &pool_token_receiver_account,
+        depositors,
+        &mut deposit_instructions,
+        &mut total_rent_free_balances,
+    )?;
     instructions.append(&mut deposit_instructions);
 
     unique_signers!(signers);
@@ -1155,6 +1752,7 @@ fn command_deposit_all_stake(
     withdraw_authority: Box<dyn Signer>,
     pool_token_receiver_account: &Option<Pubkey>,
     referrer_token_account: &Option<Pubkey>,
+    depositors: &Option<Vec<(Pubkey, u64)>>,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address, false, false, false)?;
@@ -1174,7 +1772,7 @@ fn command_deposit_all_stake(
             &config.token_owner.pubkey(),
             &mut create_token_account_instructions,
             &mut total_rent_free_balances,
-        ));
+        )?);
     if !create_token_account_instructions.is_empty() {
         let transaction = checked_transaction_with_signers_and_additional_fee(
             config,
@@ -1239,47 +1837,61 @@ fn command_deposit_all_stake(
             validator_stake_account, validator_stake_state
         );
 
-        let instructions = if let Some(stake_deposit_authority) = config.funding_authority.as_ref()
-        {
-            spl_stake_pool::instruction::deposit_stake_with_authority(
-                &config.stake_pool_program_id,
-                stake_pool_address,
-                &stake_pool.validator_list,
-                &stake_deposit_authority.pubkey(),
-                &pool_withdraw_authority,
-                &stake_address,
-                &withdraw_authority.pubkey(),
-                &validator_stake_account,
-                &stake_pool.reserve_stake,
-                &pool_token_receiver_account,
-                &stake_pool.manager_fee_account,
-                &referrer_token_account,
-                &stake_pool.pool_mint,
-                &stake_pool.token_program_id,
-            )
-        } else {
-            spl_stake_pool::instruction::deposit_stake(
-                &config.stake_pool_program_id,
-                stake_pool_address,
-                &stake_pool.validator_list,
-                &pool_withdraw_authority,
-                &stake_address,
-                &withdraw_authority.pubkey(),
-                &validator_stake_account,
-                &stake_pool.reserve_stake,
-                &pool_token_receiver_account,
-                &stake_pool.manager_fee_account,
-                &referrer_token_account,
-                &stake_pool.pool_mint,
-                &stake_pool.token_program_id,
-            )
-        };
-
-        let transaction = checked_transaction_with_signers(config, &instructions, &signers)?;
-        send_transaction(config, transaction)?;
-    }
-    Ok(())
-}
+        let mut total_rent_free_balances = 0;
+        let mut instructions =
+            if let Some(stake_deposit_authority) = config.funding_authority.as_ref() {
+                spl_stake_pool::instruction::deposit_stake_with_authority(
+                    &config.stake_pool_program_id,
+                    stake_pool_address,
+                    &stake_pool.validator_list,
+                    &stake_deposit_authority.pubkey(),
+                    &pool_withdraw_authority,
+                    &stake_address,
+                    &withdraw_authority.pubkey(),
+                    &validator_stake_account,
+                    &stake_pool.reserve_stake,
+                    &pool_token_receiver_account,
+                    &stake_pool.manager_fee_account,
+                    &referrer_token_account,
+                    &stake_pool.pool_mint,
+                    &stake_pool.token_program_id,
+                )
+            } else {
+                spl_stake_pool::instruction::deposit_stake(
+                    &config.stake_pool_program_id,
+                    stake_pool_address,
+                    &stake_pool.validator_list,
+                    &pool_withdraw_authority,
+                    &stake_address,
+                    &withdraw_authority.pubkey(),
+                    &validator_stake_account,
+                    &stake_pool.reserve_stake,
+                    &pool_token_receiver_account,
+                    &stake_pool.manager_fee_account,
+                    &referrer_token_account,
+                    &stake_pool.pool_mint,
+                    &stake_pool.token_program_id,
+                )
+            };
+        add_depositor_accounts(
+            config,
+            stake_pool_address,
+            &pool_token_receiver_account,
+            depositors,
+            &mut instructions,
+            &mut total_rent_free_balances,
+        )?;
+
+        let transaction = checked_transaction_with_signers_and_additional_fee(
+            config,
+            &instructions,
+            &signers,
+            total_rent_free_balances,
+        )?;
+        send_transaction(config, transaction)?;
+    }
+    Ok(())
+}
 
 fn command_deposit_sol(
     config: &Config,
@@ -1288,6 +1900,7 @@ fn command_deposit_sol(
     pool_token_receiver_account: &Option<Pubkey>,
     referrer_token_account: &Option<Pubkey>,
     lamports: u64,
+    depositors: &Option<Vec<(Pubkey, u64)>>,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address, false, false, false)?;
@@ -1336,59 +1949,28 @@ fn command_deposit_sol(
             &config.token_owner.pubkey(),
             &mut instructions,
             &mut total_rent_free_balances,
-        ));
-
-    let referrer_token_account = referrer_token_account.unwrap_or(pool_token_receiver_account);
-
-    let pool_withdraw_authority =
-        find_withdraw_authority_program_address(&config.stake_pool_program_id, stake_pool_address)
-            .0;
+        )?);
 
-    let deposit_instruction = if let Some(deposit_authority) = config.funding_authority.as_ref() {
-        let expected_sol_deposit_authority = stake_pool.sol_deposit_authority.ok_or_else(|| {
-            "SOL deposit authority specified in arguments but stake pool has none".to_string()
-        })?;
+    if let Some(deposit_authority) = config.funding_authority.as_ref() {
         signers.push(deposit_authority.as_ref());
-        if deposit_authority.pubkey() != expected_sol_deposit_authority {
-            let error = format!(
-                "Invalid deposit authority specified, expected {}, received {}",
-                expected_sol_deposit_authority,
-                deposit_authority.pubkey()
-            );
-            return Err(error.into());
-        }
-
-        spl_stake_pool::instruction::deposit_sol_with_authority(
-            &config.stake_pool_program_id,
-            stake_pool_address,
-            &deposit_authority.pubkey(),
-            &pool_withdraw_authority,
-            &stake_pool.reserve_stake,
-            &user_sol_transfer.pubkey(),
-            &pool_token_receiver_account,
-            &stake_pool.manager_fee_account,
-            &referrer_token_account,
-            &stake_pool.pool_mint,
-            &stake_pool.token_program_id,
-            lamports,
-        )
-    } else {
-        spl_stake_pool::instruction::deposit_sol(
-            &config.stake_pool_program_id,
+    }
+    let deposit = block_on(
+        config.stake_pool_client().deposit_sol(
             stake_pool_address,
-            &pool_withdraw_authority,
-            &stake_pool.reserve_stake,
             &user_sol_transfer.pubkey(),
-            &pool_token_receiver_account,
-            &stake_pool.manager_fee_account,
-            &referrer_token_account,
-            &stake_pool.pool_mint,
-            &stake_pool.token_program_id,
+            &config.token_owner.pubkey(),
+            &config.fee_payer.pubkey(),
+            Some(pool_token_receiver_account),
+            *referrer_token_account,
+            config
+                .funding_authority
+                .as_ref()
+                .map(|deposit_authority| deposit_authority.pubkey()),
+            depositors.as_deref(),
             lamports,
-        )
-    };
-
-    instructions.push(deposit_instruction);
+        ),
+    )?;
+    instructions.extend(deposit.instructions);
 
     unique_signers!(signers);
     let transaction = checked_transaction_with_signers_and_additional_fee(
@@ -1497,6 +2079,238 @@ fn command_list(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
+fn command_export(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    file: Option<&str>,
+    format: &str,
+    sort_by: Option<&str>,
+    statuses: &[StakeStatus],
+    min_stake_lamports: Option<u64>,
+    max_commission: Option<u8>,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let epoch = config.rpc_client.get_epoch_info()?.epoch;
+    let vote_accounts = config.rpc_client.get_vote_accounts()?;
+    let vote_accounts = vote_accounts
+        .current
+        .iter()
+        .chain(vote_accounts.delinquent.iter())
+        .map(|vote_account| (vote_account.vote_pubkey.clone(), vote_account))
+        .collect::<HashMap<_, _>>();
+
+    let mut validators = vec![];
+    for validator in &validator_list.validators {
+        let status = StakeStatus::try_from(validator.status)?;
+        let lamports = validator.stake_lamports()?;
+        let vote_account = vote_accounts.get(&validator.vote_account_address.to_string());
+        let commission = vote_account.map(|vote_account| vote_account.commission);
+        if (!statuses.is_empty() && !statuses.contains(&status))
+            || min_stake_lamports.is_some_and(|min_stake_lamports| lamports < min_stake_lamports)
+            || max_commission.is_some_and(|max_commission| {
+                commission.is_none_or(|commission| commission > max_commission)
+            })
+        {
+            continue;
+        }
+
+        // credits earned in the last full epoch
+        let last_epoch_credits = vote_account.map(|vote_account| {
+            vote_account
+                .epoch_credits
+                .iter()
+                .find(|(credits_epoch, _, _)| credits_epoch + 1 == epoch)
+                .map_or(0, |(_, credits, previous_credits)| {
+                    credits.saturating_sub(*previous_credits)
+                })
+        });
+        let (stake_account_address, _) = find_stake_program_address(
+            &config.stake_pool_program_id,
+            &validator.vote_account_address,
+            stake_pool_address,
+            NonZeroU32::new(validator.validator_seed_suffix.into()),
+        );
+        let (transient_stake_account_address, _) = find_transient_stake_program_address(
+            &config.stake_pool_program_id,
+            &validator.vote_account_address,
+            stake_pool_address,
+            validator.transient_seed_suffix.into(),
+        );
+        validators.push(CliValidatorExport {
+            vote_account_address: validator.vote_account_address.to_string(),
+            status: validator.status.into(),
+            active_stake_lamports: validator.active_stake_lamports.into(),
+            transient_stake_lamports: validator.transient_stake_lamports.into(),
+            share_of_pool: match stake_pool.total_lamports {
+                0 => 0.0,
+                total_lamports => lamports as f64 * 100.0 / total_lamports as f64,
+            },
+            commission,
+            last_epoch_credits,
+            stake_account_address: stake_account_address.to_string(),
+            transient_stake_account_address: transient_stake_account_address.to_string(),
+            last_update_epoch: validator.last_update_epoch.into(),
+        });
+    }
+
+    let stake_lamports = |validator: &CliValidatorExport| {
+        validator.active_stake_lamports + validator.transient_stake_lamports
+    };
+    match sort_by {
+        Some("stake") => {
+            validators.sort_by_key(|validator| std::cmp::Reverse(stake_lamports(validator)))
+        }
+        Some("commission") => {
+            validators.sort_by_key(|validator| validator.commission.unwrap_or(u8::MAX))
+        }
+        Some("credits") => {
+            validators.sort_by_key(|validator| std::cmp::Reverse(validator.last_epoch_credits))
+        }
+        Some("update-epoch") => {
+            validators.sort_by_key(|validator| std::cmp::Reverse(validator.last_update_epoch))
+        }
+        _ => {
+            // Default: keep original order (by index)
+        }
+    }
+
+    let contents = match format {
+        "json" => serde_json::to_string_pretty(&validators)? + "\n",
+        _ => std::iter::once(CliValidatorExport::CSV_HEADER.to_string())
+            .chain(validators.iter().map(CliValidatorExport::csv_row))
+            .map(|line| line + "\n")
+            .collect(),
+    };
+    match file {
+        Some(file) => {
+            std::fs::write(file, contents).map_err(|err| format!("{}: {}", file, err))?;
+            println!("Exported {} validator(s) to {}", validators.len(), file);
+        }
+        None => print!("{}", contents),
+    }
+    Ok(())
+}
+
+fn command_audit(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    let audit_state = block_on(config.stake_pool_client().audit_state(stake_pool_address))?;
+    let issues = audit_state.audit(&config.stake_pool_program_id, stake_pool_address);
+
+    let describe_account = |account: &PoolStakeAccount| match account {
+        PoolStakeAccount::Reserve => "Reserve stake account".to_string(),
+        PoolStakeAccount::Validator(vote_account_address) => {
+            format!("Stake account of validator {}", vote_account_address)
+        }
+        PoolStakeAccount::Transient(vote_account_address) => {
+            format!(
+                "Transient stake account of validator {}",
+                vote_account_address
+            )
+        }
+    };
+    let cli_issues = issues
+        .iter()
+        .map(|issue| match issue {
+            AuditIssue::PoolNotUpdated { last_update_epoch } => CliAuditIssue {
+                check: "poolUpdated".to_string(),
+                address: None,
+                description: format!(
+                    "Stake pool last updated in epoch {}, current epoch is {}",
+                    last_update_epoch, audit_state.epoch
+                ),
+            },
+            AuditIssue::TotalLamportsMismatch {
+                total_lamports,
+                counted_lamports,
+            } => CliAuditIssue {
+                check: "totalLamports".to_string(),
+                address: None,
+                description: format!(
+                    "Validator and reserve stake add up to {}, stake pool total is {}",
+                    Sol(*counted_lamports),
+                    Sol(*total_lamports)
+                ),
+            },
+            AuditIssue::PoolTokenSupplyMismatch {
+                pool_token_supply,
+                mint_supply,
+            } => CliAuditIssue {
+                check: "poolTokenSupply".to_string(),
+                address: Some(audit_state.stake_pool.pool_mint.to_string()),
+                description: format!(
+                    "Pool mint supply is {}, stake pool token supply is {}",
+                    mint_supply, pool_token_supply
+                ),
+            },
+            AuditIssue::MissingStakeAccount { account, address } => CliAuditIssue {
+                check: "stakeAccounts".to_string(),
+                address: Some(address.to_string()),
+                description: format!("{} does not exist", describe_account(account)),
+            },
+            AuditIssue::WrongDelegation {
+                account,
+                address,
+                delegated_to,
+            } => CliAuditIssue {
+                check: "stakeAccounts".to_string(),
+                address: Some(address.to_string()),
+                description: match delegated_to {
+                    Some(delegated_to) => format!(
+                        "{} is delegated to {}",
+                        describe_account(account),
+                        delegated_to
+                    ),
+                    None => format!("{} is not delegated", describe_account(account)),
+                },
+            },
+            AuditIssue::WrongAuthority {
+                account,
+                address,
+                staker,
+                withdrawer,
+            } => CliAuditIssue {
+                check: "stakeAccounts".to_string(),
+                address: Some(address.to_string()),
+                description: match (staker, withdrawer) {
+                    (Some(staker), Some(withdrawer)) => format!(
+                        "{} has stake authority {} and withdraw authority {}",
+                        describe_account(account),
+                        staker,
+                        withdrawer
+                    ),
+                    _ => format!("{} is not initialized", describe_account(account)),
+                },
+            },
+            AuditIssue::StrayStakeAccount { address, lamports } => CliAuditIssue {
+                check: "strayStakeAccounts".to_string(),
+                address: Some(address.to_string()),
+                description: format!(
+                    "Stake account with {} held by the pool withdraw authority is not part of the pool",
+                    Sol(*lamports)
+                ),
+            },
+        })
+        .collect::<Vec<_>>();
+
+    let cli_audit = CliAudit {
+        stake_pool: stake_pool_address.to_string(),
+        epoch: audit_state.epoch,
+        total_lamports: audit_state.stake_pool.total_lamports,
+        counted_lamports: audit_state.counted_lamports(),
+        pool_token_supply: audit_state.stake_pool.pool_token_supply,
+        mint_supply: audit_state.mint_supply,
+        stake_accounts: audit_state.stake_accounts.len(),
+        issues: cli_issues,
+    };
+    println!("{}", config.output_format.formatted_string(&cli_audit));
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Stake pool audit found {} issue(s)", issues.len()).into())
+    }
+}
+
 fn command_update(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -1509,9 +2323,9 @@ fn command_update(
         return Ok(());
     }
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
-    let epoch_info = config.rpc_client.get_epoch_info()?;
+    let stake_pool_client = config.stake_pool_client();
 
-    if stake_pool.last_update_epoch == epoch_info.epoch {
+    if !block_on(stake_pool_client.is_update_needed(&stake_pool))? {
         if force {
             println!("Update not required, but --force flag specified, so doing it anyway");
         } else {
@@ -1520,26 +2334,10 @@ fn command_update(
         }
     }
 
-    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
-
-    let (mut update_list_instructions, final_instructions) = if stale_only {
-        spl_stake_pool::instruction::update_stale_stake_pool(
-            &config.stake_pool_program_id,
-            &stake_pool,
-            &validator_list,
-            stake_pool_address,
-            no_merge,
-            epoch_info.epoch,
-        )
-    } else {
-        spl_stake_pool::instruction::update_stake_pool(
-            &config.stake_pool_program_id,
-            &stake_pool,
-            &validator_list,
-            stake_pool_address,
-            no_merge,
-        )
-    };
+    let UpdateInstructions {
+        mut update_list_instructions,
+        final_instructions,
+    } = block_on(stake_pool_client.update(stake_pool_address, no_merge, stale_only))?;
 
     let update_list_instructions_len = update_list_instructions.len();
     if update_list_instructions_len > 0 {
@@ -1572,160 +2370,183 @@ fn command_update(
     Ok(())
 }
 
-#[derive(PartialEq, Debug)]
-struct WithdrawAccount {
-    stake_address: Pubkey,
-    vote_address: Option<Pubkey>,
-    pool_amount: u64,
-}
-
-fn sorted_accounts<F>(
-    validator_list: &ValidatorList,
-    stake_pool: &StakePool,
-    get_info: F,
-) -> Vec<(Pubkey, u64, Option<Pubkey>)>
-where
-    F: Fn(&ValidatorStakeInfo) -> (Pubkey, u64, Option<Pubkey>),
-{
-    let mut result: Vec<(Pubkey, u64, Option<Pubkey>)> = validator_list
-        .validators
-        .iter()
-        .map(get_info)
-        .collect::<Vec<_>>();
-
-    result.sort_by(|left, right| {
-        if left.2 == stake_pool.preferred_withdraw_validator_vote_address {
-            Ordering::Less
-        } else if right.2 == stake_pool.preferred_withdraw_validator_vote_address {
-            Ordering::Greater
-        } else {
-            right.1.cmp(&left.1)
+/// Wait for `signatures` to be confirmed, returning how many failed or timed
+/// out
+fn confirm_signatures(config: &Config, signatures: &[Signature]) -> Result<u64, Error> {
+    const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+    let deadline = Instant::now() + CRANK_CONFIRMATION_TIMEOUT;
+    let mut pending = signatures.to_vec();
+    let mut failed = 0;
+    while !pending.is_empty() && Instant::now() < deadline {
+        thread::sleep(Duration::from_secs(1));
+        let mut still_pending = vec![];
+        for chunk in pending.chunks(MAX_SIGNATURES_PER_REQUEST) {
+            let statuses = config.rpc_client.get_signature_statuses(chunk)?.value;
+            for (signature, status) in chunk.iter().zip(statuses) {
+                match status {
+                    Some(status) if status.err.is_some() => failed += 1,
+                    Some(status) if status.satisfies_commitment(config.rpc_client.commitment()) => {
+                    }
+                    _ => still_pending.push(*signature),
+                }
+            }
         }
-    });
-
-    result
+        pending = still_pending;
+    }
+    Ok(failed + pending.len() as u64)
 }
 
-fn prepare_withdraw_accounts(
+/// Update a stake pool, sending the `UpdateValidatorListBalance` transactions
+/// again for the validators that are still stale after each attempt
+fn crank_update(
     config: &Config,
-    stake_pool: &StakePool,
-    pool_amount: u64,
     stake_pool_address: &Pubkey,
-    skip_fee: bool,
-) -> Result<Vec<WithdrawAccount>, Error> {
-    let stake_minimum_delegation = config.rpc_client.get_stake_minimum_delegation()?;
-    let stake_pool_minimum_delegation = minimum_delegation(stake_minimum_delegation);
-    let min_balance = config
-        .rpc_client
-        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?
-        .saturating_add(stake_pool_minimum_delegation);
-    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
-    let validator_list: ValidatorList =
-        get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
-
-    let mut accounts: Vec<(Pubkey, u64, Option<Pubkey>)> = Vec::new();
-
-    accounts.append(&mut sorted_accounts(
-        &validator_list,
-        stake_pool,
-        |validator| {
-            let validator_seed = NonZeroU32::new(validator.validator_seed_suffix.into());
-            let (stake_account_address, _) = find_stake_program_address(
-                &config.stake_pool_program_id,
-                &validator.vote_account_address,
-                stake_pool_address,
-                validator_seed,
-            );
-
-            (
-                stake_account_address,
-                validator.active_stake_lamports.into(),
-                Some(validator.vote_account_address),
-            )
-        },
-    ));
-
-    accounts.append(&mut sorted_accounts(
-        &validator_list,
-        stake_pool,
-        |validator| {
-            let (transient_stake_account_address, _) = find_transient_stake_program_address(
-                &config.stake_pool_program_id,
-                &validator.vote_account_address,
-                stake_pool_address,
-                validator.transient_seed_suffix.into(),
-            );
-
-            (
-                transient_stake_account_address,
-                u64::from(validator.transient_stake_lamports).saturating_sub(min_balance),
-                Some(validator.vote_account_address),
-            )
-        },
-    ));
-
-    let reserve_stake = config.rpc_client.get_account(&stake_pool.reserve_stake)?;
-
-    accounts.push((
-        stake_pool.reserve_stake,
-        reserve_stake.lamports
-            - config
-                .rpc_client
-                .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?
-            - MINIMUM_RESERVE_LAMPORTS,
-        None,
-    ));
-
-    // Prepare the list of accounts to withdraw from
-    let mut withdraw_from: Vec<WithdrawAccount> = vec![];
-    let mut remaining_amount = pool_amount;
-
-    let fee = stake_pool.stake_withdrawal_fee;
-    let inverse_fee = Fee {
-        numerator: fee.denominator - fee.numerator,
-        denominator: fee.denominator,
-    };
-
-    // Go through available accounts and withdraw from largest to smallest
-    for (stake_address, lamports, vote_address_opt) in accounts {
-        if lamports <= min_balance {
-            continue;
+    max_retries: u32,
+    metrics: &mut CliCrankMetrics,
+) -> CommandResult {
+    let stake_pool_client = config.stake_pool_client();
+    let mut last_error = None;
+    let mut update = block_on(stake_pool_client.update(stake_pool_address, false, true))?;
+    for attempt in 0..=max_retries {
+        metrics.attempts = attempt + 1;
+        if !update.update_list_instructions.is_empty() {
+            let mut signatures = vec![];
+            for instruction in update.update_list_instructions {
+                let transaction = checked_transaction_with_signers(
+                    config,
+                    &[instruction],
+                    &[config.fee_payer.as_ref()],
+                )?;
+                metrics.transactions_sent += 1;
+                match config.rpc_client.send_transaction(&transaction) {
+                    Ok(signature) => signatures.push(signature),
+                    Err(_) => metrics.transactions_failed += 1,
+                }
+            }
+            metrics.transactions_failed += confirm_signatures(config, &signatures)?;
+
+            // only update the pool balance once every validator is updated
+            update = block_on(stake_pool_client.update(stake_pool_address, false, true))?;
+            if !update.update_list_instructions.is_empty() {
+                last_error = Some(format!(
+                    "{} validator list chunk(s) not updated",
+                    update.update_list_instructions.len()
+                ));
+                continue;
+            }
         }
-
-        let available_for_withdrawal_wo_fee =
-            stake_pool.calc_pool_tokens_for_deposit(lamports).unwrap();
-
-        let available_for_withdrawal = if skip_fee {
-            available_for_withdrawal_wo_fee
-        } else {
-            available_for_withdrawal_wo_fee * inverse_fee.denominator / inverse_fee.numerator
-        };
-
-        let pool_amount = u64::min(available_for_withdrawal, remaining_amount);
-
-        // Those accounts will be withdrawn completely with `claim` instruction
-        withdraw_from.push(WithdrawAccount {
-            stake_address,
-            vote_address: vote_address_opt,
-            pool_amount,
-        });
-        remaining_amount -= pool_amount;
-
-        if remaining_amount == 0 {
-            break;
+        let transaction = checked_transaction_with_signers(
+            config,
+            &update.final_instructions,
+            &[config.fee_payer.as_ref()],
+        )?;
+        metrics.transactions_sent += 1;
+        match config.rpc_client.send_and_confirm_transaction(&transaction) {
+            Ok(_) => {
+                last_error = None;
+                break;
+            }
+            Err(err) => {
+                metrics.transactions_failed += 1;
+                last_error = Some(err.to_string());
+            }
         }
     }
+    match last_error {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
+
+/// Run one crank round on a stake pool: update it if it wasn't updated in
+/// the current epoch, then check that it was
+fn crank_pool(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    max_retries: u32,
+    metrics: &mut CliCrankMetrics,
+) -> CommandResult {
+    let epoch = config.rpc_client.get_epoch_info()?.epoch;
+    metrics.epoch = epoch;
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    metrics.update_required = stake_pool.last_update_epoch < epoch;
+    if metrics.update_required {
+        crank_update(config, stake_pool_address, max_retries, metrics)?;
+    }
 
-    // Not enough stake to withdraw the specified amount
-    if remaining_amount > 0 {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    metrics.last_update_epoch = stake_pool.last_update_epoch;
+    metrics.stale_validators = validator_list
+        .validators
+        .iter()
+        .filter(|info| u64::from(info.last_update_epoch) < epoch)
+        .count();
+    metrics.total_lamports = stake_pool.total_lamports;
+    metrics.pool_token_supply = stake_pool.pool_token_supply;
+    if stake_pool.last_update_epoch < epoch {
         return Err(format!(
-            "No stake accounts found in this pool with enough balance to withdraw {} pool tokens.",
-            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals)
+            "Stake pool last updated in epoch {}, current epoch is {}",
+            stake_pool.last_update_epoch, epoch
         )
         .into());
     }
+    Ok(())
+}
 
-    Ok(withdraw_from)
+fn command_crank(
+    config: &Config,
+    stake_pool_addresses: &[Pubkey],
+    interval: Duration,
+    max_retries: u32,
+    metrics_file: Option<&str>,
+    once: bool,
+) -> CommandResult {
+    if config.sign_only || config.dry_run {
+        return Err(
+            "The crank sends transactions, it cannot run with --sign-only or --dry-run".into(),
+        );
+    }
+    let mut metrics_file = metrics_file
+        .map(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| format!("Unable to open {}: {}", path, err))
+        })
+        .transpose()?;
+
+    loop {
+        let mut unhealthy = 0;
+        for stake_pool_address in stake_pool_addresses {
+            let mut metrics = CliCrankMetrics {
+                stake_pool: stake_pool_address.to_string(),
+                ..CliCrankMetrics::default()
+            };
+            if let Err(err) = crank_pool(config, stake_pool_address, max_retries, &mut metrics) {
+                metrics.error = Some(err.to_string());
+                unhealthy += 1;
+            }
+            metrics.healthy = metrics.error.is_none();
+            metrics.timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default();
+            let line = OutputFormat::JsonCompact.formatted_string(&metrics);
+            match &mut metrics_file {
+                Some(file) => writeln!(file, "{}", line)?,
+                None => println!("{}", line),
+            }
+        }
+        if once {
+            return match unhealthy {
+                0 => Ok(()),
+                _ => Err(format!("{} stake pool(s) not updated", unhealthy).into()),
+            };
+        }
+        thread::sleep(interval);
+    }
 }
 
 fn command_withdraw_stake(
@@ -1745,10 +2566,6 @@ fn command_withdraw_stake(
     let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
     let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
 
-    let pool_withdraw_authority =
-        find_withdraw_authority_program_address(&config.stake_pool_program_id, stake_pool_address)
-            .0;
-
     let pool_token_account =
         pool_token_account.unwrap_or(get_associated_token_address_with_program_id(
             &config.token_owner.pubkey(),
@@ -1792,108 +2609,29 @@ fn command_withdraw_stake(
         })
         .flatten();
 
-    let stake_minimum_delegation = config.rpc_client.get_stake_minimum_delegation()?;
-    let stake_pool_minimum_delegation = minimum_delegation(stake_minimum_delegation);
-
-    let withdraw_accounts = if use_reserve {
-        vec![WithdrawAccount {
-            stake_address: stake_pool.reserve_stake,
-            vote_address: None,
-            pool_amount,
-        }]
-    } else if maybe_stake_receiver_state.is_some() {
-        let vote_account = maybe_stake_receiver_state
-            .unwrap()
-            .delegation()
-            .unwrap()
-            .voter_pubkey;
+    let withdraw_source = if use_reserve {
+        WithdrawSource::Reserve
+    } else if let Some(stake_receiver_state) = &maybe_stake_receiver_state {
+        let vote_account = stake_receiver_state.delegation().unwrap().voter_pubkey;
         if let Some(vote_account_address) = vote_account_address {
             if *vote_account_address != vote_account {
                 return Err(format!("Provided withdrawal vote account {} does not match delegation on stake receiver account {},
                 remove this flag or provide a different stake account delegated to {}", vote_account_address, vote_account, vote_account_address).into());
             }
         }
-        // Check if the vote account exists in the stake pool
-        let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
-        let validator_stake_info = validator_list
-            .find(&vote_account)
-            .ok_or(format!("Provided stake account is delegated to a vote account {} which does not exist in the stake pool", vote_account))?;
-        let validator_seed = NonZeroU32::new(validator_stake_info.validator_seed_suffix.into());
-        let (stake_account_address, _) = find_stake_program_address(
-            &config.stake_pool_program_id,
-            &vote_account,
-            stake_pool_address,
-            validator_seed,
-        );
-        let stake_account = config.rpc_client.get_account(&stake_account_address)?;
-
-        let available_for_withdrawal = stake_pool
-            .calc_lamports_withdraw_amount(
-                stake_account
-                    .lamports
-                    .saturating_sub(stake_pool_minimum_delegation)
-                    .saturating_sub(stake_account_rent_exemption),
-            )
-            .unwrap();
-
-        if available_for_withdrawal < pool_amount {
-            return Err(format!(
-                "Not enough lamports available for withdrawal from {}, {} asked, {} available",
-                stake_account_address, pool_amount, available_for_withdrawal
-            )
-            .into());
-        }
-        vec![WithdrawAccount {
-            stake_address: stake_account_address,
-            vote_address: Some(vote_account),
-            pool_amount,
-        }]
+        WithdrawSource::Validator(vote_account)
     } else if let Some(vote_account_address) = vote_account_address {
-        let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
-        let validator_stake_info = validator_list.find(vote_account_address).ok_or(format!(
-            "Provided vote account address {} does not exist in the stake pool",
-            vote_account_address
-        ))?;
-        let validator_seed = NonZeroU32::new(validator_stake_info.validator_seed_suffix.into());
-        let (stake_account_address, _) = find_stake_program_address(
-            &config.stake_pool_program_id,
-            vote_account_address,
-            stake_pool_address,
-            validator_seed,
-        );
-        let stake_account = config.rpc_client.get_account(&stake_account_address)?;
-
-        let available_for_withdrawal = stake_pool
-            .calc_lamports_withdraw_amount(
-                stake_account
-                    .lamports
-                    .saturating_sub(stake_pool_minimum_delegation)
-                    .saturating_sub(stake_account_rent_exemption),
-            )
-            .unwrap();
-
-        if available_for_withdrawal < pool_amount {
-            return Err(format!(
-                "Not enough lamports available for withdrawal from {}, {} asked, {} available",
-                stake_account_address, pool_amount, available_for_withdrawal
-            )
-            .into());
-        }
-        vec![WithdrawAccount {
-            stake_address: stake_account_address,
-            vote_address: Some(*vote_account_address),
-            pool_amount,
-        }]
+        WithdrawSource::Validator(*vote_account_address)
     } else {
-        // Get the list of accounts to withdraw from
-        prepare_withdraw_accounts(
-            config,
-            &stake_pool,
-            pool_amount,
-            stake_pool_address,
-            stake_pool.manager_fee_account == pool_token_account,
-        )?
+        WithdrawSource::Any
     };
+    let stake_pool_client = config.stake_pool_client();
+    let withdraw_accounts = block_on(stake_pool_client.withdraw_stake_plan(
+        stake_pool_address,
+        &pool_token_account,
+        pool_amount,
+        withdraw_source,
+    ))?;
 
     // Construct transaction to withdraw from withdraw_accounts account list
     let mut instructions: Vec<Instruction> = vec![];
@@ -1920,10 +2658,7 @@ fn command_withdraw_stake(
     let mut total_rent_free_balances = 0;
     // Go through prepared accounts and withdraw/claim them
     for withdraw_account in withdraw_accounts {
-        // Convert pool tokens amount to lamports
-        let sol_withdraw_amount = stake_pool
-            .calc_lamports_withdraw_amount(withdraw_account.pool_amount)
-            .unwrap();
+        let sol_withdraw_amount = withdraw_account.lamports;
 
         if let Some(vote_address) = withdraw_account.vote_address {
             println!(
@@ -1957,21 +2692,16 @@ fn command_withdraw_stake(
                 stake_receiver_param.unwrap()
             };
 
-        instructions.push(spl_stake_pool::instruction::withdraw_stake(
-            &config.stake_pool_program_id,
+        let withdraw_instruction = block_on(stake_pool_client.withdraw_stake_instruction(
             stake_pool_address,
-            &stake_pool.validator_list,
-            &pool_withdraw_authority,
-            &withdraw_account.stake_address,
+            &stake_pool,
+            &withdraw_account,
             &stake_receiver,
             &config.staker.pubkey(),
             &user_transfer_authority.pubkey(),
             &pool_token_account,
-            &stake_pool.manager_fee_account,
-            &stake_pool.pool_mint,
-            &stake_pool.token_program_id,
-            withdraw_account.pool_amount,
-        ));
+        ))?;
+        instructions.push(withdraw_instruction);
     }
 
     // Merging the stake with account provided by user
@@ -2060,7 +2790,9 @@ fn command_withdraw_sol(
         find_withdraw_authority_program_address(&config.stake_pool_program_id, stake_pool_address)
             .0;
 
-    let withdraw_instruction = if let Some(withdraw_authority) = config.funding_authority.as_ref() {
+    let mut withdraw_instruction = if let Some(withdraw_authority) =
+        config.funding_authority.as_ref()
+    {
         let expected_sol_withdraw_authority =
             stake_pool.sol_withdraw_authority.ok_or_else(|| {
                 "SOL withdraw authority specified in arguments but stake pool has none".to_string()
@@ -2105,6 +2837,31 @@ fn command_withdraw_sol(
         )
     };
 
+    if let Some(transfer_hook_program_id) = block_on(
+        spl_stake_pool_client::accounts::get_transfer_hook_program_id(
+            &config.rpc_client,
+            &stake_pool,
+        ),
+    )? {
+        let pool_tokens_fee = stake_pool
+            .calc_pool_tokens_sol_withdrawal_fee(pool_amount)
+            .unwrap();
+        if pool_tokens_fee > 0 && pool_token_account != stake_pool.manager_fee_account {
+            block_on(
+                config
+                    .stake_pool_client()
+                    .add_transfer_hook_accounts_for_withdraw(
+                        &stake_pool,
+                        &mut withdraw_instruction,
+                        &transfer_hook_program_id,
+                        &pool_token_account,
+                        &user_transfer_authority.pubkey(),
+                        pool_tokens_fee,
+                    ),
+            )?;
+        }
+    }
+
     instructions.push(withdraw_instruction);
 
     unique_signers!(signers);
@@ -2113,6 +2870,278 @@ fn command_withdraw_sol(
     Ok(())
 }
 
+fn command_quote(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    operation: &str,
+    amount: &str,
+    withdraw_source: WithdrawSource,
+    pool_token_account: &Option<Pubkey>,
+) -> CommandResult {
+    let quote_state = block_on(config.stake_pool_client().quote_state(stake_pool_address))?;
+    let stake_pool = &quote_state.withdraw_state.stake_pool;
+    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
+    let fee_exempt = *pool_token_account == Some(stake_pool.manager_fee_account);
+    let check_quote = |err: StakePoolError| -> Error {
+        if err == StakePoolError::StakeListAndPoolOutOfDate {
+            format!(
+                "Stake pool not updated in the current epoch, run `update {}` first",
+                stake_pool_address
+            )
+            .into()
+        } else {
+            err.into()
+        }
+    };
+
+    let mut cli_quote = CliQuote {
+        operation: operation.to_string(),
+        pool_mint_decimals: pool_mint.decimals,
+        ..CliQuote::default()
+    };
+    match operation {
+        "deposit-sol" | "deposit-stake" => {
+            let lamports = native_token::sol_str_to_lamports(amount)
+                .ok_or_else(|| format!("Invalid amount: {}", amount))?;
+            let quote = if operation == "deposit-sol" {
+                quote_state.deposit_sol(lamports)
+            } else {
+                // the stake account's rent exemption is deposited as SOL
+                let rent = quote_state.withdraw_state.stake_rent_exemption;
+                let stake_lamports = lamports.checked_sub(rent).ok_or_else(|| {
+                    format!(
+                        "A stake account holds at least its rent exemption of {}",
+                        Sol(rent)
+                    )
+                })?;
+                quote_state.deposit_stake(stake_lamports, rent)
+            }
+            .map_err(check_quote)?;
+            cli_quote.lamports = quote.lamports;
+            cli_quote.pool_tokens = quote.pool_tokens;
+            cli_quote.pool_tokens_withheld = quote.pool_tokens_withheld;
+            cli_quote.fee = quote.fee;
+            cli_quote.referral_fee = quote.referral_fee;
+        }
+        "withdraw-sol" | "withdraw-stake" => {
+            let pool_amount = amount
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid amount: {}", amount))?;
+            let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
+            let quote = if operation == "withdraw-sol" {
+                quote_state
+                    .withdraw_sol(pool_amount, fee_exempt)
+                    .map_err(check_quote)?
+            } else {
+                quote_state
+                    .withdraw_stake(
+                        &config.stake_pool_program_id,
+                        stake_pool_address,
+                        pool_amount,
+                        withdraw_source,
+                        fee_exempt,
+                    )
+                    .map_err(|err| match err.downcast::<StakePoolError>() {
+                        Ok(err) => check_quote(*err),
+                        Err(err) => err as Error,
+                    })?
+            };
+            cli_quote.lamports = quote.lamports;
+            cli_quote.pool_tokens = quote.pool_tokens;
+            cli_quote.fee = quote.fee;
+            cli_quote.transfer_fee = quote.transfer_fee;
+            cli_quote.stake_accounts = quote
+                .accounts
+                .iter()
+                .map(|account| CliQuoteStakeAccount {
+                    stake_account_address: account.stake_address.to_string(),
+                    vote_account_address: account.vote_address.map(|x| x.to_string()),
+                    pool_tokens: account.pool_amount,
+                    lamports: account.lamports,
+                })
+                .collect();
+        }
+        _ => unreachable!(),
+    }
+
+    println!("{}", config.output_format.formatted_string(&cli_quote));
+    Ok(())
+}
+
+/// Describe the authority or fee that `change` sets, with its current and
+/// new values
+fn describe_pool_change(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    stake_pool: &StakePool,
+    change: &PoolChange,
+) -> String {
+    let or_none = |address: Option<Pubkey>| address.map_or("none".to_string(), |a| a.to_string());
+    match change {
+        PoolChange::SetFee(fee) => {
+            let describe = |fee: &FeeType| match fee {
+                FeeType::SolReferral(pct) => ("SOL referral fee", format!("{}%", pct)),
+                FeeType::StakeReferral(pct) => ("stake referral fee", format!("{}%", pct)),
+                FeeType::Epoch(fee) => ("epoch fee", fee.to_string()),
+                FeeType::StakeWithdrawal(fee) => ("stake withdrawal fee", fee.to_string()),
+                FeeType::SolDeposit(fee) => ("SOL deposit fee", fee.to_string()),
+                FeeType::StakeDeposit(fee) => ("stake deposit fee", fee.to_string()),
+                FeeType::SolWithdrawal(fee) => ("SOL withdrawal fee", fee.to_string()),
+            };
+            let (name, current) = describe(&configured_fee(stake_pool, fee));
+            format!("{}: {} -> {}", name, current, describe(fee).1)
+        }
+        PoolChange::SetFundingAuthority(funding_type, authority) => {
+            let (name, current) = match funding_type {
+                FundingType::StakeDeposit => {
+                    let (default_authority, _) = find_deposit_authority_program_address(
+                        &config.stake_pool_program_id,
+                        stake_pool_address,
+                    );
+                    (
+                        "stake deposit authority",
+                        Some(stake_pool.stake_deposit_authority)
+                            .filter(|authority| *authority != default_authority),
+                    )
+                }
+                FundingType::SolDeposit => {
+                    ("SOL deposit authority", stake_pool.sol_deposit_authority)
+                }
+                FundingType::SolWithdraw => {
+                    ("SOL withdraw authority", stake_pool.sol_withdraw_authority)
+                }
+            };
+            format!("{}: {} -> {}", name, or_none(current), or_none(*authority))
+        }
+        PoolChange::AddValidator(vote_account_address) => {
+            format!("add validator {}", vote_account_address)
+        }
+        PoolChange::SetPreferredValidator(validator_type, vote_account_address) => {
+            let (name, current) = match validator_type {
+                PreferredValidatorType::Deposit => (
+                    "preferred deposit validator",
+                    stake_pool.preferred_deposit_validator_vote_address,
+                ),
+                PreferredValidatorType::Withdraw => (
+                    "preferred withdraw validator",
+                    stake_pool.preferred_withdraw_validator_vote_address,
+                ),
+            };
+            format!(
+                "{}: {} -> {}",
+                name,
+                or_none(current),
+                or_none(*vote_account_address)
+            )
+        }
+        PoolChange::RemoveValidator(vote_account_address) => {
+            format!("remove validator {}", vote_account_address)
+        }
+        PoolChange::SetStaker(staker) => format!("staker: {} -> {}", stake_pool.staker, staker),
+    }
+}
+
+fn command_pool_config(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    config_file: &str,
+    apply: bool,
+) -> CommandResult {
+    let pool_config = read_pool_config_file(config_file)?;
+    let apply = apply && !config.dry_run;
+    if apply && !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let plan = pool_config.plan(
+        &config.stake_pool_program_id,
+        stake_pool_address,
+        &stake_pool,
+        &validator_list,
+    );
+    let mut changes = plan
+        .iter()
+        .map(|change| CliPoolChange {
+            description: describe_pool_change(config, stake_pool_address, &stake_pool, change),
+            signer: if change.is_signed_by_manager() {
+                "manager"
+            } else {
+                "staker"
+            }
+            .to_string(),
+            status: "to apply".to_string(),
+            signature: None,
+        })
+        .collect::<Vec<_>>();
+
+    if apply {
+        for (authority, signer, role) in [
+            (stake_pool.manager, config.manager.as_ref(), "manager"),
+            (stake_pool.staker, config.staker.as_ref(), "staker"),
+        ] {
+            if changes.iter().any(|change| change.signer == role) && signer.pubkey() != authority {
+                return Err(format!(
+                    "{} {} is not the {} of the stake pool, {} is",
+                    role,
+                    signer.pubkey(),
+                    role,
+                    authority
+                )
+                .into());
+            }
+        }
+
+        let instructions = PoolConfig::instructions(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &stake_pool,
+            &validator_list,
+            &plan,
+        );
+        // changes must be made in order, so only consecutive changes signed by
+        // the same authority share transactions
+        let mut start = 0;
+        for group in plan.chunk_by(|a, b| a.is_signed_by_manager() == b.is_signed_by_manager()) {
+            let end = start + group.len();
+            let authority = if group[0].is_signed_by_manager() {
+                config.manager.as_ref()
+            } else {
+                config.staker.as_ref()
+            };
+            let mut signers = vec![config.fee_payer.as_ref(), authority];
+            unique_signers!(signers);
+            let results = send_packed(config, &signers, instructions[start..end].to_vec());
+            for (change, result) in changes[start..end].iter_mut().zip(results) {
+                match result {
+                    Ok(signature) => {
+                        change.status = "applied".to_string();
+                        change.signature = Some(signature.to_string());
+                    }
+                    Err(err) => change.status = format!("failed: {}", err),
+                }
+            }
+            start = end;
+        }
+    }
+
+    let failed = changes
+        .iter()
+        .filter(|change| change.status.starts_with("failed"))
+        .count();
+    println!(
+        "{}",
+        config
+            .output_format
+            .formatted_string(&CliPoolChanges { changes })
+    );
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("Failed to apply {} change(s)", failed).into()),
+    }
+}
+
 fn command_set_manager(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -2240,65 +3269,481 @@ fn command_set_fee(
     Ok(())
 }
 
-fn command_list_all_pools(config: &Config) -> CommandResult {
-    let all_pools = get_stake_pools(&config.rpc_client, &config.stake_pool_program_id)?;
-    let cli_stake_pool_vec: Vec<CliStakePool> =
-        all_pools.into_iter().map(CliStakePool::from).collect();
-    let cli_stake_pools = CliStakePools {
-        pools: cli_stake_pool_vec,
-    };
+fn command_resize_validator_list(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    max_validators: u32,
+) -> CommandResult {
+    if max_validators > MAX_VALIDATORS_IN_POOL {
+        return Err(format!(
+            "Maximum number of validators is {}, {} requested",
+            MAX_VALIDATORS_IN_POOL, max_validators
+        )
+        .into());
+    }
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let current_max_validators = validator_list.header.max_validators;
+    if max_validators <= current_max_validators {
+        println!(
+            "Validator list already holds {} validators, ignoring",
+            current_max_validators
+        );
+        return Ok(());
+    }
+
+    let instructions = spl_stake_pool::instruction::resize_validator_list_instructions(
+        &config.stake_pool_program_id,
+        stake_pool_address,
+        &config.manager.pubkey(),
+        &stake_pool.validator_list,
+        &config.fee_payer.pubkey(),
+        current_max_validators,
+        max_validators,
+    );
     println!(
-        "{}",
-        config.output_format.formatted_string(&cli_stake_pools)
+        "Resizing validator list {} from {} to {} validators in {} instructions",
+        stake_pool.validator_list,
+        current_max_validators,
+        max_validators,
+        instructions.len()
     );
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    for chunk in instructions.chunks(RESIZE_INSTRUCTIONS_PER_TRANSACTION) {
+        let transaction = checked_transaction_with_signers(config, chunk, &signers)?;
+        send_transaction(config, transaction)?;
+    }
     Ok(())
 }
 
-fn main() {
-    solana_logger::setup_with_default("solana=info");
+fn command_shrink_validator_list(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    max_validators: Option<u32>,
+    destination: Option<Pubkey>,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let remaining_validators = validator_list
+        .validators
+        .iter()
+        .filter(|validator| validator.status != StakeStatus::ReadyForRemoval.into())
+        .count() as u32;
+    let max_validators = max_validators.unwrap_or(remaining_validators).max(1);
+    if max_validators < remaining_validators {
+        return Err(format!(
+            "Validator list contains {} validators, cannot shrink it to {}",
+            remaining_validators, max_validators
+        )
+        .into());
+    }
+    let destination = destination.unwrap_or_else(|| config.fee_payer.pubkey());
+    println!(
+        "Shrinking validator list {} from {} to {} validators, sending reclaimed rent to {}",
+        stake_pool.validator_list,
+        validator_list.header.max_validators,
+        max_validators,
+        destination
+    );
 
-    let matches = App::new(crate_name!())
-        .about(crate_description!())
-        .version(crate_version!())
-        .setting(AppSettings::SubcommandRequiredElseHelp)
-        .arg({
-            let arg = Arg::with_name("config_file")
-                .short("C")
-                .long("config")
-                .value_name("PATH")
-                .takes_value(true)
-                .global(true)
-                .help("Configuration file to use");
-            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
-                arg.default_value(config_file)
-            } else {
-                arg
-            }
-        })
-        .arg(
-            Arg::with_name("verbose")
-                .long("verbose")
-                .short("v")
-                .takes_value(false)
-                .global(true)
-                .help("Show additional information"),
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::shrink_validator_list(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.manager.pubkey(),
+            &stake_pool.validator_list,
+            &destination,
+            max_validators,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_close_pool(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    destination: Option<Pubkey>,
+    new_mint_authority: Option<Pubkey>,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    if stake_pool.pool_token_supply != 0 {
+        return Err(format!(
+            "Stake pool still has {} pool tokens in circulation, cannot close it",
+            stake_pool.pool_token_supply
         )
-        .arg(
-            Arg::with_name("output_format")
-                .long("output")
-                .value_name("FORMAT")
-                .global(true)
-                .takes_value(true)
-                .possible_values(&["json", "json-compact"])
-                .help("Return information in specified output format"),
+        .into());
+    }
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let remaining_validators = validator_list
+        .validators
+        .iter()
+        .filter(|validator| validator.status != StakeStatus::ReadyForRemoval.into())
+        .count();
+    if remaining_validators != 0 {
+        return Err(format!(
+            "Stake pool still contains {} validators, remove them before closing it",
+            remaining_validators
         )
-        .arg(
-            Arg::with_name("dry_run")
-                .long("dry-run")
-                .takes_value(false)
+        .into());
+    }
+    let destination = destination.unwrap_or_else(|| config.fee_payer.pubkey());
+    match new_mint_authority {
+        Some(new_mint_authority) => println!(
+            "Closing stake pool {}, sending all lamports to {} and setting the mint authority to {}",
+            stake_pool_address, destination, new_mint_authority
+        ),
+        None => println!(
+            "Closing stake pool {}, sending all lamports to {} and clearing the mint authority",
+            stake_pool_address, destination
+        ),
+    }
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::close_pool(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.manager.pubkey(),
+            &stake_pool.validator_list,
+            &stake_pool.reserve_stake,
+            &stake_pool.pool_mint,
+            &destination,
+            &stake_pool.token_program_id,
+            new_mint_authority.as_ref(),
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_migrate_pool(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    let stake_pool_account = config.rpc_client.get_account(stake_pool_address)?;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_account.data)?;
+    if stake_pool.account_type == AccountType::StakePoolV2
+        && stake_pool_account.data.len() >= StakePool::account_len()
+    {
+        println!(
+            "Stake pool {} already uses the current layout",
+            stake_pool_address
+        );
+        return Ok(());
+    }
+    println!(
+        "Migrating stake pool {} from {} to {} bytes",
+        stake_pool_address,
+        stake_pool_account.data.len(),
+        StakePool::account_len()
+    );
+
+    let mut signers = vec![config.fee_payer.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::migrate_pool(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.fee_payer.pubkey(),
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_partial_update_haircut(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    haircut: Option<Fee>,
+) -> CommandResult {
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::set_partial_update_haircut(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.manager.pubkey(),
+            haircut,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_transfer_hook_programs(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    program_ids: Vec<Pubkey>,
+) -> CommandResult {
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::set_transfer_hook_program_ids(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.manager.pubkey(),
+            program_ids,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_depositor_allowlist(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    enabled: bool,
+) -> CommandResult {
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::set_depositor_allowlist(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.manager.pubkey(),
+            enabled,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_add_depositor(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    depositor: &Pubkey,
+) -> CommandResult {
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::add_depositor(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.manager.pubkey(),
+            &config.fee_payer.pubkey(),
+            depositor,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_remove_depositor(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    depositor: &Pubkey,
+) -> CommandResult {
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::remove_depositor(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.manager.pubkey(),
+            depositor,
+            &config.fee_payer.pubkey(),
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_depositor_merkle_root(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    depositors: Option<Vec<(Pubkey, u64)>>,
+) -> CommandResult {
+    let root = match depositors {
+        Some(depositors) => {
+            let root = depositor_merkle_tree(&depositors)
+                .root()
+                .ok_or("The depositors file is empty")?;
+            println!(
+                "Depositor Merkle root of {} depositors: {}",
+                depositors.len(),
+                Hash::new_from_array(root)
+            );
+            Some(root)
+        }
+        None => None,
+    };
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::set_depositor_merkle_root(
+            &config.stake_pool_program_id,
+            stake_pool_address,
+            &config.manager.pubkey(),
+            root,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_submit(
+    config: &Config,
+    message: &str,
+    signatures: &[(Pubkey, Signature)],
+) -> CommandResult {
+    let message = [
+        BASE64_STANDARD.decode(message).ok(),
+        bs58::decode(message).into_vec().ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .find_map(|message_data| deserialize::<Message>(&message_data).ok())
+    .ok_or("Message is not a base58 or base64 serialized transaction message")?;
+    let recent_blockhash = message.recent_blockhash;
+    let signer_keys = message
+        .signer_keys()
+        .into_iter()
+        .copied()
+        .collect::<Vec<_>>();
+    let mut transaction = Transaction::new_unsigned(message);
+
+    // sign with the local signers first, signing resets the other signatures
+    let mut local_signers = [
+        Some(config.fee_payer.as_ref()),
+        Some(config.manager.as_ref()),
+        Some(config.staker.as_ref()),
+        Some(config.token_owner.as_ref()),
+        config.funding_authority.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|signer| {
+        signer_keys.contains(&signer.pubkey())
+            && !signatures
+                .iter()
+                .any(|(pubkey, _)| *pubkey == signer.pubkey())
+    })
+    .collect::<Vec<_>>();
+    unique_signers!(local_signers);
+    transaction.try_partial_sign(&local_signers, recent_blockhash)?;
+
+    for (pubkey, signature) in signatures {
+        let index = signer_keys
+            .iter()
+            .position(|signer_key| signer_key == pubkey)
+            .ok_or_else(|| format!("{} is not a signer of the transaction", pubkey))?;
+        transaction.signatures[index] = *signature;
+    }
+
+    let sign_only_data = return_signers_data(&transaction, &ReturnSignersConfig::default());
+    if !sign_only_data.absent.is_empty() || !sign_only_data.bad_sig.is_empty() {
+        println!("{}", config.output_format.formatted_string(&sign_only_data));
+        return Err("Transaction is not fully signed".into());
+    }
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_list_all_pools(config: &Config) -> CommandResult {
+    let all_pools = get_stake_pools(&config.rpc_client, &config.stake_pool_program_id)?;
+    let cli_stake_pool_vec: Vec<CliStakePool> =
+        all_pools.into_iter().map(CliStakePool::from).collect();
+    let cli_stake_pools = CliStakePools {
+        pools: cli_stake_pool_vec,
+    };
+    println!(
+        "{}",
+        config.output_format.formatted_string(&cli_stake_pools)
+    );
+    Ok(())
+}
+
+fn main() {
+    solana_logger::setup_with_default("solana=info");
+
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("output_format")
+                .long("output")
+                .value_name("FORMAT")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["json", "json-compact"])
+                .help("Return information in specified output format"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
                 .global(true)
                 .help("Simulate transaction instead of executing"),
         )
+        .arg(sign_only_arg().global(true))
+        .arg(blockhash_arg().global(true))
+        .arg(
+            Arg::with_name(SIGNER_ARG.name)
+                .long(SIGNER_ARG.long)
+                .takes_value(true)
+                .value_name("PUBKEY=SIGNATURE")
+                .validator(is_pubkey_sig)
+                .requires(BLOCKHASH_ARG.name)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true)
+                .help(SIGNER_ARG.help),
+        )
+        .arg(dump_transaction_message().global(true))
+        .arg(
+            Arg::with_name("message_encoding")
+                .long("message-encoding")
+                .value_name("ENCODING")
+                .takes_value(true)
+                .possible_values(&["base58", "base64"])
+                .requires(DUMP_TRANSACTION_MESSAGE.name)
+                .global(true)
+                .help("Encoding of the transaction messages displayed in sign-only mode. [default: base64]"),
+        )
         .arg(
             Arg::with_name("no_update")
                 .long("no-update")
@@ -2626,8 +4071,8 @@ fn main() {
                     .help("Vote account for the validator to remove from the pool"),
             )
         )
-        .subcommand(SubCommand::with_name("increase-validator-stake")
-            .about("Increase stake to a validator, drawing from the stake pool reserve. Must be signed by the pool staker.")
+        .subcommand(SubCommand::with_name("add-validators")
+            .about("Add the validators listed in a file to the stake pool, skipping those already in it. Must be signed by the pool staker.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2638,25 +4083,65 @@ fn main() {
                     .help("Stake pool address"),
             )
             .arg(
-                Arg::with_name("vote_account")
-                    .index(2)
+                Arg::with_name("file")
+                    .long("file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .required(true)
+                    .help("JSON file with an array of vote account addresses, or CSV file with a vote account address in the first column. With --dry-run, only show the changes to the validator list."),
+            )
+        )
+        .subcommand(SubCommand::with_name("remove-validators")
+            .about("Remove the validators listed in a file from the stake pool, skipping those not in it. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
                     .validator(is_pubkey)
-                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .value_name("POOL_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Vote account for the validator to increase stake to"),
+                    .help("Stake pool address"),
             )
             .arg(
-                Arg::with_name("amount")
-                    .index(3)
-                    .validator(is_amount)
-                    .value_name("AMOUNT")
+                Arg::with_name("file")
+                    .long("file")
+                    .value_name("PATH")
                     .takes_value(true)
-                    .help("Amount in SOL to add to the validator stake account. Must be at least the rent-exempt amount for a stake plus 1 SOL for merging."),
+                    .required(true)
+                    .help("JSON file with an array of vote account addresses, or CSV file with a vote account address in the first column. With --dry-run, only show the changes to the validator list."),
             )
         )
-        .subcommand(SubCommand::with_name("decrease-validator-stake")
-            .about("Decrease stake to a validator, splitting from the active stake. Must be signed by the pool staker.")
+        .subcommand(SubCommand::with_name("increase-validator-stake")
+            .about("Increase stake to a validator, drawing from the stake pool reserve. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to increase stake to"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Amount in SOL to add to the validator stake account. Must be at least the rent-exempt amount for a stake plus 1 SOL for merging."),
+            )
+        )
+        .subcommand(SubCommand::with_name("decrease-validator-stake")
+            .about("Decrease stake to a validator, splitting from the active stake. Must be signed by the pool staker.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2684,6 +4169,51 @@ fn main() {
                     .help("Amount in SOL to remove from the validator stake account. Must be at least the rent-exempt amount for a stake."),
             )
         )
+        .subcommand(SubCommand::with_name("rebalance")
+            .about("Move stake between the reserve and the validators of a pool towards a target allocation. \
+                    Prints the plan, and executes it if requested, which must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("allocation")
+                    .long("allocation")
+                    .value_name("ALLOCATION")
+                    .takes_value(true)
+                    .possible_values(&["equal", "stake-weighted"])
+                    .conflicts_with("weights_file")
+                    .help("Target allocation of the stake: the same for every validator, or proportional \
+                          to the activated stake of each validator on the network. [default: equal]"),
+            )
+            .arg(
+                Arg::with_name("weights_file")
+                    .long("weights-file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("File of the weights of the target allocation, one vote account address per line, \
+                          followed by a comma and its weight. Missing validators are brought to the minimum stake."),
+            )
+            .arg(
+                Arg::with_name("reserve_amount")
+                    .long("reserve-amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Amount in SOL to keep in the reserve, on top of its rent-exempt minimum. [default: 0]"),
+            )
+            .arg(
+                Arg::with_name("execute")
+                    .long("execute")
+                    .takes_value(false)
+                    .help("Send the planned increases and decreases of stake"),
+            )
+        )
         .subcommand(SubCommand::with_name("set-preferred-validator")
             .about("Set the preferred validator for deposits or withdrawals. Must be signed by the pool staker.")
             .arg(
@@ -2770,6 +4300,15 @@ fn main() {
                     .help("Pool token account to receive the referral fees for deposits. \
                           Defaults to the token receiver."),
             )
+            .arg(
+                Arg::with_name("depositors_file")
+                    .long("depositors-file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("File of the depositors allowed into the pool, one address per line, \
+                          optionally followed by a comma and the most SOL it may deposit in total. \
+                          Required if the pool has a depositor Merkle root."),
+            )
         )
         .subcommand(SubCommand::with_name("deposit-all-stake")
             .about("Deposit all active stake accounts into the stake pool in exchange for pool tokens")
@@ -2817,6 +4356,15 @@ fn main() {
                     .help("Pool token account to receive the referral fees for deposits. \
                           Defaults to the token receiver."),
             )
+            .arg(
+                Arg::with_name("depositors_file")
+                    .long("depositors-file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("File of the depositors allowed into the pool, one address per line, \
+                          optionally followed by a comma and the most SOL it may deposit in total. \
+                          Required if the pool has a depositor Merkle root."),
+            )
         )
         .subcommand(SubCommand::with_name("deposit-sol")
             .about("Deposit SOL into the stake pool in exchange for pool tokens")
@@ -2863,6 +4411,15 @@ fn main() {
                     .help("Account to receive the referral fees for deposits. \
                           Defaults to the token receiver."),
             )
+            .arg(
+                Arg::with_name("depositors_file")
+                    .long("depositors-file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("File of the depositors allowed into the pool, one address per line, \
+                          optionally followed by a comma and the most SOL it may deposit in total. \
+                          Required if the pool has a depositor Merkle root."),
+            )
         )
         .subcommand(SubCommand::with_name("list")
             .about("List stake accounts managed by this pool")
@@ -2884,6 +4441,84 @@ fn main() {
                     .help("Sort validators by: stake (largest first) or update-epoch (most recent first)"),
             )
         )
+        .subcommand(SubCommand::with_name("export")
+            .about("Export the validators of the stake pool, with their stake, commission and vote credits, as CSV or JSON")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("file")
+                    .long("file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("File to write. Defaults to the standard output. A CSV export can be read back by add-validators --file."),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .long("format")
+                    .value_name("FORMAT")
+                    .takes_value(true)
+                    .possible_values(&["csv", "json"])
+                    .help("Format of the export. Defaults to JSON for a file ending in .json, and CSV otherwise."),
+            )
+            .arg(
+                Arg::with_name("sort")
+                    .long("sort")
+                    .value_name("SORT_OPTION")
+                    .takes_value(true)
+                    .possible_values(&["stake", "commission", "credits", "update-epoch"])
+                    .help("Sort validators by: stake (largest first), commission (lowest first), credits earned in the last epoch (most first) or update-epoch (most recent first)"),
+            )
+            .arg(
+                Arg::with_name("status")
+                    .long("status")
+                    .value_name("STATUS")
+                    .takes_value(true)
+                    .multiple(true)
+                    .possible_values(&[
+                        "active",
+                        "deactivating-transient",
+                        "ready-for-removal",
+                        "deactivating-validator",
+                        "deactivating-all",
+                    ])
+                    .help("Only export validators with one of these statuses"),
+            )
+            .arg(
+                Arg::with_name("min_stake")
+                    .long("min-stake")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Only export validators with at least this much stake, in SOL"),
+            )
+            .arg(
+                Arg::with_name("max_commission")
+                    .long("max-commission")
+                    .validator(is_valid_percentage)
+                    .value_name("PERCENTAGE")
+                    .takes_value(true)
+                    .help("Only export validators with a commission of at most this percentage"),
+            )
+        )
+        .subcommand(SubCommand::with_name("audit")
+            .about("Check the invariants of the pool's accounts, exiting with an error if any is broken")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+        )
         .subcommand(SubCommand::with_name("update")
             .about("Updates all balances in the pool after validator stake accounts receive rewards.")
             .arg(
@@ -2914,8 +4549,424 @@ fn main() {
                     .help("If set, only updates validator list balances that have not been updated for this epoch. Otherwise, updates all validator balances on the validator list."),
             )
         )
-        .subcommand(SubCommand::with_name("withdraw-stake")
-            .about("Withdraw active stake from the stake pool in exchange for pool tokens")
+        .subcommand(SubCommand::with_name("crank")
+            .about("Keep stake pools updated, sending the update transactions after every epoch rollover and writing health metrics as JSON lines")
+            .arg(
+                Arg::with_name("pools")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required(true)
+                    .help("Stake pool addresses."),
+            )
+            .arg(
+                Arg::with_name("interval")
+                    .long("interval")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SECONDS")
+                    .takes_value(true)
+                    .default_value("60")
+                    .help("Seconds between checks of the stake pools."),
+            )
+            .arg(
+                Arg::with_name("max_retries")
+                    .long("max-retries")
+                    .validator(is_parsable::<u32>)
+                    .value_name("NUMBER")
+                    .takes_value(true)
+                    .default_value("5")
+                    .help("Number of times to send the update transactions again for the validators still not updated."),
+            )
+            .arg(
+                Arg::with_name("metrics_file")
+                    .long("metrics-file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("File to append the health metrics to. Defaults to stdout."),
+            )
+            .arg(
+                Arg::with_name("once")
+                    .long("once")
+                    .takes_value(false)
+                    .help("Check the stake pools once, exiting with an error if any is not updated."),
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw-stake")
+            .about("Withdraw active stake from the stake pool in exchange for pool tokens")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(2)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of pool tokens to withdraw for activated stake."),
+            )
+            .arg(
+                Arg::with_name("pool_account")
+                    .long("pool-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Pool token account to withdraw tokens from. Defaults to the token-owner's associated token account."),
+            )
+            .arg(
+                Arg::with_name("stake_receiver")
+                    .long("stake-receiver")
+                    .validator(is_pubkey)
+                    .value_name("STAKE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .requires("withdraw_from")
+                    .help("Stake account from which to receive a stake from the stake pool. Defaults to a new stake account."),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .long("vote-account")
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .help("Validator to withdraw from. Defaults to the largest validator stakes in the pool."),
+            )
+            .arg(
+                Arg::with_name("use_reserve")
+                    .long("use-reserve")
+                    .takes_value(false)
+                    .help("Withdraw from the stake pool's reserve. Only possible if all validator stakes are at the minimum possible amount."),
+            )
+            .group(ArgGroup::with_name("withdraw_from")
+                .arg("use_reserve")
+                .arg("vote_account")
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw-sol")
+            .about("Withdraw SOL from the stake pool's reserve in exchange for pool tokens")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("sol_receiver")
+                    .index(2)
+                    .validator(is_valid_pubkey)
+                    .value_name("SYSTEM_ACCOUNT_ADDRESS_OR_KEYPAIR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("System account to receive SOL from the stake pool. Defaults to the payer."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of pool tokens to withdraw for SOL."),
+            )
+            .arg(
+                Arg::with_name("pool_account")
+                    .long("pool-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Pool token account to withdraw tokens from. Defaults to the token-owner's associated token account."),
+            )
+        )
+        .subcommand(SubCommand::with_name("quote")
+            .about("Preview a deposit or withdrawal, with its fees, without sending it")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("operation")
+                    .index(2)
+                    .value_name("OPERATION")
+                    .takes_value(true)
+                    .required(true)
+                    .possible_values(&["deposit-sol", "deposit-stake", "withdraw-sol", "withdraw-stake"])
+                    .help("Operation to quote."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of SOL to deposit, including the rent exemption of a deposited stake account, or amount of pool tokens to withdraw."),
+            )
+            .arg(
+                Arg::with_name("pool_account")
+                    .long("pool-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Pool token account to withdraw tokens from. Withdrawals from the manager fee account pay no fee."),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .long("vote-account")
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .help("Validator to withdraw stake from. Defaults to the largest validator stakes in the pool."),
+            )
+            .arg(
+                Arg::with_name("use_reserve")
+                    .long("use-reserve")
+                    .takes_value(false)
+                    .help("Withdraw stake from the stake pool's reserve."),
+            )
+            .group(ArgGroup::with_name("withdraw_from")
+                .arg("use_reserve")
+                .arg("vote_account")
+            )
+        )
+        .subcommand(SubCommand::with_name("plan")
+            .about("Show the changes bringing the stake pool to the configuration in a file, without making them")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("pool_config_file")
+                    .index(2)
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .required(true)
+                    .help("TOML file, or JSON file, with the fees, authorities, preferred validators and validators of the stake pool. Settings left out are kept as they are."),
+            )
+        )
+        .subcommand(SubCommand::with_name("apply")
+            .about("Make the changes bringing the stake pool to the configuration in a file. Must be signed by the pool manager for fee, authority and staker changes, and by the pool staker for validator changes.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("pool_config_file")
+                    .index(2)
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .required(true)
+                    .help("TOML file, or JSON file, with the fees, authorities, preferred validators and validators of the stake pool. Settings left out are kept as they are."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-manager")
+            .about("Change manager or fee receiver account for the stake pool. Must be signed by the current manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("new_manager")
+                    .long("new-manager")
+                    .validator(is_valid_signer)
+                    .value_name("KEYPAIR")
+                    .takes_value(true)
+                    .help("Keypair for the new stake pool manager."),
+            )
+            .arg(
+                Arg::with_name("new_fee_receiver")
+                    .long("new-fee-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Public key for the new account to set as the stake pool fee receiver."),
+            )
+            .group(ArgGroup::with_name("new_accounts")
+                .arg("new_manager")
+                .arg("new_fee_receiver")
+                .required(true)
+                .multiple(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set-staker")
+            .about("Change staker account for the stake pool. Must be signed by the manager or current staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("new_staker")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Public key for the new stake pool staker."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-funding-authority")
+            .about("Change one of the funding authorities for the stake pool. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("funding_type")
+                    .index(2)
+                    .value_name("FUNDING_TYPE")
+                    .possible_values(&["stake-deposit", "sol-deposit", "sol-withdraw"]) // FundingType enum
+                    .takes_value(true)
+                    .required(true)
+                    .help("Funding type to be updated."),
+            )
+            .arg(
+                Arg::with_name("new_authority")
+                    .index(3)
+                    .validator(is_pubkey)
+                    .value_name("AUTHORITY_ADDRESS")
+                    .takes_value(true)
+                    .help("Public key for the new stake pool funding authority."),
+            )
+            .arg(
+                Arg::with_name("unset")
+                    .long("unset")
+                    .takes_value(false)
+                    .help("Unset the stake deposit authority. The program will use a program derived address.")
+            )
+            .group(ArgGroup::with_name("validator")
+                .arg("new_authority")
+                .arg("unset")
+                .required(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set-fee")
+            .about("Change the [epoch/withdraw/stake deposit/sol deposit] fee assessed by the stake pool. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(Arg::with_name("fee_type")
+                .index(2)
+                .value_name("FEE_TYPE")
+                .possible_values(&["epoch", "stake-deposit", "sol-deposit", "stake-withdrawal", "sol-withdrawal"]) // FeeType enum
+                .takes_value(true)
+                .required(true)
+                .help("Fee type to be updated."),
+            )
+            .arg(
+                Arg::with_name("fee_numerator")
+                    .index(3)
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee numerator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("fee_denominator")
+                    .index(4)
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee denominator, fee amount is numerator divided by denominator."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-referral-fee")
+            .about("Change the referral fee assessed by the stake pool for stake deposits. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(Arg::with_name("fee_type")
+                .index(2)
+                .value_name("FEE_TYPE")
+                .possible_values(&["stake", "sol"]) // FeeType enum, kind of
+                .takes_value(true)
+                .required(true)
+                .help("Fee type to be updated."),
+            )
+            .arg(
+                Arg::with_name("fee")
+                    .index(3)
+                    .validator(is_valid_percentage)
+                    .value_name("FEE_PERCENTAGE")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee percentage, maximum 100"),
+            )
+        )
+        .subcommand(SubCommand::with_name("resize-validator-list")
+            .about("Grow the validator list to hold more validators. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("max_validators")
+                    .index(2)
+                    .validator(is_parsable::<u32>)
+                    .value_name("NUMBER")
+                    .takes_value(true)
+                    .required(true)
+                    .help("New max number of validators included in the stake pool"),
+            )
+        )
+        .subcommand(SubCommand::with_name("shrink-validator-list")
+            .about("Remove validators ready for removal and shrink the validator list to reclaim rent. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2926,52 +4977,66 @@ fn main() {
                     .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("amount")
-                    .index(2)
-                    .validator(is_amount)
-                    .value_name("AMOUNT")
+                Arg::with_name("max_validators")
+                    .long("max-validators")
+                    .short("m")
+                    .validator(is_parsable::<u32>)
+                    .value_name("NUMBER")
                     .takes_value(true)
-                    .required(true)
-                    .help("Amount of pool tokens to withdraw for activated stake."),
+                    .help("New max number of validators included in the stake pool. \
+                          Defaults to the number of validators currently in the pool."),
             )
             .arg(
-                Arg::with_name("pool_account")
-                    .long("pool-account")
+                Arg::with_name("destination")
+                    .long("destination")
                     .validator(is_pubkey)
                     .value_name("ADDRESS")
                     .takes_value(true)
-                    .help("Pool token account to withdraw tokens from. Defaults to the token-owner's associated token account."),
+                    .help("Account to receive the reclaimed rent. Defaults to the fee payer."),
             )
+        )
+        .subcommand(SubCommand::with_name("close-pool")
+            .about("Close an empty stake pool, withdrawing the reserve and recovering all rent. Must be signed by the manager.")
             .arg(
-                Arg::with_name("stake_receiver")
-                    .long("stake-receiver")
+                Arg::with_name("pool")
+                    .index(1)
                     .validator(is_pubkey)
-                    .value_name("STAKE_ACCOUNT_ADDRESS")
+                    .value_name("POOL_ADDRESS")
                     .takes_value(true)
-                    .requires("withdraw_from")
-                    .help("Stake account from which to receive a stake from the stake pool. Defaults to a new stake account."),
+                    .required(true)
+                    .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("vote_account")
-                    .long("vote-account")
+                Arg::with_name("destination")
+                    .long("destination")
                     .validator(is_pubkey)
-                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .value_name("ADDRESS")
                     .takes_value(true)
-                    .help("Validator to withdraw from. Defaults to the largest validator stakes in the pool."),
+                    .help("Account to receive the recovered lamports. Defaults to the fee payer."),
             )
             .arg(
-                Arg::with_name("use_reserve")
-                    .long("use-reserve")
-                    .takes_value(false)
-                    .help("Withdraw from the stake pool's reserve. Only possible if all validator stakes are at the minimum possible amount."),
+                Arg::with_name("new_mint_authority")
+                    .long("new-mint-authority")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("New mint authority for the pool mint. If not provided, the mint authority is cleared."),
             )
-            .group(ArgGroup::with_name("withdraw_from")
-                .arg("use_reserve")
-                .arg("vote_account")
+        )
+        .subcommand(SubCommand::with_name("migrate-pool")
+            .about("Migrate the stake pool account to the current layout, paying for any additional rent")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
             )
         )
-        .subcommand(SubCommand::with_name("withdraw-sol")
-            .about("Withdraw SOL from the stake pool's reserve in exchange for pool tokens")
+        .subcommand(SubCommand::with_name("set-partial-update-haircut")
+            .about("Allow SOL deposits and withdrawals before the stake pool is updated for the epoch, withholding a share of the pool tokens of each deposit. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2982,34 +5047,33 @@ fn main() {
                     .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("sol_receiver")
+                Arg::with_name("haircut_numerator")
                     .index(2)
-                    .validator(is_valid_pubkey)
-                    .value_name("SYSTEM_ACCOUNT_ADDRESS_OR_KEYPAIR")
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
                     .takes_value(true)
-                    .required(true)
-                    .help("System account to receive SOL from the stake pool. Defaults to the payer."),
+                    .required_unless("unset")
+                    .help("Haircut numerator, haircut amount is numerator divided by denominator."),
             )
             .arg(
-                Arg::with_name("amount")
+                Arg::with_name("haircut_denominator")
                     .index(3)
-                    .validator(is_amount)
-                    .value_name("AMOUNT")
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
                     .takes_value(true)
-                    .required(true)
-                    .help("Amount of pool tokens to withdraw for SOL."),
+                    .required_unless("unset")
+                    .help("Haircut denominator, haircut amount is numerator divided by denominator."),
             )
             .arg(
-                Arg::with_name("pool_account")
-                    .long("pool-account")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
-                    .takes_value(true)
-                    .help("Pool token account to withdraw tokens from. Defaults to the token-owner's associated token account."),
+                Arg::with_name("unset")
+                    .long("unset")
+                    .takes_value(false)
+                    .conflicts_with_all(&["haircut_numerator", "haircut_denominator"])
+                    .help("Reject SOL deposits and withdrawals until the stake pool is updated for the epoch."),
             )
         )
-        .subcommand(SubCommand::with_name("set-manager")
-            .about("Change manager or fee receiver account for the stake pool. Must be signed by the current manager.")
+        .subcommand(SubCommand::with_name("set-transfer-hook-programs")
+            .about("Set the transfer hook programs allowed on the pool mint, replacing the current list. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -3020,30 +5084,36 @@ fn main() {
                     .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("new_manager")
-                    .long("new-manager")
-                    .validator(is_valid_signer)
-                    .value_name("KEYPAIR")
+                Arg::with_name("program_ids")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("PROGRAM_ID")
                     .takes_value(true)
-                    .help("Keypair for the new stake pool manager."),
+                    .multiple(true)
+                    .max_values(MAX_TRANSFER_HOOK_PROGRAMS as u64)
+                    .help("Allowed transfer hook programs. Leave empty to disallow all transfer hooks."),
             )
+        )
+        .subcommand(SubCommand::with_name("set-depositor-allowlist")
+            .about("Require deposits to be made to accounts owned by depositors on the allowlist. Must be signed by the manager.")
             .arg(
-                Arg::with_name("new_fee_receiver")
-                    .long("new-fee-receiver")
+                Arg::with_name("pool")
+                    .index(1)
                     .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                    .value_name("POOL_ADDRESS")
                     .takes_value(true)
-                    .help("Public key for the new account to set as the stake pool fee receiver."),
+                    .required(true)
+                    .help("Stake pool address."),
             )
-            .group(ArgGroup::with_name("new_accounts")
-                .arg("new_manager")
-                .arg("new_fee_receiver")
-                .required(true)
-                .multiple(true)
+            .arg(
+                Arg::with_name("disable")
+                    .long("disable")
+                    .takes_value(false)
+                    .help("Stop requiring depositors to be on the allowlist."),
             )
         )
-        .subcommand(SubCommand::with_name("set-staker")
-            .about("Change staker account for the stake pool. Must be signed by the manager or current staker.")
+        .subcommand(SubCommand::with_name("add-depositor")
+            .about("Add a depositor to the allowlist. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -3054,16 +5124,17 @@ fn main() {
                     .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("new_staker")
+                Arg::with_name("depositor")
                     .index(2)
                     .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                    .value_name("DEPOSITOR_ADDRESS")
                     .takes_value(true)
-                    .help("Public key for the new stake pool staker."),
+                    .required(true)
+                    .help("Owner of the accounts allowed to receive pool tokens from deposits."),
             )
         )
-        .subcommand(SubCommand::with_name("set-funding-authority")
-            .about("Change one of the funding authorities for the stake pool. Must be signed by the manager.")
+        .subcommand(SubCommand::with_name("remove-depositor")
+            .about("Remove a depositor from the allowlist, returning the rent of its entry to the fee payer. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -3074,36 +5145,17 @@ fn main() {
                     .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("funding_type")
+                Arg::with_name("depositor")
                     .index(2)
-                    .value_name("FUNDING_TYPE")
-                    .possible_values(&["stake-deposit", "sol-deposit", "sol-withdraw"]) // FundingType enum
-                    .takes_value(true)
-                    .required(true)
-                    .help("Funding type to be updated."),
-            )
-            .arg(
-                Arg::with_name("new_authority")
-                    .index(3)
                     .validator(is_pubkey)
-                    .value_name("AUTHORITY_ADDRESS")
+                    .value_name("DEPOSITOR_ADDRESS")
                     .takes_value(true)
-                    .help("Public key for the new stake pool funding authority."),
-            )
-            .arg(
-                Arg::with_name("unset")
-                    .long("unset")
-                    .takes_value(false)
-                    .help("Unset the stake deposit authority. The program will use a program derived address.")
-            )
-            .group(ArgGroup::with_name("validator")
-                .arg("new_authority")
-                .arg("unset")
-                .required(true)
+                    .required(true)
+                    .help("Depositor to remove from the allowlist."),
             )
         )
-        .subcommand(SubCommand::with_name("set-fee")
-            .about("Change the [epoch/withdraw/stake deposit/sol deposit] fee assessed by the stake pool. Must be signed by the manager.")
+        .subcommand(SubCommand::with_name("set-depositor-merkle-root")
+            .about("Require deposits to prove that the owner of the account receiving the pool tokens is in a depositors file. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -3113,60 +5165,47 @@ fn main() {
                     .required(true)
                     .help("Stake pool address."),
             )
-            .arg(Arg::with_name("fee_type")
-                .index(2)
-                .value_name("FEE_TYPE")
-                .possible_values(&["epoch", "stake-deposit", "sol-deposit", "stake-withdrawal", "sol-withdrawal"]) // FeeType enum
-                .takes_value(true)
-                .required(true)
-                .help("Fee type to be updated."),
-            )
             .arg(
-                Arg::with_name("fee_numerator")
-                    .index(3)
-                    .validator(is_parsable::<u64>)
-                    .value_name("NUMERATOR")
+                Arg::with_name("depositors_file")
+                    .long("depositors-file")
+                    .value_name("PATH")
                     .takes_value(true)
-                    .required(true)
-                    .help("Fee numerator, fee amount is numerator divided by denominator."),
+                    .help("File of the depositors allowed into the pool, one address per line, \
+                          optionally followed by a comma and the most SOL it may deposit in total. \
+                          Depositors need the same file to deposit. Each depositor with a cap also \
+                          needs a depositor record, paying rent, created on its first deposit."),
             )
             .arg(
-                Arg::with_name("fee_denominator")
-                    .index(4)
-                    .validator(is_parsable::<u64>)
-                    .value_name("DENOMINATOR")
-                    .takes_value(true)
-                    .required(true)
-                    .help("Fee denominator, fee amount is numerator divided by denominator."),
+                Arg::with_name("unset")
+                    .long("unset")
+                    .takes_value(false)
+                    .help("Stop requiring depositor proofs."),
+            )
+            .group(ArgGroup::with_name("root")
+                .arg("depositors_file")
+                .arg("unset")
+                .required(true)
             )
         )
-        .subcommand(SubCommand::with_name("set-referral-fee")
-            .about("Change the referral fee assessed by the stake pool for stake deposits. Must be signed by the manager.")
+        .subcommand(SubCommand::with_name("submit")
+            .about("Send a transaction message displayed in sign-only mode, with the signatures collected for it. \
+                    Missing signatures are added from the local signers.")
             .arg(
-                Arg::with_name("pool")
+                Arg::with_name("message")
                     .index(1)
-                    .validator(is_pubkey)
-                    .value_name("POOL_ADDRESS")
+                    .value_name("MESSAGE")
                     .takes_value(true)
                     .required(true)
-                    .help("Stake pool address."),
-            )
-            .arg(Arg::with_name("fee_type")
-                .index(2)
-                .value_name("FEE_TYPE")
-                .possible_values(&["stake", "sol"]) // FeeType enum, kind of
-                .takes_value(true)
-                .required(true)
-                .help("Fee type to be updated."),
+                    .help("Base58 or base64 serialized transaction message"),
             )
             .arg(
-                Arg::with_name("fee")
-                    .index(3)
-                    .validator(is_valid_percentage)
-                    .value_name("FEE_PERCENTAGE")
+                Arg::with_name("signatures")
+                    .index(2)
+                    .validator(is_pubkey_sig)
+                    .value_name("PUBKEY=SIGNATURE")
                     .takes_value(true)
-                    .required(true)
-                    .help("Fee percentage, maximum 100"),
+                    .multiple(true)
+                    .help("Signatures of the transaction message, given with the public key of their signer"),
             )
         )
         .subcommand(SubCommand::with_name("list-all")
@@ -3188,6 +5227,9 @@ fn main() {
     let config = {
         let json_rpc_url = value_t!(matches, "json_rpc_url", String)
             .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
+        // signers missing from this side only need their pubkey in sign-only
+        // mode, with their signature given with --signer otherwise
+        let sign_only = matches.is_present(SIGN_ONLY_ARG.name);
 
         let staker = get_signer(
             &matches,
@@ -3195,7 +5237,7 @@ fn main() {
             default_keypair_path,
             &mut wallet_manager,
             SignerFromPathConfig {
-                allow_null_signer: false,
+                allow_null_signer: sign_only,
             },
         );
 
@@ -3206,7 +5248,7 @@ fn main() {
                 default_keypair_path,
                 &mut wallet_manager,
                 SignerFromPathConfig {
-                    allow_null_signer: false,
+                    allow_null_signer: sign_only,
                 },
             ))
         } else {
@@ -3218,7 +5260,7 @@ fn main() {
             default_keypair_path,
             &mut wallet_manager,
             SignerFromPathConfig {
-                allow_null_signer: false,
+                allow_null_signer: sign_only,
             },
         );
         let token_owner = get_signer(
@@ -3227,7 +5269,7 @@ fn main() {
             default_keypair_path,
             &mut wallet_manager,
             SignerFromPathConfig {
-                allow_null_signer: false,
+                allow_null_signer: sign_only,
             },
         );
         let fee_payer = get_signer(
@@ -3236,7 +5278,7 @@ fn main() {
             default_keypair_path,
             &mut wallet_manager,
             SignerFromPathConfig {
-                allow_null_signer: false,
+                allow_null_signer: sign_only,
             },
         );
         let verbose = matches.is_present("verbose");
@@ -3254,6 +5296,15 @@ fn main() {
             } else {
                 OutputFormat::Display
             });
+        let blockhash = value_of::<Hash>(&matches, BLOCKHASH_ARG.name);
+        let dump_transaction_message =
+            matches.is_present(DUMP_TRANSACTION_MESSAGE.name).then(|| {
+                match matches.value_of("message_encoding") {
+                    Some("base58") => MessageEncoding::Base58,
+                    _ => MessageEncoding::Base64,
+                }
+            });
+        let presigners = pubkeys_sigs_of(&matches, SIGNER_ARG.name).unwrap_or_default();
         let dry_run = matches.is_present("dry_run");
         let no_update = matches.is_present("no_update");
         let compute_unit_price = value_t!(matches, COMPUTE_UNIT_PRICE_ARG.name, u64).ok();
@@ -3285,6 +5336,10 @@ fn main() {
             no_update,
             compute_unit_price,
             compute_unit_limit,
+            sign_only,
+            blockhash,
+            dump_transaction_message,
+            presigners,
         }
     };
 
@@ -3352,6 +5407,16 @@ fn main() {
             let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
             command_vsa_remove(&config, &stake_pool_address, &vote_account)
         }
+        ("add-validators", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let file = arg_matches.value_of("file").unwrap();
+            command_vsa_add_from_file(&config, &stake_pool_address, file)
+        }
+        ("remove-validators", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let file = arg_matches.value_of("file").unwrap();
+            command_vsa_remove_from_file(&config, &stake_pool_address, file)
+        }
         ("increase-validator-stake", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
@@ -3366,6 +5431,31 @@ fn main() {
             let lamports = native_token::sol_str_to_lamports(amount_str).unwrap();
             command_decrease_validator_stake(&config, &stake_pool_address, &vote_account, lamports)
         }
+        ("rebalance", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let allocation = match arg_matches.value_of("weights_file") {
+                Some(path) => Allocation::Weights(read_weights_file(path).unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    exit(1);
+                })),
+                None if arg_matches.value_of("allocation") == Some("stake-weighted") => {
+                    Allocation::StakeWeighted
+                }
+                None => Allocation::Equal,
+            };
+            let retained_reserve = arg_matches
+                .value_of("reserve_amount")
+                .map(|amount| native_token::sol_str_to_lamports(amount).unwrap())
+                .unwrap_or(0);
+            let execute = arg_matches.is_present("execute");
+            command_rebalance(
+                &config,
+                &stake_pool_address,
+                allocation,
+                retained_reserve,
+                execute,
+            )
+        }
         ("set-preferred-validator", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let preferred_type = match arg_matches.value_of("preferred_type").unwrap() {
@@ -3389,6 +5479,14 @@ fn main() {
             let stake_account = pubkey_of(arg_matches, "stake_account").unwrap();
             let token_receiver: Option<Pubkey> = pubkey_of(arg_matches, "token_receiver");
             let referrer: Option<Pubkey> = pubkey_of(arg_matches, "referrer");
+            let depositors = arg_matches
+                .value_of("depositors_file")
+                .map(read_depositors_file)
+                .transpose()
+                .unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    exit(1);
+                });
             let withdraw_authority = get_signer(
                 arg_matches,
                 "withdraw_authority",
@@ -3405,12 +5503,21 @@ fn main() {
                 withdraw_authority,
                 &token_receiver,
                 &referrer,
+                &depositors,
             )
         }
         ("deposit-sol", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let token_receiver: Option<Pubkey> = pubkey_of(arg_matches, "token_receiver");
             let referrer: Option<Pubkey> = pubkey_of(arg_matches, "referrer");
+            let depositors = arg_matches
+                .value_of("depositors_file")
+                .map(read_depositors_file)
+                .transpose()
+                .unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    exit(1);
+                });
             let from = keypair_of(arg_matches, "from");
             let amount_str = arg_matches.value_of("amount").unwrap();
             let lamports = native_token::sol_str_to_lamports(amount_str).unwrap();
@@ -3421,6 +5528,7 @@ fn main() {
                 &token_receiver,
                 &referrer,
                 lamports,
+                &depositors,
             )
         }
         ("list", Some(arg_matches)) => {
@@ -3428,6 +5536,51 @@ fn main() {
             let sort_by = arg_matches.value_of("sort");
             command_list(&config, &stake_pool_address, sort_by)
         }
+        ("export", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let file = arg_matches.value_of("file");
+            let format = arg_matches.value_of("format").unwrap_or(
+                if file.is_some_and(|file| file.ends_with(".json")) {
+                    "json"
+                } else {
+                    "csv"
+                },
+            );
+            let sort_by = arg_matches.value_of("sort");
+            let statuses = arg_matches
+                .values_of("status")
+                .map(|statuses| {
+                    statuses
+                        .map(|status| match status {
+                            "active" => StakeStatus::Active,
+                            "deactivating-transient" => StakeStatus::DeactivatingTransient,
+                            "ready-for-removal" => StakeStatus::ReadyForRemoval,
+                            "deactivating-validator" => StakeStatus::DeactivatingValidator,
+                            "deactivating-all" => StakeStatus::DeactivatingAll,
+                            _ => unreachable!(),
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            let min_stake_lamports = arg_matches
+                .value_of("min_stake")
+                .map(|amount| native_token::sol_str_to_lamports(amount).unwrap());
+            let max_commission = value_t!(arg_matches, "max_commission", u8).ok();
+            command_export(
+                &config,
+                &stake_pool_address,
+                file,
+                format,
+                sort_by,
+                &statuses,
+                min_stake_lamports,
+                max_commission,
+            )
+        }
+        ("audit", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_audit(&config, &stake_pool_address)
+        }
         ("update", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let no_merge = arg_matches.is_present("no_merge");
@@ -3435,6 +5588,21 @@ fn main() {
             let stale_only = arg_matches.is_present("stale_only");
             command_update(&config, &stake_pool_address, force, no_merge, stale_only)
         }
+        ("crank", Some(arg_matches)) => {
+            let stake_pool_addresses = pubkeys_of(arg_matches, "pools").unwrap();
+            let interval = Duration::from_secs(value_t_or_exit!(arg_matches, "interval", u64));
+            let max_retries = value_t_or_exit!(arg_matches, "max_retries", u32);
+            let metrics_file = arg_matches.value_of("metrics_file");
+            let once = arg_matches.is_present("once");
+            command_crank(
+                &config,
+                &stake_pool_addresses,
+                interval,
+                max_retries,
+                metrics_file,
+                once,
+            )
+        }
         ("withdraw-stake", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let vote_account = pubkey_of(arg_matches, "vote_account");
@@ -3474,6 +5642,37 @@ fn main() {
                 pool_amount,
             )
         }
+        ("quote", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let operation = arg_matches.value_of("operation").unwrap();
+            let amount = arg_matches.value_of("amount").unwrap();
+            let pool_account = pubkey_of(arg_matches, "pool_account");
+            let withdraw_source = if arg_matches.is_present("use_reserve") {
+                WithdrawSource::Reserve
+            } else if let Some(vote_account) = pubkey_of(arg_matches, "vote_account") {
+                WithdrawSource::Validator(vote_account)
+            } else {
+                WithdrawSource::Any
+            };
+            command_quote(
+                &config,
+                &stake_pool_address,
+                operation,
+                amount,
+                withdraw_source,
+                &pool_account,
+            )
+        }
+        ("plan", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let config_file = arg_matches.value_of("pool_config_file").unwrap();
+            command_pool_config(&config, &stake_pool_address, config_file, false)
+        }
+        ("apply", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let config_file = arg_matches.value_of("pool_config_file").unwrap();
+            command_pool_config(&config, &stake_pool_address, config_file, true)
+        }
         ("set-manager", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
 
@@ -3563,12 +5762,95 @@ fn main() {
             };
             command_set_fee(&config, &stake_pool_address, fee_type)
         }
+        ("resize-validator-list", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let max_validators = value_t_or_exit!(arg_matches, "max_validators", u32);
+            command_resize_validator_list(&config, &stake_pool_address, max_validators)
+        }
+        ("shrink-validator-list", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let max_validators = value_t!(arg_matches, "max_validators", u32).ok();
+            let destination = pubkey_of(arg_matches, "destination");
+            command_shrink_validator_list(&config, &stake_pool_address, max_validators, destination)
+        }
+        ("close-pool", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let destination = pubkey_of(arg_matches, "destination");
+            let new_mint_authority = pubkey_of(arg_matches, "new_mint_authority");
+            command_close_pool(
+                &config,
+                &stake_pool_address,
+                destination,
+                new_mint_authority,
+            )
+        }
+        ("migrate-pool", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_migrate_pool(&config, &stake_pool_address)
+        }
+        ("set-partial-update-haircut", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let haircut = if arg_matches.is_present("unset") {
+                None
+            } else {
+                Some(Fee {
+                    numerator: value_t_or_exit!(arg_matches, "haircut_numerator", u64),
+                    denominator: value_t_or_exit!(arg_matches, "haircut_denominator", u64),
+                })
+            };
+            command_set_partial_update_haircut(&config, &stake_pool_address, haircut)
+        }
+        ("set-transfer-hook-programs", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let program_ids = pubkeys_of(arg_matches, "program_ids").unwrap_or_default();
+            command_set_transfer_hook_programs(&config, &stake_pool_address, program_ids)
+        }
+        ("set-depositor-allowlist", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let enabled = !arg_matches.is_present("disable");
+            command_set_depositor_allowlist(&config, &stake_pool_address, enabled)
+        }
+        ("add-depositor", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let depositor = pubkey_of(arg_matches, "depositor").unwrap();
+            command_add_depositor(&config, &stake_pool_address, &depositor)
+        }
+        ("remove-depositor", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let depositor = pubkey_of(arg_matches, "depositor").unwrap();
+            command_remove_depositor(&config, &stake_pool_address, &depositor)
+        }
+        ("set-depositor-merkle-root", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let depositors = arg_matches
+                .value_of("depositors_file")
+                .map(read_depositors_file)
+                .transpose()
+                .unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    exit(1);
+                });
+            command_set_depositor_merkle_root(&config, &stake_pool_address, depositors)
+        }
+        ("submit", Some(arg_matches)) => {
+            let message = arg_matches.value_of("message").unwrap();
+            let signatures = pubkeys_sigs_of(arg_matches, "signatures").unwrap_or_default();
+            command_submit(&config, message, &signatures)
+        }
         ("list-all", _) => command_list_all_pools(&config),
         ("deposit-all-stake", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let stake_authority = pubkey_of(arg_matches, "stake_authority").unwrap();
             let token_receiver: Option<Pubkey> = pubkey_of(arg_matches, "token_receiver");
             let referrer: Option<Pubkey> = pubkey_of(arg_matches, "referrer");
+            let depositors = arg_matches
+                .value_of("depositors_file")
+                .map(read_depositors_file)
+                .transpose()
+                .unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    exit(1);
+                });
             let withdraw_authority = get_signer(
                 arg_matches,
                 "withdraw_authority",
@@ -3585,6 +5867,7 @@ fn main() {
                 withdraw_authority,
                 &token_receiver,
                 &referrer,
+                &depositors,
             )
         }
         _ => unreachable!(),
@@ -414,6 +414,18 @@ pub(crate) enum CliStakePoolValidatorStakeStatus {
     DeactivatingAll,
 }
 
+impl Display for CliStakePoolValidatorStakeStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Active => write!(f, "Active"),
+            Self::DeactivatingTransient => write!(f, "DeactivatingTransient"),
+            Self::ReadyForRemoval => write!(f, "ReadyForRemoval"),
+            Self::DeactivatingValidator => write!(f, "DeactivatingValidator"),
+            Self::DeactivatingAll => write!(f, "DeactivatingAll"),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CliStakePoolLockup {
@@ -432,6 +444,293 @@ impl From<Lockup> for CliStakePoolLockup {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliAudit {
+    pub stake_pool: String,
+    pub epoch: u64,
+    pub total_lamports: u64,
+    pub counted_lamports: u64,
+    pub pool_token_supply: u64,
+    pub mint_supply: u64,
+    pub stake_accounts: usize,
+    pub issues: Vec<CliAuditIssue>,
+}
+
+impl Display for CliAudit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "Stake Pool: {}", &self.stake_pool)?;
+        writeln!(
+            f,
+            "Total Lamports: {}\tCounted Lamports: {}",
+            Sol(self.total_lamports),
+            Sol(self.counted_lamports),
+        )?;
+        writeln!(
+            f,
+            "Pool Token Supply: {}\tMint Supply: {}",
+            self.pool_token_supply, self.mint_supply,
+        )?;
+        writeln!(f, "Stake Accounts Checked: {}", self.stake_accounts)?;
+        if self.issues.is_empty() {
+            writeln!(f, "No issues found")?;
+        }
+        for issue in &self.issues {
+            writeln!(f, "[{}] {}", issue.check, issue.description)?;
+            if let Some(address) = &issue.address {
+                writeln!(f, "  Address: {}", address)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliAudit {}
+impl VerboseDisplay for CliAudit {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliAuditIssue {
+    pub check: String,
+    pub address: Option<String>,
+    pub description: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliCrankMetrics {
+    pub timestamp: u64,
+    pub stake_pool: String,
+    pub epoch: u64,
+    pub last_update_epoch: u64,
+    pub update_required: bool,
+    pub attempts: u32,
+    pub transactions_sent: u64,
+    pub transactions_failed: u64,
+    pub stale_validators: usize,
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+    pub healthy: bool,
+    pub error: Option<String>,
+}
+
+impl Display for CliCrankMetrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(
+            f,
+            "Stake Pool: {}\tEpoch: {}\tLast Update Epoch: {}\tHealthy: {}",
+            &self.stake_pool, self.epoch, self.last_update_epoch, self.healthy,
+        )?;
+        if let Some(error) = &self.error {
+            writeln!(f, "Error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliCrankMetrics {}
+impl VerboseDisplay for CliCrankMetrics {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliValidatorChanges {
+    pub changes: Vec<CliValidatorChange>,
+}
+
+impl Display for CliValidatorChanges {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for change in &self.changes {
+            write!(f, "{}: {}", change.vote_account_address, change.status)?;
+            if let Some(signature) = &change.signature {
+                write!(f, "\tSignature: {}", signature)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliValidatorChanges {}
+impl VerboseDisplay for CliValidatorChanges {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliValidatorChange {
+    pub vote_account_address: String,
+    pub status: String,
+    pub signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliPoolChanges {
+    pub changes: Vec<CliPoolChange>,
+}
+
+impl Display for CliPoolChanges {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "Stake pool matches its configuration");
+        }
+        for change in &self.changes {
+            write!(
+                f,
+                "{} (signed by the {}): {}",
+                change.description, change.signer, change.status
+            )?;
+            if let Some(signature) = &change.signature {
+                write!(f, "\tSignature: {}", signature)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliPoolChanges {}
+impl VerboseDisplay for CliPoolChanges {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliPoolChange {
+    pub description: String,
+    pub signer: String,
+    pub status: String,
+    pub signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliValidatorExport {
+    pub vote_account_address: String,
+    pub status: CliStakePoolValidatorStakeStatus,
+    pub active_stake_lamports: u64,
+    pub transient_stake_lamports: u64,
+    pub share_of_pool: f64,
+    pub commission: Option<u8>,
+    pub last_epoch_credits: Option<u64>,
+    pub stake_account_address: String,
+    pub transient_stake_account_address: String,
+    pub last_update_epoch: u64,
+}
+
+impl CliValidatorExport {
+    /// Header of the CSV export, with the vote account address first so that
+    /// the file can be read back by `add-validators --file`
+    pub const CSV_HEADER: &'static str = "vote_account_address,status,active_stake_lamports,\
+        transient_stake_lamports,share_of_pool,commission,last_epoch_credits,\
+        stake_account_address,transient_stake_account_address,last_update_epoch";
+
+    /// Line of the CSV export, leaving unknown values empty
+    pub fn csv_row(&self) -> String {
+        let or_empty = |value: Option<String>| value.unwrap_or_default();
+        format!(
+            "{},{},{},{},{:.4},{},{},{},{},{}",
+            self.vote_account_address,
+            self.status,
+            self.active_stake_lamports,
+            self.transient_stake_lamports,
+            self.share_of_pool,
+            or_empty(self.commission.map(|commission| commission.to_string())),
+            or_empty(self.last_epoch_credits.map(|credits| credits.to_string())),
+            self.stake_account_address,
+            self.transient_stake_account_address,
+            self.last_update_epoch,
+        )
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliQuote {
+    pub operation: String,
+    pub pool_mint_decimals: u8,
+    pub lamports: u64,
+    pub pool_tokens: u64,
+    pub pool_tokens_withheld: u64,
+    pub fee: u64,
+    pub referral_fee: u64,
+    pub transfer_fee: u64,
+    pub stake_accounts: Vec<CliQuoteStakeAccount>,
+}
+
+impl CliQuote {
+    fn ui_amount(&self, amount: u64) -> f64 {
+        spl_token::amount_to_ui_amount(amount, self.pool_mint_decimals)
+    }
+}
+
+impl Display for CliQuote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if self.operation.starts_with("deposit") {
+            writeln!(f, "Deposit: {}", Sol(self.lamports))?;
+            writeln!(
+                f,
+                "Pool tokens received: {}",
+                self.ui_amount(self.pool_tokens)
+            )?;
+            writeln!(
+                f,
+                "Deposit fee: {} pool tokens, of which {} to the referrer",
+                self.ui_amount(self.fee),
+                self.ui_amount(self.referral_fee),
+            )?;
+            if self.pool_tokens_withheld > 0 {
+                writeln!(
+                    f,
+                    "Withheld until the pool is updated: {} pool tokens",
+                    self.ui_amount(self.pool_tokens_withheld),
+                )?;
+            }
+        } else {
+            writeln!(
+                f,
+                "Pool tokens withdrawn: {}",
+                self.ui_amount(self.pool_tokens)
+            )?;
+            writeln!(
+                f,
+                "Withdrawal fee: {} pool tokens",
+                self.ui_amount(self.fee)
+            )?;
+            if self.transfer_fee > 0 {
+                writeln!(
+                    f,
+                    "Transfer fee withheld from the withdrawal fee: {} pool tokens",
+                    self.ui_amount(self.transfer_fee),
+                )?;
+            }
+            writeln!(f, "Received: {}", Sol(self.lamports))?;
+            for stake_account in &self.stake_accounts {
+                writeln!(
+                    f,
+                    "Stake Account: {}\tVote Account: {}\tPool Tokens: {}\tBalance: {}",
+                    stake_account.stake_account_address,
+                    stake_account
+                        .vote_account_address
+                        .as_deref()
+                        .unwrap_or("reserve"),
+                    self.ui_amount(stake_account.pool_tokens),
+                    Sol(stake_account.lamports),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliQuote {}
+impl VerboseDisplay for CliQuote {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliQuoteStakeAccount {
+    pub stake_account_address: String,
+    pub vote_account_address: Option<String>,
+    pub pool_tokens: u64,
+    pub lamports: u64,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct CliStakePoolFee {
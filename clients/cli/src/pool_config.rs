@@ -0,0 +1,132 @@
+//! File format of the declarative configuration of a stake pool, in TOML or
+//! JSON
+
+use {
+    crate::client::Error,
+    serde::Deserialize,
+    solana_program::pubkey::Pubkey,
+    spl_stake_pool::state::{Fee, FeeType},
+    spl_stake_pool_client::pool_config::PoolConfig,
+};
+
+/// Value unsetting an optional authority or preferred validator
+const NONE: &str = "none";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PoolConfigFee {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl From<PoolConfigFee> for Fee {
+    fn from(fee: PoolConfigFee) -> Self {
+        Self {
+            numerator: fee.numerator,
+            denominator: fee.denominator,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PoolConfigFile {
+    staker: Option<String>,
+    stake_deposit_authority: Option<String>,
+    sol_deposit_authority: Option<String>,
+    sol_withdraw_authority: Option<String>,
+    preferred_deposit_validator: Option<String>,
+    preferred_withdraw_validator: Option<String>,
+    epoch_fee: Option<PoolConfigFee>,
+    stake_deposit_fee: Option<PoolConfigFee>,
+    sol_deposit_fee: Option<PoolConfigFee>,
+    stake_withdrawal_fee: Option<PoolConfigFee>,
+    sol_withdrawal_fee: Option<PoolConfigFee>,
+    stake_referral_fee: Option<u8>,
+    sol_referral_fee: Option<u8>,
+    validators: Option<Vec<String>>,
+}
+
+/// Read the configuration of a stake pool from a JSON file, or a TOML file
+/// otherwise. Settings left out are not managed, and optional authorities
+/// and preferred validators are unset with `"none"`.
+pub(crate) fn read_pool_config_file(path: &str) -> Result<PoolConfig, Error> {
+    let contents = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let file: PoolConfigFile = if path.ends_with(".json") {
+        serde_json::from_str(&contents).map_err(|err| format!("{}: {}", path, err))?
+    } else {
+        toml::from_str(&contents).map_err(|err| format!("{}: {}", path, err))?
+    };
+
+    let pubkey = |field: &str, value: &str| -> Result<Pubkey, Error> {
+        value
+            .parse::<Pubkey>()
+            .map_err(|_| format!("{}: invalid {} \"{}\"", path, field, value).into())
+    };
+    let optional_pubkey = |field: &str, value: Option<String>| {
+        value
+            .map(|value| match value.as_str() {
+                NONE => Ok(None),
+                value => pubkey(field, value).map(Some),
+            })
+            .transpose()
+    };
+
+    let fees = [
+        file.epoch_fee.map(|fee| FeeType::Epoch(fee.into())),
+        file.stake_deposit_fee
+            .map(|fee| FeeType::StakeDeposit(fee.into())),
+        file.sol_deposit_fee
+            .map(|fee| FeeType::SolDeposit(fee.into())),
+        file.stake_withdrawal_fee
+            .map(|fee| FeeType::StakeWithdrawal(fee.into())),
+        file.sol_withdrawal_fee
+            .map(|fee| FeeType::SolWithdrawal(fee.into())),
+        file.stake_referral_fee.map(FeeType::StakeReferral),
+        file.sol_referral_fee.map(FeeType::SolReferral),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    for fee in &fees {
+        fee.check_too_high()
+            .map_err(|_| format!("{}: fee greater than 100%: {:?}", path, fee))?;
+    }
+
+    Ok(PoolConfig {
+        staker: file
+            .staker
+            .map(|staker| pubkey("staker", &staker))
+            .transpose()?,
+        stake_deposit_authority: optional_pubkey(
+            "stake deposit authority",
+            file.stake_deposit_authority,
+        )?,
+        sol_deposit_authority: optional_pubkey(
+            "SOL deposit authority",
+            file.sol_deposit_authority,
+        )?,
+        sol_withdraw_authority: optional_pubkey(
+            "SOL withdraw authority",
+            file.sol_withdraw_authority,
+        )?,
+        preferred_deposit_validator: optional_pubkey(
+            "preferred deposit validator",
+            file.preferred_deposit_validator,
+        )?,
+        preferred_withdraw_validator: optional_pubkey(
+            "preferred withdraw validator",
+            file.preferred_withdraw_validator,
+        )?,
+        fees,
+        validators: file
+            .validators
+            .map(|validators| {
+                validators
+                    .iter()
+                    .map(|validator| pubkey("validator", validator))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?,
+    })
+}
@@ -465,6 +465,8 @@ export class StakePoolInstruction {
 
   /**
    * Creates instruction to update a set of validators in the stake pool.
+   * The stake pool is writable so that migrated pools can tally the update,
+   * so the updates of one pool are executed one after another.
    */
   static updateValidatorListBalance(
     params: UpdateValidatorListBalanceParams,
@@ -484,7 +486,7 @@ export class StakePoolInstruction {
     const data = encodeData(type, { startIndex, noMerge: noMerge ? 1 : 0 });
 
     const keys = [
-      { pubkey: stakePool, isSigner: false, isWritable: false },
+      { pubkey: stakePool, isSigner: false, isWritable: true },
       { pubkey: withdrawAuthority, isSigner: false, isWritable: false },
       { pubkey: validatorList, isSigner: false, isWritable: true },
       { pubkey: reserveStake, isSigner: false, isWritable: true },
@@ -25,6 +25,7 @@ export enum AccountType {
   Uninitialized,
   StakePool,
   ValidatorList,
+  StakePoolV2,
 }
 
 export const BigNumFromString = coerce(instance(BN), string(), (value) => {
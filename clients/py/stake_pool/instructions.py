@@ -262,7 +262,7 @@ class UpdateValidatorListBalanceParams(NamedTuple):
     program_id: Pubkey
     """SPL Stake Pool program account."""
     stake_pool: Pubkey
-    """`[]` Stake pool."""
+    """`[w]` Stake pool."""
     withdraw_authority: Pubkey
     """`[]` Stake pool withdraw authority."""
     validator_list: Pubkey
@@ -1002,7 +1002,7 @@ def withdraw_sol(params: WithdrawSolParams) -> Instruction:
 def update_validator_list_balance(params: UpdateValidatorListBalanceParams) -> Instruction:
     """Creates instruction to update a set of validators in the stake pool."""
     accounts = [
-        AccountMeta(pubkey=params.stake_pool, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=params.stake_pool, is_signer=False, is_writable=True),
         AccountMeta(pubkey=params.withdraw_authority, is_signer=False, is_writable=False),
         AccountMeta(pubkey=params.validator_list, is_signer=False, is_writable=True),
         AccountMeta(pubkey=params.reserve_stake, is_signer=False, is_writable=True),
@@ -0,0 +1,133 @@
+//! Readers of the accounts used by stake pools
+
+use {
+    crate::{Error, StakePoolRpc},
+    solana_borsh::v1::try_from_slice_unchecked,
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    solana_stake_interface::state::StakeStateV2,
+    spl_stake_pool::state::{StakePool, StakePoolConfig, ValidatorList},
+    spl_token_2022_interface::{
+        extension::{
+            transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions,
+            StateWithExtensions,
+        },
+        state::{Account, Mint},
+    },
+};
+
+/// Get the data of an account, failing if it doesn't exist
+pub async fn get_account_data<R: StakePoolRpc>(
+    rpc: &R,
+    address: &Pubkey,
+) -> Result<Vec<u8>, Error> {
+    rpc.get_account(address)
+        .await?
+        .map(|account| account.data)
+        .ok_or_else(|| format!("Account {} does not exist", address).into())
+}
+
+/// Get a stake pool
+pub async fn get_stake_pool<R: StakePoolRpc>(
+    rpc: &R,
+    stake_pool_address: &Pubkey,
+) -> Result<StakePool, Error> {
+    let account_data = get_account_data(rpc, stake_pool_address).await?;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(account_data.as_slice())
+        .map_err(|err| format!("Invalid stake pool {}: {}", stake_pool_address, err))?;
+    Ok(stake_pool)
+}
+
+/// Get the configuration of a stake pool, which is the default configuration
+/// if the pool wasn't migrated
+pub async fn get_stake_pool_config<R: StakePoolRpc>(
+    rpc: &R,
+    stake_pool_address: &Pubkey,
+) -> Result<StakePoolConfig, Error> {
+    let account_data = get_account_data(rpc, stake_pool_address).await?;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(account_data.as_slice())
+        .map_err(|err| format!("Invalid stake pool {}: {}", stake_pool_address, err))?;
+    let config = StakePoolConfig::load(&stake_pool, &account_data)
+        .map_err(|err| format!("Invalid stake pool {}: {}", stake_pool_address, err))?;
+    Ok(config)
+}
+
+/// Get a validator list
+pub async fn get_validator_list<R: StakePoolRpc>(
+    rpc: &R,
+    validator_list_address: &Pubkey,
+) -> Result<ValidatorList, Error> {
+    let account_data = get_account_data(rpc, validator_list_address).await?;
+    let validator_list = try_from_slice_unchecked::<ValidatorList>(account_data.as_slice())
+        .map_err(|err| format!("Invalid validator list {}: {}", validator_list_address, err))?;
+    Ok(validator_list)
+}
+
+/// Get a token account, checking its mint
+pub async fn get_token_account<R: StakePoolRpc>(
+    rpc: &R,
+    token_account_address: &Pubkey,
+    expected_token_mint: &Pubkey,
+) -> Result<Account, Error> {
+    let account_data = get_account_data(rpc, token_account_address).await?;
+    let token_account = Account::unpack_from_slice(account_data.as_slice())
+        .map_err(|err| format!("Invalid token account {}: {}", token_account_address, err))?;
+
+    if token_account.mint != *expected_token_mint {
+        Err(format!(
+            "Invalid token mint for {}, expected mint is {}",
+            token_account_address, expected_token_mint
+        )
+        .into())
+    } else {
+        Ok(token_account)
+    }
+}
+
+/// Get a token mint
+pub async fn get_token_mint<R: StakePoolRpc>(
+    rpc: &R,
+    token_mint_address: &Pubkey,
+) -> Result<Mint, Error> {
+    let account_data = get_account_data(rpc, token_mint_address).await?;
+    let token_mint = Mint::unpack_from_slice(account_data.as_slice())
+        .map_err(|err| format!("Invalid token mint {}: {}", token_mint_address, err))?;
+    Ok(token_mint)
+}
+
+/// Get the state of a stake account
+pub async fn get_stake_state<R: StakePoolRpc>(
+    rpc: &R,
+    stake_address: &Pubkey,
+) -> Result<StakeStateV2, Error> {
+    let account_data = get_account_data(rpc, stake_address).await?;
+    let stake_state = bincode::deserialize(account_data.as_slice())
+        .map_err(|err| format!("Invalid stake account {}: {}", stake_address, err))?;
+    Ok(stake_state)
+}
+
+/// Get the transfer hook program of the pool mint, if it has one
+pub async fn get_transfer_hook_program_id<R: StakePoolRpc>(
+    rpc: &R,
+    stake_pool: &StakePool,
+) -> Result<Option<Pubkey>, Error> {
+    if stake_pool.token_program_id != spl_token_2022_interface::id() {
+        return Ok(None);
+    }
+    let account_data = get_account_data(rpc, &stake_pool.pool_mint).await?;
+    let pool_mint = StateWithExtensions::<Mint>::unpack(&account_data)?;
+    Ok(transfer_hook::get_program_id(&pool_mint))
+}
+
+/// Get the transfer fee of the pool mint, if it has one
+pub async fn get_transfer_fee_config<R: StakePoolRpc>(
+    rpc: &R,
+    stake_pool: &StakePool,
+) -> Result<Option<TransferFeeConfig>, Error> {
+    if stake_pool.token_program_id != spl_token_2022_interface::id() {
+        return Ok(None);
+    }
+    let account_data = get_account_data(rpc, &stake_pool.pool_mint).await?;
+    let pool_mint = StateWithExtensions::<Mint>::unpack(&account_data)?;
+    Ok(pool_mint.get_extension::<TransferFeeConfig>().ok().copied())
+}
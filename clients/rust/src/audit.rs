@@ -0,0 +1,467 @@
+//! Audits of the invariants of a stake pool.
+//!
+//! [`AuditState`] holds the accounts of a pool, and [`AuditState::audit`]
+//! reports every way in which they disagree with each other: lamports that
+//! don't add up to the pool's total, a pool mint supply that differs from the
+//! pool token supply, stake accounts missing or delegated elsewhere, and stake
+//! accounts held by the pool that it doesn't know of.
+
+use {
+    crate::withdraw::PoolStakeAccount,
+    solana_clock::Epoch,
+    solana_pubkey::Pubkey,
+    solana_stake_interface::state::StakeStateV2,
+    spl_stake_pool::{
+        find_stake_program_address, find_transient_stake_program_address,
+        find_withdraw_authority_program_address, minimum_reserve_lamports,
+        state::{StakePool, StakeStatus, ValidatorList},
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        num::NonZeroU32,
+    },
+};
+
+/// Invariant of a stake pool that its accounts break
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuditIssue {
+    /// The pool wasn't updated in the current epoch, so its balances may not
+    /// include the latest rewards
+    PoolNotUpdated {
+        /// Epoch of the last update
+        last_update_epoch: Epoch,
+    },
+    /// The lamports of the validator list and of the reserve don't add up to
+    /// the total lamports of the pool
+    TotalLamportsMismatch {
+        /// Total lamports of the pool
+        total_lamports: u64,
+        /// Lamports of the validator list and of the reserve, beyond its
+        /// minimum balance
+        counted_lamports: u64,
+    },
+    /// The supply of the pool mint differs from the pool token supply
+    PoolTokenSupplyMismatch {
+        /// Pool token supply of the pool
+        pool_token_supply: u64,
+        /// Supply of the pool mint
+        mint_supply: u64,
+    },
+    /// A stake account of the pool doesn't exist
+    MissingStakeAccount {
+        /// Account of the pool
+        account: PoolStakeAccount,
+        /// Expected address of the stake account
+        address: Pubkey,
+    },
+    /// A stake account of the pool isn't delegated as expected: the reserve
+    /// is delegated, or a validator stake account is delegated to another vote
+    /// account
+    WrongDelegation {
+        /// Account of the pool
+        account: PoolStakeAccount,
+        /// Address of the stake account
+        address: Pubkey,
+        /// Vote account the stake is delegated to, if any
+        delegated_to: Option<Pubkey>,
+    },
+    /// The authorities of a stake account of the pool aren't the pool
+    /// withdraw authority
+    WrongAuthority {
+        /// Account of the pool
+        account: PoolStakeAccount,
+        /// Address of the stake account
+        address: Pubkey,
+        /// Stake authority of the account, none if it isn't initialized
+        staker: Option<Pubkey>,
+        /// Withdraw authority of the account, none if it isn't initialized
+        withdrawer: Option<Pubkey>,
+    },
+    /// A stake account held by the pool withdraw authority isn't one of the
+    /// pool's stake accounts
+    StrayStakeAccount {
+        /// Address of the stake account
+        address: Pubkey,
+        /// Lamports of the stake account
+        lamports: u64,
+    },
+}
+
+/// Accounts of a stake pool that its invariants are checked against
+#[derive(Clone, Debug)]
+pub struct AuditState {
+    /// The stake pool
+    pub stake_pool: StakePool,
+    /// Its validator list
+    pub validator_list: ValidatorList,
+    /// The current epoch
+    pub epoch: Epoch,
+    /// Lamports of the reserve stake account
+    pub reserve_lamports: u64,
+    /// Rent exemption of a stake account
+    pub stake_rent_exemption: u64,
+    /// Supply of the pool mint
+    pub mint_supply: u64,
+    /// States of the stake accounts of the pool that exist, by address
+    pub stake_accounts: HashMap<Pubkey, StakeStateV2>,
+    /// Addresses and lamports of the stake accounts whose withdraw authority
+    /// is the pool withdraw authority
+    pub withdraw_authority_stake_accounts: Vec<(Pubkey, u64)>,
+}
+
+impl AuditState {
+    /// Stake accounts that the pool should hold, with their addresses: the
+    /// reserve, the stake account of every validator in the list, and the
+    /// transient stake account of every validator with transient stake
+    pub fn pool_stake_accounts(
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+        stake_pool: &StakePool,
+        validator_list: &ValidatorList,
+    ) -> Vec<(PoolStakeAccount, Pubkey)> {
+        let mut accounts = vec![(PoolStakeAccount::Reserve, stake_pool.reserve_stake)];
+        for info in &validator_list.validators {
+            let (stake_address, _) = find_stake_program_address(
+                program_id,
+                &info.vote_account_address,
+                stake_pool_address,
+                NonZeroU32::new(info.validator_seed_suffix.into()),
+            );
+            accounts.push((
+                PoolStakeAccount::Validator(info.vote_account_address),
+                stake_address,
+            ));
+            if u64::from(info.transient_stake_lamports) > 0 {
+                let (transient_stake_address, _) = find_transient_stake_program_address(
+                    program_id,
+                    &info.vote_account_address,
+                    stake_pool_address,
+                    info.transient_seed_suffix.into(),
+                );
+                accounts.push((
+                    PoolStakeAccount::Transient(info.vote_account_address),
+                    transient_stake_address,
+                ));
+            }
+        }
+        accounts
+    }
+
+    /// Lamports of the validator list and of the reserve beyond its minimum
+    /// balance, which the pool's total lamports are set to on update
+    pub fn counted_lamports(&self) -> u64 {
+        self.validator_list.validators.iter().fold(
+            self.reserve_lamports
+                .saturating_sub(minimum_reserve_lamports(self.stake_rent_exemption)),
+            |lamports, info| {
+                lamports
+                    .saturating_add(info.active_stake_lamports.into())
+                    .saturating_add(info.transient_stake_lamports.into())
+            },
+        )
+    }
+
+    /// Check every invariant of the pool, returning the issues found
+    pub fn audit(&self, program_id: &Pubkey, stake_pool_address: &Pubkey) -> Vec<AuditIssue> {
+        let mut issues = vec![];
+        if self.stake_pool.last_update_epoch < self.epoch {
+            issues.push(AuditIssue::PoolNotUpdated {
+                last_update_epoch: self.stake_pool.last_update_epoch,
+            });
+        }
+
+        let counted_lamports = self.counted_lamports();
+        if counted_lamports != self.stake_pool.total_lamports {
+            issues.push(AuditIssue::TotalLamportsMismatch {
+                total_lamports: self.stake_pool.total_lamports,
+                counted_lamports,
+            });
+        }
+
+        if self.mint_supply != self.stake_pool.pool_token_supply {
+            issues.push(AuditIssue::PoolTokenSupplyMismatch {
+                pool_token_supply: self.stake_pool.pool_token_supply,
+                mint_supply: self.mint_supply,
+            });
+        }
+
+        let (withdraw_authority, _) =
+            find_withdraw_authority_program_address(program_id, stake_pool_address);
+        let pool_stake_accounts = Self::pool_stake_accounts(
+            program_id,
+            stake_pool_address,
+            &self.stake_pool,
+            &self.validator_list,
+        );
+        for (account, address) in &pool_stake_accounts {
+            if let Some(issue) = self.audit_stake_account(*account, *address, &withdraw_authority) {
+                issues.push(issue);
+            }
+        }
+
+        let pool_stake_addresses = pool_stake_accounts
+            .iter()
+            .map(|(_, address)| *address)
+            .collect::<HashSet<_>>();
+        for (address, lamports) in &self.withdraw_authority_stake_accounts {
+            if !pool_stake_addresses.contains(address) {
+                issues.push(AuditIssue::StrayStakeAccount {
+                    address: *address,
+                    lamports: *lamports,
+                });
+            }
+        }
+        issues
+    }
+
+    fn audit_stake_account(
+        &self,
+        account: PoolStakeAccount,
+        address: Pubkey,
+        withdraw_authority: &Pubkey,
+    ) -> Option<AuditIssue> {
+        let Some(stake_state) = self.stake_accounts.get(&address) else {
+            // the stake account of a validator being removed is merged into
+            // the reserve once deactivated
+            let removed = match account {
+                PoolStakeAccount::Validator(vote_account_address) => self
+                    .validator_list
+                    .find(&vote_account_address)
+                    .is_some_and(|info| info.status != StakeStatus::Active.into()),
+                _ => false,
+            };
+            return (!removed).then_some(AuditIssue::MissingStakeAccount { account, address });
+        };
+
+        let authorized = stake_state.authorized();
+        if authorized.is_none_or(|authorized| {
+            authorized.staker != *withdraw_authority || authorized.withdrawer != *withdraw_authority
+        }) {
+            return Some(AuditIssue::WrongAuthority {
+                account,
+                address,
+                staker: authorized.map(|authorized| authorized.staker),
+                withdrawer: authorized.map(|authorized| authorized.withdrawer),
+            });
+        }
+
+        let delegated_to = stake_state
+            .delegation()
+            .map(|delegation| delegation.voter_pubkey);
+        let expected_delegation = match account {
+            PoolStakeAccount::Reserve => None,
+            PoolStakeAccount::Validator(vote_account_address)
+            | PoolStakeAccount::Transient(vote_account_address) => Some(vote_account_address),
+        };
+        (delegated_to != expected_delegation).then_some(AuditIssue::WrongDelegation {
+            account,
+            address,
+            delegated_to,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::arithmetic_side_effects)]
+    use {
+        super::*,
+        solana_stake_interface::{
+            stake_flags::StakeFlags,
+            state::{Authorized, Delegation, Meta, Stake},
+        },
+        spl_stake_pool::state::{AccountType, ValidatorListHeader, ValidatorStakeInfo},
+    };
+
+    const STAKE_RENT_EXEMPTION: u64 = 2_282_880;
+
+    fn initialized(authority: &Pubkey) -> StakeStateV2 {
+        StakeStateV2::Initialized(Meta {
+            authorized: Authorized::auto(authority),
+            ..Meta::default()
+        })
+    }
+
+    fn delegated(authority: &Pubkey, vote_account_address: &Pubkey) -> StakeStateV2 {
+        let StakeStateV2::Initialized(meta) = initialized(authority) else {
+            unreachable!()
+        };
+        StakeStateV2::Stake(
+            meta,
+            Stake {
+                delegation: Delegation {
+                    voter_pubkey: *vote_account_address,
+                    ..Delegation::default()
+                },
+                credits_observed: 0,
+            },
+            StakeFlags::empty(),
+        )
+    }
+
+    /// A pool of two validators, the second with transient stake, with all
+    /// of its accounts in order
+    fn audit_state(program_id: &Pubkey, stake_pool_address: &Pubkey) -> AuditState {
+        let vote_account_addresses = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let validator_list = ValidatorList {
+            header: ValidatorListHeader {
+                account_type: AccountType::ValidatorList,
+                max_validators: 2,
+            },
+            validators: vote_account_addresses
+                .iter()
+                .zip([0, 1_000])
+                .map(
+                    |(vote_account_address, transient_stake_lamports)| ValidatorStakeInfo {
+                        vote_account_address: *vote_account_address,
+                        active_stake_lamports: 10_000.into(),
+                        transient_stake_lamports: transient_stake_lamports.into(),
+                        status: StakeStatus::Active.into(),
+                        ..ValidatorStakeInfo::default()
+                    },
+                )
+                .collect(),
+        };
+        let stake_pool = StakePool {
+            account_type: AccountType::StakePool,
+            reserve_stake: Pubkey::new_unique(),
+            total_lamports: 26_000,
+            pool_token_supply: 20_000,
+            last_update_epoch: 10,
+            ..StakePool::default()
+        };
+        let (withdraw_authority, _) =
+            find_withdraw_authority_program_address(program_id, stake_pool_address);
+        let pool_stake_accounts = AuditState::pool_stake_accounts(
+            program_id,
+            stake_pool_address,
+            &stake_pool,
+            &validator_list,
+        );
+        let stake_accounts = pool_stake_accounts
+            .iter()
+            .map(|(account, address)| {
+                let stake_state = match account {
+                    PoolStakeAccount::Reserve => initialized(&withdraw_authority),
+                    PoolStakeAccount::Validator(vote_account_address)
+                    | PoolStakeAccount::Transient(vote_account_address) => {
+                        delegated(&withdraw_authority, vote_account_address)
+                    }
+                };
+                (*address, stake_state)
+            })
+            .collect();
+        let withdraw_authority_stake_accounts = pool_stake_accounts
+            .iter()
+            .map(|(_, address)| (*address, 1))
+            .collect();
+        AuditState {
+            stake_pool,
+            validator_list,
+            epoch: 10,
+            reserve_lamports: 5_000 + minimum_reserve_lamports(STAKE_RENT_EXEMPTION),
+            stake_rent_exemption: STAKE_RENT_EXEMPTION,
+            mint_supply: 20_000,
+            stake_accounts,
+            withdraw_authority_stake_accounts,
+        }
+    }
+
+    #[test]
+    fn healthy_pool() {
+        let program_id = spl_stake_pool::id();
+        let stake_pool_address = Pubkey::new_unique();
+        let state = audit_state(&program_id, &stake_pool_address);
+        assert_eq!(state.counted_lamports(), 26_000);
+        assert_eq!(state.audit(&program_id, &stake_pool_address), vec![]);
+    }
+
+    #[test]
+    fn balance_mismatches() {
+        let program_id = spl_stake_pool::id();
+        let stake_pool_address = Pubkey::new_unique();
+        let mut state = audit_state(&program_id, &stake_pool_address);
+        state.epoch = 11;
+        state.reserve_lamports += 100;
+        state.mint_supply -= 1;
+        assert_eq!(
+            state.audit(&program_id, &stake_pool_address),
+            vec![
+                AuditIssue::PoolNotUpdated {
+                    last_update_epoch: 10
+                },
+                AuditIssue::TotalLamportsMismatch {
+                    total_lamports: 26_000,
+                    counted_lamports: 26_100,
+                },
+                AuditIssue::PoolTokenSupplyMismatch {
+                    pool_token_supply: 20_000,
+                    mint_supply: 19_999,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stake_account_issues() {
+        let program_id = spl_stake_pool::id();
+        let stake_pool_address = Pubkey::new_unique();
+        let mut state = audit_state(&program_id, &stake_pool_address);
+        let pool_stake_accounts = AuditState::pool_stake_accounts(
+            &program_id,
+            &stake_pool_address,
+            &state.stake_pool,
+            &state.validator_list,
+        );
+        let (_, reserve_address) = pool_stake_accounts[0];
+        let (validator, validator_address) = pool_stake_accounts[1];
+        let (transient, transient_address) = pool_stake_accounts[3];
+        let other = Pubkey::new_unique();
+        state.stake_accounts.remove(&validator_address);
+        state
+            .stake_accounts
+            .insert(transient_address, delegated(&other, &other));
+        let (withdraw_authority, _) =
+            find_withdraw_authority_program_address(&program_id, &stake_pool_address);
+        state
+            .stake_accounts
+            .insert(reserve_address, delegated(&withdraw_authority, &other));
+        let stray_address = Pubkey::new_unique();
+        state
+            .withdraw_authority_stake_accounts
+            .push((stray_address, 42));
+        assert_eq!(
+            state.audit(&program_id, &stake_pool_address),
+            vec![
+                AuditIssue::WrongDelegation {
+                    account: PoolStakeAccount::Reserve,
+                    address: reserve_address,
+                    delegated_to: Some(other),
+                },
+                AuditIssue::MissingStakeAccount {
+                    account: validator,
+                    address: validator_address,
+                },
+                AuditIssue::WrongAuthority {
+                    account: transient,
+                    address: transient_address,
+                    staker: Some(other),
+                    withdrawer: Some(other),
+                },
+                AuditIssue::StrayStakeAccount {
+                    address: stray_address,
+                    lamports: 42,
+                },
+            ]
+        );
+
+        // the stake account of a validator being removed may be gone
+        state.validator_list.validators[0].status = StakeStatus::DeactivatingValidator.into();
+        assert!(!state.audit(&program_id, &stake_pool_address).contains(
+            &AuditIssue::MissingStakeAccount {
+                account: validator,
+                address: validator_address,
+            }
+        ));
+    }
+}
@@ -0,0 +1,881 @@
+use {
+    crate::{
+        accounts::{
+            get_stake_pool, get_stake_pool_config, get_stake_state, get_token_mint,
+            get_transfer_fee_config, get_transfer_hook_program_id, get_validator_list,
+        },
+        audit::AuditState,
+        quote::QuoteState,
+        rebalance::{RebalanceState, TransientStake},
+        withdraw::{WithdrawAccount, WithdrawSource, WithdrawState},
+        Error, StakePoolRpc,
+    },
+    solana_borsh::v1::get_packed_len,
+    solana_clock::Epoch,
+    solana_instruction::Instruction,
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    solana_stake_interface::state::StakeStateV2,
+    spl_associated_token_account_interface::{
+        address::get_associated_token_address_with_program_id,
+        instruction::create_associated_token_account,
+    },
+    spl_stake_pool::{
+        find_depositor_record_address, find_transient_stake_program_address,
+        find_withdraw_authority_program_address,
+        instruction::DepositorProof,
+        merkle::{self, MerkleTree},
+        state::{DepositorRecord, StakePool},
+    },
+    spl_token_2022_interface::{extension::StateWithExtensions, state::Account},
+    std::collections::HashMap,
+};
+
+/// Associated token account, and the instruction creating it if it doesn't
+/// exist yet
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssociatedTokenAccount {
+    /// Address of the account
+    pub address: Pubkey,
+    /// Instruction creating the account, if it doesn't exist yet
+    pub create_instruction: Option<Instruction>,
+    /// Lamports paid to create the account
+    pub rent: u64,
+}
+
+/// Instructions of a deposit into a stake pool
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deposit {
+    /// Instructions creating the account receiving the pool tokens if
+    /// needed, then depositing
+    pub instructions: Vec<Instruction>,
+    /// Account receiving the pool tokens
+    pub pool_token_receiver: Pubkey,
+    /// Lamports paid to create accounts
+    pub rent: u64,
+}
+
+/// Instructions updating a stake pool for the current epoch
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateInstructions {
+    /// `UpdateValidatorListBalance` instructions, each fitting in its own
+    /// transaction, which may be sent in any order
+    pub update_list_instructions: Vec<Instruction>,
+    /// Instructions updating the pool balance and cleaning up the validator
+    /// list, to send together once all of the list is updated
+    pub final_instructions: Vec<Instruction>,
+}
+
+/// Build the Merkle tree of depositors and the most lamports each may deposit,
+/// in order
+pub fn depositor_merkle_tree(depositors: &[(Pubkey, u64)]) -> MerkleTree {
+    MerkleTree::new(
+        depositors
+            .iter()
+            .map(|(depositor, cap)| merkle::leaf_hash(depositor, *cap))
+            .collect(),
+    )
+}
+
+/// Client building the instructions of stake pool operations from the
+/// on-chain data of an [`StakePoolRpc`]
+pub struct StakePoolClient<R> {
+    rpc: R,
+    program_id: Pubkey,
+}
+
+impl<R: StakePoolRpc> StakePoolClient<R> {
+    /// Create a client for the stake pool program `program_id`
+    pub fn new(rpc: R, program_id: Pubkey) -> Self {
+        Self { rpc, program_id }
+    }
+
+    /// Source of on-chain data of the client
+    pub fn rpc(&self) -> &R {
+        &self.rpc
+    }
+
+    /// Stake pool program of the client
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// Get the associated token account of `owner` for `mint`, with the
+    /// instruction creating it, paid by `payer`, if it doesn't exist yet
+    pub async fn associated_token_account(
+        &self,
+        payer: &Pubkey,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        token_program_id: &Pubkey,
+    ) -> Result<AssociatedTokenAccount, Error> {
+        let address = get_associated_token_address_with_program_id(owner, mint, token_program_id);
+        if self.rpc.get_account(&address).await?.is_some() {
+            return Ok(AssociatedTokenAccount {
+                address,
+                create_instruction: None,
+                rent: 0,
+            });
+        }
+        let rent = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(Account::LEN)
+            .await?;
+        Ok(AssociatedTokenAccount {
+            address,
+            create_instruction: Some(create_associated_token_account(
+                payer,
+                owner,
+                mint,
+                token_program_id,
+            )),
+            rent,
+        })
+    }
+
+    /// Get the owner of the account receiving the pool tokens of a deposit,
+    /// or `owner` if the account is created along with the deposit
+    async fn get_depositor(
+        &self,
+        pool_token_receiver: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<Pubkey, Error> {
+        match self.rpc.get_account(pool_token_receiver).await? {
+            Some(account) => Ok(StateWithExtensions::<Account>::unpack(&account.data)?
+                .base
+                .owner),
+            None => Ok(*owner),
+        }
+    }
+
+    /// Add the accounts and data required by the pool's depositor allowlist
+    /// and depositor Merkle root, if any, to the deposit instruction at the
+    /// end of `instructions`, inserting the instruction creating the
+    /// depositor's record, paid by `payer`, before it if their cap requires
+    /// one that doesn't exist yet. Returns the lamports paid for the record.
+    ///
+    /// The depositor is the owner of `pool_token_receiver`, or `owner` if the
+    /// account doesn't exist yet. `depositors` lists the depositors of the
+    /// Merkle root along with their caps, as given to `depositor_merkle_tree`.
+    pub async fn add_depositor_accounts(
+        &self,
+        stake_pool_address: &Pubkey,
+        pool_token_receiver: &Pubkey,
+        owner: &Pubkey,
+        payer: &Pubkey,
+        depositors: Option<&[(Pubkey, u64)]>,
+        instructions: &mut Vec<Instruction>,
+    ) -> Result<u64, Error> {
+        let mut deposit_instruction = instructions.pop().ok_or("No deposit instruction")?;
+        let stake_pool_config = get_stake_pool_config(&self.rpc, stake_pool_address).await?;
+        let mut rent: u64 = 0;
+        if stake_pool_config.depositor_allowlist_enabled {
+            let depositor = self.get_depositor(pool_token_receiver, owner).await?;
+            spl_stake_pool::instruction::add_depositor_allowlist_entry(
+                &mut deposit_instruction,
+                &self.program_id,
+                stake_pool_address,
+                &depositor,
+            );
+        }
+        if let Some(root) = stake_pool_config.depositor_merkle_root {
+            let depositors = depositors.ok_or("The stake pool requires a depositor proof")?;
+            let tree = depositor_merkle_tree(depositors);
+            if tree.root() != Some(root) {
+                return Err(
+                    "The depositors do not match the stake pool's depositor Merkle root".into(),
+                );
+            }
+            let depositor = self.get_depositor(pool_token_receiver, owner).await?;
+            let (index, (_, cap)) = depositors
+                .iter()
+                .enumerate()
+                .find(|(_, (address, _))| *address == depositor)
+                .ok_or_else(|| format!("Depositor {} is not in the depositors", depositor))?;
+            spl_stake_pool::instruction::add_depositor_proof(
+                &mut deposit_instruction,
+                &DepositorProof {
+                    cap: *cap,
+                    proof: tree.proof(index).unwrap(),
+                },
+            );
+            if *cap != merkle::NO_CAP {
+                let record =
+                    find_depositor_record_address(&self.program_id, stake_pool_address, &depositor)
+                        .0;
+                if self.rpc.get_account(&record).await?.is_none() {
+                    instructions.push(spl_stake_pool::instruction::create_depositor_record(
+                        &self.program_id,
+                        stake_pool_address,
+                        payer,
+                        &depositor,
+                    ));
+                    rent = rent.saturating_add(
+                        self.rpc
+                            .get_minimum_balance_for_rent_exemption(
+                                get_packed_len::<DepositorRecord>(),
+                            )
+                            .await?,
+                    );
+                }
+                spl_stake_pool::instruction::add_depositor_record(
+                    &mut deposit_instruction,
+                    &self.program_id,
+                    stake_pool_address,
+                    &depositor,
+                );
+            }
+        }
+        instructions.push(deposit_instruction);
+        Ok(rent)
+    }
+
+    /// Build the instructions depositing `lamports` from `lamports_from`
+    /// into the stake pool.
+    ///
+    /// Pool tokens go to `pool_token_receiver`, or to the associated token
+    /// account of `owner`, created and paid by `payer` if needed. The
+    /// referral fees go to `referrer`, or to the receiver of the pool tokens.
+    /// `sol_deposit_authority` must be given if the pool has one.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn deposit_sol(
+        &self,
+        stake_pool_address: &Pubkey,
+        lamports_from: &Pubkey,
+        owner: &Pubkey,
+        payer: &Pubkey,
+        pool_token_receiver: Option<Pubkey>,
+        referrer: Option<Pubkey>,
+        sol_deposit_authority: Option<Pubkey>,
+        depositors: Option<&[(Pubkey, u64)]>,
+        lamports: u64,
+    ) -> Result<Deposit, Error> {
+        let stake_pool = get_stake_pool(&self.rpc, stake_pool_address).await?;
+
+        let mut instructions = vec![];
+        let mut rent: u64 = 0;
+        let pool_token_receiver = match pool_token_receiver {
+            Some(pool_token_receiver) => pool_token_receiver,
+            None => {
+                let associated_token_account = self
+                    .associated_token_account(
+                        payer,
+                        owner,
+                        &stake_pool.pool_mint,
+                        &stake_pool.token_program_id,
+                    )
+                    .await?;
+                instructions.extend(associated_token_account.create_instruction);
+                rent = rent.saturating_add(associated_token_account.rent);
+                associated_token_account.address
+            }
+        };
+        let referrer = referrer.unwrap_or(pool_token_receiver);
+
+        let pool_withdraw_authority =
+            find_withdraw_authority_program_address(&self.program_id, stake_pool_address).0;
+
+        let deposit_instruction = if let Some(sol_deposit_authority) = sol_deposit_authority {
+            let expected_sol_deposit_authority = stake_pool
+                .sol_deposit_authority
+                .ok_or("SOL deposit authority specified in arguments but stake pool has none")?;
+            if sol_deposit_authority != expected_sol_deposit_authority {
+                return Err(format!(
+                    "Invalid deposit authority specified, expected {}, received {}",
+                    expected_sol_deposit_authority, sol_deposit_authority
+                )
+                .into());
+            }
+
+            spl_stake_pool::instruction::deposit_sol_with_authority(
+                &self.program_id,
+                stake_pool_address,
+                &sol_deposit_authority,
+                &pool_withdraw_authority,
+                &stake_pool.reserve_stake,
+                lamports_from,
+                &pool_token_receiver,
+                &stake_pool.manager_fee_account,
+                &referrer,
+                &stake_pool.pool_mint,
+                &stake_pool.token_program_id,
+                lamports,
+            )
+        } else {
+            spl_stake_pool::instruction::deposit_sol(
+                &self.program_id,
+                stake_pool_address,
+                &pool_withdraw_authority,
+                &stake_pool.reserve_stake,
+                lamports_from,
+                &pool_token_receiver,
+                &stake_pool.manager_fee_account,
+                &referrer,
+                &stake_pool.pool_mint,
+                &stake_pool.token_program_id,
+                lamports,
+            )
+        };
+        instructions.push(deposit_instruction);
+        let depositor_rent = self
+            .add_depositor_accounts(
+                stake_pool_address,
+                &pool_token_receiver,
+                owner,
+                payer,
+                depositors,
+                &mut instructions,
+            )
+            .await?;
+        rent = rent.saturating_add(depositor_rent);
+
+        Ok(Deposit {
+            instructions,
+            pool_token_receiver,
+            rent,
+        })
+    }
+
+    /// Check if the stake pool was not updated yet in the current epoch
+    pub async fn is_update_needed(&self, stake_pool: &StakePool) -> Result<bool, Error> {
+        Ok(stake_pool.last_update_epoch != self.rpc.get_epoch().await?)
+    }
+
+    /// Build the instructions updating the stake pool, only going through
+    /// the validators not updated in the current epoch if `stale_only`
+    pub async fn update(
+        &self,
+        stake_pool_address: &Pubkey,
+        no_merge: bool,
+        stale_only: bool,
+    ) -> Result<UpdateInstructions, Error> {
+        let stake_pool = get_stake_pool(&self.rpc, stake_pool_address).await?;
+        let validator_list = get_validator_list(&self.rpc, &stake_pool.validator_list).await?;
+
+        let (update_list_instructions, final_instructions) = if stale_only {
+            spl_stake_pool::instruction::update_stale_stake_pool(
+                &self.program_id,
+                &stake_pool,
+                &validator_list,
+                stake_pool_address,
+                no_merge,
+                self.rpc.get_epoch().await?,
+            )
+        } else {
+            spl_stake_pool::instruction::update_stake_pool(
+                &self.program_id,
+                &stake_pool,
+                &validator_list,
+                stake_pool_address,
+                no_merge,
+            )
+        };
+        Ok(UpdateInstructions {
+            update_list_instructions,
+            final_instructions,
+        })
+    }
+
+    /// Build the instructions updating the stake pool, if it was not updated
+    /// yet in the current epoch
+    pub async fn update_if_needed(
+        &self,
+        stake_pool_address: &Pubkey,
+        no_merge: bool,
+        stale_only: bool,
+    ) -> Result<Option<UpdateInstructions>, Error> {
+        let stake_pool = get_stake_pool(&self.rpc, stake_pool_address).await?;
+        if !self.is_update_needed(&stake_pool).await? {
+            return Ok(None);
+        }
+        self.update(stake_pool_address, no_merge, stale_only)
+            .await
+            .map(Some)
+    }
+
+    /// Get the state of a stake pool that stake withdrawals are checked and
+    /// planned against
+    pub async fn withdraw_state(
+        &self,
+        stake_pool_address: &Pubkey,
+    ) -> Result<WithdrawState, Error> {
+        let stake_pool = get_stake_pool(&self.rpc, stake_pool_address).await?;
+        let validator_list = get_validator_list(&self.rpc, &stake_pool.validator_list).await?;
+        let reserve_lamports = self
+            .rpc
+            .get_account(&stake_pool.reserve_stake)
+            .await?
+            .ok_or_else(|| format!("Account {} does not exist", stake_pool.reserve_stake))?
+            .lamports;
+        let stake_rent_exemption = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(StakeStateV2::size_of())
+            .await?;
+        let stake_minimum_delegation = self.rpc.get_stake_minimum_delegation().await?;
+        Ok(WithdrawState {
+            stake_pool,
+            validator_list,
+            reserve_lamports,
+            stake_rent_exemption,
+            stake_minimum_delegation,
+        })
+    }
+
+    /// Plan the withdrawal of `pool_amount` pool tokens from
+    /// `pool_token_account` as stake, taking it from `source`
+    pub async fn withdraw_stake_plan(
+        &self,
+        stake_pool_address: &Pubkey,
+        pool_token_account: &Pubkey,
+        pool_amount: u64,
+        source: WithdrawSource,
+    ) -> Result<Vec<WithdrawAccount>, Error> {
+        let withdraw_state = self.withdraw_state(stake_pool_address).await?;
+        if let WithdrawSource::Validator(vote_account_address) = source {
+            if !withdraw_state
+                .validator_list
+                .contains(&vote_account_address)
+            {
+                return Err(format!(
+                    "Vote account {} does not exist in the stake pool",
+                    vote_account_address
+                )
+                .into());
+            }
+        }
+        let fee_exempt = withdraw_state.stake_pool.manager_fee_account == *pool_token_account;
+        withdraw_state.plan(
+            &self.program_id,
+            stake_pool_address,
+            pool_amount,
+            source,
+            fee_exempt,
+        )
+    }
+
+    /// Get the state of a stake pool that deposits and withdrawals are quoted
+    /// against
+    pub async fn quote_state(&self, stake_pool_address: &Pubkey) -> Result<QuoteState, Error> {
+        let withdraw_state = self.withdraw_state(stake_pool_address).await?;
+        let epoch = self.rpc.get_epoch().await?;
+        let partial_update_haircut = get_stake_pool_config(&self.rpc, stake_pool_address)
+            .await?
+            .partial_update_haircut;
+        let transfer_fee_config =
+            get_transfer_fee_config(&self.rpc, &withdraw_state.stake_pool).await?;
+        Ok(QuoteState {
+            withdraw_state,
+            epoch,
+            partial_update_haircut,
+            transfer_fee_config,
+        })
+    }
+
+    /// Get the state of a stake pool that moves of stake between its reserve
+    /// and validators are planned against, reading every transient stake
+    /// account holding lamports
+    pub async fn rebalance_state(
+        &self,
+        stake_pool_address: &Pubkey,
+    ) -> Result<RebalanceState, Error> {
+        let WithdrawState {
+            stake_pool,
+            validator_list,
+            reserve_lamports,
+            stake_rent_exemption,
+            stake_minimum_delegation,
+        } = self.withdraw_state(stake_pool_address).await?;
+        let epoch = self.rpc.get_epoch().await?;
+        let mut transient_stakes = HashMap::new();
+        for info in &validator_list.validators {
+            if u64::from(info.transient_stake_lamports) == 0 {
+                continue;
+            }
+            let (transient_stake_address, _) = find_transient_stake_program_address(
+                &self.program_id,
+                &info.vote_account_address,
+                stake_pool_address,
+                info.transient_seed_suffix.into(),
+            );
+            let transient_stake = match get_stake_state(&self.rpc, &transient_stake_address)
+                .await?
+                .delegation()
+            {
+                Some(delegation) if delegation.deactivation_epoch != Epoch::MAX => {
+                    TransientStake::Deactivating
+                }
+                Some(delegation) if delegation.activation_epoch == epoch => {
+                    TransientStake::ActivatingThisEpoch
+                }
+                _ => TransientStake::Activating,
+            };
+            transient_stakes.insert(info.vote_account_address, transient_stake);
+        }
+        Ok(RebalanceState {
+            stake_pool,
+            validator_list,
+            reserve_lamports,
+            stake_rent_exemption,
+            stake_minimum_delegation,
+            transient_stakes,
+        })
+    }
+
+    /// Get the accounts of a stake pool that its invariants are checked
+    /// against, including every stake account held by its withdraw authority
+    pub async fn audit_state(&self, stake_pool_address: &Pubkey) -> Result<AuditState, Error> {
+        let WithdrawState {
+            stake_pool,
+            validator_list,
+            reserve_lamports,
+            stake_rent_exemption,
+            ..
+        } = self.withdraw_state(stake_pool_address).await?;
+        let epoch = self.rpc.get_epoch().await?;
+        let mint_supply = get_token_mint(&self.rpc, &stake_pool.pool_mint)
+            .await?
+            .supply;
+        let mut stake_accounts = HashMap::new();
+        for (_, address) in AuditState::pool_stake_accounts(
+            &self.program_id,
+            stake_pool_address,
+            &stake_pool,
+            &validator_list,
+        ) {
+            let Some(account) = self.rpc.get_account(&address).await? else {
+                continue;
+            };
+            if account.owner != solana_stake_interface::program::id() {
+                continue;
+            }
+            let stake_state = bincode::deserialize(account.data.as_slice())
+                .map_err(|err| format!("Invalid stake account {}: {}", address, err))?;
+            stake_accounts.insert(address, stake_state);
+        }
+        let (withdraw_authority, _) =
+            find_withdraw_authority_program_address(&self.program_id, stake_pool_address);
+        let withdraw_authority_stake_accounts = self
+            .rpc
+            .get_stake_accounts_by_withdrawer(&withdraw_authority)
+            .await?;
+        Ok(AuditState {
+            stake_pool,
+            validator_list,
+            epoch,
+            reserve_lamports,
+            stake_rent_exemption,
+            mint_supply,
+            stake_accounts,
+            withdraw_authority_stake_accounts,
+        })
+    }
+
+    /// Add the accounts required by the pool mint's transfer hook to a
+    /// withdraw instruction, for the transfer of `fee_amount` pool tokens
+    /// from `pool_token_account` to the manager fee account
+    pub async fn add_transfer_hook_accounts_for_withdraw(
+        &self,
+        stake_pool: &StakePool,
+        instruction: &mut Instruction,
+        transfer_hook_program_id: &Pubkey,
+        pool_token_account: &Pubkey,
+        user_transfer_authority: &Pubkey,
+        fee_amount: u64,
+    ) -> Result<(), Error> {
+        spl_stake_pool::instruction::add_transfer_hook_accounts_for_withdraw(
+            instruction,
+            transfer_hook_program_id,
+            &stake_pool.pool_mint,
+            pool_token_account,
+            &stake_pool.manager_fee_account,
+            user_transfer_authority,
+            fee_amount,
+            |address| async move {
+                Ok(self
+                    .rpc
+                    .get_account(&address)
+                    .await?
+                    .map(|account| account.data))
+            },
+        )
+        .await
+    }
+
+    /// Build the instruction withdrawing the stake of one account of a
+    /// withdrawal plan into `stake_receiver`, an uninitialized stake account,
+    /// which ends up owned by `user_stake_authority`.
+    ///
+    /// `user_transfer_authority` must be approved to burn the pool tokens of
+    /// `pool_token_account`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn withdraw_stake_instruction(
+        &self,
+        stake_pool_address: &Pubkey,
+        stake_pool: &StakePool,
+        withdraw_account: &WithdrawAccount,
+        stake_receiver: &Pubkey,
+        user_stake_authority: &Pubkey,
+        user_transfer_authority: &Pubkey,
+        pool_token_account: &Pubkey,
+    ) -> Result<Instruction, Error> {
+        let pool_withdraw_authority =
+            find_withdraw_authority_program_address(&self.program_id, stake_pool_address).0;
+        let mut withdraw_instruction = spl_stake_pool::instruction::withdraw_stake(
+            &self.program_id,
+            stake_pool_address,
+            &stake_pool.validator_list,
+            &pool_withdraw_authority,
+            &withdraw_account.stake_address,
+            stake_receiver,
+            user_stake_authority,
+            user_transfer_authority,
+            pool_token_account,
+            &stake_pool.manager_fee_account,
+            &stake_pool.pool_mint,
+            &stake_pool.token_program_id,
+            withdraw_account.pool_amount,
+        );
+        let pool_tokens_fee = stake_pool
+            .calc_pool_tokens_stake_withdrawal_fee(withdraw_account.pool_amount)
+            .ok_or("Pool token fee calculation failed")?;
+        if pool_tokens_fee > 0 && *pool_token_account != stake_pool.manager_fee_account {
+            if let Some(transfer_hook_program_id) =
+                get_transfer_hook_program_id(&self.rpc, stake_pool).await?
+            {
+                self.add_transfer_hook_accounts_for_withdraw(
+                    stake_pool,
+                    &mut withdraw_instruction,
+                    &transfer_hook_program_id,
+                    pool_token_account,
+                    user_transfer_authority,
+                    pool_tokens_fee,
+                )
+                .await?;
+            }
+        }
+        Ok(withdraw_instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::arithmetic_side_effects)]
+    use {
+        super::*,
+        solana_account::Account as SolanaAccount,
+        spl_stake_pool::state::{AccountType, ValidatorList},
+        std::future::Future,
+    };
+
+    const EPOCH: Epoch = 10;
+
+    #[derive(Default)]
+    struct TestRpc {
+        accounts: HashMap<Pubkey, SolanaAccount>,
+    }
+
+    impl TestRpc {
+        fn add_account(&mut self, address: Pubkey, lamports: u64, data: Vec<u8>) {
+            self.accounts.insert(
+                address,
+                SolanaAccount {
+                    lamports,
+                    data,
+                    ..SolanaAccount::default()
+                },
+            );
+        }
+    }
+
+    impl StakePoolRpc for TestRpc {
+        fn get_account(
+            &self,
+            address: &Pubkey,
+        ) -> impl Future<Output = Result<Option<SolanaAccount>, Error>> + Send {
+            let account = self.accounts.get(address).cloned();
+            async move { Ok(account) }
+        }
+
+        async fn get_minimum_balance_for_rent_exemption(
+            &self,
+            data_len: usize,
+        ) -> Result<u64, Error> {
+            Ok(data_len as u64 * 10)
+        }
+
+        async fn get_epoch(&self) -> Result<Epoch, Error> {
+            Ok(EPOCH)
+        }
+
+        async fn get_stake_minimum_delegation(&self) -> Result<u64, Error> {
+            Ok(1)
+        }
+
+        async fn get_stake_accounts_by_withdrawer(
+            &self,
+            withdrawer: &Pubkey,
+        ) -> Result<Vec<(Pubkey, u64)>, Error> {
+            Ok(self
+                .accounts
+                .iter()
+                .filter(|(_, account)| {
+                    bincode::deserialize::<StakeStateV2>(&account.data)
+                        .ok()
+                        .and_then(|stake_state| stake_state.authorized())
+                        .is_some_and(|authorized| authorized.withdrawer == *withdrawer)
+                })
+                .map(|(address, account)| (*address, account.lamports))
+                .collect())
+        }
+    }
+
+    fn setup(last_update_epoch: Epoch) -> (StakePoolClient<TestRpc>, Pubkey, StakePool) {
+        let mut rpc = TestRpc::default();
+        let stake_pool_address = Pubkey::new_unique();
+        let stake_pool = StakePool {
+            account_type: AccountType::StakePool,
+            validator_list: Pubkey::new_unique(),
+            reserve_stake: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            manager_fee_account: Pubkey::new_unique(),
+            token_program_id: spl_token_2022_interface::id(),
+            total_lamports: 10_000,
+            pool_token_supply: 10_000,
+            last_update_epoch,
+            ..StakePool::default()
+        };
+        rpc.add_account(stake_pool_address, 1, borsh::to_vec(&stake_pool).unwrap());
+        rpc.add_account(
+            stake_pool.reserve_stake,
+            10_000 + StakeStateV2::size_of() as u64 * 10,
+            vec![],
+        );
+        rpc.add_account(
+            stake_pool.validator_list,
+            1,
+            borsh::to_vec(&ValidatorList::new(4)).unwrap(),
+        );
+        (
+            StakePoolClient::new(rpc, spl_stake_pool::id()),
+            stake_pool_address,
+            stake_pool,
+        )
+    }
+
+    #[test]
+    fn update_only_if_needed() {
+        let (client, stake_pool_address, _) = setup(EPOCH);
+        assert_eq!(
+            futures::executor::block_on(client.update_if_needed(&stake_pool_address, false, false))
+                .unwrap(),
+            None
+        );
+
+        let (client, stake_pool_address, _) = setup(EPOCH - 1);
+        let update =
+            futures::executor::block_on(client.update_if_needed(&stake_pool_address, false, false))
+                .unwrap()
+                .unwrap();
+        assert!(!update.final_instructions.is_empty());
+    }
+
+    #[test]
+    fn deposit_sol_creates_associated_token_account() {
+        let (mut client, stake_pool_address, stake_pool) = setup(EPOCH);
+        let owner = Pubkey::new_unique();
+        let lamports_from = Pubkey::new_unique();
+
+        let deposit = futures::executor::block_on(client.deposit_sol(
+            &stake_pool_address,
+            &lamports_from,
+            &owner,
+            &owner,
+            None,
+            None,
+            None,
+            None,
+            1_000,
+        ))
+        .unwrap();
+        let associated_token_account = get_associated_token_address_with_program_id(
+            &owner,
+            &stake_pool.pool_mint,
+            &stake_pool.token_program_id,
+        );
+        assert_eq!(deposit.pool_token_receiver, associated_token_account);
+        assert_eq!(deposit.instructions.len(), 2);
+        assert_eq!(deposit.rent, Account::LEN as u64 * 10);
+
+        client
+            .rpc
+            .add_account(associated_token_account, 1, vec![0; Account::LEN]);
+        let deposit = futures::executor::block_on(client.deposit_sol(
+            &stake_pool_address,
+            &lamports_from,
+            &owner,
+            &owner,
+            None,
+            None,
+            None,
+            None,
+            1_000,
+        ))
+        .unwrap();
+        assert_eq!(deposit.instructions.len(), 1);
+        assert_eq!(deposit.rent, 0);
+    }
+
+    #[test]
+    fn deposit_sol_checks_deposit_authority() {
+        let (client, stake_pool_address, _) = setup(EPOCH);
+        let owner = Pubkey::new_unique();
+        assert!(futures::executor::block_on(client.deposit_sol(
+            &stake_pool_address,
+            &owner,
+            &owner,
+            &owner,
+            Some(Pubkey::new_unique()),
+            None,
+            Some(Pubkey::new_unique()),
+            None,
+            1_000,
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn withdraw_stake_plan_from_reserve() {
+        let (client, stake_pool_address, stake_pool) = setup(EPOCH);
+        let plan = futures::executor::block_on(client.withdraw_stake_plan(
+            &stake_pool_address,
+            &Pubkey::new_unique(),
+            1_000,
+            WithdrawSource::Reserve,
+        ))
+        .unwrap();
+        assert_eq!(
+            plan,
+            vec![WithdrawAccount {
+                stake_address: stake_pool.reserve_stake,
+                vote_address: None,
+                pool_amount: 1_000,
+                lamports: 1_000,
+            }]
+        );
+
+        assert!(futures::executor::block_on(client.withdraw_stake_plan(
+            &stake_pool_address,
+            &Pubkey::new_unique(),
+            10_001,
+            WithdrawSource::Reserve,
+        ))
+        .is_err());
+        assert!(futures::executor::block_on(client.withdraw_stake_plan(
+            &stake_pool_address,
+            &Pubkey::new_unique(),
+            1_000,
+            WithdrawSource::Validator(Pubkey::new_unique()),
+        ))
+        .is_err());
+    }
+}
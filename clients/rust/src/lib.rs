@@ -0,0 +1,24 @@
+#![deny(missing_docs)]
+
+//! A client for stake pools, building the instructions of high-level
+//! operations such as depositing SOL or withdrawing stake from on-chain data
+//! fetched through any [`StakePoolRpc`] implementation
+
+pub mod accounts;
+pub mod audit;
+mod client;
+pub mod pool_config;
+pub mod quote;
+pub mod rebalance;
+pub mod rpc;
+pub mod withdraw;
+
+pub use {
+    client::{
+        depositor_merkle_tree, AssociatedTokenAccount, Deposit, StakePoolClient, UpdateInstructions,
+    },
+    rpc::StakePoolRpc,
+};
+
+/// Errors returned by the client
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -0,0 +1,454 @@
+//! Declarative configuration of a stake pool.
+//!
+//! A [`PoolConfig`] describes the fees, authorities, preferred validators and
+//! validator set that a pool should have. [`PoolConfig::plan`] diffs it
+//! against the pool's on-chain state, and [`PoolConfig::instructions`] builds
+//! the instructions of the changes, so that the configuration of a pool can
+//! be kept in version control and applied with the fewest instructions.
+
+use {
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    spl_stake_pool::{
+        find_deposit_authority_program_address,
+        instruction::{
+            add_validator_to_pool_with_vote, remove_validator_from_pool_with_vote, set_fee,
+            set_funding_authority, set_preferred_validator, set_staker, FundingType,
+            PreferredValidatorType,
+        },
+        state::{Fee, FeeType, StakePool, StakeStatus, ValidatorList},
+    },
+    std::{collections::HashSet, num::NonZeroU32},
+};
+
+/// Desired configuration of a stake pool. Settings left to `None` are not
+/// managed, and kept as they are on-chain.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoolConfig {
+    /// Staker of the pool
+    pub staker: Option<Pubkey>,
+    /// Stake deposit authority, none for the program's default authority
+    pub stake_deposit_authority: Option<Option<Pubkey>>,
+    /// SOL deposit authority, none to allow anyone to deposit SOL
+    pub sol_deposit_authority: Option<Option<Pubkey>>,
+    /// SOL withdraw authority, none to allow anyone to withdraw SOL
+    pub sol_withdraw_authority: Option<Option<Pubkey>>,
+    /// Preferred validator for deposits, given by its vote account
+    pub preferred_deposit_validator: Option<Option<Pubkey>>,
+    /// Preferred validator for withdrawals, given by its vote account
+    pub preferred_withdraw_validator: Option<Option<Pubkey>>,
+    /// Fees of the pool, one per fee type
+    pub fees: Vec<FeeType>,
+    /// Vote accounts of the validators of the pool
+    pub validators: Option<Vec<Pubkey>>,
+}
+
+/// Change of a stake pool bringing it closer to its configuration, made by a
+/// single instruction
+#[derive(Clone, Debug, PartialEq)]
+pub enum PoolChange {
+    /// Set a fee, signed by the manager
+    SetFee(FeeType),
+    /// Set a funding authority, signed by the manager
+    SetFundingAuthority(FundingType, Option<Pubkey>),
+    /// Add a validator, signed by the staker
+    AddValidator(Pubkey),
+    /// Set a preferred validator, signed by the staker
+    SetPreferredValidator(PreferredValidatorType, Option<Pubkey>),
+    /// Remove a validator, signed by the staker
+    RemoveValidator(Pubkey),
+    /// Set the staker, signed by the manager
+    SetStaker(Pubkey),
+}
+
+impl PoolChange {
+    /// Check if the manager, rather than the staker, signs the change
+    pub fn is_signed_by_manager(&self) -> bool {
+        matches!(
+            self,
+            Self::SetFee(_) | Self::SetFundingAuthority(..) | Self::SetStaker(_)
+        )
+    }
+}
+
+/// Fee of the type of `fee` that a pool will have once its pending fee
+/// changes take effect
+pub fn configured_fee(stake_pool: &StakePool, fee: &FeeType) -> FeeType {
+    let next_or = |next_fee: Option<Fee>, fee: Fee| next_fee.unwrap_or(fee);
+    match fee {
+        FeeType::SolReferral(_) => FeeType::SolReferral(stake_pool.sol_referral_fee),
+        FeeType::StakeReferral(_) => FeeType::StakeReferral(stake_pool.stake_referral_fee),
+        FeeType::Epoch(_) => FeeType::Epoch(next_or(
+            stake_pool.next_epoch_fee.into(),
+            stake_pool.epoch_fee,
+        )),
+        FeeType::StakeWithdrawal(_) => FeeType::StakeWithdrawal(next_or(
+            stake_pool.next_stake_withdrawal_fee.into(),
+            stake_pool.stake_withdrawal_fee,
+        )),
+        FeeType::SolDeposit(_) => FeeType::SolDeposit(stake_pool.sol_deposit_fee),
+        FeeType::StakeDeposit(_) => FeeType::StakeDeposit(stake_pool.stake_deposit_fee),
+        FeeType::SolWithdrawal(_) => FeeType::SolWithdrawal(next_or(
+            stake_pool.next_sol_withdrawal_fee.into(),
+            stake_pool.sol_withdrawal_fee,
+        )),
+    }
+}
+
+impl PoolConfig {
+    /// Plan the changes bringing a pool to this configuration, in the order
+    /// they must be made: validators are added before being preferred and
+    /// removed after the preferred validators change, and the staker is set
+    /// last, as the current staker signs the validator changes.
+    pub fn plan(
+        &self,
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+        stake_pool: &StakePool,
+        validator_list: &ValidatorList,
+    ) -> Vec<PoolChange> {
+        let mut changes = vec![];
+        for fee in &self.fees {
+            if configured_fee(stake_pool, fee) != *fee {
+                changes.push(PoolChange::SetFee(fee.clone()));
+            }
+        }
+
+        let (default_stake_deposit_authority, _) =
+            find_deposit_authority_program_address(program_id, stake_pool_address);
+        let stake_deposit_authority = Some(stake_pool.stake_deposit_authority)
+            .filter(|authority| *authority != default_stake_deposit_authority);
+        for (funding_type, configured, current) in [
+            (
+                FundingType::StakeDeposit,
+                self.stake_deposit_authority,
+                stake_deposit_authority,
+            ),
+            (
+                FundingType::SolDeposit,
+                self.sol_deposit_authority,
+                stake_pool.sol_deposit_authority,
+            ),
+            (
+                FundingType::SolWithdraw,
+                self.sol_withdraw_authority,
+                stake_pool.sol_withdraw_authority,
+            ),
+        ] {
+            if let Some(authority) = configured.filter(|authority| *authority != current) {
+                changes.push(PoolChange::SetFundingAuthority(funding_type, authority));
+            }
+        }
+
+        let mut removals = vec![];
+        if let Some(validators) = &self.validators {
+            let configured = validators.iter().collect::<HashSet<_>>();
+            let mut added = HashSet::new();
+            for vote_account_address in validators {
+                if !validator_list.contains(vote_account_address)
+                    && added.insert(vote_account_address)
+                {
+                    changes.push(PoolChange::AddValidator(*vote_account_address));
+                }
+            }
+            for info in &validator_list.validators {
+                if info.status == StakeStatus::Active.into()
+                    && !configured.contains(&info.vote_account_address)
+                {
+                    removals.push(PoolChange::RemoveValidator(info.vote_account_address));
+                }
+            }
+        }
+
+        for (validator_type, configured, current) in [
+            (
+                PreferredValidatorType::Deposit,
+                self.preferred_deposit_validator,
+                stake_pool.preferred_deposit_validator_vote_address,
+            ),
+            (
+                PreferredValidatorType::Withdraw,
+                self.preferred_withdraw_validator,
+                stake_pool.preferred_withdraw_validator_vote_address,
+            ),
+        ] {
+            if let Some(vote_account_address) =
+                configured.filter(|vote_account_address| *vote_account_address != current)
+            {
+                changes.push(PoolChange::SetPreferredValidator(
+                    validator_type,
+                    vote_account_address,
+                ));
+            }
+        }
+        changes.append(&mut removals);
+
+        if let Some(staker) = self.staker.filter(|staker| *staker != stake_pool.staker) {
+            changes.push(PoolChange::SetStaker(staker));
+        }
+        changes
+    }
+
+    /// Build the instruction of each change, signed by the pool's current
+    /// manager or staker
+    pub fn instructions(
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+        stake_pool: &StakePool,
+        validator_list: &ValidatorList,
+        changes: &[PoolChange],
+    ) -> Vec<Instruction> {
+        changes
+            .iter()
+            .map(|change| match change {
+                PoolChange::SetFee(fee) => set_fee(
+                    program_id,
+                    stake_pool_address,
+                    &stake_pool.manager,
+                    fee.clone(),
+                ),
+                PoolChange::SetFundingAuthority(funding_type, authority) => set_funding_authority(
+                    program_id,
+                    stake_pool_address,
+                    &stake_pool.manager,
+                    authority.as_ref(),
+                    funding_type.clone(),
+                ),
+                PoolChange::AddValidator(vote_account_address) => add_validator_to_pool_with_vote(
+                    program_id,
+                    stake_pool,
+                    stake_pool_address,
+                    vote_account_address,
+                    None,
+                ),
+                PoolChange::SetPreferredValidator(validator_type, vote_account_address) => {
+                    set_preferred_validator(
+                        program_id,
+                        stake_pool_address,
+                        &stake_pool.staker,
+                        &stake_pool.validator_list,
+                        validator_type.clone(),
+                        *vote_account_address,
+                    )
+                }
+                PoolChange::RemoveValidator(vote_account_address) => {
+                    let info = validator_list.find(vote_account_address);
+                    remove_validator_from_pool_with_vote(
+                        program_id,
+                        stake_pool,
+                        stake_pool_address,
+                        vote_account_address,
+                        info.and_then(|info| NonZeroU32::new(info.validator_seed_suffix.into())),
+                        info.map_or(0, |info| info.transient_seed_suffix.into()),
+                    )
+                }
+                PoolChange::SetStaker(staker) => {
+                    set_staker(program_id, stake_pool_address, &stake_pool.manager, staker)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        spl_stake_pool::state::{
+            AccountType, FutureEpoch, ValidatorListHeader, ValidatorStakeInfo,
+        },
+    };
+
+    fn pool(program_id: &Pubkey, stake_pool_address: &Pubkey) -> (StakePool, ValidatorList) {
+        let vote_account_addresses = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let stake_pool = StakePool {
+            account_type: AccountType::StakePool,
+            manager: Pubkey::new_unique(),
+            staker: Pubkey::new_unique(),
+            stake_deposit_authority: find_deposit_authority_program_address(
+                program_id,
+                stake_pool_address,
+            )
+            .0,
+            validator_list: Pubkey::new_unique(),
+            epoch_fee: Fee {
+                numerator: 1,
+                denominator: 100,
+            },
+            sol_deposit_fee: Fee {
+                numerator: 1,
+                denominator: 100,
+            },
+            sol_referral_fee: 50,
+            preferred_deposit_validator_vote_address: Some(vote_account_addresses[1]),
+            ..StakePool::default()
+        };
+        let validator_list = ValidatorList {
+            header: ValidatorListHeader {
+                account_type: AccountType::ValidatorList,
+                max_validators: 10,
+            },
+            validators: vote_account_addresses
+                .iter()
+                .map(|vote_account_address| ValidatorStakeInfo {
+                    vote_account_address: *vote_account_address,
+                    status: StakeStatus::Active.into(),
+                    ..ValidatorStakeInfo::default()
+                })
+                .collect(),
+        };
+        (stake_pool, validator_list)
+    }
+
+    #[test]
+    fn matching_config_plans_nothing() {
+        let program_id = spl_stake_pool::id();
+        let stake_pool_address = Pubkey::new_unique();
+        let (stake_pool, validator_list) = pool(&program_id, &stake_pool_address);
+        let config = PoolConfig {
+            staker: Some(stake_pool.staker),
+            stake_deposit_authority: Some(None),
+            sol_deposit_authority: Some(None),
+            sol_withdraw_authority: Some(None),
+            preferred_deposit_validator: Some(stake_pool.preferred_deposit_validator_vote_address),
+            preferred_withdraw_validator: Some(None),
+            fees: vec![
+                FeeType::Epoch(stake_pool.epoch_fee),
+                FeeType::SolDeposit(stake_pool.sol_deposit_fee),
+                FeeType::SolReferral(50),
+            ],
+            validators: Some(
+                validator_list
+                    .validators
+                    .iter()
+                    .map(|info| info.vote_account_address)
+                    .rev()
+                    .collect(),
+            ),
+        };
+        assert_eq!(
+            config.plan(
+                &program_id,
+                &stake_pool_address,
+                &stake_pool,
+                &validator_list
+            ),
+            vec![]
+        );
+        assert_eq!(
+            PoolConfig::default().plan(
+                &program_id,
+                &stake_pool_address,
+                &stake_pool,
+                &validator_list
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn changes_are_ordered() {
+        let program_id = spl_stake_pool::id();
+        let stake_pool_address = Pubkey::new_unique();
+        let (stake_pool, validator_list) = pool(&program_id, &stake_pool_address);
+        let kept = validator_list.validators[0].vote_account_address;
+        let removed = validator_list.validators[1].vote_account_address;
+        let added = Pubkey::new_unique();
+        let staker = Pubkey::new_unique();
+        let sol_deposit_authority = Pubkey::new_unique();
+        let fee = Fee {
+            numerator: 2,
+            denominator: 100,
+        };
+        let config = PoolConfig {
+            staker: Some(staker),
+            sol_deposit_authority: Some(Some(sol_deposit_authority)),
+            preferred_deposit_validator: Some(Some(added)),
+            fees: vec![FeeType::Epoch(fee), FeeType::SolReferral(50)],
+            validators: Some(vec![kept, added, added]),
+            ..PoolConfig::default()
+        };
+        let changes = config.plan(
+            &program_id,
+            &stake_pool_address,
+            &stake_pool,
+            &validator_list,
+        );
+        assert_eq!(
+            changes,
+            vec![
+                PoolChange::SetFee(FeeType::Epoch(fee)),
+                PoolChange::SetFundingAuthority(
+                    FundingType::SolDeposit,
+                    Some(sol_deposit_authority)
+                ),
+                PoolChange::AddValidator(added),
+                PoolChange::SetPreferredValidator(PreferredValidatorType::Deposit, Some(added)),
+                PoolChange::RemoveValidator(removed),
+                PoolChange::SetStaker(staker),
+            ]
+        );
+
+        let instructions = PoolConfig::instructions(
+            &program_id,
+            &stake_pool_address,
+            &stake_pool,
+            &validator_list,
+            &changes,
+        );
+        assert_eq!(instructions.len(), changes.len());
+        for (change, instruction) in changes.iter().zip(instructions) {
+            let signer = if change.is_signed_by_manager() {
+                stake_pool.manager
+            } else {
+                stake_pool.staker
+            };
+            assert!(instruction
+                .accounts
+                .iter()
+                .any(|account| account.is_signer && account.pubkey == signer));
+        }
+    }
+
+    #[test]
+    fn pending_fees_are_configured() {
+        let program_id = spl_stake_pool::id();
+        let stake_pool_address = Pubkey::new_unique();
+        let (mut stake_pool, validator_list) = pool(&program_id, &stake_pool_address);
+        let next_fee = Fee {
+            numerator: 3,
+            denominator: 100,
+        };
+        stake_pool.next_epoch_fee = FutureEpoch::new(next_fee);
+
+        // the next fee is already set
+        let config = PoolConfig {
+            fees: vec![FeeType::Epoch(next_fee)],
+            ..PoolConfig::default()
+        };
+        assert_eq!(
+            config.plan(
+                &program_id,
+                &stake_pool_address,
+                &stake_pool,
+                &validator_list
+            ),
+            vec![]
+        );
+
+        // keeping the current fee cancels the next one
+        let config = PoolConfig {
+            fees: vec![FeeType::Epoch(stake_pool.epoch_fee)],
+            ..PoolConfig::default()
+        };
+        assert_eq!(
+            config.plan(
+                &program_id,
+                &stake_pool_address,
+                &stake_pool,
+                &validator_list
+            ),
+            vec![PoolChange::SetFee(FeeType::Epoch(stake_pool.epoch_fee))]
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! Quotes of deposits and withdrawals.
+//!
+//! [`QuoteState`] runs the computations of the deposit and withdrawal
+//! instructions on the current state of a pool, so that users can see what
+//! they get, and the fees they pay, before sending them.
+
+use {
+    crate::{
+        withdraw::{WithdrawAccount, WithdrawSource, WithdrawState},
+        Error,
+    },
+    solana_clock::Epoch,
+    solana_pubkey::Pubkey,
+    spl_stake_pool::{error::StakePoolError, state::Fee},
+    spl_token_2022_interface::extension::transfer_fee::TransferFeeConfig,
+};
+
+/// Outcome of a deposit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepositQuote {
+    /// Lamports deposited
+    pub lamports: u64,
+    /// Pool tokens never minted, covering the rewards that an outdated pool
+    /// doesn't account for yet
+    pub pool_tokens_withheld: u64,
+    /// Pool tokens minted as the deposit fee, including the referral fee
+    pub fee: u64,
+    /// Pool tokens of the deposit fee minted to the referrer
+    pub referral_fee: u64,
+    /// Pool tokens minted to the depositor
+    pub pool_tokens: u64,
+}
+
+/// Outcome of a withdrawal
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WithdrawalQuote {
+    /// Pool tokens taken from the user, including the fee
+    pub pool_tokens: u64,
+    /// Pool tokens transferred to the manager fee account
+    pub fee: u64,
+    /// Pool tokens of the fee withheld by the pool mint's transfer fee
+    pub transfer_fee: u64,
+    /// Lamports withdrawn
+    pub lamports: u64,
+    /// Stake accounts that a stake withdrawal splits from, empty for a SOL
+    /// withdrawal
+    pub accounts: Vec<WithdrawAccount>,
+}
+
+/// State of a stake pool that deposits and withdrawals are quoted against
+#[derive(Clone, Debug)]
+pub struct QuoteState {
+    /// State of the pool for stake withdrawals
+    pub withdraw_state: WithdrawState,
+    /// The current epoch
+    pub epoch: Epoch,
+    /// Share of the pool tokens withheld from SOL deposits into an outdated
+    /// pool, if these are allowed
+    pub partial_update_haircut: Option<Fee>,
+    /// Transfer fee of the pool mint, if it has one
+    pub transfer_fee_config: Option<TransferFeeConfig>,
+}
+
+impl QuoteState {
+    fn is_outdated(&self) -> bool {
+        self.withdraw_state.stake_pool.last_update_epoch < self.epoch
+    }
+
+    fn check_updated(&self) -> Result<(), StakePoolError> {
+        if self.is_outdated() {
+            Err(StakePoolError::StakeListAndPoolOutOfDate)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn transfer_fee(&self, amount: u64) -> Result<u64, StakePoolError> {
+        match &self.transfer_fee_config {
+            Some(transfer_fee_config) => transfer_fee_config
+                .calculate_epoch_fee(self.epoch, amount)
+                .ok_or(StakePoolError::CalculationFailure),
+            None => Ok(0),
+        }
+    }
+
+    /// Quote a `DepositSol` of `lamports`
+    pub fn deposit_sol(&self, lamports: u64) -> Result<DepositQuote, StakePoolError> {
+        let stake_pool = &self.withdraw_state.stake_pool;
+        let partial_update_haircut = if self.is_outdated() {
+            Some(
+                self.partial_update_haircut
+                    .ok_or(StakePoolError::StakeListAndPoolOutOfDate)?,
+            )
+        } else {
+            None
+        };
+        let mut new_pool_tokens = stake_pool
+            .calc_pool_tokens_for_deposit(lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let pool_tokens_withheld = match partial_update_haircut {
+            Some(partial_update_haircut) => partial_update_haircut
+                .apply(new_pool_tokens)
+                .and_then(|pool_tokens| u64::try_from(pool_tokens).ok())
+                .ok_or(StakePoolError::CalculationFailure)?,
+            None => 0,
+        };
+        new_pool_tokens = new_pool_tokens
+            .checked_sub(pool_tokens_withheld)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let fee = stake_pool
+            .calc_pool_tokens_sol_deposit_fee(new_pool_tokens)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let referral_fee = stake_pool
+            .calc_pool_tokens_sol_referral_fee(fee)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let pool_tokens = new_pool_tokens
+            .checked_sub(fee)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        if pool_tokens == 0 {
+            return Err(StakePoolError::DepositTooSmall);
+        }
+        Ok(DepositQuote {
+            lamports,
+            pool_tokens_withheld,
+            fee,
+            referral_fee,
+            pool_tokens,
+        })
+    }
+
+    /// Quote a `DepositStake` of a stake account with `stake_lamports`
+    /// delegated, and `sol_lamports` more, such as its rent exemption
+    pub fn deposit_stake(
+        &self,
+        stake_lamports: u64,
+        sol_lamports: u64,
+    ) -> Result<DepositQuote, StakePoolError> {
+        self.check_updated()?;
+        let stake_pool = &self.withdraw_state.stake_pool;
+        let lamports = stake_lamports
+            .checked_add(sol_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let new_pool_tokens = stake_pool
+            .calc_pool_tokens_for_deposit(lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let new_pool_tokens_from_stake = stake_pool
+            .calc_pool_tokens_for_deposit(stake_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let new_pool_tokens_from_sol = new_pool_tokens
+            .checked_sub(new_pool_tokens_from_stake)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let fee = stake_pool
+            .calc_pool_tokens_stake_deposit_fee(new_pool_tokens_from_stake)
+            .zip(stake_pool.calc_pool_tokens_sol_deposit_fee(new_pool_tokens_from_sol))
+            .and_then(|(stake_fee, sol_fee)| stake_fee.checked_add(sol_fee))
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let referral_fee = stake_pool
+            .calc_pool_tokens_stake_referral_fee(fee)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let pool_tokens = new_pool_tokens
+            .checked_sub(fee)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        if pool_tokens == 0 {
+            return Err(StakePoolError::DepositTooSmall);
+        }
+        Ok(DepositQuote {
+            lamports,
+            pool_tokens_withheld: 0,
+            fee,
+            referral_fee,
+            pool_tokens,
+        })
+    }
+
+    /// Quote a `WithdrawSol` of `pool_tokens`. Withdrawals from the manager
+    /// fee account are `fee_exempt`.
+    pub fn withdraw_sol(
+        &self,
+        pool_tokens: u64,
+        fee_exempt: bool,
+    ) -> Result<WithdrawalQuote, StakePoolError> {
+        if self.is_outdated() && self.partial_update_haircut.is_none() {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate);
+        }
+        let stake_pool = &self.withdraw_state.stake_pool;
+        let fee = if fee_exempt {
+            0
+        } else {
+            stake_pool
+                .calc_pool_tokens_sol_withdrawal_fee(pool_tokens)
+                .ok_or(StakePoolError::CalculationFailure)?
+        };
+        let lamports = pool_tokens
+            .checked_sub(fee)
+            .and_then(|pool_tokens_burnt| {
+                stake_pool.calc_lamports_withdraw_amount(pool_tokens_burnt)
+            })
+            .ok_or(StakePoolError::CalculationFailure)?;
+        if lamports == 0 {
+            return Err(StakePoolError::WithdrawalTooSmall);
+        }
+        Ok(WithdrawalQuote {
+            pool_tokens,
+            fee,
+            transfer_fee: self.transfer_fee(fee)?,
+            lamports,
+            accounts: vec![],
+        })
+    }
+
+    /// Quote the `WithdrawStake` instructions of `pool_tokens`, taken from
+    /// `source`. Withdrawals from the manager fee account are `fee_exempt`.
+    pub fn withdraw_stake(
+        &self,
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+        pool_tokens: u64,
+        source: WithdrawSource,
+        fee_exempt: bool,
+    ) -> Result<WithdrawalQuote, Error> {
+        self.check_updated()?;
+        let accounts = self.withdraw_state.plan(
+            program_id,
+            stake_pool_address,
+            pool_tokens,
+            source,
+            fee_exempt,
+        )?;
+        let mut quote = WithdrawalQuote {
+            pool_tokens,
+            ..WithdrawalQuote::default()
+        };
+        for account in &accounts {
+            let withdrawal = self
+                .withdraw_state
+                .withdrawal(account.pool_amount, fee_exempt)?;
+            quote.fee = quote.fee.saturating_add(withdrawal.fee);
+            quote.transfer_fee = quote
+                .transfer_fee
+                .saturating_add(self.transfer_fee(withdrawal.fee)?);
+            quote.lamports = quote.lamports.saturating_add(account.lamports);
+        }
+        quote.accounts = accounts;
+        Ok(quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        spl_stake_pool::state::{AccountType, StakePool, ValidatorList, ValidatorListHeader},
+        spl_token_2022_interface::extension::transfer_fee::TransferFee,
+    };
+
+    const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+    fn quote_state(epoch: Epoch) -> QuoteState {
+        QuoteState {
+            withdraw_state: WithdrawState {
+                stake_pool: StakePool {
+                    account_type: AccountType::StakePool,
+                    total_lamports: 200 * LAMPORTS_PER_SOL,
+                    pool_token_supply: 100 * LAMPORTS_PER_SOL,
+                    last_update_epoch: 10,
+                    sol_deposit_fee: Fee {
+                        numerator: 1,
+                        denominator: 100,
+                    },
+                    sol_referral_fee: 50,
+                    stake_deposit_fee: Fee {
+                        numerator: 1,
+                        denominator: 10,
+                    },
+                    stake_referral_fee: 20,
+                    sol_withdrawal_fee: Fee {
+                        numerator: 1,
+                        denominator: 10,
+                    },
+                    ..StakePool::default()
+                },
+                validator_list: ValidatorList {
+                    header: ValidatorListHeader {
+                        account_type: AccountType::ValidatorList,
+                        max_validators: 0,
+                    },
+                    validators: vec![],
+                },
+                reserve_lamports: 200 * LAMPORTS_PER_SOL,
+                stake_rent_exemption: 2_282_880,
+                stake_minimum_delegation: 1,
+            },
+            epoch,
+            partial_update_haircut: None,
+            transfer_fee_config: None,
+        }
+    }
+
+    #[test]
+    fn deposit_fees() {
+        let state = quote_state(10);
+        assert_eq!(
+            state.deposit_sol(10 * LAMPORTS_PER_SOL),
+            Ok(DepositQuote {
+                lamports: 10 * LAMPORTS_PER_SOL,
+                pool_tokens_withheld: 0,
+                fee: LAMPORTS_PER_SOL / 20,
+                referral_fee: LAMPORTS_PER_SOL / 40,
+                pool_tokens: 5 * LAMPORTS_PER_SOL - LAMPORTS_PER_SOL / 20,
+            })
+        );
+        assert_eq!(
+            state.deposit_stake(10 * LAMPORTS_PER_SOL, 0),
+            Ok(DepositQuote {
+                lamports: 10 * LAMPORTS_PER_SOL,
+                pool_tokens_withheld: 0,
+                fee: LAMPORTS_PER_SOL / 2,
+                referral_fee: LAMPORTS_PER_SOL / 10,
+                pool_tokens: 5 * LAMPORTS_PER_SOL - LAMPORTS_PER_SOL / 2,
+            })
+        );
+        assert_eq!(state.deposit_sol(1), Err(StakePoolError::DepositTooSmall));
+    }
+
+    #[test]
+    fn outdated_pool() {
+        let mut state = quote_state(11);
+        assert_eq!(
+            state.deposit_sol(LAMPORTS_PER_SOL),
+            Err(StakePoolError::StakeListAndPoolOutOfDate)
+        );
+        assert_eq!(
+            state.deposit_stake(LAMPORTS_PER_SOL, 0),
+            Err(StakePoolError::StakeListAndPoolOutOfDate)
+        );
+        assert_eq!(
+            state.withdraw_sol(LAMPORTS_PER_SOL, false),
+            Err(StakePoolError::StakeListAndPoolOutOfDate)
+        );
+
+        state.partial_update_haircut = Some(Fee {
+            numerator: 1,
+            denominator: 100,
+        });
+        let quote = state.deposit_sol(2 * LAMPORTS_PER_SOL).unwrap();
+        assert_eq!(quote.pool_tokens_withheld, LAMPORTS_PER_SOL / 100);
+        assert_eq!(
+            quote.pool_tokens + quote.fee,
+            LAMPORTS_PER_SOL - LAMPORTS_PER_SOL / 100
+        );
+        assert!(state.withdraw_sol(LAMPORTS_PER_SOL, false).is_ok());
+        assert_eq!(
+            state.deposit_stake(LAMPORTS_PER_SOL, 0),
+            Err(StakePoolError::StakeListAndPoolOutOfDate)
+        );
+    }
+
+    #[test]
+    fn withdrawal_transfer_fee() {
+        let mut state = quote_state(10);
+        let quote = state.withdraw_sol(10 * LAMPORTS_PER_SOL, false).unwrap();
+        assert_eq!(quote.fee, LAMPORTS_PER_SOL);
+        assert_eq!(quote.transfer_fee, 0);
+        assert_eq!(quote.lamports, 18 * LAMPORTS_PER_SOL);
+
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        state.transfer_fee_config = Some(TransferFeeConfig {
+            older_transfer_fee: transfer_fee,
+            newer_transfer_fee: transfer_fee,
+            ..TransferFeeConfig::default()
+        });
+        let quote = state.withdraw_sol(10 * LAMPORTS_PER_SOL, false).unwrap();
+        assert_eq!(quote.transfer_fee, LAMPORTS_PER_SOL / 100);
+        assert_eq!(quote.lamports, 18 * LAMPORTS_PER_SOL);
+
+        let quote = state.withdraw_sol(10 * LAMPORTS_PER_SOL, true).unwrap();
+        assert_eq!(quote.fee, 0);
+        assert_eq!(quote.transfer_fee, 0);
+        assert_eq!(quote.lamports, 20 * LAMPORTS_PER_SOL);
+    }
+}
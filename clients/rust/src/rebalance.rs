@@ -0,0 +1,656 @@
+//! Plans of stake moves between the reserve and the validators of a pool.
+//!
+//! [`RebalanceState::plan`] targets an allocation of the pool's lamports
+//! given by a weight per validator, and only plans the
+//! `DecreaseValidatorStakeWithReserve` and `IncreaseAdditionalValidatorStake`
+//! instructions that the program accepts when sent in order.
+
+use {
+    crate::Error,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    spl_stake_pool::{
+        instruction::{
+            decrease_validator_stake_with_vote, increase_additional_validator_stake_with_vote,
+        },
+        minimum_delegation, minimum_reserve_lamports, minimum_stake_lamports,
+        state::{StakePool, StakeStatus, ValidatorList},
+    },
+    std::{collections::HashMap, num::NonZeroU32},
+};
+
+/// Seed of the ephemeral stake account of `IncreaseAdditionalValidatorStake`,
+/// which is closed by the end of each instruction
+const EPHEMERAL_STAKE_SEED: u64 = 0;
+
+/// State of a transient stake account, which decides the moves the program
+/// accepts for its validator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransientStake {
+    /// Activating since the current epoch, so more stake can be added to it
+    ActivatingThisEpoch,
+    /// Activating since an earlier epoch, waiting to be merged into the
+    /// validator stake account
+    Activating,
+    /// Deactivating, going back to the reserve once inactive
+    Deactivating,
+}
+
+/// Why a validator away from its target isn't moved
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The transient stake account of the validator is in use
+    TransientStakeInUse,
+    /// The validator stake account can't go below the minimum of the pool
+    MinimumStake,
+    /// The reserve can't fund the move
+    ReserveDepleted,
+}
+
+/// Move of stake planned for a validator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RebalanceAction {
+    /// The validator is within a minimum delegation of its target
+    None,
+    /// `IncreaseAdditionalValidatorStake` of the given lamports, which takes
+    /// a stake account rent exemption more from the reserve
+    Increase(u64),
+    /// `DecreaseValidatorStakeWithReserve` of the given lamports, which
+    /// funds the transient stake account rent exemption from the reserve
+    Decrease(u64),
+    /// The validator is away from its target, but can't be moved
+    Skip(SkipReason),
+}
+
+/// Validator of a rebalance plan
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorRebalance {
+    /// Vote account of the validator
+    pub vote_account_address: Pubkey,
+    /// Lamports staked to the validator, including activating transient stake
+    pub current_lamports: u64,
+    /// Lamports the allocation gives to the validator
+    pub target_lamports: u64,
+    /// Move of stake planned for the validator
+    pub action: RebalanceAction,
+}
+
+/// Moves of stake bringing the validators of a pool towards their targets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RebalancePlan {
+    /// Active validators of the pool, in the order of the validator list
+    pub validators: Vec<ValidatorRebalance>,
+    /// Lamports in the reserve once every move is done
+    pub reserve_lamports: u64,
+}
+
+/// State of a stake pool, as seen by the instructions moving its stake
+#[derive(Clone, Debug)]
+pub struct RebalanceState {
+    /// The stake pool, up to date for the current epoch
+    pub stake_pool: StakePool,
+    /// The validator list of the pool
+    pub validator_list: ValidatorList,
+    /// Lamports in the reserve stake account
+    pub reserve_lamports: u64,
+    /// Rent exemption of a stake account
+    pub stake_rent_exemption: u64,
+    /// Minimum delegation of the stake program
+    pub stake_minimum_delegation: u64,
+    /// State of the transient stake accounts holding lamports, by vote
+    /// account. Missing ones are taken as activating since an earlier epoch.
+    pub transient_stakes: HashMap<Pubkey, TransientStake>,
+}
+
+impl RebalanceState {
+    fn transient_stake(&self, vote_account_address: &Pubkey) -> TransientStake {
+        self.transient_stakes
+            .get(vote_account_address)
+            .copied()
+            .unwrap_or(TransientStake::Activating)
+    }
+
+    /// Plan the moves of stake giving each active validator a share of the
+    /// pool proportional to its weight in `weights`, while keeping
+    /// `retained_reserve` lamports in the reserve on top of its minimum.
+    /// Validators missing from `weights` have a weight of zero.
+    ///
+    /// Decreases only reach the validators next epoch, so a pool may take a
+    /// few epochs of plans to reach the allocation.
+    pub fn plan(
+        &self,
+        weights: &[(Pubkey, u64)],
+        retained_reserve: u64,
+    ) -> Result<RebalancePlan, Error> {
+        let rent = self.stake_rent_exemption;
+        let minimum_lamports = minimum_delegation(self.stake_minimum_delegation);
+        let minimum_validator_lamports =
+            minimum_stake_lamports(rent, self.stake_minimum_delegation);
+        let reserve_floor = minimum_reserve_lamports(rent).saturating_add(retained_reserve);
+
+        let mut validator_weights = HashMap::new();
+        for (vote_account_address, weight) in weights {
+            if !self.validator_list.validators.iter().any(|info| {
+                info.vote_account_address == *vote_account_address
+                    && info.status == StakeStatus::Active.into()
+            }) {
+                return Err(format!(
+                    "Vote account {} is not an active validator of the pool",
+                    vote_account_address
+                )
+                .into());
+            }
+            let validator_weight = validator_weights
+                .entry(*vote_account_address)
+                .or_insert(0u128);
+            *validator_weight = validator_weight.saturating_add(u128::from(*weight));
+        }
+        let total_weight = validator_weights.values().sum::<u128>();
+        if total_weight == 0 {
+            return Err("The allocation gives no weight to any validator of the pool".into());
+        }
+
+        // Deactivating transient stake goes back to the reserve, so it only
+        // counts with the reserve
+        let mut available_lamports = u128::from(self.reserve_lamports);
+        let mut validators = vec![];
+        for info in &self.validator_list.validators {
+            if info.status != StakeStatus::Active.into() {
+                continue;
+            }
+            let active_lamports = u64::from(info.active_stake_lamports);
+            let transient_lamports = u64::from(info.transient_stake_lamports);
+            let current_lamports = if transient_lamports > 0
+                && self.transient_stake(&info.vote_account_address) == TransientStake::Deactivating
+            {
+                available_lamports = available_lamports.saturating_add(transient_lamports.into());
+                active_lamports
+            } else {
+                active_lamports.saturating_add(transient_lamports)
+            };
+            available_lamports = available_lamports.saturating_add(current_lamports.into());
+            validators.push(ValidatorRebalance {
+                vote_account_address: info.vote_account_address,
+                current_lamports,
+                target_lamports: 0,
+                action: RebalanceAction::None,
+            });
+        }
+        let available_lamports = available_lamports.saturating_sub(reserve_floor.into());
+        for validator in validators.iter_mut() {
+            let weight = validator_weights
+                .get(&validator.vote_account_address)
+                .copied()
+                .unwrap_or(0);
+            validator.target_lamports = available_lamports
+                .saturating_mul(weight)
+                .checked_div(total_weight)
+                .and_then(|target| u64::try_from(target).ok())
+                .unwrap_or(u64::MAX);
+        }
+
+        // Decreases come first, so that their stake is back in the reserve
+        // as soon as possible, then the furthest validators take the reserve
+        let mut order = (0..validators.len()).collect::<Vec<_>>();
+        order.sort_by_key(|index| {
+            let validator = &validators[*index];
+            (
+                validator.current_lamports <= validator.target_lamports,
+                std::cmp::Reverse(
+                    validator
+                        .current_lamports
+                        .abs_diff(validator.target_lamports),
+                ),
+            )
+        });
+
+        let mut reserve_lamports = self.reserve_lamports;
+        for index in order {
+            let validator = &mut validators[index];
+            let info = self
+                .validator_list
+                .find(&validator.vote_account_address)
+                .ok_or("Validator list changed while planning")?;
+            let active_lamports = u64::from(info.active_stake_lamports);
+            let transient_lamports = u64::from(info.transient_stake_lamports);
+
+            validator.action = if validator.current_lamports > validator.target_lamports {
+                let excess_lamports = validator
+                    .current_lamports
+                    .saturating_sub(validator.target_lamports);
+                let lamports =
+                    excess_lamports.min(active_lamports.saturating_sub(minimum_validator_lamports));
+                if excess_lamports < minimum_lamports {
+                    RebalanceAction::None
+                } else if transient_lamports > 0 {
+                    RebalanceAction::Skip(SkipReason::TransientStakeInUse)
+                } else if lamports < minimum_lamports {
+                    RebalanceAction::Skip(SkipReason::MinimumStake)
+                } else if reserve_lamports.saturating_sub(reserve_floor) < rent {
+                    RebalanceAction::Skip(SkipReason::ReserveDepleted)
+                } else {
+                    reserve_lamports = reserve_lamports.saturating_sub(rent);
+                    RebalanceAction::Decrease(lamports)
+                }
+            } else {
+                // the rent exemption of the transient stake account ends up
+                // staked to the validator too
+                let missing_lamports = validator
+                    .target_lamports
+                    .saturating_sub(validator.current_lamports);
+                let lamports = missing_lamports.saturating_sub(rent).min(
+                    reserve_lamports
+                        .saturating_sub(reserve_floor)
+                        .saturating_sub(rent),
+                );
+                if missing_lamports.saturating_sub(rent) < minimum_lamports {
+                    RebalanceAction::None
+                } else if transient_lamports > 0
+                    && self.transient_stake(&validator.vote_account_address)
+                        != TransientStake::ActivatingThisEpoch
+                {
+                    RebalanceAction::Skip(SkipReason::TransientStakeInUse)
+                } else if lamports < minimum_lamports {
+                    RebalanceAction::Skip(SkipReason::ReserveDepleted)
+                } else {
+                    reserve_lamports =
+                        reserve_lamports.saturating_sub(lamports.saturating_add(rent));
+                    RebalanceAction::Increase(lamports)
+                }
+            };
+        }
+
+        Ok(RebalancePlan {
+            validators,
+            reserve_lamports,
+        })
+    }
+
+    /// Build the instructions of a plan, to be signed by the staker. The
+    /// decreases come first, as they need the reserve to fund their transient
+    /// stake accounts.
+    pub fn instructions(
+        &self,
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+        plan: &RebalancePlan,
+    ) -> Result<Vec<Instruction>, Error> {
+        let mut decrease_instructions = vec![];
+        let mut increase_instructions = vec![];
+        for validator in &plan.validators {
+            let info = self
+                .validator_list
+                .find(&validator.vote_account_address)
+                .ok_or_else(|| {
+                    format!(
+                        "Vote account {} does not exist in the stake pool",
+                        validator.vote_account_address
+                    )
+                })?;
+            let validator_stake_seed = NonZeroU32::new(info.validator_seed_suffix.into());
+            let transient_stake_seed = u64::from(info.transient_seed_suffix);
+            match validator.action {
+                RebalanceAction::Decrease(lamports) => {
+                    decrease_instructions.push(decrease_validator_stake_with_vote(
+                        program_id,
+                        &self.stake_pool,
+                        stake_pool_address,
+                        &validator.vote_account_address,
+                        lamports,
+                        validator_stake_seed,
+                        transient_stake_seed,
+                    ))
+                }
+                RebalanceAction::Increase(lamports) => {
+                    increase_instructions.push(increase_additional_validator_stake_with_vote(
+                        program_id,
+                        &self.stake_pool,
+                        stake_pool_address,
+                        &validator.vote_account_address,
+                        lamports,
+                        validator_stake_seed,
+                        transient_stake_seed,
+                        EPHEMERAL_STAKE_SEED,
+                    ))
+                }
+                RebalanceAction::None | RebalanceAction::Skip(_) => {}
+            }
+        }
+        decrease_instructions.append(&mut increase_instructions);
+        Ok(decrease_instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::arithmetic_side_effects)]
+    use {
+        super::*,
+        proptest::prelude::*,
+        spl_stake_pool::{
+            state::{AccountType, ValidatorListHeader, ValidatorStakeInfo},
+            MINIMUM_ACTIVE_STAKE,
+        },
+    };
+
+    const STAKE_RENT_EXEMPTION: u64 = 2_282_880;
+    const STAKE_MINIMUM_DELEGATION: u64 = 1;
+    const MINIMUM_STAKE_LAMPORTS: u64 = STAKE_RENT_EXEMPTION + MINIMUM_ACTIVE_STAKE;
+    const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+    fn rebalance_state(
+        validators: &[(u64, u64, TransientStake)],
+        reserve_lamports: u64,
+    ) -> RebalanceState {
+        let mut transient_stakes = HashMap::new();
+        let validators = validators
+            .iter()
+            .map(
+                |(active_stake_lamports, transient_stake_lamports, transient_stake)| {
+                    let vote_account_address = Pubkey::new_unique();
+                    if *transient_stake_lamports > 0 {
+                        transient_stakes.insert(vote_account_address, *transient_stake);
+                    }
+                    ValidatorStakeInfo {
+                        active_stake_lamports: (*active_stake_lamports).into(),
+                        transient_stake_lamports: (*transient_stake_lamports).into(),
+                        status: StakeStatus::Active.into(),
+                        vote_account_address,
+                        ..ValidatorStakeInfo::default()
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+        RebalanceState {
+            stake_pool: StakePool {
+                account_type: AccountType::StakePool,
+                reserve_stake: Pubkey::new_unique(),
+                ..StakePool::default()
+            },
+            validator_list: ValidatorList {
+                header: ValidatorListHeader {
+                    account_type: AccountType::ValidatorList,
+                    max_validators: validators.len() as u32,
+                },
+                validators,
+            },
+            reserve_lamports,
+            stake_rent_exemption: STAKE_RENT_EXEMPTION,
+            stake_minimum_delegation: STAKE_MINIMUM_DELEGATION,
+            transient_stakes,
+        }
+    }
+
+    fn equal_weights(state: &RebalanceState) -> Vec<(Pubkey, u64)> {
+        state
+            .validator_list
+            .validators
+            .iter()
+            .map(|info| (info.vote_account_address, 1))
+            .collect()
+    }
+
+    fn actions(plan: &RebalancePlan) -> Vec<RebalanceAction> {
+        plan.validators
+            .iter()
+            .map(|validator| validator.action)
+            .collect()
+    }
+
+    #[test]
+    fn equal_split_of_the_reserve() {
+        let reserve_lamports = 30 * LAMPORTS_PER_SOL + 6 * STAKE_RENT_EXEMPTION;
+        let state = rebalance_state(
+            &[
+                (MINIMUM_STAKE_LAMPORTS, 0, TransientStake::Activating),
+                (MINIMUM_STAKE_LAMPORTS, 0, TransientStake::Activating),
+                (MINIMUM_STAKE_LAMPORTS, 0, TransientStake::Activating),
+            ],
+            reserve_lamports,
+        );
+        let plan = state
+            .plan(&equal_weights(&state), 2 * STAKE_RENT_EXEMPTION)
+            .unwrap();
+        let target_lamports = MINIMUM_STAKE_LAMPORTS + 10 * LAMPORTS_PER_SOL + STAKE_RENT_EXEMPTION;
+        for validator in &plan.validators {
+            assert_eq!(validator.target_lamports, target_lamports);
+        }
+        assert_eq!(
+            actions(&plan),
+            vec![RebalanceAction::Increase(10 * LAMPORTS_PER_SOL); 3]
+        );
+        assert_eq!(plan.reserve_lamports, 3 * STAKE_RENT_EXEMPTION);
+    }
+
+    #[test]
+    fn overweight_validators_are_decreased_to_the_minimum() {
+        let state = rebalance_state(
+            &[
+                (10 * LAMPORTS_PER_SOL, 0, TransientStake::Activating),
+                (MINIMUM_STAKE_LAMPORTS, 0, TransientStake::Activating),
+            ],
+            MINIMUM_STAKE_LAMPORTS + 2 * STAKE_RENT_EXEMPTION,
+        );
+        let weights = vec![(state.validator_list.validators[1].vote_account_address, 1)];
+        let plan = state.plan(&weights, 0).unwrap();
+        assert_eq!(plan.validators[0].target_lamports, 0);
+        assert_eq!(
+            actions(&plan),
+            vec![
+                RebalanceAction::Decrease(10 * LAMPORTS_PER_SOL - MINIMUM_STAKE_LAMPORTS),
+                RebalanceAction::Increase(MINIMUM_ACTIVE_STAKE),
+            ]
+        );
+        assert_eq!(plan.reserve_lamports, STAKE_RENT_EXEMPTION);
+
+        let stake_pool_address = Pubkey::new_unique();
+        let instructions = state
+            .instructions(&spl_stake_pool::id(), &stake_pool_address, &plan)
+            .unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                decrease_validator_stake_with_vote(
+                    &spl_stake_pool::id(),
+                    &state.stake_pool,
+                    &stake_pool_address,
+                    &plan.validators[0].vote_account_address,
+                    10 * LAMPORTS_PER_SOL - MINIMUM_STAKE_LAMPORTS,
+                    None,
+                    0,
+                ),
+                increase_additional_validator_stake_with_vote(
+                    &spl_stake_pool::id(),
+                    &state.stake_pool,
+                    &stake_pool_address,
+                    &plan.validators[1].vote_account_address,
+                    MINIMUM_ACTIVE_STAKE,
+                    None,
+                    0,
+                    EPHEMERAL_STAKE_SEED,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn transient_stake_is_respected() {
+        let state = rebalance_state(
+            &[
+                (
+                    20 * LAMPORTS_PER_SOL,
+                    LAMPORTS_PER_SOL,
+                    TransientStake::Deactivating,
+                ),
+                (
+                    MINIMUM_STAKE_LAMPORTS,
+                    LAMPORTS_PER_SOL,
+                    TransientStake::ActivatingThisEpoch,
+                ),
+                (
+                    MINIMUM_STAKE_LAMPORTS,
+                    LAMPORTS_PER_SOL,
+                    TransientStake::Activating,
+                ),
+            ],
+            10 * LAMPORTS_PER_SOL,
+        );
+        let plan = state.plan(&equal_weights(&state), 0).unwrap();
+        assert_eq!(plan.validators[0].current_lamports, 20 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            plan.validators[1].current_lamports,
+            MINIMUM_STAKE_LAMPORTS + LAMPORTS_PER_SOL
+        );
+        assert_eq!(
+            plan.validators[0].action,
+            RebalanceAction::Skip(SkipReason::TransientStakeInUse)
+        );
+        assert!(matches!(
+            plan.validators[1].action,
+            RebalanceAction::Increase(_)
+        ));
+        assert_eq!(
+            plan.validators[2].action,
+            RebalanceAction::Skip(SkipReason::TransientStakeInUse)
+        );
+    }
+
+    #[test]
+    fn retained_reserve_is_kept() {
+        let state = rebalance_state(
+            &[(MINIMUM_STAKE_LAMPORTS, 0, TransientStake::Activating)],
+            10 * LAMPORTS_PER_SOL,
+        );
+        let plan = state
+            .plan(&equal_weights(&state), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        assert_eq!(
+            actions(&plan),
+            vec![RebalanceAction::Skip(SkipReason::MinimumStake)]
+        );
+        assert_eq!(plan.reserve_lamports, 10 * LAMPORTS_PER_SOL);
+
+        let plan = state
+            .plan(&equal_weights(&state), 5 * LAMPORTS_PER_SOL)
+            .unwrap();
+        assert_eq!(
+            plan.reserve_lamports,
+            5 * LAMPORTS_PER_SOL + STAKE_RENT_EXEMPTION
+        );
+    }
+
+    #[test]
+    fn invalid_weights() {
+        let state = rebalance_state(
+            &[(MINIMUM_STAKE_LAMPORTS, 0, TransientStake::Activating)],
+            10 * LAMPORTS_PER_SOL,
+        );
+        assert!(state.plan(&[(Pubkey::new_unique(), 1)], 0).is_err());
+        assert!(state.plan(&[], 0).is_err());
+        let vote_account_address = state.validator_list.validators[0].vote_account_address;
+        assert!(state.plan(&[(vote_account_address, 0)], 0).is_err());
+    }
+
+    fn arbitrary_transient_stake() -> impl Strategy<Value = TransientStake> {
+        prop_oneof![
+            Just(TransientStake::ActivatingThisEpoch),
+            Just(TransientStake::Activating),
+            Just(TransientStake::Deactivating),
+        ]
+    }
+
+    fn arbitrary_rebalance_state() -> impl Strategy<Value = (RebalanceState, Vec<u64>, u64)> {
+        (
+            prop::collection::vec(
+                (
+                    MINIMUM_STAKE_LAMPORTS..100 * LAMPORTS_PER_SOL,
+                    prop_oneof![Just(0), MINIMUM_STAKE_LAMPORTS..10 * LAMPORTS_PER_SOL],
+                    arbitrary_transient_stake(),
+                    0..10u64,
+                ),
+                1..8,
+            ),
+            0..200 * LAMPORTS_PER_SOL,
+            prop_oneof![Just(0), 0..20 * LAMPORTS_PER_SOL],
+        )
+            .prop_filter_map("no weight", |(validators, reserve_lamports, retained)| {
+                let weights = validators
+                    .iter()
+                    .map(|(_, _, _, weight)| *weight)
+                    .collect::<Vec<_>>();
+                (weights.iter().sum::<u64>() > 0).then(|| {
+                    let validators = validators
+                        .into_iter()
+                        .map(|(active, transient, transient_stake, _)| {
+                            (active, transient, transient_stake)
+                        })
+                        .collect::<Vec<_>>();
+                    (
+                        rebalance_state(&validators, reserve_lamports),
+                        weights,
+                        retained,
+                    )
+                })
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn plans_follow_the_program(
+            (state, weights, retained_reserve) in arbitrary_rebalance_state(),
+        ) {
+            let weights = state
+                .validator_list
+                .validators
+                .iter()
+                .zip(weights)
+                .map(|(info, weight)| (info.vote_account_address, weight))
+                .collect::<Vec<_>>();
+            let plan = state.plan(&weights, retained_reserve).unwrap();
+            let rent = STAKE_RENT_EXEMPTION;
+            let minimum_lamports = minimum_delegation(STAKE_MINIMUM_DELEGATION);
+
+            // replay the moves in the order the CLI sends them
+            let mut moves = plan.validators.iter().collect::<Vec<_>>();
+            moves.sort_by_key(|validator| !matches!(validator.action, RebalanceAction::Decrease(_)));
+            let mut reserve_lamports = state.reserve_lamports;
+            for validator in moves {
+                let info = state.validator_list.find(&validator.vote_account_address).unwrap();
+                let transient_lamports = u64::from(info.transient_stake_lamports);
+                match validator.action {
+                    RebalanceAction::Decrease(lamports) => {
+                        prop_assert_eq!(transient_lamports, 0);
+                        prop_assert!(lamports >= minimum_lamports);
+                        prop_assert!(
+                            u64::from(info.active_stake_lamports) - lamports
+                                >= MINIMUM_STAKE_LAMPORTS
+                        );
+                        prop_assert!(validator.current_lamports - lamports >= validator.target_lamports);
+                        prop_assert!(rent < reserve_lamports);
+                        reserve_lamports -= rent;
+                        prop_assert!(reserve_lamports >= rent + retained_reserve);
+                    }
+                    RebalanceAction::Increase(lamports) => {
+                        prop_assert!(
+                            transient_lamports == 0
+                                || state.transient_stakes[&validator.vote_account_address]
+                                    == TransientStake::ActivatingThisEpoch
+                        );
+                        prop_assert!(lamports >= minimum_lamports);
+                        prop_assert!(
+                            validator.current_lamports + lamports + rent <= validator.target_lamports
+                        );
+                        prop_assert!(reserve_lamports - (lamports + rent) >= rent + retained_reserve);
+                        reserve_lamports -= lamports + rent;
+                    }
+                    RebalanceAction::None => {
+                        let difference = validator.current_lamports.abs_diff(validator.target_lamports);
+                        prop_assert!(difference < minimum_lamports + rent);
+                    }
+                    RebalanceAction::Skip(_) => {}
+                }
+            }
+            prop_assert_eq!(reserve_lamports, plan.reserve_lamports);
+        }
+    }
+}
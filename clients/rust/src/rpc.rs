@@ -0,0 +1,203 @@
+//! Source of the on-chain data used by the client
+
+use {
+    crate::Error, solana_account::Account, solana_clock::Epoch, solana_pubkey::Pubkey,
+    std::future::Future,
+};
+
+/// On-chain data required by the client, to be implemented on top of an RPC
+/// client, a bank, or a cache of accounts
+pub trait StakePoolRpc {
+    /// Get an account, if it exists
+    fn get_account(
+        &self,
+        address: &Pubkey,
+    ) -> impl Future<Output = Result<Option<Account>, Error>> + Send;
+
+    /// Get the minimum balance for an account of `data_len` bytes to be rent
+    /// exempt
+    fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> impl Future<Output = Result<u64, Error>> + Send;
+
+    /// Get the current epoch
+    fn get_epoch(&self) -> impl Future<Output = Result<Epoch, Error>> + Send;
+
+    /// Get the minimum delegation of the stake program
+    fn get_stake_minimum_delegation(&self) -> impl Future<Output = Result<u64, Error>> + Send;
+
+    /// Get the addresses and lamports of the stake accounts whose withdraw
+    /// authority is `withdrawer`
+    fn get_stake_accounts_by_withdrawer(
+        &self,
+        withdrawer: &Pubkey,
+    ) -> impl Future<Output = Result<Vec<(Pubkey, u64)>, Error>> + Send;
+}
+
+impl<T: StakePoolRpc + Sync> StakePoolRpc for &T {
+    fn get_account(
+        &self,
+        address: &Pubkey,
+    ) -> impl Future<Output = Result<Option<Account>, Error>> + Send {
+        (**self).get_account(address)
+    }
+
+    fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> impl Future<Output = Result<u64, Error>> + Send {
+        (**self).get_minimum_balance_for_rent_exemption(data_len)
+    }
+
+    fn get_epoch(&self) -> impl Future<Output = Result<Epoch, Error>> + Send {
+        (**self).get_epoch()
+    }
+
+    fn get_stake_minimum_delegation(&self) -> impl Future<Output = Result<u64, Error>> + Send {
+        (**self).get_stake_minimum_delegation()
+    }
+
+    fn get_stake_accounts_by_withdrawer(
+        &self,
+        withdrawer: &Pubkey,
+    ) -> impl Future<Output = Result<Vec<(Pubkey, u64)>, Error>> + Send {
+        (**self).get_stake_accounts_by_withdrawer(withdrawer)
+    }
+}
+
+#[cfg(feature = "rpc-client")]
+mod rpc_client {
+    use {
+        super::*,
+        solana_client::{
+            nonblocking,
+            rpc_client::RpcClient,
+            rpc_config::{
+                RpcAccountInfoConfig, RpcProgramAccountsConfig, UiAccountEncoding,
+                UiDataSliceConfig,
+            },
+            rpc_filter::{Memcmp, RpcFilterType},
+        },
+    };
+
+    /// Offset of `Meta::authorized::withdrawer` in a stake account
+    const STAKE_WITHDRAWER_OFFSET: usize = 44;
+
+    fn stake_accounts_by_withdrawer_config(withdrawer: &Pubkey) -> RpcProgramAccountsConfig {
+        RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                STAKE_WITHDRAWER_OFFSET,
+                withdrawer.as_ref(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                // only the lamports are needed
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: 0,
+                }),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        }
+    }
+
+    impl StakePoolRpc for nonblocking::rpc_client::RpcClient {
+        async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>, Error> {
+            Ok(self
+                .get_account_with_commitment(address, self.commitment())
+                .await?
+                .value)
+        }
+
+        async fn get_minimum_balance_for_rent_exemption(
+            &self,
+            data_len: usize,
+        ) -> Result<u64, Error> {
+            Ok(self
+                .get_minimum_balance_for_rent_exemption(data_len)
+                .await?)
+        }
+
+        async fn get_epoch(&self) -> Result<Epoch, Error> {
+            Ok(self.get_epoch_info().await?.epoch)
+        }
+
+        async fn get_stake_minimum_delegation(&self) -> Result<u64, Error> {
+            Ok(self.get_stake_minimum_delegation().await?)
+        }
+
+        async fn get_stake_accounts_by_withdrawer(
+            &self,
+            withdrawer: &Pubkey,
+        ) -> Result<Vec<(Pubkey, u64)>, Error> {
+            Ok(self
+                .get_program_ui_accounts_with_config(
+                    &solana_stake_interface::program::id(),
+                    stake_accounts_by_withdrawer_config(withdrawer),
+                )
+                .await?
+                .into_iter()
+                .map(|(address, account)| (address, account.lamports))
+                .collect())
+        }
+    }
+
+    /// The blocking client runs each request to completion before returning
+    /// its future, which suits synchronous callers such as command-line tools
+    impl StakePoolRpc for RpcClient {
+        fn get_account(
+            &self,
+            address: &Pubkey,
+        ) -> impl Future<Output = Result<Option<Account>, Error>> + Send {
+            let result = self
+                .get_account_with_commitment(address, self.commitment())
+                .map(|response| response.value)
+                .map_err(Into::into);
+            async move { result }
+        }
+
+        fn get_minimum_balance_for_rent_exemption(
+            &self,
+            data_len: usize,
+        ) -> impl Future<Output = Result<u64, Error>> + Send {
+            let result = self
+                .get_minimum_balance_for_rent_exemption(data_len)
+                .map_err(Into::into);
+            async move { result }
+        }
+
+        fn get_epoch(&self) -> impl Future<Output = Result<Epoch, Error>> + Send {
+            let result = self
+                .get_epoch_info()
+                .map(|epoch_info| epoch_info.epoch)
+                .map_err(Into::into);
+            async move { result }
+        }
+
+        fn get_stake_minimum_delegation(&self) -> impl Future<Output = Result<u64, Error>> + Send {
+            let result = self.get_stake_minimum_delegation().map_err(Into::into);
+            async move { result }
+        }
+
+        fn get_stake_accounts_by_withdrawer(
+            &self,
+            withdrawer: &Pubkey,
+        ) -> impl Future<Output = Result<Vec<(Pubkey, u64)>, Error>> + Send {
+            let result = self
+                .get_program_ui_accounts_with_config(
+                    &solana_stake_interface::program::id(),
+                    stake_accounts_by_withdrawer_config(withdrawer),
+                )
+                .map(|accounts| {
+                    accounts
+                        .into_iter()
+                        .map(|(address, account)| (address, account.lamports))
+                        .collect()
+                })
+                .map_err(Into::into);
+            async move { result }
+        }
+    }
+}
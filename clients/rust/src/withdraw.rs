@@ -0,0 +1,864 @@
+//! Plans of stake withdrawals.
+//!
+//! [`WithdrawState`] mirrors every check of the `WithdrawStake` instruction,
+//! so that a plan only contains withdrawals that the program accepts when
+//! sent in order, each with the exact lamports it splits off.
+
+use {
+    crate::Error,
+    solana_pubkey::Pubkey,
+    spl_stake_pool::{
+        error::StakePoolError,
+        find_stake_program_address, find_transient_stake_program_address, minimum_reserve_lamports,
+        minimum_stake_lamports,
+        state::{StakePool, StakeStatus, ValidatorList, ValidatorStakeInfo},
+    },
+    std::{num::NonZeroU32, ops::RangeInclusive},
+};
+
+/// Stake account to withdraw from, and the pool tokens burned for it
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithdrawAccount {
+    /// Stake account to split the stake from
+    pub stake_address: Pubkey,
+    /// Vote account the stake is delegated to, none for the reserve
+    pub vote_address: Option<Pubkey>,
+    /// Pool tokens to burn, including the withdrawal fee
+    pub pool_amount: u64,
+    /// Lamports split off the stake account
+    pub lamports: u64,
+}
+
+/// Stake accounts a withdrawal may take stake from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WithdrawSource {
+    /// Any stake account of the pool, in the order required by the program:
+    /// the preferred withdraw validator, then active stake, then transient
+    /// stake, then the reserve
+    Any,
+    /// The active or transient stake account of a validator, given by its vote
+    /// account
+    Validator(Pubkey),
+    /// The reserve stake account
+    Reserve,
+}
+
+/// Stake account of a stake pool that a withdrawal splits stake from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolStakeAccount {
+    /// The reserve stake account
+    Reserve,
+    /// The stake account of a validator, given by its vote account
+    Validator(Pubkey),
+    /// The transient stake account of a validator, given by its vote account
+    Transient(Pubkey),
+}
+
+/// Outcome of a `WithdrawStake` instruction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Withdrawal {
+    /// Pool tokens taken from the user, including the fee
+    pub pool_tokens: u64,
+    /// Pool tokens transferred to the manager fee account
+    pub fee: u64,
+    /// Lamports split off the pool's stake account
+    pub lamports: u64,
+}
+
+/// Lamports that `WithdrawStake` accepts to split from a stake account
+struct SplitBounds {
+    /// Accepted lamports, before any truncation
+    lamports: RangeInclusive<u64>,
+    /// Whether the whole account is withdrawn, removing the validator
+    whole_account: bool,
+}
+
+/// State of a stake pool, as seen by `WithdrawStake`
+#[derive(Clone, Debug)]
+pub struct WithdrawState {
+    /// The stake pool, up to date for the current epoch
+    pub stake_pool: StakePool,
+    /// The validator list of the pool
+    pub validator_list: ValidatorList,
+    /// Lamports in the reserve stake account
+    pub reserve_lamports: u64,
+    /// Rent exemption of a stake account
+    pub stake_rent_exemption: u64,
+    /// Minimum delegation of the stake program
+    pub stake_minimum_delegation: u64,
+}
+
+impl WithdrawState {
+    /// Compute the fee and lamports of a withdrawal of `pool_tokens`, without
+    /// checking where they come from. Withdrawals from the manager fee account
+    /// are `fee_exempt`.
+    pub fn withdrawal(
+        &self,
+        pool_tokens: u64,
+        fee_exempt: bool,
+    ) -> Result<Withdrawal, StakePoolError> {
+        let fee = if fee_exempt {
+            0
+        } else {
+            self.stake_pool
+                .calc_pool_tokens_stake_withdrawal_fee(pool_tokens)
+                .ok_or(StakePoolError::CalculationFailure)?
+        };
+        let pool_tokens_burnt = pool_tokens
+            .checked_sub(fee)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let lamports = self
+            .stake_pool
+            .calc_lamports_withdraw_amount(pool_tokens_burnt)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        Ok(Withdrawal {
+            pool_tokens,
+            fee,
+            lamports,
+        })
+    }
+
+    fn validator(&self, vote_account_address: &Pubkey) -> Option<&ValidatorStakeInfo> {
+        self.validator_list.find(vote_account_address)
+    }
+
+    /// Apply the checks of `WithdrawStake` that don't depend on the amount
+    fn split_bounds(&self, account: PoolStakeAccount) -> Result<SplitBounds, StakePoolError> {
+        let required_lamports =
+            minimum_stake_lamports(self.stake_rent_exemption, self.stake_minimum_delegation);
+        let lamports_per_pool_token = self
+            .stake_pool
+            .get_lamports_per_pool_token()
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let minimum_lamports_with_tolerance = required_lamports
+            .saturating_add(self.stake_minimum_delegation)
+            .saturating_add(lamports_per_pool_token);
+
+        let is_active = |info: &&ValidatorStakeInfo| info.status == StakeStatus::Active.into();
+        let has_withdrawable_active_stake = self
+            .validator_list
+            .validators
+            .iter()
+            .filter(is_active)
+            .any(|info| u64::from(info.active_stake_lamports) > minimum_lamports_with_tolerance);
+        let has_withdrawable_transient_stake = self
+            .validator_list
+            .validators
+            .iter()
+            .filter(is_active)
+            .any(|info| u64::from(info.transient_stake_lamports) > minimum_lamports_with_tolerance);
+
+        let vote_account_address = match account {
+            PoolStakeAccount::Reserve => {
+                if has_withdrawable_active_stake || has_withdrawable_transient_stake {
+                    return Err(StakePoolError::StakeLamportsNotEqualToMinimum);
+                }
+                let available = self
+                    .reserve_lamports
+                    .saturating_sub(minimum_reserve_lamports(self.stake_rent_exemption));
+                return Ok(SplitBounds {
+                    lamports: 1..=available,
+                    whole_account: false,
+                });
+            }
+            PoolStakeAccount::Validator(vote_account_address)
+            | PoolStakeAccount::Transient(vote_account_address) => vote_account_address,
+        };
+
+        if let Some(preferred_withdraw_validator) =
+            self.stake_pool.preferred_withdraw_validator_vote_address
+        {
+            if let Some(preferred_validator_info) = self.validator(&preferred_withdraw_validator) {
+                if preferred_withdraw_validator != vote_account_address
+                    && u64::from(preferred_validator_info.active_stake_lamports)
+                        >= minimum_lamports_with_tolerance
+                {
+                    return Err(StakePoolError::IncorrectWithdrawVoteAddress);
+                }
+            }
+        }
+
+        let validator_stake_info = self
+            .validator(&vote_account_address)
+            .ok_or(StakePoolError::ValidatorNotFound)?;
+        let active_stake_lamports = u64::from(validator_stake_info.active_stake_lamports);
+        let transient_stake_lamports = u64::from(validator_stake_info.transient_stake_lamports);
+
+        let (split_from_lamports, whole_account) = if has_withdrawable_active_stake {
+            match account {
+                PoolStakeAccount::Validator(_) => (active_stake_lamports, false),
+                _ => return Err(StakePoolError::InvalidStakeAccountAddress),
+            }
+        } else if has_withdrawable_transient_stake || transient_stake_lamports != 0 {
+            match account {
+                PoolStakeAccount::Transient(_) => (transient_stake_lamports, false),
+                _ => return Err(StakePoolError::InvalidStakeAccountAddress),
+            }
+        } else {
+            match account {
+                PoolStakeAccount::Validator(_) => (active_stake_lamports, true),
+                _ => return Err(StakePoolError::InvalidStakeAccountAddress),
+            }
+        };
+
+        if validator_stake_info.status != StakeStatus::Active.into() {
+            return Err(StakePoolError::ValidatorNotFound);
+        }
+
+        let lamports = if whole_account {
+            split_from_lamports..=split_from_lamports.saturating_add(lamports_per_pool_token)
+        } else {
+            1..=split_from_lamports.saturating_sub(required_lamports)
+        };
+        Ok(SplitBounds {
+            lamports,
+            whole_account,
+        })
+    }
+
+    /// Check a withdrawal of `pool_tokens` from `account` as `WithdrawStake`
+    /// does, returning its outcome or the error of the program
+    pub fn check(
+        &self,
+        account: PoolStakeAccount,
+        pool_tokens: u64,
+        fee_exempt: bool,
+    ) -> Result<Withdrawal, StakePoolError> {
+        let mut withdrawal = self.withdrawal(pool_tokens, fee_exempt)?;
+        if withdrawal.lamports == 0 {
+            return Err(StakePoolError::WithdrawalTooSmall);
+        }
+        let bounds = self.split_bounds(account)?;
+        if !bounds.lamports.contains(&withdrawal.lamports) {
+            return Err(match account {
+                PoolStakeAccount::Reserve => StakePoolError::SolWithdrawalTooLarge,
+                _ => StakePoolError::StakeLamportsNotEqualToMinimum,
+            });
+        }
+        if bounds.whole_account {
+            withdrawal.lamports = *bounds.lamports.start();
+        }
+        Ok(withdrawal)
+    }
+
+    /// Update the state after a successful withdrawal from `account`, as
+    /// `WithdrawStake` does
+    pub fn apply(&mut self, account: PoolStakeAccount, withdrawal: &Withdrawal) {
+        let pool_tokens_burnt = withdrawal.pool_tokens.saturating_sub(withdrawal.fee);
+        self.stake_pool.pool_token_supply = self
+            .stake_pool
+            .pool_token_supply
+            .saturating_sub(pool_tokens_burnt);
+        self.stake_pool.total_lamports = self
+            .stake_pool
+            .total_lamports
+            .saturating_sub(withdrawal.lamports);
+
+        let vote_account_address = match account {
+            PoolStakeAccount::Reserve => {
+                self.reserve_lamports = self.reserve_lamports.saturating_sub(withdrawal.lamports);
+                return;
+            }
+            PoolStakeAccount::Validator(vote_account_address)
+            | PoolStakeAccount::Transient(vote_account_address) => vote_account_address,
+        };
+        let Some(validator_stake_info) = self.validator_list.find_mut(&vote_account_address) else {
+            return;
+        };
+        if let PoolStakeAccount::Transient(_) = account {
+            validator_stake_info.transient_stake_lamports =
+                u64::from(validator_stake_info.transient_stake_lamports)
+                    .saturating_sub(withdrawal.lamports)
+                    .into();
+            return;
+        }
+        let active_stake_lamports = u64::from(validator_stake_info.active_stake_lamports)
+            .saturating_sub(withdrawal.lamports);
+        validator_stake_info.active_stake_lamports = active_stake_lamports.into();
+        if active_stake_lamports == 0 {
+            validator_stake_info.status = StakeStatus::ReadyForRemoval.into();
+            let removed = Some(vote_account_address);
+            if self.stake_pool.preferred_deposit_validator_vote_address == removed {
+                self.stake_pool.preferred_deposit_validator_vote_address = None;
+            }
+            if self.stake_pool.preferred_withdraw_validator_vote_address == removed {
+                self.stake_pool.preferred_withdraw_validator_vote_address = None;
+            }
+        }
+    }
+
+    /// Smallest amount of pool tokens, up to `max_pool_tokens`, worth at least
+    /// `lamports`
+    fn min_pool_tokens(
+        &self,
+        lamports: u64,
+        max_pool_tokens: u64,
+        fee_exempt: bool,
+    ) -> Option<u64> {
+        let worth_enough = |pool_tokens| {
+            self.withdrawal(pool_tokens, fee_exempt)
+                .is_ok_and(|withdrawal| withdrawal.lamports >= lamports)
+        };
+        if !worth_enough(max_pool_tokens) {
+            return None;
+        }
+        let (mut low, mut high) = (0, max_pool_tokens);
+        while low < high {
+            let middle = low.saturating_add(high.saturating_sub(low) / 2);
+            if worth_enough(middle) {
+                high = middle;
+            } else {
+                low = middle.saturating_add(1);
+            }
+        }
+        Some(low)
+    }
+
+    /// Largest amount of pool tokens, up to `max_pool_tokens`, worth at most
+    /// `lamports`
+    fn max_pool_tokens(&self, lamports: u64, max_pool_tokens: u64, fee_exempt: bool) -> u64 {
+        let worth_little_enough = |pool_tokens| {
+            self.withdrawal(pool_tokens, fee_exempt)
+                .is_ok_and(|withdrawal| withdrawal.lamports <= lamports)
+        };
+        let (mut low, mut high) = (0, max_pool_tokens);
+        while low < high {
+            let middle = high.saturating_sub(high.saturating_sub(low) / 2);
+            if worth_little_enough(middle) {
+                low = middle;
+            } else {
+                high = middle.saturating_sub(1);
+            }
+        }
+        low
+    }
+
+    /// Largest withdrawal of at most `max_pool_tokens` from `account` that
+    /// succeeds, or the cheapest one if it takes the whole account.
+    ///
+    /// When the account can't be taken whole, the stake program also requires
+    /// the split stake to be at least its minimum delegation, and the
+    /// withdrawal leaves enough pool tokens for another one if it doesn't
+    /// take all of them.
+    fn largest_withdrawal(
+        &self,
+        account: PoolStakeAccount,
+        max_pool_tokens: u64,
+        fee_exempt: bool,
+    ) -> Option<Withdrawal> {
+        let bounds = self.split_bounds(account).ok()?;
+        let pool_tokens = if bounds.whole_account {
+            if self.check(account, max_pool_tokens, fee_exempt).is_ok() {
+                max_pool_tokens
+            } else {
+                // more pool tokens than needed wouldn't bring any more lamports
+                self.min_pool_tokens(*bounds.lamports.start(), max_pool_tokens, fee_exempt)?
+            }
+        } else {
+            let minimum_lamports = match account {
+                PoolStakeAccount::Reserve => *bounds.lamports.start(),
+                _ => (*bounds.lamports.start()).max(self.stake_minimum_delegation),
+            };
+            let minimum_pool_tokens =
+                self.min_pool_tokens(minimum_lamports, max_pool_tokens, fee_exempt)?;
+            let mut pool_tokens =
+                self.max_pool_tokens(*bounds.lamports.end(), max_pool_tokens, fee_exempt);
+            let smallest_withdrawal = self
+                .min_pool_tokens(
+                    self.stake_minimum_delegation.max(1),
+                    max_pool_tokens,
+                    fee_exempt,
+                )
+                .unwrap_or(max_pool_tokens);
+            let remaining_pool_tokens = max_pool_tokens.checked_sub(pool_tokens)?;
+            if remaining_pool_tokens > 0 && remaining_pool_tokens < smallest_withdrawal {
+                pool_tokens = max_pool_tokens.saturating_sub(smallest_withdrawal);
+            }
+            if pool_tokens < minimum_pool_tokens {
+                return None;
+            }
+            pool_tokens
+        };
+        self.check(account, pool_tokens, fee_exempt).ok()
+    }
+
+    /// Stake accounts that `source` may withdraw from, the preferred withdraw
+    /// validator first
+    fn candidates(&self, source: WithdrawSource) -> Vec<PoolStakeAccount> {
+        let validator_accounts = |info: &ValidatorStakeInfo| {
+            let mut accounts = vec![PoolStakeAccount::Validator(info.vote_account_address)];
+            if u64::from(info.transient_stake_lamports) > 0 {
+                accounts.push(PoolStakeAccount::Transient(info.vote_account_address));
+            }
+            accounts
+        };
+        match source {
+            WithdrawSource::Reserve => vec![PoolStakeAccount::Reserve],
+            WithdrawSource::Validator(vote_account_address) => self
+                .validator(&vote_account_address)
+                .map(validator_accounts)
+                .unwrap_or_default(),
+            WithdrawSource::Any => {
+                let preferred = self.stake_pool.preferred_withdraw_validator_vote_address;
+                let mut validators = self
+                    .validator_list
+                    .validators
+                    .iter()
+                    .filter(|info| info.status == StakeStatus::Active.into())
+                    .collect::<Vec<_>>();
+                validators.sort_by_key(|info| Some(info.vote_account_address) != preferred);
+                let mut accounts = validators
+                    .into_iter()
+                    .flat_map(validator_accounts)
+                    .collect::<Vec<_>>();
+                accounts.push(PoolStakeAccount::Reserve);
+                accounts
+            }
+        }
+    }
+
+    fn withdraw_account(
+        &self,
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+        account: PoolStakeAccount,
+        withdrawal: &Withdrawal,
+    ) -> WithdrawAccount {
+        let (stake_address, vote_address) = match account {
+            PoolStakeAccount::Reserve => (self.stake_pool.reserve_stake, None),
+            PoolStakeAccount::Validator(vote_account_address) => {
+                let validator_seed = self
+                    .validator(&vote_account_address)
+                    .and_then(|info| NonZeroU32::new(info.validator_seed_suffix.into()));
+                let (stake_address, _) = find_stake_program_address(
+                    program_id,
+                    &vote_account_address,
+                    stake_pool_address,
+                    validator_seed,
+                );
+                (stake_address, Some(vote_account_address))
+            }
+            PoolStakeAccount::Transient(vote_account_address) => {
+                let transient_seed = self
+                    .validator(&vote_account_address)
+                    .map_or(0, |info| info.transient_seed_suffix.into());
+                let (stake_address, _) = find_transient_stake_program_address(
+                    program_id,
+                    &vote_account_address,
+                    stake_pool_address,
+                    transient_seed,
+                );
+                (stake_address, Some(vote_account_address))
+            }
+        };
+        WithdrawAccount {
+            stake_address,
+            vote_address,
+            pool_amount: withdrawal.pool_tokens,
+            lamports: withdrawal.lamports,
+        }
+    }
+
+    /// Plan the withdrawal of `pool_amount` pool tokens from `source`, in as
+    /// few withdrawals as possible.
+    ///
+    /// Each withdrawal takes as much as the program allows from the stake
+    /// account that can give the most, once the previous withdrawals are
+    /// done, so the plan must be sent in order.
+    pub fn plan(
+        &self,
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+        pool_amount: u64,
+        source: WithdrawSource,
+        fee_exempt: bool,
+    ) -> Result<Vec<WithdrawAccount>, Error> {
+        let mut state = self.clone();
+        let mut remaining_amount = pool_amount;
+        let mut withdraw_accounts = vec![];
+        while remaining_amount > 0 {
+            let mut best: Option<(PoolStakeAccount, Withdrawal)> = None;
+            for account in state.candidates(source) {
+                if let Some(withdrawal) =
+                    state.largest_withdrawal(account, remaining_amount, fee_exempt)
+                {
+                    if best.is_none_or(|(_, best)| withdrawal.pool_tokens > best.pool_tokens) {
+                        best = Some((account, withdrawal));
+                    }
+                }
+            }
+            let Some((account, withdrawal)) = best else {
+                return Err(match state.withdrawal(remaining_amount, fee_exempt) {
+                    Ok(withdrawal) if withdrawal.lamports == 0 => format!(
+                        "Cannot withdraw the last {} pool tokens, which are worth less than a lamport",
+                        remaining_amount
+                    ),
+                    _ => format!(
+                        "No stake accounts found in this pool with enough balance to withdraw {} pool tokens",
+                        remaining_amount
+                    ),
+                }
+                .into());
+            };
+            withdraw_accounts.push(state.withdraw_account(
+                program_id,
+                stake_pool_address,
+                account,
+                &withdrawal,
+            ));
+            state.apply(account, &withdrawal);
+            remaining_amount = remaining_amount
+                .checked_sub(withdrawal.pool_tokens)
+                .ok_or(StakePoolError::CalculationFailure)?;
+        }
+        Ok(withdraw_accounts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::arithmetic_side_effects)]
+    use {
+        super::*,
+        proptest::prelude::*,
+        spl_stake_pool::{
+            state::{AccountType, Fee, ValidatorListHeader},
+            MINIMUM_ACTIVE_STAKE,
+        },
+    };
+
+    const STAKE_RENT_EXEMPTION: u64 = 2_282_880;
+    const STAKE_MINIMUM_DELEGATION: u64 = 1;
+    const MINIMUM_STAKE_LAMPORTS: u64 = STAKE_RENT_EXEMPTION + MINIMUM_ACTIVE_STAKE;
+    const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+    const STAKE_POOL_ADDRESS: Pubkey =
+        Pubkey::from_str_const("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+
+    fn withdraw_state(validators: &[(u64, u64)], reserve_lamports: u64) -> WithdrawState {
+        let validators = validators
+            .iter()
+            .map(
+                |(active_stake_lamports, transient_stake_lamports)| ValidatorStakeInfo {
+                    active_stake_lamports: (*active_stake_lamports).into(),
+                    transient_stake_lamports: (*transient_stake_lamports).into(),
+                    status: StakeStatus::Active.into(),
+                    vote_account_address: Pubkey::new_unique(),
+                    ..ValidatorStakeInfo::default()
+                },
+            )
+            .collect::<Vec<_>>();
+        let total_lamports = validators
+            .iter()
+            .map(|info| info.stake_lamports().unwrap())
+            .sum::<u64>()
+            + reserve_lamports.saturating_sub(STAKE_RENT_EXEMPTION);
+        WithdrawState {
+            stake_pool: StakePool {
+                account_type: AccountType::StakePool,
+                reserve_stake: Pubkey::new_unique(),
+                total_lamports,
+                pool_token_supply: total_lamports,
+                ..StakePool::default()
+            },
+            validator_list: ValidatorList {
+                header: ValidatorListHeader {
+                    account_type: AccountType::ValidatorList,
+                    max_validators: validators.len() as u32,
+                },
+                validators,
+            },
+            reserve_lamports,
+            stake_rent_exemption: STAKE_RENT_EXEMPTION,
+            stake_minimum_delegation: STAKE_MINIMUM_DELEGATION,
+        }
+    }
+
+    fn vote_account(state: &WithdrawState, index: usize) -> Pubkey {
+        state.validator_list.validators[index].vote_account_address
+    }
+
+    fn plan(state: &WithdrawState, pool_amount: u64) -> Result<Vec<WithdrawAccount>, Error> {
+        state.plan(
+            &spl_stake_pool::id(),
+            &STAKE_POOL_ADDRESS,
+            pool_amount,
+            WithdrawSource::Any,
+            false,
+        )
+    }
+
+    /// Stake account of a planned withdrawal
+    fn pool_stake_account(
+        state: &WithdrawState,
+        withdraw_account: &WithdrawAccount,
+    ) -> PoolStakeAccount {
+        let Some(vote_account_address) = withdraw_account.vote_address else {
+            assert_eq!(
+                withdraw_account.stake_address,
+                state.stake_pool.reserve_stake
+            );
+            return PoolStakeAccount::Reserve;
+        };
+        let (stake_address, _) = find_stake_program_address(
+            &spl_stake_pool::id(),
+            &vote_account_address,
+            &STAKE_POOL_ADDRESS,
+            None,
+        );
+        if withdraw_account.stake_address == stake_address {
+            PoolStakeAccount::Validator(vote_account_address)
+        } else {
+            PoolStakeAccount::Transient(vote_account_address)
+        }
+    }
+
+    #[test]
+    fn preferred_validator_first() {
+        let mut state = withdraw_state(
+            &[(100 * LAMPORTS_PER_SOL, 0), (10 * LAMPORTS_PER_SOL, 0)],
+            MINIMUM_STAKE_LAMPORTS,
+        );
+        let preferred = vote_account(&state, 1);
+        state.stake_pool.preferred_withdraw_validator_vote_address = Some(preferred);
+        let other = vote_account(&state, 0);
+
+        assert_eq!(
+            state.check(PoolStakeAccount::Validator(other), LAMPORTS_PER_SOL, false),
+            Err(StakePoolError::IncorrectWithdrawVoteAddress)
+        );
+
+        let plan = plan(&state, 20 * LAMPORTS_PER_SOL).unwrap();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].vote_address, Some(preferred));
+        assert_eq!(
+            plan[0].lamports,
+            10 * LAMPORTS_PER_SOL - MINIMUM_STAKE_LAMPORTS
+        );
+        assert_eq!(plan[1].vote_address, Some(other));
+        assert_eq!(
+            plan.iter().map(|account| account.lamports).sum::<u64>(),
+            20 * LAMPORTS_PER_SOL
+        );
+    }
+
+    #[test]
+    fn reserve_only_once_validators_are_empty() {
+        let state = withdraw_state(&[(10 * LAMPORTS_PER_SOL, 0)], 10 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            state.check(PoolStakeAccount::Reserve, LAMPORTS_PER_SOL, false),
+            Err(StakePoolError::StakeLamportsNotEqualToMinimum)
+        );
+
+        let state = withdraw_state(&[(MINIMUM_STAKE_LAMPORTS, 0)], 10 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            state.check(
+                PoolStakeAccount::Reserve,
+                10 * LAMPORTS_PER_SOL - STAKE_RENT_EXEMPTION,
+                false
+            ),
+            Ok(Withdrawal {
+                pool_tokens: 10 * LAMPORTS_PER_SOL - STAKE_RENT_EXEMPTION,
+                fee: 0,
+                lamports: 10 * LAMPORTS_PER_SOL - STAKE_RENT_EXEMPTION,
+            })
+        );
+        assert_eq!(
+            state.check(PoolStakeAccount::Reserve, 10 * LAMPORTS_PER_SOL, false),
+            Err(StakePoolError::SolWithdrawalTooLarge)
+        );
+    }
+
+    #[test]
+    fn transient_stake_before_removal() {
+        let state = withdraw_state(
+            &[(MINIMUM_STAKE_LAMPORTS, 5 * LAMPORTS_PER_SOL)],
+            MINIMUM_STAKE_LAMPORTS,
+        );
+        let vote_account_address = vote_account(&state, 0);
+        assert_eq!(
+            state.check(
+                PoolStakeAccount::Validator(vote_account_address),
+                MINIMUM_STAKE_LAMPORTS,
+                false
+            ),
+            Err(StakePoolError::InvalidStakeAccountAddress)
+        );
+
+        let plan = plan(&state, LAMPORTS_PER_SOL).unwrap();
+        assert_eq!(plan.len(), 1);
+        let (transient_stake_address, _) = find_transient_stake_program_address(
+            &spl_stake_pool::id(),
+            &vote_account_address,
+            &STAKE_POOL_ADDRESS,
+            0,
+        );
+        assert_eq!(plan[0].stake_address, transient_stake_address);
+        assert_eq!(plan[0].lamports, LAMPORTS_PER_SOL);
+    }
+
+    #[test]
+    fn whole_validator_removal() {
+        let mut state = withdraw_state(
+            &[(MINIMUM_STAKE_LAMPORTS, 0), (MINIMUM_STAKE_LAMPORTS, 0)],
+            STAKE_RENT_EXEMPTION,
+        );
+        let removed = vote_account(&state, 0);
+        state.stake_pool.preferred_withdraw_validator_vote_address = Some(removed);
+
+        // part of a minimum stake account can't be withdrawn
+        assert_eq!(
+            state.check(
+                PoolStakeAccount::Validator(removed),
+                LAMPORTS_PER_SOL / 1000,
+                false
+            ),
+            Err(StakePoolError::StakeLamportsNotEqualToMinimum)
+        );
+
+        let withdrawal = state
+            .check(
+                PoolStakeAccount::Validator(removed),
+                MINIMUM_STAKE_LAMPORTS,
+                false,
+            )
+            .unwrap();
+        assert_eq!(withdrawal.lamports, MINIMUM_STAKE_LAMPORTS);
+        state.apply(PoolStakeAccount::Validator(removed), &withdrawal);
+        let removed_info = state.validator_list.find(&removed).unwrap();
+        assert_eq!(removed_info.status, StakeStatus::ReadyForRemoval.into());
+        assert_eq!(
+            state.stake_pool.preferred_withdraw_validator_vote_address,
+            None
+        );
+        assert_eq!(
+            state.check(
+                PoolStakeAccount::Validator(removed),
+                MINIMUM_STAKE_LAMPORTS,
+                false
+            ),
+            Err(StakePoolError::ValidatorNotFound)
+        );
+    }
+
+    #[test]
+    fn dust_is_too_small() {
+        let mut state = withdraw_state(&[(100 * LAMPORTS_PER_SOL, 0)], STAKE_RENT_EXEMPTION);
+        state.stake_pool.pool_token_supply = state.stake_pool.total_lamports * 4;
+        let vote_account_address = vote_account(&state, 0);
+        assert_eq!(
+            state.check(PoolStakeAccount::Validator(vote_account_address), 3, false),
+            Err(StakePoolError::WithdrawalTooSmall)
+        );
+        assert!(plan(&state, 3).is_err());
+
+        // the plan leaves enough pool tokens for its last withdrawal
+        let mut state = withdraw_state(
+            &[(10 * LAMPORTS_PER_SOL, 0), (10 * LAMPORTS_PER_SOL, 0)],
+            STAKE_RENT_EXEMPTION,
+        );
+        state.stake_pool.pool_token_supply = state.stake_pool.total_lamports * 4;
+        let pool_amount = (10 * LAMPORTS_PER_SOL - MINIMUM_STAKE_LAMPORTS) * 4 + 6;
+        let plan = plan(&state, pool_amount).unwrap();
+        assert_eq!(plan.len(), 2);
+        assert!(plan.iter().all(|account| account.lamports > 0));
+    }
+
+    fn arbitrary_withdraw_state() -> impl Strategy<Value = WithdrawState> {
+        let validator = (
+            prop_oneof![
+                Just(MINIMUM_STAKE_LAMPORTS),
+                MINIMUM_STAKE_LAMPORTS..100 * LAMPORTS_PER_SOL
+            ],
+            prop_oneof![Just(0), STAKE_RENT_EXEMPTION..10 * LAMPORTS_PER_SOL],
+        );
+        (
+            prop::collection::vec(validator, 0..6),
+            prop_oneof![
+                Just(STAKE_RENT_EXEMPTION),
+                STAKE_RENT_EXEMPTION..100 * LAMPORTS_PER_SOL
+            ],
+            1..=4u64,
+            1..=4u64,
+            0..=10u64,
+            prop::option::of(0..6usize),
+        )
+            .prop_map(
+                |(
+                    validators,
+                    reserve_lamports,
+                    supply_numerator,
+                    supply_denominator,
+                    fee_numerator,
+                    preferred,
+                )| {
+                    let mut state = withdraw_state(&validators, reserve_lamports);
+                    state.stake_pool.pool_token_supply =
+                        state.stake_pool.total_lamports * supply_numerator / supply_denominator;
+                    state.stake_pool.stake_withdrawal_fee = Fee {
+                        numerator: fee_numerator,
+                        denominator: 1_000,
+                    };
+                    state.stake_pool.preferred_withdraw_validator_vote_address = preferred
+                        .filter(|index| *index < validators.len())
+                        .map(|index| vote_account(&state, index));
+                    state
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn plans_follow_the_program(
+            state in arbitrary_withdraw_state(),
+            share in 1..=1_000u64,
+            fee_exempt in any::<bool>(),
+        ) {
+            let pool_amount = (state.stake_pool.pool_token_supply / 1_000 * share).max(1);
+            let plan = state.plan(
+                &spl_stake_pool::id(),
+                &STAKE_POOL_ADDRESS,
+                pool_amount,
+                WithdrawSource::Any,
+                fee_exempt,
+            );
+            if let Ok(plan) = plan {
+                prop_assert_eq!(
+                    plan.iter().map(|account| account.pool_amount).sum::<u64>(),
+                    pool_amount
+                );
+                let mut state = state;
+                for withdraw_account in plan {
+                    let account = pool_stake_account(&state, &withdraw_account);
+                    let withdrawal = state
+                        .check(account, withdraw_account.pool_amount, fee_exempt)
+                        .unwrap();
+                    prop_assert_eq!(withdrawal.lamports, withdraw_account.lamports);
+                    state.apply(account, &withdrawal);
+                }
+            }
+        }
+
+        #[test]
+        fn single_withdrawals_are_planned_alone(
+            state in arbitrary_withdraw_state(),
+            share in 1..=1_000u64,
+            fee_exempt in any::<bool>(),
+        ) {
+            let pool_amount = (state.stake_pool.pool_token_supply / 1_000 * share).max(1);
+            prop_assume!(state
+                .candidates(WithdrawSource::Any)
+                .into_iter()
+                .any(|account| state.check(account, pool_amount, fee_exempt).is_ok()));
+            let plan = state
+                .plan(
+                    &spl_stake_pool::id(),
+                    &STAKE_POOL_ADDRESS,
+                    pool_amount,
+                    WithdrawSource::Any,
+                    fee_exempt,
+                )
+                .unwrap();
+            prop_assert_eq!(plan.len(), 1);
+        }
+    }
+}
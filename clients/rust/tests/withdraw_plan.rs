@@ -0,0 +1,372 @@
+#![allow(clippy::arithmetic_side_effects)]
+#[path = "../../../program/tests/helpers/mod.rs"]
+mod helpers;
+
+use {
+    helpers::*,
+    proptest::{prelude::*, test_runner::TestCaseError},
+    solana_program::{
+        instruction::InstructionError, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        account::Account as SolanaAccount,
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    solana_stake_interface as stake,
+    spl_stake_pool::{
+        id,
+        state::{Fee, StakeStatus},
+    },
+    spl_stake_pool_client::withdraw::{
+        PoolStakeAccount, WithdrawAccount, WithdrawSource, WithdrawState,
+    },
+    spl_token_2022_interface::state::{Account, AccountState},
+};
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+struct Setup {
+    context: ProgramTestContext,
+    stake_pool_accounts: StakePoolAccounts,
+    vote_accounts: Vec<Pubkey>,
+    user: Keypair,
+    pool_token_account: Pubkey,
+}
+
+/// Set up a pool whose validators have `validator_stakes` of active stake
+/// besides their rent exemption, with all the pool tokens held by the user
+async fn setup(
+    validator_stakes: &[u64],
+    reserve_stake: u64,
+    preferred_withdraw_validator: Option<usize>,
+    withdrawal_fee_numerator: u64,
+) -> Setup {
+    let mut program_test = program_test();
+    let stake_pool_accounts = StakePoolAccounts::default();
+    let stake_pool_pubkey = stake_pool_accounts.stake_pool.pubkey();
+    let (mut stake_pool, mut validator_list) = stake_pool_accounts.state();
+    stake_pool.last_update_epoch = FIRST_NORMAL_EPOCH;
+
+    let mut vote_accounts = vec![];
+    for stake_amount in validator_stakes {
+        let vote_account_address = add_vote_account(&mut program_test);
+        add_validator_stake_account(
+            &mut program_test,
+            &mut stake_pool,
+            &mut validator_list,
+            &stake_pool_pubkey,
+            &stake_pool_accounts.withdraw_authority,
+            &vote_account_address,
+            *stake_amount,
+            StakeStatus::Active,
+        );
+        vote_accounts.push(vote_account_address);
+    }
+    add_reserve_stake_account(
+        &mut program_test,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        reserve_stake,
+    );
+    stake_pool.total_lamports += reserve_stake;
+    stake_pool.pool_token_supply += reserve_stake;
+    stake_pool.stake_withdrawal_fee = Fee {
+        numerator: withdrawal_fee_numerator,
+        denominator: 1_000,
+    };
+    stake_pool.preferred_withdraw_validator_vote_address =
+        preferred_withdraw_validator.map(|index| vote_accounts[index]);
+
+    add_stake_pool_account(&mut program_test, &stake_pool_pubkey, &stake_pool);
+    add_validator_list_account(
+        &mut program_test,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &validator_list,
+        stake_pool_accounts.max_validators,
+    );
+    add_mint_account(
+        &mut program_test,
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        stake_pool.pool_token_supply,
+    );
+    add_token_account(
+        &mut program_test,
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+    );
+
+    // the user holds all the pool tokens
+    let user = Keypair::new();
+    let pool_token_account = Pubkey::new_unique();
+    let mut data = vec![0; Account::LEN];
+    Account {
+        mint: stake_pool_accounts.pool_mint.pubkey(),
+        owner: user.pubkey(),
+        amount: stake_pool.pool_token_supply,
+        delegate: COption::None,
+        state: AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    program_test.add_account(
+        pool_token_account,
+        SolanaAccount {
+            lamports: LAMPORTS_PER_SOL,
+            data,
+            owner: stake_pool_accounts.token_program_id,
+            ..SolanaAccount::default()
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let epoch_schedule = &context.genesis_config().epoch_schedule;
+    let slot = epoch_schedule.first_normal_slot + epoch_schedule.slots_per_epoch + 1;
+    context.warp_to_slot(slot).unwrap();
+
+    Setup {
+        context,
+        stake_pool_accounts,
+        vote_accounts,
+        user,
+        pool_token_account,
+    }
+}
+
+async fn withdraw_state(setup: &mut Setup) -> WithdrawState {
+    let banks_client = &mut setup.context.banks_client;
+    let stake_pool = setup.stake_pool_accounts.get_stake_pool(banks_client).await;
+    let validator_list = setup
+        .stake_pool_accounts
+        .get_validator_list(banks_client)
+        .await;
+    let reserve_lamports = get_account(banks_client, &stake_pool.reserve_stake)
+        .await
+        .lamports;
+    let stake_rent_exemption = banks_client
+        .get_rent()
+        .await
+        .unwrap()
+        .minimum_balance(std::mem::size_of::<stake::state::StakeStateV2>());
+    let stake_minimum_delegation = stake_get_minimum_delegation(
+        banks_client,
+        &setup.context.payer,
+        &setup.context.last_blockhash,
+    )
+    .await;
+    WithdrawState {
+        stake_pool,
+        validator_list,
+        reserve_lamports,
+        stake_rent_exemption,
+        stake_minimum_delegation,
+    }
+}
+
+/// Withdraw from `stake_address` into a new stake account, returning the
+/// lamports it receives
+async fn withdraw(
+    setup: &mut Setup,
+    stake_address: &Pubkey,
+    pool_amount: u64,
+) -> Result<u64, TransportError> {
+    let stake_receiver = Keypair::new();
+    let rent = create_blank_stake_account(
+        &mut setup.context.banks_client,
+        &setup.context.payer,
+        &setup.context.last_blockhash,
+        &stake_receiver,
+    )
+    .await;
+    if let Some(error) = setup
+        .stake_pool_accounts
+        .withdraw_stake(
+            &mut setup.context.banks_client,
+            &setup.context.payer,
+            &setup.context.last_blockhash,
+            &stake_receiver.pubkey(),
+            &setup.user,
+            &setup.pool_token_account,
+            stake_address,
+            &setup.user.pubkey(),
+            pool_amount,
+        )
+        .await
+    {
+        return Err(error);
+    }
+    let lamports = get_account(&mut setup.context.banks_client, &stake_receiver.pubkey())
+        .await
+        .lamports;
+    Ok(lamports - rent)
+}
+
+fn stake_address(setup: &Setup, account: PoolStakeAccount) -> Pubkey {
+    let stake_pool_address = setup.stake_pool_accounts.stake_pool.pubkey();
+    match account {
+        PoolStakeAccount::Reserve => setup.stake_pool_accounts.reserve_stake.pubkey(),
+        PoolStakeAccount::Validator(vote_account_address) => {
+            spl_stake_pool::find_stake_program_address(
+                &id(),
+                &vote_account_address,
+                &stake_pool_address,
+                None,
+            )
+            .0
+        }
+        PoolStakeAccount::Transient(vote_account_address) => {
+            spl_stake_pool::find_transient_stake_program_address(
+                &id(),
+                &vote_account_address,
+                &stake_pool_address,
+                0,
+            )
+            .0
+        }
+    }
+}
+
+fn arbitrary_pool() -> impl Strategy<Value = (Vec<u64>, u64, Option<usize>, u64)> {
+    (
+        prop::collection::vec(
+            prop_oneof![
+                Just(LAMPORTS_PER_SOL / 1_000),
+                LAMPORTS_PER_SOL / 1_000..10 * LAMPORTS_PER_SOL
+            ],
+            1..4,
+        ),
+        0..10 * LAMPORTS_PER_SOL,
+        prop::option::of(0..4usize),
+        0..=10u64,
+    )
+        .prop_map(
+            |(validator_stakes, reserve_stake, preferred, fee_numerator)| {
+                let preferred = preferred.filter(|index| *index < validator_stakes.len());
+                (validator_stakes, reserve_stake, preferred, fee_numerator)
+            },
+        )
+}
+
+fn run(
+    test: impl std::future::Future<Output = Result<(), TestCaseError>>,
+) -> Result<(), TestCaseError> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(test)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn withdrawals_are_checked_like_the_program(
+        (validator_stakes, reserve_stake, preferred, fee_numerator) in arbitrary_pool(),
+        attempts in prop::collection::vec((0..9usize, 1..=1_000u64), 1..6),
+    ) {
+        run(async move {
+            let mut setup =
+                setup(&validator_stakes, reserve_stake, preferred, fee_numerator).await;
+            for (account_index, share) in attempts {
+                let account = match setup.vote_accounts.get(account_index / 2) {
+                    Some(vote_account_address) if account_index % 2 == 0 => {
+                        PoolStakeAccount::Validator(*vote_account_address)
+                    }
+                    Some(vote_account_address) => {
+                        PoolStakeAccount::Transient(*vote_account_address)
+                    }
+                    None => PoolStakeAccount::Reserve,
+                };
+                // transient stake accounts don't exist in this pool
+                if let PoolStakeAccount::Transient(_) = account {
+                    continue;
+                }
+                let state = withdraw_state(&mut setup).await;
+                let pool_tokens = state.stake_pool.pool_token_supply / 1_000 * share;
+                let expected = state.check(account, pool_tokens, false);
+                if let Ok(withdrawal) = expected {
+                    // the stake program also requires a minimum delegation on splits
+                    if withdrawal.lamports < state.stake_minimum_delegation {
+                        continue;
+                    }
+                }
+
+                let stake_address = stake_address(&setup, account);
+                let result = withdraw(&mut setup, &stake_address, pool_tokens).await;
+                match (expected, result) {
+                    (Ok(withdrawal), Ok(lamports)) => {
+                        prop_assert_eq!(withdrawal.lamports, lamports);
+                        let mut expected_state = state;
+                        expected_state.apply(account, &withdrawal);
+                        let state = withdraw_state(&mut setup).await;
+                        prop_assert_eq!(
+                            expected_state.stake_pool.total_lamports,
+                            state.stake_pool.total_lamports
+                        );
+                        prop_assert_eq!(
+                            expected_state.stake_pool.pool_token_supply,
+                            state.stake_pool.pool_token_supply
+                        );
+                        prop_assert_eq!(
+                            expected_state.stake_pool.preferred_withdraw_validator_vote_address,
+                            state.stake_pool.preferred_withdraw_validator_vote_address
+                        );
+                        prop_assert_eq!(expected_state.validator_list, state.validator_list);
+                        prop_assert_eq!(expected_state.reserve_lamports, state.reserve_lamports);
+                    }
+                    (Err(error), Err(TransportError::TransactionError(
+                        TransactionError::InstructionError(_, instruction_error),
+                    ))) => {
+                        prop_assert_eq!(instruction_error, InstructionError::Custom(error as u32));
+                    }
+                    (expected, result) => {
+                        prop_assert!(false, "expected {:?}, got {:?}", expected, result);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn plans_succeed_on_chain(
+        (validator_stakes, reserve_stake, preferred, fee_numerator) in arbitrary_pool(),
+        share in 1..=1_000u64,
+    ) {
+        run(async move {
+            let mut setup =
+                setup(&validator_stakes, reserve_stake, preferred, fee_numerator).await;
+            let state = withdraw_state(&mut setup).await;
+            let pool_amount = state.stake_pool.pool_token_supply / 1_000 * share;
+            let Ok(plan) = state.plan(
+                &id(),
+                &setup.stake_pool_accounts.stake_pool.pubkey(),
+                pool_amount,
+                WithdrawSource::Any,
+                false,
+            ) else {
+                return Ok(());
+            };
+            for WithdrawAccount { stake_address, pool_amount, lamports, .. } in plan {
+                let result = withdraw(&mut setup, &stake_address, pool_amount).await;
+                prop_assert_eq!(result.ok(), Some(lamports));
+            }
+            let pool_tokens = get_token_balance(
+                &mut setup.context.banks_client,
+                &setup.pool_token_account,
+            )
+            .await;
+            prop_assert_eq!(pool_tokens, state.stake_pool.pool_token_supply - pool_amount);
+            Ok(())
+        })?;
+    }
+}
@@ -170,6 +170,37 @@ pub enum StakePoolError {
     /// The stake pool has too many validators in the pool
     #[error("The stake pool has too many validators in the pool")]
     TooManyValidatorsInPool,
+
+    // 45.
+    /// The validator list still contains validators, which must all be
+    /// removed before the pool can be closed
+    #[error("ValidatorListNotEmpty")]
+    ValidatorListNotEmpty,
+    /// The stake pool account is too small for the current configuration
+    /// layout, and must be migrated first
+    #[error("StakePoolNotMigrated")]
+    StakePoolNotMigrated,
+    /// The pool mint's transfer hook program is not allowed by the manager
+    #[error("TransferHookProgramNotAllowed")]
+    TransferHookProgramNotAllowed,
+    /// Too many transfer hook programs provided
+    #[error("TooManyTransferHookPrograms")]
+    TooManyTransferHookPrograms,
+    /// The owner of the account receiving the pool tokens is not on the
+    /// depositor allowlist
+    #[error("DepositorNotAllowed")]
+    DepositorNotAllowed,
+
+    // 50.
+    /// The depositor Merkle proof is missing or does not match the pool's root
+    #[error("InvalidDepositorProof")]
+    InvalidDepositorProof,
+    /// The deposit would take the depositor's total deposits over their cap
+    #[error("DepositorCapExceeded")]
+    DepositorCapExceeded,
+    /// The depositor record is missing or does not belong to the depositor
+    #[error("InvalidDepositorRecord")]
+    InvalidDepositorRecord,
 }
 impl From<StakePoolError> for ProgramError {
     fn from(e: StakePoolError) -> Self {
@@ -216,7 +247,7 @@ impl ToStr for StakePoolError {
             // 20.
             Self::UnexpectedValidatorListAccountSize => "Error: The size of the given validator stake list does match the expected amount",
             Self::WrongStaker => "Error: Wrong pool staker account.",
-            Self::NonZeroPoolTokenSupply => "Error: Pool token supply is not zero on initialization",
+            Self::NonZeroPoolTokenSupply => "Error: Pool token supply is not zero",
             Self::StakeLamportsNotEqualToMinimum => "Error: The lamports in the validator stake account is not equal to the minimum",
             Self::IncorrectDepositVoteAddress => "Error: The provided deposit stake account is not delegated to the preferred deposit vote account",
 
@@ -247,6 +278,18 @@ impl ToStr for StakePoolError {
             Self::MissingRequiredSysvar => "Error: Missing required sysvar account",
             Self::EpochRewardDistributionInProgress => "Error: Epoch reward distribution is currently in progress, stakes are still being updated",
             Self::TooManyValidatorsInPool => "Error: The stake pool has too many validators in the pool",
+
+            // 45.
+            Self::ValidatorListNotEmpty => "Error: All validators must be removed from the pool before closing it",
+            Self::StakePoolNotMigrated => "Error: The stake pool account must be migrated with `MigratePool` first",
+            Self::TransferHookProgramNotAllowed => "Error: The pool mint's transfer hook program is not allowed by the manager",
+            Self::TooManyTransferHookPrograms => "Error: Too many transfer hook programs provided",
+            Self::DepositorNotAllowed => "Error: The owner of the account receiving the pool tokens is not on the depositor allowlist",
+
+            // 50.
+            Self::InvalidDepositorProof => "Error: The depositor Merkle proof is missing or does not match the pool's root",
+            Self::DepositorCapExceeded => "Error: The deposit would take the depositor's total deposits over their cap",
+            Self::InvalidDepositorRecord => "Error: The depositor record is missing or does not belong to the depositor",
         }
     }
 }
@@ -7,7 +7,8 @@
 
 use {
     crate::{
-        find_deposit_authority_program_address, find_ephemeral_stake_program_address,
+        find_deposit_authority_program_address, find_depositor_allowlist_entry_address,
+        find_depositor_record_address, find_ephemeral_stake_program_address,
         find_stake_program_address, find_transient_stake_program_address,
         find_withdraw_authority_program_address,
         inline_mpl_token_metadata::{self, pda::find_metadata_account},
@@ -19,7 +20,10 @@ use {
     solana_program_error::ProgramError,
     solana_pubkey::Pubkey,
     solana_stake_interface::stake_history::Epoch,
-    std::num::NonZeroU32,
+    spl_transfer_hook_interface::offchain::{
+        add_extra_account_metas_for_execute, AccountDataResult, AccountFetchError,
+    },
+    std::{future::Future, num::NonZeroU32},
 };
 
 /// Defines which validator vote account is set during the
@@ -46,6 +50,51 @@ pub enum FundingType {
     SolWithdraw,
 }
 
+/// Return data set by the `DepositStake`, `DepositSol`, `WithdrawStake` and
+/// `WithdrawSol` instructions (and their slippage variants), so that
+/// programs invoking the stake pool through CPI can read the outcome with
+/// `get_return_data` instead of diffing balances.
+///
+/// All amounts are computed by the stake pool before any token-2022 transfer
+/// fee is applied by the token program.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct DepositWithdrawReturnData {
+    /// Amount received by the user: pool tokens minted for deposits, lamports
+    /// sent to the user for withdrawals
+    pub amount_out: u64,
+    /// Pool tokens taken by the manager as a deposit or withdrawal fee, not
+    /// including the referral fee
+    pub fee: u64,
+    /// Pool tokens minted to the referrer, always 0 for withdrawals
+    pub referral_fee: u64,
+}
+
+/// Proof that the owner of the account receiving the pool tokens of a deposit
+/// is in the pool's depositor Merkle tree, appended to the instruction data of
+/// `DepositStake`, `DepositSol`, and their slippage variants
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct DepositorProof {
+    /// Most lamports the depositor may deposit over the life of the pool,
+    /// whatever pool token accounts receive them, or `merkle::NO_CAP`.
+    ///
+    /// Deposits are tracked in the depositor's `DepositorRecord`, so every
+    /// wallet with a cap needs its own record, created by
+    /// `CreateDepositorRecord` and paying rent, before its first deposit.
+    pub cap: u64,
+    /// Sibling hashes from the depositor's leaf up to the root
+    pub proof: Vec<[u8; 32]>,
+}
+
+/// Index of the depositor allowlist entry among the accounts of `DepositStake`
+/// and `DepositStakeWithSlippage`, followed by the depositor record
+pub const DEPOSIT_STAKE_DEPOSITOR_ACCOUNTS_INDEX: usize = 15;
+
+/// Index of the depositor allowlist entry among the accounts of `DepositSol`
+/// and `DepositSolWithSlippage`, followed by the depositor record
+pub const DEPOSIT_SOL_DEPOSITOR_ACCOUNTS_INDEX: usize = 11;
+
 /// Instructions supported by the `StakePool` program.
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
@@ -233,7 +282,13 @@ pub enum StakePoolInstruction {
     ///  validator stake account. In all other states, nothing is done, and
     ///  the balance is simply added to the canonical stake account balance.
     ///
-    ///  0. `[]` Stake pool
+    ///  If the stake pool is writable and migrated to `StakePoolV2`, the
+    ///  updated entries are tallied as in `UpdateValidatorBalance`. A
+    ///  read-only stake pool is still accepted, but its updates are left out
+    ///  of the tally, so `UpdateStakePoolBalance` sums the validator list for
+    ///  the epoch, and entries already updated in the epoch are skipped.
+    ///
+    ///  0. `[w]` Stake pool
     ///  1. `[]` Stake pool withdraw authority
     ///  2. `[w]` Validator stake list storage account
     ///  3. `[w]` Reserve stake account
@@ -254,6 +309,11 @@ pub enum StakePoolInstruction {
     ///   Updates total pool balance based on balances in the reserve and
     ///   validator list
     ///
+    ///   If the pool mint has the Token-2022 `ScaledUiAmount` extension, with
+    ///   the withdraw authority as its authority, the multiplier is also
+    ///   updated to `total_lamports / pool_token_supply`, so that UI amounts
+    ///   of pool tokens show their value in SOL.
+    ///
     ///   0. `[w]` Stake pool
     ///   1. `[]` Stake pool withdraw authority
     ///   2. `[w]` Validator stake list storage account
@@ -265,6 +325,10 @@ pub enum StakePoolInstruction {
 
     ///   Cleans up validator stake account entries marked as `ReadyForRemoval`
     ///
+    ///   During an epoch update of a migrated pool, the entries already
+    ///   tallied are only cleaned up if the stake pool is writable, and are
+    ///   otherwise kept until the pool is updated.
+    ///
     ///   0. `[]` Stake pool
     ///   1. `[w]` Validator stake list storage account
     CleanupRemovedValidatorEntries,
@@ -292,6 +356,16 @@ pub enum StakePoolInstruction {
     ///   12. '[]' Sysvar stake history account
     ///   13. `[]` Pool token program id,
     ///   14. `[]` Stake program id,
+    ///   15. `[]` (Optional) Depositor allowlist entry of the owner of the
+    ///       account receiving pool tokens, required if the pool has an
+    ///       allowlist, or any account if only the record is needed
+    ///   16. `[w]` (Optional) Depositor record of the owner of the account
+    ///       receiving pool tokens, required if their proof has a cap
+    ///
+    ///   The instruction data may be followed by a [`DepositorProof`], required
+    ///   if the pool has a depositor Merkle root.
+    ///
+    ///   Sets [`DepositWithdrawReturnData`] as return data.
     DepositStake,
 
     ///   Withdraw the token from the pool at the current ratio.
@@ -328,8 +402,12 @@ pub enum StakePoolInstruction {
     ///  10. `[]` Sysvar clock account (required)
     ///  11. `[]` Pool token program id
     ///  12. `[]` Stake program id,
+    ///  13. ..13+N `[]` N accounts required by the pool mint's transfer hook
+    ///      program, if any, for the withdrawal fee transfer
     ///
     ///  User data: amount of pool tokens to withdraw
+    ///
+    ///  Sets [`DepositWithdrawReturnData`] as return data.
     WithdrawStake(u64),
 
     ///  (Manager only) Update manager
@@ -370,7 +448,18 @@ pub enum StakePoolInstruction {
     ///   7. `[w]` Pool token mint account
     ///   8. `[]` System program account
     ///   9. `[]` Token program id
-    ///  10. `[s]` (Optional) Stake pool sol deposit authority.
+    ///  10. `[s]` (Optional) Stake pool sol deposit authority, or any account
+    ///      if the pool has none and the depositor accounts follow
+    ///  11. `[]` (Optional) Depositor allowlist entry of the pool token owner,
+    ///      required if the pool has an allowlist, or any account if only the
+    ///      record is needed
+    ///  12. `[w]` (Optional) Depositor record of the pool token owner,
+    ///      required if their proof has a cap
+    ///
+    ///   The instruction data may be followed by a [`DepositorProof`], required
+    ///   if the pool has a depositor Merkle root.
+    ///
+    ///   Sets [`DepositWithdrawReturnData`] as return data.
     DepositSol(u64),
 
     ///  (Manager only) Update SOL deposit, stake deposit, or SOL withdrawal
@@ -398,10 +487,21 @@ pub enum StakePoolInstruction {
     ///  10. `[]` Stake program account
     ///  11. `[]` Token program id
     ///  12. `[s]` (Optional) Stake pool sol withdraw authority
+    ///  13. ..13+N `[]` N accounts required by the pool mint's transfer hook
+    ///      program, if any, for the withdrawal fee transfer
+    ///
+    ///   Sets [`DepositWithdrawReturnData`] as return data.
     WithdrawSol(u64),
 
     /// Create token metadata for the stake-pool token in the
     /// metaplex-token program
+    ///
+    /// For Token-2022 pool mints, the metadata may instead be held in the
+    /// mint's own `TokenMetadata` extension, with the withdraw authority as
+    /// its update authority, by passing the pool mint as the token metadata
+    /// account and the Token-2022 program as the metadata program. The mint
+    /// must already point to itself through its `MetadataPointer`.
+    ///
     /// 0. `[]` Stake pool
     /// 1. `[s]` Manager
     /// 2. `[]` Stake pool withdraw authority
@@ -421,11 +521,19 @@ pub enum StakePoolInstruction {
     /// Update token metadata for the stake-pool token in the
     /// metaplex-token program
     ///
+    /// As with `CreateTokenMetadata`, Token-2022 pool mints holding their own
+    /// metadata are updated by passing the pool mint as the token metadata
+    /// account and the Token-2022 program as the metadata program. If the
+    /// new metadata is longer, the payer funds the mint's additional rent.
+    ///
     /// 0. `[]` Stake pool
     /// 1. `[s]` Manager
     /// 2. `[]` Stake pool withdraw authority
     /// 3. `[w]` Token metadata account
     /// 4. `[]` Metadata program id
+    /// 5. `[s, w]` (Optional) Payer for the additional rent of Token-2022
+    ///    metadata
+    /// 6. `[]` (Optional) System program id
     UpdateTokenMetadata {
         /// Token name
         name: String,
@@ -649,6 +757,16 @@ pub enum StakePoolInstruction {
     ///   12. '[]' Sysvar stake history account
     ///   13. `[]` Pool token program id,
     ///   14. `[]` Stake program id,
+    ///   15. `[]` (Optional) Depositor allowlist entry of the owner of the
+    ///       account receiving pool tokens, required if the pool has an
+    ///       allowlist, or any account if only the record is needed
+    ///   16. `[w]` (Optional) Depositor record of the owner of the account
+    ///       receiving pool tokens, required if their proof has a cap
+    ///
+    ///   The instruction data may be followed by a [`DepositorProof`], required
+    ///   if the pool has a depositor Merkle root.
+    ///
+    ///   Sets [`DepositWithdrawReturnData`] as return data.
     DepositStakeWithSlippage {
         /// Minimum amount of pool tokens that must be received
         minimum_pool_tokens_out: u64,
@@ -677,8 +795,12 @@ pub enum StakePoolInstruction {
     ///  10. `[]` Sysvar clock account (required)
     ///  11. `[]` Pool token program id
     ///  12. `[]` Stake program id,
+    ///  13. ..13+N `[]` N accounts required by the pool mint's transfer hook
+    ///      program, if any, for the withdrawal fee transfer
     ///
     ///  User data: amount of pool tokens to withdraw
+    ///
+    ///  Sets [`DepositWithdrawReturnData`] as return data.
     WithdrawStakeWithSlippage {
         /// Pool tokens to burn in exchange for lamports
         pool_tokens_in: u64,
@@ -701,7 +823,18 @@ pub enum StakePoolInstruction {
     ///   7. `[w]` Pool token mint account
     ///   8. `[]` System program account
     ///   9. `[]` Token program id
-    ///  10. `[s]` (Optional) Stake pool sol deposit authority.
+    ///  10. `[s]` (Optional) Stake pool sol deposit authority, or any account
+    ///      if the pool has none and the depositor accounts follow
+    ///  11. `[]` (Optional) Depositor allowlist entry of the pool token owner,
+    ///      required if the pool has an allowlist, or any account if only the
+    ///      record is needed
+    ///  12. `[w]` (Optional) Depositor record of the pool token owner,
+    ///      required if their proof has a cap
+    ///
+    ///   The instruction data may be followed by a [`DepositorProof`], required
+    ///   if the pool has a depositor Merkle root.
+    ///
+    ///   Sets [`DepositWithdrawReturnData`] as return data.
     DepositSolWithSlippage {
         /// Amount of lamports to deposit into the reserve
         lamports_in: u64,
@@ -727,12 +860,223 @@ pub enum StakePoolInstruction {
     ///  10. `[]` Stake program account
     ///  11. `[]` Token program id
     ///  12. `[s]` (Optional) Stake pool sol withdraw authority
+    ///  13. ..13+N `[]` N accounts required by the pool mint's transfer hook
+    ///      program, if any, for the withdrawal fee transfer
+    ///
+    ///   Sets [`DepositWithdrawReturnData`] as return data.
     WithdrawSolWithSlippage {
         /// Pool tokens to burn in exchange for lamports
         pool_tokens_in: u64,
         /// Minimum amount of lamports that must be received
         minimum_lamports_out: u64,
     },
+
+    ///  (Manager only) Grow the validator list account to hold more
+    ///  validators, up to `MAX_VALIDATORS_IN_POOL`.
+    ///
+    ///  The account can only grow by `MAX_PERMITTED_DATA_INCREASE` bytes per
+    ///  instruction, so reaching a large `max_validators` may require
+    ///  sending this instruction multiple times. After each instruction, the
+    ///  header's `max_validators` is set to the number of validators that
+    ///  fit in the account. Any lamports needed for rent exemption are
+    ///  transferred from the payer.
+    ///
+    ///  0. `[]` Stake pool
+    ///  1. `[s]` Manager
+    ///  2. `[w]` Validator stake list storage account
+    ///  3. `[s, w]` Payer for the additional rent
+    ///  4. `[]` System program
+    ResizeValidatorList {
+        /// Desired maximum number of validators in the list
+        max_validators: u32,
+    },
+
+    ///  (Manager only) Shrink the validator list account to reclaim rent.
+    ///
+    ///  Entries marked as `ReadyForRemoval` are first cleaned up, as in
+    ///  `CleanupRemovedValidatorEntries`, then the account is reallocated to
+    ///  hold `max_validators` entries, which cannot be less than the number of
+    ///  validators remaining in the list. The lamports above the new
+    ///  rent-exempt minimum are sent to the destination.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[s]` Manager
+    ///  2. `[w]` Validator stake list storage account
+    ///  3. `[w]` Account to receive the reclaimed lamports
+    ShrinkValidatorList {
+        /// New maximum number of validators in the list
+        max_validators: u32,
+    },
+
+    ///  (Manager only) Close an empty stake pool, recovering all of its rent.
+    ///
+    ///  The pool must have no pool tokens in circulation, and every validator
+    ///  must have been fully removed from the validator list. All lamports in
+    ///  the reserve are withdrawn to the destination, along with the lamports
+    ///  of the stake pool and validator list accounts, which are closed.
+    ///
+    ///  The mint authority of the pool mint is given to the new mint authority
+    ///  if provided, or cleared otherwise, preventing any further minting.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[s]` Manager
+    ///  2. `[]` Stake pool withdraw authority
+    ///  3. `[w]` Validator stake list storage account
+    ///  4. `[w]` Reserve stake account
+    ///  5. `[w]` Pool token mint account
+    ///  6. `[w]` Account to receive all recovered lamports
+    ///  7. `[]` Sysvar clock account
+    ///  8. `[]` Stake history sysvar
+    ///  9. `[]` Stake program
+    /// 10. `[]` Token program id
+    /// 11. `[]` (Optional) New mint authority for the pool mint
+    ClosePool,
+
+    ///  Migrate a stake pool account to the current `StakePoolV2` layout.
+    ///
+    ///  Reallocates the stake pool account to hold the current
+    ///  `StakePoolConfig`, with any new configuration set to its default
+    ///  value, and funds the additional rent from the payer. Anyone may
+    ///  migrate a pool. Does nothing if the pool already uses the current
+    ///  layout.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[s, w]` Payer for the additional rent
+    ///  2. `[]` System program
+    MigratePool,
+
+    ///  Updates the balance of a single validator in the validator list,
+    ///  identified by its index, in the same way as
+    ///  `UpdateValidatorListBalance`.
+    ///
+    ///  Validators can be updated in any order, and in separate transactions.
+    ///  Each update is tallied in the stake pool, so once every validator has
+    ///  been updated in the epoch, `UpdateStakePoolBalance` uses the tally
+    ///  instead of summing the whole validator list. Requires a pool migrated
+    ///  to `StakePoolV2`.
+    ///
+    ///  Like every update, this write-locks the validator list and the stake
+    ///  pool, so updates of the same pool are not executed in parallel, even
+    ///  when sent in separate transactions.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[]` Stake pool withdraw authority
+    ///  2. `[w]` Validator stake list storage account
+    ///  3. `[w]` Reserve stake account
+    ///  4. `[]` Sysvar clock
+    ///  5. `[]` Sysvar stake history
+    ///  6. `[]` Stake program
+    ///  7. `[w]` Validator stake account
+    ///  8. `[w]` Transient stake account
+    UpdateValidatorBalance {
+        /// Index of the validator in the validator list
+        validator_index: u32,
+        /// If true, don't try merging transient stake accounts, as in
+        /// `UpdateValidatorListBalance`
+        no_merge: bool,
+    },
+
+    ///  (Manager only) Allow or disallow SOL deposits and withdrawals before
+    ///  the stake pool is updated for the epoch.
+    ///
+    ///  Until `UpdateStakePoolBalance` runs, the pool's total lamports don't
+    ///  include the rewards of the epoch, so the pool token price is too low.
+    ///  If a haircut is set, `DepositSol` still succeeds during that window,
+    ///  withholding the haircut's share of the minted pool tokens to cover
+    ///  the missing rewards, and `WithdrawSol` succeeds at the last known
+    ///  price. Requires a pool migrated to `StakePoolV2`.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[s]` Manager
+    SetPartialUpdateHaircut {
+        /// Share of the pool tokens withheld from deposits made before the
+        /// pool is updated for the epoch, or `None` to reject them
+        haircut: Option<Fee>,
+    },
+
+    ///  (Manager only) Set the transfer hook programs allowed on the pool
+    ///  mint, replacing the current list.
+    ///
+    ///  Deposits and withdrawals fail if the pool mint has a `TransferHook`
+    ///  extension whose program is not in the list. Withdrawals then need the
+    ///  accounts required by the hook program for the withdrawal fee
+    ///  transfer, which `add_transfer_hook_accounts_for_withdraw` resolves.
+    ///  Requires a pool migrated to `StakePoolV2`.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[s]` Manager
+    SetTransferHookProgramIds {
+        /// Allowed transfer hook programs, at most
+        /// `MAX_TRANSFER_HOOK_PROGRAMS`
+        program_ids: Vec<Pubkey>,
+    },
+
+    ///  (Manager only) Require or stop requiring depositors to be on the
+    ///  pool's allowlist.
+    ///
+    ///  While enabled, `DepositStake` and `DepositSol` fail unless the owner
+    ///  of the account receiving the pool tokens has an entry, created by
+    ///  `AddDepositor`, passed after the other accounts. Requires a pool
+    ///  migrated to `StakePoolV2`.
+    ///
+    ///  The allowlist gates who receives the pool tokens, not who funds the
+    ///  deposit: anyone may deposit into the account of an allowed owner.
+    ///  Pools that must also restrict the funding wallet need a deposit
+    ///  authority.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[s]` Manager
+    SetDepositorAllowlist {
+        /// Whether deposits require an allowlist entry
+        enabled: bool,
+    },
+
+    ///  (Manager only) Add a depositor to the pool's allowlist, creating its
+    ///  entry at the address given by `find_depositor_allowlist_entry_address`
+    ///
+    ///  0. `[]` Stake pool
+    ///  1. `[s]` Manager
+    ///  2. `[ws]` Payer for the entry's rent
+    ///  3. `[w]` Depositor allowlist entry
+    ///  4. `[]` Depositor
+    ///  5. `[]` System program
+    AddDepositor,
+
+    ///  (Manager only) Remove a depositor from the pool's allowlist, closing
+    ///  its entry
+    ///
+    ///  0. `[]` Stake pool
+    ///  1. `[s]` Manager
+    ///  2. `[w]` Depositor allowlist entry
+    ///  3. `[w]` Account receiving the entry's lamports
+    RemoveDepositor,
+
+    ///  (Manager only) Set or clear the root of the Merkle tree of depositors
+    ///  allowed into the pool.
+    ///
+    ///  While set, `DepositStake` and `DepositSol` fail unless a
+    ///  `DepositorProof` for the owner of the account receiving the pool
+    ///  tokens is appended to their instruction data. Requires a pool
+    ///  migrated to `StakePoolV2`.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[s]` Manager
+    SetDepositorMerkleRoot {
+        /// Root of the tree, built with `merkle::MerkleTree`, or `None` to
+        /// stop requiring proofs
+        root: Option<[u8; 32]>,
+    },
+
+    ///  Create the record of a depositor's deposits into the pool, at the
+    ///  address given by `find_depositor_record_address`. Deposits for a
+    ///  depositor whose `DepositorProof` has a cap require the record.
+    ///
+    ///  0. `[]` Stake pool
+    ///  1. `[ws]` Payer for the record's rent
+    ///  2. `[w]` Depositor record
+    ///  3. `[]` Depositor
+    ///  4. `[]` System program
+    CreateDepositorRecord,
 }
 
 /// Creates an `Initialize` instruction.
@@ -1445,7 +1789,7 @@ pub fn update_validator_list_balance(
     no_merge: bool,
 ) -> Instruction {
     let mut accounts = vec![
-        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(*stake_pool, false),
         AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
         AccountMeta::new(*validator_list_address, false),
         AccountMeta::new(*reserve_stake, false),
@@ -1496,6 +1840,10 @@ pub fn update_validator_list_balance(
 /// account balances) to update `validator_list[start_index..start_index +
 /// len]`.
 ///
+/// The stake pool is writable so that migrated pools can tally the update.
+/// Along with the validator list, this means that the chunks of one pool are
+/// executed one after another, even in separate transactions.
+///
 /// Returns `Err(ProgramError::InvalidInstructionData)` if:
 /// - `start_index..start_index + len` is out of bounds for
 ///   `validator_list.validators`
@@ -1511,7 +1859,7 @@ pub fn update_validator_list_balance_chunk(
     no_merge: bool,
 ) -> Result<Instruction, ProgramError> {
     let mut accounts = vec![
-        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(*stake_pool, false),
         AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
         AccountMeta::new(*validator_list_address, false),
         AccountMeta::new(*reserve_stake, false),
@@ -1600,6 +1948,64 @@ pub fn update_stale_validator_list_balance_chunk(
     .map(Some)
 }
 
+/// Creates an `UpdateValidatorBalance` instruction (update the balance of a
+/// single validator) for `validator_list.validators[validator_index]`.
+///
+/// Returns `Err(ProgramError::InvalidInstructionData)` if `validator_index` is
+/// out of bounds for `validator_list.validators`
+pub fn update_validator_balance(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    validator_list_address: &Pubkey,
+    reserve_stake: &Pubkey,
+    validator_list: &ValidatorList,
+    validator_index: usize,
+    no_merge: bool,
+) -> Result<Instruction, ProgramError> {
+    let ValidatorStakeInfo {
+        vote_account_address,
+        validator_seed_suffix,
+        transient_seed_suffix,
+        ..
+    } = validator_list
+        .validators
+        .get(validator_index)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (validator_stake_account, _) = find_stake_program_address(
+        program_id,
+        vote_account_address,
+        stake_pool,
+        NonZeroU32::new((*validator_seed_suffix).into()),
+    );
+    let (transient_stake_account, _) = find_transient_stake_program_address(
+        program_id,
+        vote_account_address,
+        stake_pool,
+        (*transient_seed_suffix).into(),
+    );
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*validator_list_address, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new_readonly(solana_sdk_ids::sysvar::clock::id(), false),
+        AccountMeta::new_readonly(solana_sdk_ids::sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(solana_stake_interface::program::id(), false),
+        AccountMeta::new(validator_stake_account, false),
+        AccountMeta::new(transient_stake_account, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::UpdateValidatorBalance {
+            validator_index: validator_index.try_into().unwrap(),
+            no_merge,
+        })
+        .unwrap(),
+    })
+}
+
 /// Creates `UpdateStakePoolBalance` instruction (pool balance from the stake
 /// account list balances)
 pub fn update_stake_pool_balance(
@@ -2642,3 +3048,451 @@ pub fn create_token_metadata(
             .unwrap(),
     }
 }
+
+/// Creates an instruction to update the metadata held in a Token-2022 pool
+/// mint's own `TokenMetadata` extension. A payer is needed if the new metadata
+/// is longer than the current one.
+pub fn update_token_2022_metadata(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: Option<&Pubkey>,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Instruction {
+    let (stake_pool_withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, stake_pool);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(stake_pool_withdraw_authority, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(spl_token_2022_interface::id(), false),
+    ];
+    if let Some(payer) = payer {
+        accounts.push(AccountMeta::new(*payer, true));
+        accounts.push(AccountMeta::new_readonly(
+            solana_system_interface::program::id(),
+            false,
+        ));
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::UpdateTokenMetadata { name, symbol, uri })
+            .unwrap(),
+    }
+}
+
+/// Creates an instruction to initialize the metadata held in a Token-2022 pool
+/// mint's own `TokenMetadata` extension
+pub fn create_token_2022_metadata(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: &Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Instruction {
+    let (stake_pool_withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, stake_pool);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(stake_pool_withdraw_authority, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(spl_token_2022_interface::id(), false),
+        AccountMeta::new_readonly(solana_system_interface::program::id(), false),
+    ];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::CreateTokenMetadata { name, symbol, uri })
+            .unwrap(),
+    }
+}
+
+/// Creates a `ResizeValidatorList` instruction, which grows the validator list
+/// by at most `MAX_PERMITTED_DATA_INCREASE` bytes towards `max_validators`
+pub fn resize_validator_list(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    validator_list: &Pubkey,
+    payer: &Pubkey,
+    max_validators: u32,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(solana_system_interface::program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::ResizeValidatorList { max_validators }).unwrap(),
+    }
+}
+
+/// Creates a `ShrinkValidatorList` instruction, which compacts the validator
+/// list and reallocates it to hold `max_validators` entries
+pub fn shrink_validator_list(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    validator_list: &Pubkey,
+    destination: &Pubkey,
+    max_validators: u32,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new(*destination, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::ShrinkValidatorList { max_validators }).unwrap(),
+    }
+}
+
+/// Creates a `ClosePool` instruction, which closes an empty stake pool and
+/// hands the mint authority to `new_mint_authority`, or clears it if `None`
+#[allow(clippy::too_many_arguments)]
+pub fn close_pool(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    validator_list: &Pubkey,
+    reserve_stake: &Pubkey,
+    pool_mint: &Pubkey,
+    destination: &Pubkey,
+    token_program_id: &Pubkey,
+    new_mint_authority: Option<&Pubkey>,
+) -> Instruction {
+    let stake_pool_withdraw_authority =
+        find_withdraw_authority_program_address(program_id, stake_pool).0;
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(stake_pool_withdraw_authority, false),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(solana_sdk_ids::sysvar::clock::id(), false),
+        AccountMeta::new_readonly(solana_sdk_ids::sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(solana_stake_interface::program::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(new_mint_authority) = new_mint_authority {
+        accounts.push(AccountMeta::new_readonly(*new_mint_authority, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::ClosePool).unwrap(),
+    }
+}
+
+/// Creates a `MigratePool` instruction, which upgrades the stake pool account
+/// to the current `StakePoolV2` layout
+pub fn migrate_pool(program_id: &Pubkey, stake_pool: &Pubkey, payer: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(solana_system_interface::program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::MigratePool).unwrap(),
+    }
+}
+
+/// Creates a `SetPartialUpdateHaircut` instruction.
+pub fn set_partial_update_haircut(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    haircut: Option<Fee>,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::SetPartialUpdateHaircut { haircut }).unwrap(),
+    }
+}
+
+/// Creates a `SetTransferHookProgramIds` instruction.
+pub fn set_transfer_hook_program_ids(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    program_ids: Vec<Pubkey>,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::SetTransferHookProgramIds { program_ids })
+            .unwrap(),
+    }
+}
+
+/// Creates a `SetDepositorAllowlist` instruction.
+pub fn set_depositor_allowlist(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    enabled: bool,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::SetDepositorAllowlist { enabled }).unwrap(),
+    }
+}
+
+/// Creates an `AddDepositor` instruction.
+pub fn add_depositor(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    payer: &Pubkey,
+    depositor: &Pubkey,
+) -> Instruction {
+    let (entry, _) = find_depositor_allowlist_entry_address(program_id, stake_pool, depositor);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(entry, false),
+        AccountMeta::new_readonly(*depositor, false),
+        AccountMeta::new_readonly(solana_system_interface::program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::AddDepositor).unwrap(),
+    }
+}
+
+/// Creates a `RemoveDepositor` instruction.
+pub fn remove_depositor(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    depositor: &Pubkey,
+    lamports_destination: &Pubkey,
+) -> Instruction {
+    let (entry, _) = find_depositor_allowlist_entry_address(program_id, stake_pool, depositor);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new(entry, false),
+        AccountMeta::new(*lamports_destination, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::RemoveDepositor).unwrap(),
+    }
+}
+
+/// Index of the depositor allowlist entry among the accounts of a deposit
+/// instruction, as in `DEPOSIT_STAKE_DEPOSITOR_ACCOUNTS_INDEX`
+fn depositor_accounts_index(instruction: &Instruction) -> usize {
+    match StakePoolInstruction::deserialize(&mut instruction.data.as_slice()) {
+        Ok(StakePoolInstruction::DepositStake)
+        | Ok(StakePoolInstruction::DepositStakeWithSlippage { .. }) => {
+            DEPOSIT_STAKE_DEPOSITOR_ACCOUNTS_INDEX
+        }
+        Ok(StakePoolInstruction::DepositSol(_))
+        | Ok(StakePoolInstruction::DepositSolWithSlippage { .. }) => {
+            DEPOSIT_SOL_DEPOSITOR_ACCOUNTS_INDEX
+        }
+        _ => panic!("Depositor accounts only go into DepositStake or DepositSol instructions"),
+    }
+}
+
+/// Sets the account at `index` of a deposit instruction, filling the optional
+/// accounts before it, if missing, with the program id
+fn set_deposit_account(instruction: &mut Instruction, index: usize, account: AccountMeta) {
+    if instruction.accounts.len() <= index {
+        let placeholder = AccountMeta::new_readonly(instruction.program_id, false);
+        instruction
+            .accounts
+            .resize(index.saturating_add(1), placeholder);
+    }
+    instruction.accounts[index] = account;
+}
+
+/// Adds the depositor allowlist entry of `depositor`, the owner of the
+/// account receiving the pool tokens, to a `DepositStake` or `DepositSol`
+/// instruction, for pools with a depositor allowlist
+pub fn add_depositor_allowlist_entry(
+    instruction: &mut Instruction,
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    depositor: &Pubkey,
+) {
+    let (entry, _) = find_depositor_allowlist_entry_address(program_id, stake_pool, depositor);
+    let index = depositor_accounts_index(instruction);
+    set_deposit_account(instruction, index, AccountMeta::new_readonly(entry, false));
+}
+
+/// Creates a `SetDepositorMerkleRoot` instruction.
+pub fn set_depositor_merkle_root(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    root: Option<[u8; 32]>,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::SetDepositorMerkleRoot { root }).unwrap(),
+    }
+}
+
+/// Appends a depositor Merkle proof to the data of a `DepositStake` or
+/// `DepositSol` instruction, for pools with a depositor Merkle root
+pub fn add_depositor_proof(instruction: &mut Instruction, depositor_proof: &DepositorProof) {
+    instruction
+        .data
+        .extend(borsh::to_vec(depositor_proof).unwrap());
+}
+
+/// Creates a `CreateDepositorRecord` instruction.
+pub fn create_depositor_record(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    payer: &Pubkey,
+    depositor: &Pubkey,
+) -> Instruction {
+    let (record, _) = find_depositor_record_address(program_id, stake_pool, depositor);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(record, false),
+        AccountMeta::new_readonly(*depositor, false),
+        AccountMeta::new_readonly(solana_system_interface::program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::CreateDepositorRecord).unwrap(),
+    }
+}
+
+/// Adds the depositor record of `depositor`, the owner of the account
+/// receiving the pool tokens, to a `DepositStake` or `DepositSol` instruction,
+/// for depositors whose proof has a cap
+pub fn add_depositor_record(
+    instruction: &mut Instruction,
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    depositor: &Pubkey,
+) {
+    let (record, _) = find_depositor_record_address(program_id, stake_pool, depositor);
+    let index = depositor_accounts_index(instruction).saturating_add(1);
+    set_deposit_account(instruction, index, AccountMeta::new(record, false));
+}
+
+/// Adds the accounts required by the pool mint's transfer hook program to a
+/// `WithdrawStake` or `WithdrawSol` instruction, for the transfer of
+/// `fee_amount` pool tokens from the user's account to the manager fee
+/// account.
+///
+/// `fetch_account_data_fn` returns the data of an account, if it exists, as
+/// in `spl_transfer_hook_interface::offchain::add_extra_account_metas_for_execute`.
+pub async fn add_transfer_hook_accounts_for_withdraw<F, Fut>(
+    instruction: &mut Instruction,
+    transfer_hook_program_id: &Pubkey,
+    pool_mint: &Pubkey,
+    burn_from: &Pubkey,
+    manager_fee_account: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    fee_amount: u64,
+    fetch_account_data_fn: F,
+) -> Result<(), AccountFetchError>
+where
+    F: Fn(Pubkey) -> Fut,
+    Fut: Future<Output = AccountDataResult>,
+{
+    add_extra_account_metas_for_execute(
+        instruction,
+        transfer_hook_program_id,
+        burn_from,
+        pool_mint,
+        manager_fee_account,
+        user_transfer_authority,
+        fee_amount,
+        fetch_account_data_fn,
+    )
+    .await
+}
+
+/// Creates all `ResizeValidatorList` instructions required to grow the
+/// validator list from `current_max_validators` to `max_validators`.
+///
+/// Each instruction targets the number of validators that fit after its own
+/// reallocation, so all of the instructions are distinct and can be sent in
+/// separate transactions.
+pub fn resize_validator_list_instructions(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    validator_list: &Pubkey,
+    payer: &Pubkey,
+    current_max_validators: u32,
+    max_validators: u32,
+) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut current_len = ValidatorList::calculate_len(current_max_validators);
+    let target_len = ValidatorList::calculate_len(max_validators);
+    while current_len < target_len {
+        current_len = current_len
+            .saturating_add(solana_account_info::MAX_PERMITTED_DATA_INCREASE)
+            .min(target_len);
+        let step_max_validators = ValidatorList::calculate_max_validators(current_len) as u32;
+        instructions.push(resize_validator_list(
+            program_id,
+            stake_pool,
+            manager,
+            validator_list,
+            payer,
+            step_max_validators,
+        ));
+    }
+    instructions
+}
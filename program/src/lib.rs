@@ -6,6 +6,7 @@ pub mod big_vec;
 pub mod error;
 pub mod inline_mpl_token_metadata;
 pub mod instruction;
+pub mod merkle;
 pub mod processor;
 pub mod state;
 
@@ -26,6 +27,12 @@ const TRANSIENT_STAKE_SEED_PREFIX: &[u8] = b"transient";
 /// Seed for ephemeral stake account
 const EPHEMERAL_STAKE_SEED_PREFIX: &[u8] = b"ephemeral";
 
+/// Seed for depositor allowlist entry
+const DEPOSITOR_ALLOWLIST_SEED_PREFIX: &[u8] = b"depositor";
+
+/// Seed for depositor record
+const DEPOSITOR_RECORD_SEED_PREFIX: &[u8] = b"depositor_record";
+
 /// Minimum amount of staked lamports required in a validator stake account to
 /// allow for merges without a mismatch on credits observed
 pub const MINIMUM_ACTIVE_STAKE: u64 = 1_000_000;
@@ -65,6 +72,10 @@ pub const MAX_TRANSIENT_STAKE_ACCOUNTS: usize = 10;
 /// for stake withdrawals to still work
 pub const MAX_VALIDATORS_IN_POOL: u32 = 20_000;
 
+/// The maximum number of transfer hook programs the manager can allow on the
+/// pool mint
+pub const MAX_TRANSFER_HOOK_PROGRAMS: usize = 4;
+
 /// Get the stake amount under consideration when calculating pool token
 /// conversions
 #[inline]
@@ -162,6 +173,38 @@ pub fn find_ephemeral_stake_program_address(
     )
 }
 
+/// Generates the depositor allowlist entry address for a depositor
+pub fn find_depositor_allowlist_entry_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    depositor: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            DEPOSITOR_ALLOWLIST_SEED_PREFIX,
+            stake_pool_address.as_ref(),
+            depositor.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+/// Generates the address of the record of a depositor's deposits
+pub fn find_depositor_record_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    depositor: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            DEPOSITOR_RECORD_SEED_PREFIX,
+            stake_pool_address.as_ref(),
+            depositor.as_ref(),
+        ],
+        program_id,
+    )
+}
+
 solana_pubkey::declare_id!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
 /// Program id for devnet
 pub mod devnet {
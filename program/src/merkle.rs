@@ -0,0 +1,130 @@
+//! Merkle tree of the depositors allowed into a stake pool
+
+use {solana_pubkey::Pubkey, solana_sha256_hasher::hashv};
+
+/// Domain separator for leaf hashes, so that an inner node can never be
+/// passed off as a leaf
+const LEAF_PREFIX: &[u8] = &[0];
+
+/// Domain separator for inner node hashes
+const NODE_PREFIX: &[u8] = &[1];
+
+/// Cap of depositors without one
+pub const NO_CAP: u64 = u64::MAX;
+
+/// Hash of the leaf allowing `depositor` to deposit at most `cap` lamports over
+/// the life of the pool
+pub fn leaf_hash(depositor: &Pubkey, cap: u64) -> [u8; 32] {
+    hashv(&[LEAF_PREFIX, depositor.as_ref(), &cap.to_le_bytes()]).to_bytes()
+}
+
+/// Hash of an inner node. The children are sorted, so that proofs don't need
+/// to say which side each sibling is on.
+fn node_hash(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+/// Check that `proof`, the sibling hashes from the leaf up, links `leaf` to
+/// `root`
+pub fn verify_proof(root: &[u8; 32], leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+    proof
+        .iter()
+        .fold(leaf, |hash, sibling| node_hash(&hash, sibling))
+        == *root
+}
+
+/// Merkle tree of leaf hashes, to compute the root and proofs off-chain.
+///
+/// A node without a sibling is carried up to the next layer as is, and has
+/// no entry in the proof for that layer.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    layers: Vec<Vec<[u8; 32]>>,
+}
+impl MerkleTree {
+    /// Build the tree from its leaves, in order
+    pub fn new(leaves: Vec<[u8; 32]>) -> Self {
+        let mut layers = vec![leaves];
+        while let Some(layer) = layers.last().filter(|layer| layer.len() > 1) {
+            let next_layer = layer
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            layers.push(next_layer);
+        }
+        Self { layers }
+    }
+
+    /// Root of the tree, if it has any leaves
+    pub fn root(&self) -> Option<[u8; 32]> {
+        self.layers.last().and_then(|layer| layer.first()).copied()
+    }
+
+    /// Proof for the leaf at `index`, if it exists
+    pub fn proof(&self, mut index: usize) -> Option<Vec<[u8; 32]>> {
+        if index >= self.layers[0].len() {
+            return None;
+        }
+        let mut proof = vec![];
+        for layer in &self.layers[..self.layers.len().saturating_sub(1)] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index >>= 1;
+        }
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::arithmetic_side_effects)]
+    use super::*;
+
+    fn depositors(count: usize) -> Vec<(Pubkey, u64)> {
+        (0..count)
+            .map(|i| (Pubkey::new_unique(), i as u64 * 1_000_000_000))
+            .collect()
+    }
+
+    #[test]
+    fn proofs_verify() {
+        for count in 1..=17 {
+            let depositors = depositors(count);
+            let tree = MerkleTree::new(
+                depositors
+                    .iter()
+                    .map(|(depositor, cap)| leaf_hash(depositor, *cap))
+                    .collect(),
+            );
+            let root = tree.root().unwrap();
+            for (index, (depositor, cap)) in depositors.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                assert!(verify_proof(&root, leaf_hash(depositor, *cap), &proof));
+                assert!(!verify_proof(
+                    &root,
+                    leaf_hash(depositor, cap.saturating_add(1)),
+                    &proof
+                ));
+                assert!(!verify_proof(
+                    &root,
+                    leaf_hash(&Pubkey::new_unique(), *cap),
+                    &proof
+                ));
+            }
+            assert!(tree.proof(count).is_none());
+        }
+    }
+
+    #[test]
+    fn empty_tree() {
+        let tree = MerkleTree::new(vec![]);
+        assert_eq!(tree.root(), None);
+        assert!(tree.proof(0).is_none());
+    }
+}
@@ -2,29 +2,36 @@
 
 use {
     crate::{
+        big_vec::BigVec,
         error::StakePoolError,
-        find_deposit_authority_program_address,
+        find_deposit_authority_program_address, find_depositor_allowlist_entry_address,
+        find_depositor_record_address,
         inline_mpl_token_metadata::{
             self,
             instruction::{create_metadata_accounts_v3, update_metadata_accounts_v2},
             pda::find_metadata_account,
             state::DataV2,
         },
-        instruction::{FundingType, PreferredValidatorType, StakePoolInstruction},
-        minimum_delegation, minimum_reserve_lamports, minimum_stake_lamports,
+        instruction::{
+            DepositWithdrawReturnData, DepositorProof, FundingType, PreferredValidatorType,
+            StakePoolInstruction, DEPOSIT_SOL_DEPOSITOR_ACCOUNTS_INDEX,
+            DEPOSIT_STAKE_DEPOSITOR_ACCOUNTS_INDEX,
+        },
+        merkle, minimum_delegation, minimum_reserve_lamports, minimum_stake_lamports,
         state::{
-            is_extension_supported_for_mint, AccountType, Fee, FeeType, FutureEpoch, StakePool,
-            StakeStatus, StakeWithdrawSource, ValidatorList, ValidatorListHeader,
-            ValidatorStakeInfo,
+            is_extension_supported_for_mint, AccountType, DepositorAllowlistEntry, DepositorRecord,
+            Fee, FeeType, FutureEpoch, StakePool, StakePoolConfig, StakePoolView, StakeStatus,
+            StakeWithdrawSource, ValidatorList, ValidatorListHeader, ValidatorStakeInfo,
         },
-        AUTHORITY_DEPOSIT, AUTHORITY_WITHDRAW, EPHEMERAL_STAKE_SEED_PREFIX, MAX_VALIDATORS_IN_POOL,
-        TRANSIENT_STAKE_SEED_PREFIX,
+        AUTHORITY_DEPOSIT, AUTHORITY_WITHDRAW, DEPOSITOR_ALLOWLIST_SEED_PREFIX,
+        DEPOSITOR_RECORD_SEED_PREFIX, EPHEMERAL_STAKE_SEED_PREFIX, MAX_TRANSFER_HOOK_PROGRAMS,
+        MAX_VALIDATORS_IN_POOL, TRANSIENT_STAKE_SEED_PREFIX,
     },
     borsh::BorshDeserialize,
-    solana_account_info::{next_account_info, AccountInfo},
-    solana_borsh::v1::try_from_slice_unchecked,
+    solana_account_info::{next_account_info, AccountInfo, MAX_PERMITTED_DATA_INCREASE},
+    solana_borsh::v1::{get_packed_len, try_from_slice_unchecked},
     solana_clock::{Clock, Epoch},
-    solana_cpi::{invoke, invoke_signed},
+    solana_cpi::{invoke, invoke_signed, set_return_data},
     solana_epoch_rewards::EpochRewards,
     solana_msg::msg,
     solana_program_error::{ProgramError, ProgramResult},
@@ -35,10 +42,18 @@ use {
     solana_sysvar::{Sysvar, SysvarSerialize},
     spl_token_2022_interface::{
         check_spl_token_program_account,
-        extension::{BaseStateWithExtensions, StateWithExtensions},
+        extension::{
+            scaled_ui_amount::{self, ScaledUiAmountConfig},
+            transfer_hook, BaseStateWithExtensions, StateWithExtensions,
+        },
         native_mint,
-        state::Mint,
+        state::{Account, Mint},
     },
+    spl_token_metadata_interface::{
+        instruction as token_metadata_instruction,
+        state::{Field, TokenMetadata},
+    },
+    spl_transfer_hook_interface::onchain::add_extra_accounts_for_execute_cpi,
     std::num::NonZeroU32,
 };
 
@@ -54,6 +69,117 @@ fn get_stake_state(
     }
 }
 
+/// Check if validator updates can be tallied in the stake pool configuration,
+/// which must be writable and migrated. The tally comes first in every layout
+/// of the configuration, so pools migrated with an older layout can store it.
+fn can_track_validator_updates(stake_pool_info: &AccountInfo, stake_pool: &StakePool) -> bool {
+    stake_pool_info.is_writable && stake_pool.account_type == AccountType::StakePoolV2
+}
+
+/// Drop the validator list entries about to be cleaned up from the tally of an
+/// in-progress epoch update, otherwise the tally would count more validators
+/// than remain in the list.
+///
+/// If the stake pool is read-only, the tally can't be changed, so this returns
+/// its epoch instead, and the removed entries updated in that epoch must stay
+/// in the list until a later cleanup.
+fn untrack_removed_validators(
+    stake_pool_info: &AccountInfo,
+    stake_pool: &StakePool,
+    validator_list: &BigVec,
+) -> Result<Option<u64>, ProgramError> {
+    if stake_pool.account_type != AccountType::StakePoolV2 {
+        return Ok(None);
+    }
+    let mut config = StakePoolConfig::load(stake_pool, &stake_pool_info.data.borrow())?;
+    if config.updated_validator_count == 0 || config.update_epoch <= stake_pool.last_update_epoch {
+        return Ok(None);
+    }
+    let mut removed_validator_count = 0u32;
+    for validator in
+        validator_list.deserialize_slice::<ValidatorStakeInfo>(0, validator_list.len() as usize)?
+    {
+        if validator.status == StakeStatus::ReadyForRemoval.into()
+            && validator.stake_lamports()? == 0
+            && u64::from(validator.last_update_epoch) == config.update_epoch
+        {
+            removed_validator_count = removed_validator_count.saturating_add(1);
+        }
+    }
+    if removed_validator_count == 0 {
+        return Ok(None);
+    }
+    if !stake_pool_info.is_writable {
+        msg!("Stake pool is read-only, keeping the validators removed during the epoch update");
+        return Ok(Some(config.update_epoch));
+    }
+    config.untrack_removed_validators(removed_validator_count);
+    config.store(&mut stake_pool_info.data.borrow_mut())?;
+    Ok(None)
+}
+
+/// Check if a validator list entry can be cleaned up, given the epoch returned
+/// by `untrack_removed_validators`
+fn can_clean_up_validator(data: &[u8], kept_update_epoch: Option<u64>) -> bool {
+    ValidatorStakeInfo::is_removed(data)
+        && !kept_update_epoch.is_some_and(|epoch| ValidatorStakeInfo::is_updated_in(data, epoch))
+}
+
+/// Reset the preferred validators if they don't exist or aren't active
+fn reset_inactive_preferred_validators(stake_pool: &mut StakePool, validator_list: &BigVec) {
+    // Check preferred deposit validator
+    if let Some(preferred_deposit) = stake_pool.preferred_deposit_validator_vote_address {
+        let maybe_validator = validator_list.find::<ValidatorStakeInfo, _>(|x| {
+            ValidatorStakeInfo::memcmp_pubkey(x, &preferred_deposit)
+        });
+
+        let should_reset = match maybe_validator {
+            Some(validator) => {
+                // Check if validator status is not Active
+                match validator.status.try_into() {
+                    Ok(StakeStatus::Active) => false, // Valid, keep it
+                    _ => true,                        // Not active, reset it
+                }
+            }
+            None => true, // Not found in list, reset it
+        };
+
+        if should_reset {
+            msg!(
+                "Preferred deposit validator {} not found or not active, resetting",
+                preferred_deposit
+            );
+            stake_pool.preferred_deposit_validator_vote_address = None;
+        }
+    }
+
+    // Check preferred withdrawal validator
+    if let Some(preferred_withdraw) = stake_pool.preferred_withdraw_validator_vote_address {
+        let maybe_validator = validator_list.find::<ValidatorStakeInfo, _>(|x| {
+            ValidatorStakeInfo::memcmp_pubkey(x, &preferred_withdraw)
+        });
+
+        let should_reset = match maybe_validator {
+            Some(validator) => {
+                // Check if validator status is not Active
+                match validator.status.try_into() {
+                    Ok(StakeStatus::Active) => false, // Valid, keep it
+                    _ => true,                        // Not active, reset it
+                }
+            }
+            None => true, // Not found in list, reset it
+        };
+
+        if should_reset {
+            msg!(
+                "Preferred withdrawal validator {} not found or not active, resetting",
+                preferred_withdraw
+            );
+            stake_pool.preferred_withdraw_validator_vote_address = None;
+        }
+    }
+}
+
 /// Check validity of vote address for a particular stake account
 fn check_validator_stake_address(
     program_id: &Pubkey,
@@ -130,6 +256,165 @@ fn check_mpl_metadata_account_address(
     }
 }
 
+/// Check that the metadata account is the Token-2022 pool mint, which holds
+/// its own metadata
+fn check_token_2022_metadata_account(
+    metadata_info: &AccountInfo,
+    stake_pool: &StakePool,
+) -> Result<(), ProgramError> {
+    if stake_pool.token_program_id != spl_token_2022_interface::id() {
+        msg!("Only Token-2022 pool mints can hold their own metadata");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if *metadata_info.key != stake_pool.pool_mint {
+        return Err(StakePoolError::InvalidMetadataAccount.into());
+    }
+    check_account_owner(metadata_info, &spl_token_2022_interface::id())
+}
+
+/// Check that the pool mint's transfer hook program, if it has one, is
+/// allowed by the manager, and return it
+fn check_transfer_hook_program(
+    config: &StakePoolConfig,
+    pool_mint_info: &AccountInfo,
+) -> Result<Option<Pubkey>, ProgramError> {
+    let pool_mint_data = pool_mint_info.try_borrow_data()?;
+    let pool_mint = StateWithExtensions::<Mint>::unpack(&pool_mint_data)?;
+    if let Some(program_id) = transfer_hook::get_program_id(&pool_mint) {
+        if !config.is_transfer_hook_program_allowed(&program_id) {
+            msg!(
+                "Transfer hook program {} is not allowed for the pool mint",
+                program_id
+            );
+            return Err(StakePoolError::TransferHookProgramNotAllowed.into());
+        }
+        Ok(Some(program_id))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Get the owner of the account receiving the pool tokens of a deposit, who
+/// is the depositor for the allowlist and Merkle proof checks
+fn get_depositor(
+    stake_pool: &StakePoolView,
+    dest_user_pool_info: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+    check_account_owner(dest_user_pool_info, &stake_pool.token_program_id)?;
+    let dest_user_pool_data = dest_user_pool_info.try_borrow_data()?;
+    Ok(
+        StateWithExtensions::<Account>::unpack(&dest_user_pool_data)?
+            .base
+            .owner,
+    )
+}
+
+/// Check that the owner of the account receiving the pool tokens of a deposit
+/// has its entry in `entry_info`, if the pool has a depositor allowlist.
+///
+/// The wallet funding the deposit is not checked, since `DepositStake` only
+/// sees the stake account once its authority is the pool's.
+fn check_depositor_allowlist(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    stake_pool: &StakePoolView,
+    config: &StakePoolConfig,
+    dest_user_pool_info: &AccountInfo,
+    entry_info: Option<&AccountInfo>,
+) -> Result<(), ProgramError> {
+    if !config.depositor_allowlist_enabled {
+        return Ok(());
+    }
+    let depositor = get_depositor(stake_pool, dest_user_pool_info)?;
+    let (entry_address, _) =
+        find_depositor_allowlist_entry_address(program_id, stake_pool_address, &depositor);
+    let is_allowed = entry_info
+        .filter(|entry_info| *entry_info.key == entry_address && entry_info.owner == program_id)
+        .and_then(|entry_info| {
+            let data = entry_info.try_borrow_data().ok()?;
+            try_from_slice_unchecked::<DepositorAllowlistEntry>(&data).ok()
+        })
+        .is_some_and(|entry| entry.allows(stake_pool_address, &depositor));
+    if !is_allowed {
+        msg!("Depositor {} is not on the allowlist", depositor);
+        return Err(StakePoolError::DepositorNotAllowed.into());
+    }
+    Ok(())
+}
+
+/// Check the depositor's Merkle proof, if the pool has a depositor Merkle
+/// root, and return the depositor and their cap, if they have one
+fn check_depositor_proof(
+    stake_pool: &StakePoolView,
+    config: &StakePoolConfig,
+    dest_user_pool_info: &AccountInfo,
+    depositor_proof: Option<&DepositorProof>,
+) -> Result<Option<(Pubkey, u64)>, ProgramError> {
+    let Some(root) = config.depositor_merkle_root else {
+        return Ok(None);
+    };
+    let depositor = get_depositor(stake_pool, dest_user_pool_info)?;
+    let Some(depositor_proof) = depositor_proof else {
+        msg!("Deposits into the pool require a depositor proof");
+        return Err(StakePoolError::InvalidDepositorProof.into());
+    };
+    if !merkle::verify_proof(
+        &root,
+        merkle::leaf_hash(&depositor, depositor_proof.cap),
+        &depositor_proof.proof,
+    ) {
+        msg!("Invalid depositor proof for {}", depositor);
+        return Err(StakePoolError::InvalidDepositorProof.into());
+    }
+    Ok(Some((depositor, depositor_proof.cap)).filter(|(_, cap)| *cap != merkle::NO_CAP))
+}
+
+/// Add `deposit_lamports` to the depositor's record in `record_info`, and
+/// check that their total deposits stay under `cap`, if they have one
+fn check_depositor_cap(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    record_info: Option<&AccountInfo>,
+    depositor_cap: Option<(Pubkey, u64)>,
+    deposit_lamports: u64,
+) -> Result<(), ProgramError> {
+    let Some((depositor, cap)) = depositor_cap else {
+        return Ok(());
+    };
+    let (record_address, _) =
+        find_depositor_record_address(program_id, stake_pool_address, &depositor);
+    let Some(record_info) = record_info.filter(|record_info| {
+        *record_info.key == record_address
+            && record_info.owner == program_id
+            && record_info.is_writable
+    }) else {
+        msg!(
+            "Deposits for {} require their depositor record {}",
+            depositor,
+            record_address
+        );
+        return Err(StakePoolError::InvalidDepositorRecord.into());
+    };
+    let mut record = try_from_slice_unchecked::<DepositorRecord>(&record_info.data.borrow())?;
+    if !record.is_for(stake_pool_address, &depositor) {
+        return Err(StakePoolError::InvalidDepositorRecord.into());
+    }
+    record.deposited = record
+        .deposited
+        .checked_add(deposit_lamports)
+        .ok_or(StakePoolError::CalculationFailure)?;
+    if record.deposited > cap {
+        msg!(
+            "Depositor would have deposited {} lamports, over their cap of {}",
+            record.deposited,
+            cap
+        );
+        return Err(StakePoolError::DepositorCapExceeded.into());
+    }
+    borsh::to_writer(&mut record_info.data.borrow_mut()[..], &record)?;
+    Ok(())
+}
+
 /// Check system program address
 fn check_system_program(program_id: &Pubkey) -> Result<(), ProgramError> {
     if *program_id != system_program::id() {
@@ -323,6 +608,22 @@ fn create_stake_account(
     )
 }
 
+/// Close a program-owned account, moving all of its lamports to the destination
+fn close_program_account(
+    account_info: &AccountInfo,
+    destination_info: &AccountInfo,
+) -> Result<(), ProgramError> {
+    let destination_lamports = destination_info
+        .lamports()
+        .checked_add(account_info.lamports())
+        .ok_or(StakePoolError::CalculationFailure)?;
+    **destination_info.try_borrow_mut_lamports()? = destination_lamports;
+    **account_info.try_borrow_mut_lamports()? = 0;
+    account_info.resize(0)?;
+    account_info.assign(&system_program::id());
+    Ok(())
+}
+
 /// Program state handler.
 pub struct Processor {}
 impl Processor {
@@ -608,7 +909,35 @@ impl Processor {
         invoke_signed(&ix, &[mint, destination, authority], signers)
     }
 
-    /// Issue a SPL Token `Transfer` instruction.
+    /// Issue a Token-2022 `UpdateMultiplier` instruction for the scaled UI
+    /// amount, signed by the withdraw authority.
+    fn token_update_multiplier<'a>(
+        stake_pool: &Pubkey,
+        token_program: AccountInfo<'a>,
+        mint: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        bump_seed: u8,
+        multiplier: f64,
+        effective_timestamp: i64,
+    ) -> Result<(), ProgramError> {
+        let authority_signature_seeds = [stake_pool.as_ref(), AUTHORITY_WITHDRAW, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = scaled_ui_amount::instruction::update_multiplier(
+            token_program.key,
+            mint.key,
+            authority.key,
+            &[],
+            multiplier,
+            effective_timestamp,
+        )?;
+
+        invoke_signed(&ix, &[mint, authority], signers)
+    }
+
+    /// Issue a SPL Token `Transfer` instruction, resolving the accounts
+    /// required by the mint's transfer hook program, if any, from
+    /// `additional_accounts`
     #[allow(clippy::too_many_arguments)]
     fn token_transfer<'a>(
         token_program: AccountInfo<'a>,
@@ -618,8 +947,10 @@ impl Processor {
         authority: AccountInfo<'a>,
         amount: u64,
         decimals: u8,
+        transfer_hook_program_id: Option<Pubkey>,
+        additional_accounts: &[AccountInfo<'a>],
     ) -> Result<(), ProgramError> {
-        let ix = spl_token_2022_interface::instruction::transfer_checked(
+        let mut ix = spl_token_2022_interface::instruction::transfer_checked(
             token_program.key,
             source.key,
             mint.key,
@@ -629,7 +960,26 @@ impl Processor {
             amount,
             decimals,
         )?;
-        invoke(&ix, &[source, mint, destination, authority])
+        let mut account_infos = vec![
+            source.clone(),
+            mint.clone(),
+            destination.clone(),
+            authority.clone(),
+        ];
+        if let Some(transfer_hook_program_id) = transfer_hook_program_id {
+            add_extra_accounts_for_execute_cpi(
+                &mut ix,
+                &mut account_infos,
+                &transfer_hook_program_id,
+                source,
+                mint,
+                destination,
+                authority,
+                amount,
+                additional_accounts,
+            )?;
+        }
+        invoke(&ix, &account_infos)
     }
 
     fn sol_transfer<'a>(
@@ -641,6 +991,27 @@ impl Processor {
         invoke(&ix, &[source, destination])
     }
 
+    /// Transfer the lamports needed for the pool mint to stay rent-exempt
+    /// once Token-2022 grows it by `additional_len` bytes of metadata
+    fn fund_token_2022_metadata<'a>(
+        payer_info: &AccountInfo<'a>,
+        pool_mint_info: &AccountInfo<'a>,
+        additional_len: usize,
+    ) -> Result<(), ProgramError> {
+        let rent = Rent::get()?;
+        let required_lamports =
+            rent.minimum_balance(pool_mint_info.data_len().saturating_add(additional_len));
+        let additional_lamports = required_lamports.saturating_sub(pool_mint_info.lamports());
+        if additional_lamports > 0 {
+            Self::sol_transfer(
+                payer_info.clone(),
+                pool_mint_info.clone(),
+                additional_lamports,
+            )?;
+        }
+        Ok(())
+    }
+
     /// Processes `Initialize` instruction.
     #[inline(never)] // needed due to stack size violation
     fn process_initialize(
@@ -856,7 +1227,14 @@ impl Processor {
             &validator_list,
         )?;
 
-        stake_pool.account_type = AccountType::StakePool;
+        // pools created in accounts large enough for the configuration start out
+        // migrated, others need `MigratePool` before using any configuration
+        let is_v2 = stake_pool_info.data_len() >= StakePool::account_len();
+        stake_pool.account_type = if is_v2 {
+            AccountType::StakePoolV2
+        } else {
+            AccountType::StakePool
+        };
         stake_pool.manager = *manager_info.key;
         stake_pool.staker = *staker_info.key;
         stake_pool.stake_deposit_authority = stake_deposit_authority;
@@ -885,8 +1263,12 @@ impl Processor {
         stake_pool.last_epoch_pool_token_supply = 0;
         stake_pool.last_epoch_total_lamports = 0;
 
-        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)
-            .map_err(|e| e.into())
+        let mut stake_pool_data = stake_pool_info.data.borrow_mut();
+        borsh::to_writer(&mut stake_pool_data[..], &stake_pool)?;
+        if is_v2 {
+            StakePoolConfig::default().store(&mut stake_pool_data)?;
+        }
+        Ok(())
     }
 
     /// Processes `AddValidatorToPool` instruction.
@@ -1846,6 +2228,260 @@ impl Processor {
         Ok(())
     }
 
+    /// Updates a validator list entry from its validator and transient stake
+    /// accounts, merging or deactivating them as needed, and tallies the
+    /// update in the stake pool configuration if provided
+    #[allow(clippy::too_many_arguments)]
+    #[inline(always)] // needed to maximize number of validators
+    fn update_validator_stake_record<'a>(
+        stake_pool_info: &AccountInfo<'a>,
+        stake_pool: &StakePool,
+        config: Option<&mut StakePoolConfig>,
+        withdraw_authority_info: &AccountInfo<'a>,
+        reserve_stake_info: &AccountInfo<'a>,
+        clock_info: &AccountInfo<'a>,
+        clock: &Clock,
+        stake_history_info: &AccountInfo<'a>,
+        rent: &Rent,
+        validator_stake_record: &mut ValidatorStakeInfo,
+        validator_stake_info: &AccountInfo<'a>,
+        transient_stake_info: &AccountInfo<'a>,
+        no_merge: bool,
+    ) -> ProgramResult {
+        let previous_update_epoch = u64::from(validator_stake_record.last_update_epoch);
+        let previous_lamports = validator_stake_record.stake_lamports()?;
+
+        let validator_stake_rent = rent.minimum_balance(validator_stake_info.data_len());
+
+        let mut active_stake_lamports = 0;
+        let mut transient_stake_lamports = 0;
+        let validator_stake_state = try_from_slice_unchecked::<stake::state::StakeStateV2>(
+            &validator_stake_info.data.borrow(),
+        )
+        .ok();
+        let transient_stake_state = try_from_slice_unchecked::<stake::state::StakeStateV2>(
+            &transient_stake_info.data.borrow(),
+        )
+        .ok();
+
+        // Possible merge situations for transient stake
+        //  * active -> merge into validator stake
+        //  * activating -> nothing, just account its lamports
+        //  * deactivating -> nothing, just account its lamports
+        //  * inactive -> merge into reserve stake
+        //  * not a stake -> ignore
+        if validator_stake_record.transient_stake_lamports != 0.into() {
+            match transient_stake_state {
+                Some(stake::state::StakeStateV2::Initialized(meta)) => {
+                    if stake_is_usable_by_pool(
+                        &meta,
+                        withdraw_authority_info.key,
+                        &stake_pool.lockup,
+                    ) {
+                        if no_merge {
+                            transient_stake_lamports = transient_stake_info.lamports();
+                        } else {
+                            // merge into reserve
+                            Self::stake_merge(
+                                stake_pool_info.key,
+                                transient_stake_info.clone(),
+                                withdraw_authority_info.clone(),
+                                AUTHORITY_WITHDRAW,
+                                stake_pool.stake_withdraw_bump_seed,
+                                reserve_stake_info.clone(),
+                                clock_info.clone(),
+                                stake_history_info.clone(),
+                            )?;
+                            validator_stake_record.status.remove_transient_stake()?;
+                        }
+                    }
+                }
+                Some(stake::state::StakeStateV2::Stake(meta, stake, _)) => {
+                    if stake_is_usable_by_pool(
+                        &meta,
+                        withdraw_authority_info.key,
+                        &stake_pool.lockup,
+                    ) {
+                        if !no_merge {
+                            if stake_is_inactive_without_history(&stake, clock.epoch) {
+                                // deactivated, merge into reserve
+                                Self::stake_merge(
+                                    stake_pool_info.key,
+                                    transient_stake_info.clone(),
+                                    withdraw_authority_info.clone(),
+                                    AUTHORITY_WITHDRAW,
+                                    stake_pool.stake_withdraw_bump_seed,
+                                    reserve_stake_info.clone(),
+                                    clock_info.clone(),
+                                    stake_history_info.clone(),
+                                )?;
+                                validator_stake_record.status.remove_transient_stake()?;
+                            } else if validator_stake_record.status.try_into()
+                                == Ok(StakeStatus::Active)
+                            {
+                                if stake.delegation.activation_epoch < clock.epoch {
+                                    if let Some(stake::state::StakeStateV2::Stake(
+                                        _,
+                                        validator_stake,
+                                        _,
+                                    )) = validator_stake_state
+                                    {
+                                        if validator_stake.delegation.activation_epoch < clock.epoch
+                                        {
+                                            Self::stake_merge(
+                                                stake_pool_info.key,
+                                                transient_stake_info.clone(),
+                                                withdraw_authority_info.clone(),
+                                                AUTHORITY_WITHDRAW,
+                                                stake_pool.stake_withdraw_bump_seed,
+                                                validator_stake_info.clone(),
+                                                clock_info.clone(),
+                                                stake_history_info.clone(),
+                                            )?;
+                                        } else {
+                                            msg!("Stake activating or just active, not ready to merge");
+                                        }
+                                    } else {
+                                        msg!("Transient stake is activating or active, but validator stake is not, need to add the validator stake account on {} back into the stake pool", stake.delegation.voter_pubkey);
+                                    }
+                                } else {
+                                    msg!("Transient stake not ready to be merged anywhere");
+                                }
+                            } else if stake.delegation.deactivation_epoch == Epoch::MAX {
+                                msg!("Transient stake is activating or active, deactivating.");
+                                Self::stake_deactivate(
+                                    transient_stake_info.clone(),
+                                    clock_info.clone(),
+                                    withdraw_authority_info.clone(),
+                                    stake_pool_info.key,
+                                    AUTHORITY_WITHDRAW,
+                                    stake_pool.stake_withdraw_bump_seed,
+                                )?;
+                            }
+                        }
+                        transient_stake_lamports = transient_stake_info.lamports();
+                    }
+                }
+                None
+                | Some(stake::state::StakeStateV2::Uninitialized)
+                | Some(stake::state::StakeStateV2::RewardsPool) => {} // do nothing
+            }
+        }
+        // Status for validator stake
+        //  * active -> do everything
+        //  * any other state / not a stake -> error state, but account for transient
+        //    stake
+        let validator_stake_state = try_from_slice_unchecked::<stake::state::StakeStateV2>(
+            &validator_stake_info.data.borrow(),
+        )
+        .ok();
+        match validator_stake_state {
+            Some(stake::state::StakeStateV2::Stake(meta, stake, _))
+                if stake_is_usable_by_pool(
+                    &meta,
+                    withdraw_authority_info.key,
+                    &stake_pool.lockup,
+                ) =>
+            {
+                let additional_lamports = validator_stake_info
+                    .lamports()
+                    .saturating_sub(stake.delegation.stake)
+                    .saturating_sub(validator_stake_rent);
+                // withdraw any extra lamports back to the reserve
+                if additional_lamports > 0 {
+                    Self::stake_withdraw(
+                        stake_pool_info.key,
+                        validator_stake_info.clone(),
+                        withdraw_authority_info.clone(),
+                        AUTHORITY_WITHDRAW,
+                        stake_pool.stake_withdraw_bump_seed,
+                        reserve_stake_info.clone(),
+                        clock_info.clone(),
+                        stake_history_info.clone(),
+                        additional_lamports,
+                    )?;
+                }
+                match validator_stake_record.status.try_into()? {
+                    StakeStatus::Active => {
+                        active_stake_lamports = validator_stake_info.lamports();
+                    }
+                    StakeStatus::DeactivatingValidator | StakeStatus::DeactivatingAll => {
+                        if no_merge {
+                            active_stake_lamports = validator_stake_info.lamports();
+                        } else if stake_is_inactive_without_history(&stake, clock.epoch) {
+                            // Validator was removed through normal means.
+                            // Absorb the lamports into the reserve.
+                            Self::stake_merge(
+                                stake_pool_info.key,
+                                validator_stake_info.clone(),
+                                withdraw_authority_info.clone(),
+                                AUTHORITY_WITHDRAW,
+                                stake_pool.stake_withdraw_bump_seed,
+                                reserve_stake_info.clone(),
+                                clock_info.clone(),
+                                stake_history_info.clone(),
+                            )?;
+                            validator_stake_record.status.remove_validator_stake()?;
+                        } else {
+                            active_stake_lamports = validator_stake_info.lamports();
+                        }
+                    }
+                    StakeStatus::DeactivatingTransient | StakeStatus::ReadyForRemoval => {
+                        msg!("Validator stake account no longer part of the pool, ignoring");
+                    }
+                }
+            }
+            Some(stake::state::StakeStateV2::Initialized(meta))
+                if stake_is_usable_by_pool(
+                    &meta,
+                    withdraw_authority_info.key,
+                    &stake_pool.lockup,
+                ) =>
+            {
+                // If a validator stake is `Initialized`, the validator could
+                // have been destaked during a cluster restart or removed through
+                // normal means. Either way, absorb those lamports into the reserve.
+                // The transient stake was likely absorbed into the reserve earlier.
+                Self::stake_merge(
+                    stake_pool_info.key,
+                    validator_stake_info.clone(),
+                    withdraw_authority_info.clone(),
+                    AUTHORITY_WITHDRAW,
+                    stake_pool.stake_withdraw_bump_seed,
+                    reserve_stake_info.clone(),
+                    clock_info.clone(),
+                    stake_history_info.clone(),
+                )?;
+                if transient_stake_lamports != 0 {
+                    validator_stake_record.status = StakeStatus::DeactivatingTransient.into();
+                } else {
+                    validator_stake_record.status = StakeStatus::ReadyForRemoval.into();
+                }
+            }
+            Some(stake::state::StakeStateV2::Stake(_, _, _))
+            | Some(stake::state::StakeStateV2::Initialized(_))
+            | Some(stake::state::StakeStateV2::Uninitialized)
+            | Some(stake::state::StakeStateV2::RewardsPool)
+            | None => {
+                msg!("Validator stake account no longer part of the pool, ignoring");
+            }
+        }
+
+        validator_stake_record.last_update_epoch = clock.epoch.into();
+        validator_stake_record.active_stake_lamports = active_stake_lamports.into();
+        validator_stake_record.transient_stake_lamports = transient_stake_lamports.into();
+
+        if let Some(config) = config {
+            config.track_validator_update(
+                clock.epoch,
+                previous_update_epoch,
+                previous_lamports,
+                validator_stake_record.stake_lamports()?,
+            )?;
+        }
+        Ok(())
+    }
+
     /// Processes `UpdateValidatorListBalance` instruction.
     #[inline(always)] // needed to maximize number of validators
     fn process_update_validator_list_balance(
@@ -1911,6 +2547,23 @@ impl Processor {
             return Err(StakePoolError::InvalidState.into());
         }
 
+        // Older clients pass a read-only stake pool, whose updates can't be
+        // tallied. An entry first updated that way is never counted during the
+        // epoch, so the tally falls short of the list length, and
+        // `UpdateStakePoolBalance` sums the list instead. Entries already
+        // updated in the epoch may be in the tally, which a read-only update
+        // would make stale, so migrated pools skip them.
+        let mut config = if can_track_validator_updates(stake_pool_info, &stake_pool) {
+            Some(StakePoolConfig::load(
+                &stake_pool,
+                &stake_pool_info.data.borrow(),
+            )?)
+        } else {
+            None
+        };
+        let skip_updated_validators =
+            config.is_none() && stake_pool.account_type == AccountType::StakePoolV2;
+
         let validator_iter = &mut validator_slice
             .iter_mut()
             .zip(validator_stake_accounts.chunks_exact(2));
@@ -1922,6 +2575,11 @@ impl Processor {
             let transient_stake_info = validator_stakes
                 .last()
                 .ok_or(ProgramError::InvalidInstructionData)?;
+            if skip_updated_validators
+                && u64::from(validator_stake_record.last_update_epoch) >= clock.epoch
+            {
+                continue;
+            }
             if check_validator_stake_address(
                 program_id,
                 stake_pool_info.key,
@@ -1945,227 +2603,119 @@ impl Processor {
                 continue;
             };
 
-            let validator_stake_rent = rent.minimum_balance(validator_stake_info.data_len());
+            Self::update_validator_stake_record(
+                stake_pool_info,
+                &stake_pool,
+                config.as_mut(),
+                withdraw_authority_info,
+                reserve_stake_info,
+                clock_info,
+                clock,
+                stake_history_info,
+                &rent,
+                validator_stake_record,
+                validator_stake_info,
+                transient_stake_info,
+                no_merge,
+            )?;
+        }
 
-            let mut active_stake_lamports = 0;
-            let mut transient_stake_lamports = 0;
-            let validator_stake_state = try_from_slice_unchecked::<stake::state::StakeStateV2>(
-                &validator_stake_info.data.borrow(),
-            )
-            .ok();
-            let transient_stake_state = try_from_slice_unchecked::<stake::state::StakeStateV2>(
-                &transient_stake_info.data.borrow(),
-            )
-            .ok();
-
-            // Possible merge situations for transient stake
-            //  * active -> merge into validator stake
-            //  * activating -> nothing, just account its lamports
-            //  * deactivating -> nothing, just account its lamports
-            //  * inactive -> merge into reserve stake
-            //  * not a stake -> ignore
-            if validator_stake_record.transient_stake_lamports != 0.into() {
-                match transient_stake_state {
-                    Some(stake::state::StakeStateV2::Initialized(meta)) => {
-                        if stake_is_usable_by_pool(
-                            &meta,
-                            withdraw_authority_info.key,
-                            &stake_pool.lockup,
-                        ) {
-                            if no_merge {
-                                transient_stake_lamports = transient_stake_info.lamports();
-                            } else {
-                                // merge into reserve
-                                Self::stake_merge(
-                                    stake_pool_info.key,
-                                    transient_stake_info.clone(),
-                                    withdraw_authority_info.clone(),
-                                    AUTHORITY_WITHDRAW,
-                                    stake_pool.stake_withdraw_bump_seed,
-                                    reserve_stake_info.clone(),
-                                    clock_info.clone(),
-                                    stake_history_info.clone(),
-                                )?;
-                                validator_stake_record.status.remove_transient_stake()?;
-                            }
-                        }
-                    }
-                    Some(stake::state::StakeStateV2::Stake(meta, stake, _)) => {
-                        if stake_is_usable_by_pool(
-                            &meta,
-                            withdraw_authority_info.key,
-                            &stake_pool.lockup,
-                        ) {
-                            if !no_merge {
-                                if stake_is_inactive_without_history(&stake, clock.epoch) {
-                                    // deactivated, merge into reserve
-                                    Self::stake_merge(
-                                        stake_pool_info.key,
-                                        transient_stake_info.clone(),
-                                        withdraw_authority_info.clone(),
-                                        AUTHORITY_WITHDRAW,
-                                        stake_pool.stake_withdraw_bump_seed,
-                                        reserve_stake_info.clone(),
-                                        clock_info.clone(),
-                                        stake_history_info.clone(),
-                                    )?;
-                                    validator_stake_record.status.remove_transient_stake()?;
-                                } else if validator_stake_record.status.try_into()
-                                    == Ok(StakeStatus::Active)
-                                {
-                                    if stake.delegation.activation_epoch < clock.epoch {
-                                        if let Some(stake::state::StakeStateV2::Stake(
-                                            _,
-                                            validator_stake,
-                                            _,
-                                        )) = validator_stake_state
-                                        {
-                                            if validator_stake.delegation.activation_epoch
-                                                < clock.epoch
-                                            {
-                                                Self::stake_merge(
-                                                    stake_pool_info.key,
-                                                    transient_stake_info.clone(),
-                                                    withdraw_authority_info.clone(),
-                                                    AUTHORITY_WITHDRAW,
-                                                    stake_pool.stake_withdraw_bump_seed,
-                                                    validator_stake_info.clone(),
-                                                    clock_info.clone(),
-                                                    stake_history_info.clone(),
-                                                )?;
-                                            } else {
-                                                msg!("Stake activating or just active, not ready to merge");
-                                            }
-                                        } else {
-                                            msg!("Transient stake is activating or active, but validator stake is not, need to add the validator stake account on {} back into the stake pool", stake.delegation.voter_pubkey);
-                                        }
-                                    } else {
-                                        msg!("Transient stake not ready to be merged anywhere");
-                                    }
-                                } else if stake.delegation.deactivation_epoch == Epoch::MAX {
-                                    msg!("Transient stake is activating or active, deactivating.");
-                                    Self::stake_deactivate(
-                                        transient_stake_info.clone(),
-                                        clock_info.clone(),
-                                        withdraw_authority_info.clone(),
-                                        stake_pool_info.key,
-                                        AUTHORITY_WITHDRAW,
-                                        stake_pool.stake_withdraw_bump_seed,
-                                    )?;
-                                }
-                            }
-                            transient_stake_lamports = transient_stake_info.lamports();
-                        }
-                    }
-                    None
-                    | Some(stake::state::StakeStateV2::Uninitialized)
-                    | Some(stake::state::StakeStateV2::RewardsPool) => {} // do nothing
-                }
-            }
-            // Status for validator stake
-            //  * active -> do everything
-            //  * any other state / not a stake -> error state, but account for transient
-            //    stake
-            let validator_stake_state = try_from_slice_unchecked::<stake::state::StakeStateV2>(
-                &validator_stake_info.data.borrow(),
-            )
-            .ok();
-            match validator_stake_state {
-                Some(stake::state::StakeStateV2::Stake(meta, stake, _))
-                    if stake_is_usable_by_pool(
-                        &meta,
-                        withdraw_authority_info.key,
-                        &stake_pool.lockup,
-                    ) =>
-                {
-                    let additional_lamports = validator_stake_info
-                        .lamports()
-                        .saturating_sub(stake.delegation.stake)
-                        .saturating_sub(validator_stake_rent);
-                    // withdraw any extra lamports back to the reserve
-                    if additional_lamports > 0 {
-                        Self::stake_withdraw(
-                            stake_pool_info.key,
-                            validator_stake_info.clone(),
-                            withdraw_authority_info.clone(),
-                            AUTHORITY_WITHDRAW,
-                            stake_pool.stake_withdraw_bump_seed,
-                            reserve_stake_info.clone(),
-                            clock_info.clone(),
-                            stake_history_info.clone(),
-                            additional_lamports,
-                        )?;
-                    }
-                    match validator_stake_record.status.try_into()? {
-                        StakeStatus::Active => {
-                            active_stake_lamports = validator_stake_info.lamports();
-                        }
-                        StakeStatus::DeactivatingValidator | StakeStatus::DeactivatingAll => {
-                            if no_merge {
-                                active_stake_lamports = validator_stake_info.lamports();
-                            } else if stake_is_inactive_without_history(&stake, clock.epoch) {
-                                // Validator was removed through normal means.
-                                // Absorb the lamports into the reserve.
-                                Self::stake_merge(
-                                    stake_pool_info.key,
-                                    validator_stake_info.clone(),
-                                    withdraw_authority_info.clone(),
-                                    AUTHORITY_WITHDRAW,
-                                    stake_pool.stake_withdraw_bump_seed,
-                                    reserve_stake_info.clone(),
-                                    clock_info.clone(),
-                                    stake_history_info.clone(),
-                                )?;
-                                validator_stake_record.status.remove_validator_stake()?;
-                            } else {
-                                active_stake_lamports = validator_stake_info.lamports();
-                            }
-                        }
-                        StakeStatus::DeactivatingTransient | StakeStatus::ReadyForRemoval => {
-                            msg!("Validator stake account no longer part of the pool, ignoring");
-                        }
-                    }
-                }
-                Some(stake::state::StakeStateV2::Initialized(meta))
-                    if stake_is_usable_by_pool(
-                        &meta,
-                        withdraw_authority_info.key,
-                        &stake_pool.lockup,
-                    ) =>
-                {
-                    // If a validator stake is `Initialized`, the validator could
-                    // have been destaked during a cluster restart or removed through
-                    // normal means. Either way, absorb those lamports into the reserve.
-                    // The transient stake was likely absorbed into the reserve earlier.
-                    Self::stake_merge(
-                        stake_pool_info.key,
-                        validator_stake_info.clone(),
-                        withdraw_authority_info.clone(),
-                        AUTHORITY_WITHDRAW,
-                        stake_pool.stake_withdraw_bump_seed,
-                        reserve_stake_info.clone(),
-                        clock_info.clone(),
-                        stake_history_info.clone(),
-                    )?;
-                    if transient_stake_lamports != 0 {
-                        validator_stake_record.status = StakeStatus::DeactivatingTransient.into();
-                    } else {
-                        validator_stake_record.status = StakeStatus::ReadyForRemoval.into();
-                    }
-                }
-                Some(stake::state::StakeStateV2::Stake(_, _, _))
-                | Some(stake::state::StakeStateV2::Initialized(_))
-                | Some(stake::state::StakeStateV2::Uninitialized)
-                | Some(stake::state::StakeStateV2::RewardsPool)
-                | None => {
-                    msg!("Validator stake account no longer part of the pool, ignoring");
-                }
-            }
+        if let Some(config) = config {
+            config.store(&mut stake_pool_info.data.borrow_mut())?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes [`UpdateValidatorBalance`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_update_validator_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        validator_index: u32,
+        no_merge: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let withdraw_authority_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+        let validator_stake_info = next_account_info(account_info_iter)?;
+        let transient_stake_info = next_account_info(account_info_iter)?;
+
+        let rent = Rent::get()?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        if !can_track_validator_updates(stake_pool_info, &stake_pool) {
+            msg!("Stake pool must be writable and migrated to track validator updates");
+            return Err(StakePoolError::StakePoolNotMigrated.into());
+        }
+        stake_pool.check_validator_list(validator_list_info)?;
+        stake_pool.check_authority_withdraw(
+            withdraw_authority_info.key,
+            program_id,
+            stake_pool_info.key,
+        )?;
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
+        check_stake_program(stake_program_info.key)?;
+
+        // If rewards are being distributed, abort
+        let epoch_rewards = EpochRewards::get()?;
+        if epoch_rewards.active {
+            return Err(StakePoolError::EpochRewardDistributionInProgress.into());
+        }
 
-            validator_stake_record.last_update_epoch = clock.epoch.into();
-            validator_stake_record.active_stake_lamports = active_stake_lamports.into();
-            validator_stake_record.transient_stake_lamports = transient_stake_lamports.into();
+        check_account_owner(validator_list_info, program_id)?;
+        let mut validator_list_data = validator_list_info.data.borrow_mut();
+        let (validator_list_header, mut big_vec) =
+            ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+        if !validator_list_header.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
         }
+        let validator_stake_record =
+            ValidatorListHeader::deserialize_mut_slice(&mut big_vec, validator_index as usize, 1)?
+                .first_mut()
+                .ok_or(StakePoolError::ValidatorNotFound)?;
+        check_validator_stake_address(
+            program_id,
+            stake_pool_info.key,
+            validator_stake_info.key,
+            &validator_stake_record.vote_account_address,
+            NonZeroU32::new(validator_stake_record.validator_seed_suffix.into()),
+        )?;
+        check_transient_stake_address(
+            program_id,
+            stake_pool_info.key,
+            transient_stake_info.key,
+            &validator_stake_record.vote_account_address,
+            validator_stake_record.transient_seed_suffix.into(),
+        )?;
+
+        let mut config = StakePoolConfig::load(&stake_pool, &stake_pool_info.data.borrow())?;
+        Self::update_validator_stake_record(
+            stake_pool_info,
+            &stake_pool,
+            Some(&mut config),
+            withdraw_authority_info,
+            reserve_stake_info,
+            clock_info,
+            clock,
+            stake_history_info,
+            &rent,
+            validator_stake_record,
+            validator_stake_info,
+            transient_stake_info,
+            no_merge,
+        )?;
+        config.store(&mut stake_pool_info.data.borrow_mut())?;
 
         Ok(())
     }
@@ -2224,15 +2774,30 @@ impl Processor {
             .lamports()
             .saturating_sub(minimum_reserve_lamports(reserve_rent));
 
-        for validator_stake_record in validator_list
-            .deserialize_slice::<ValidatorStakeInfo>(0, validator_list.len() as usize)?
-        {
-            if u64::from(validator_stake_record.last_update_epoch) < clock.epoch {
-                return Err(StakePoolError::StakeListOutOfDate.into());
-            }
+        // Until the pool is updated for the epoch, nothing else changes the
+        // validator list, so if every entry was tallied while being updated,
+        // the tally can be used without summing the whole list
+        let config = StakePoolConfig::load(&stake_pool, &stake_pool_info.data.borrow())?;
+        let tallied_lamports = if stake_pool.last_update_epoch < clock.epoch {
+            config.updated_validator_lamports(clock.epoch, validator_list.len())
+        } else {
+            None
+        };
+        if let Some(tallied_lamports) = tallied_lamports {
             total_lamports = total_lamports
-                .checked_add(validator_stake_record.stake_lamports()?)
+                .checked_add(tallied_lamports)
                 .ok_or(StakePoolError::CalculationFailure)?;
+        } else {
+            for validator_stake_record in validator_list
+                .deserialize_slice::<ValidatorStakeInfo>(0, validator_list.len() as usize)?
+            {
+                if u64::from(validator_stake_record.last_update_epoch) < clock.epoch {
+                    return Err(StakePoolError::StakeListOutOfDate.into());
+                }
+                total_lamports = total_lamports
+                    .checked_add(validator_stake_record.stake_lamports()?)
+                    .ok_or(StakePoolError::CalculationFailure)?;
+            }
         }
 
         let reward_lamports = total_lamports.saturating_sub(previous_lamports);
@@ -2285,6 +2850,26 @@ impl Processor {
         let pool_mint = StateWithExtensions::<Mint>::unpack(&pool_mint_data)?;
         stake_pool.pool_token_supply = pool_mint.base.supply;
 
+        // If the withdraw authority controls the pool mint's scaled UI amount,
+        // keep the UI amount of pool tokens pegged to their value in SOL
+        let scaled_ui_multiplier = pool_mint
+            .get_extension::<ScaledUiAmountConfig>()
+            .ok()
+            .filter(|config| Option::<Pubkey>::from(config.authority) == Some(*withdraw_info.key))
+            .and_then(|_| stake_pool.calc_scaled_ui_multiplier(pool_mint.base.decimals));
+        drop(pool_mint_data);
+        if let Some(multiplier) = scaled_ui_multiplier {
+            Self::token_update_multiplier(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                withdraw_info.clone(),
+                stake_pool.stake_withdraw_bump_seed,
+                multiplier,
+                clock.unix_timestamp,
+            )?;
+        }
+
         borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
 
         Ok(())
@@ -2315,65 +2900,17 @@ impl Processor {
             return Err(StakePoolError::InvalidState.into());
         }
 
-        validator_list.retain::<ValidatorStakeInfo, _>(|x| !ValidatorStakeInfo::is_removed(x))?;
+        let kept_update_epoch =
+            untrack_removed_validators(stake_pool_info, &stake_pool, &validator_list)?;
+        validator_list
+            .retain::<ValidatorStakeInfo, _>(|x| !can_clean_up_validator(x, kept_update_epoch))?;
 
         if stake_pool_info.is_writable {
             msg!("Checking preferred validators");
             let mut stake_pool =
                 try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
 
-            // Check and reset preferred validators if they don't exist or aren't active
-            // Check preferred deposit validator
-            if let Some(preferred_deposit) = stake_pool.preferred_deposit_validator_vote_address {
-                let maybe_validator = validator_list.find::<ValidatorStakeInfo, _>(|x| {
-                    ValidatorStakeInfo::memcmp_pubkey(x, &preferred_deposit)
-                });
-
-                let should_reset = match maybe_validator {
-                    Some(validator) => {
-                        // Check if validator status is not Active
-                        match validator.status.try_into() {
-                            Ok(StakeStatus::Active) => false, // Valid, keep it
-                            _ => true,                        // Not active, reset it
-                        }
-                    }
-                    None => true, // Not found in list, reset it
-                };
-
-                if should_reset {
-                    msg!(
-                        "Preferred deposit validator {} not found or not active, resetting",
-                        preferred_deposit
-                    );
-                    stake_pool.preferred_deposit_validator_vote_address = None;
-                }
-            }
-
-            // Check preferred withdrawal validator
-            if let Some(preferred_withdraw) = stake_pool.preferred_withdraw_validator_vote_address {
-                let maybe_validator = validator_list.find::<ValidatorStakeInfo, _>(|x| {
-                    ValidatorStakeInfo::memcmp_pubkey(x, &preferred_withdraw)
-                });
-
-                let should_reset = match maybe_validator {
-                    Some(validator) => {
-                        // Check if validator status is not Active
-                        match validator.status.try_into() {
-                            Ok(StakeStatus::Active) => false, // Valid, keep it
-                            _ => true,                        // Not active, reset it
-                        }
-                    }
-                    None => true, // Not found in list, reset it
-                };
-
-                if should_reset {
-                    msg!(
-                        "Preferred withdrawal validator {} not found or not active, resetting",
-                        preferred_withdraw
-                    );
-                    stake_pool.preferred_withdraw_validator_vote_address = None;
-                }
-            }
+            reset_inactive_preferred_validators(&mut stake_pool, &validator_list);
 
             // Save the updated stake pool state
             borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
@@ -2388,6 +2925,7 @@ impl Processor {
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         minimum_pool_tokens_out: Option<u64>,
+        depositor_proof: Option<DepositorProof>,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
@@ -2410,10 +2948,12 @@ impl Processor {
         check_stake_program(stake_program_info.key)?;
 
         check_account_owner(stake_pool_info, program_id)?;
-        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        let mut stake_pool_data = stake_pool_info.data.borrow_mut();
+        let mut stake_pool = StakePoolView::new(&mut stake_pool_data)?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
+        let config = stake_pool.config()?;
 
         stake_pool.check_authority_withdraw(
             withdraw_authority_info.key,
@@ -2422,6 +2962,23 @@ impl Processor {
         )?;
         stake_pool.check_stake_deposit_authority(stake_deposit_authority_info.key)?;
         stake_pool.check_mint(pool_mint_info)?;
+        // minting pool tokens doesn't invoke the transfer hook, but holders
+        // would be stuck with tokens that can't be withdrawn
+        check_transfer_hook_program(&config, pool_mint_info)?;
+        check_depositor_allowlist(
+            program_id,
+            stake_pool_info.key,
+            &stake_pool,
+            &config,
+            dest_user_pool_info,
+            accounts.get(DEPOSIT_STAKE_DEPOSITOR_ACCOUNTS_INDEX),
+        )?;
+        let depositor_cap = check_depositor_proof(
+            &stake_pool,
+            &config,
+            dest_user_pool_info,
+            depositor_proof.as_ref(),
+        )?;
         stake_pool.check_validator_list(validator_list_info)?;
         stake_pool.check_reserve_stake(reserve_stake_account_info)?;
 
@@ -2436,7 +2993,7 @@ impl Processor {
         // don't hold user funds hostage, so if the fee account is invalid, users
         // cannot deposit in the pool.  Let it fail here!
 
-        if stake_pool.last_update_epoch < clock.epoch {
+        if u64::from(stake_pool.last_update_epoch) < clock.epoch {
             return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
         }
 
@@ -2451,7 +3008,7 @@ impl Processor {
         let (_, validator_stake) = get_stake_state(validator_stake_account_info)?;
         let pre_all_validator_lamports = validator_stake_account_info.lamports();
         let vote_account_address = validator_stake.delegation.voter_pubkey;
-        if let Some(preferred_deposit) = stake_pool.preferred_deposit_validator_vote_address {
+        if let Some(preferred_deposit) = stake_pool.preferred_deposit_validator_vote_address() {
             if preferred_deposit != vote_account_address {
                 msg!(
                     "Incorrect deposit address, expected {}, received {}",
@@ -2630,20 +3187,32 @@ impl Processor {
             )?;
         }
 
-        stake_pool.pool_token_supply = stake_pool
-            .pool_token_supply
+        stake_pool.pool_token_supply = u64::from(stake_pool.pool_token_supply)
             .checked_add(new_pool_tokens)
-            .ok_or(StakePoolError::CalculationFailure)?;
+            .ok_or(StakePoolError::CalculationFailure)?
+            .into();
         // We treat the extra lamports as though they were
         // transferred directly to the reserve stake account.
-        stake_pool.total_lamports = stake_pool
-            .total_lamports
+        stake_pool.total_lamports = u64::from(stake_pool.total_lamports)
             .checked_add(total_deposit_lamports)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+            .ok_or(StakePoolError::CalculationFailure)?
+            .into();
+        check_depositor_cap(
+            program_id,
+            stake_pool_info.key,
+            accounts.get(DEPOSIT_STAKE_DEPOSITOR_ACCOUNTS_INDEX.saturating_add(1)),
+            depositor_cap,
+            total_deposit_lamports,
+        )?;
 
         validator_stake_info.active_stake_lamports = validator_stake_account_info.lamports().into();
 
+        set_return_data(&borsh::to_vec(&DepositWithdrawReturnData {
+            amount_out: pool_tokens_user,
+            fee: pool_tokens_manager_deposit_fee,
+            referral_fee: pool_tokens_referral_fee,
+        })?);
+
         Ok(())
     }
 
@@ -2654,6 +3223,7 @@ impl Processor {
         accounts: &[AccountInfo],
         deposit_lamports: u64,
         minimum_pool_tokens_out: Option<u64>,
+        depositor_proof: Option<DepositorProof>,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
@@ -2671,10 +3241,12 @@ impl Processor {
         let clock = Clock::get()?;
 
         check_account_owner(stake_pool_info, program_id)?;
-        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        let mut stake_pool_data = stake_pool_info.data.borrow_mut();
+        let mut stake_pool = StakePoolView::new(&mut stake_pool_data)?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
+        let config = stake_pool.config()?;
 
         stake_pool.check_authority_withdraw(
             withdraw_authority_info.key,
@@ -2683,6 +3255,23 @@ impl Processor {
         )?;
         stake_pool.check_sol_deposit_authority(sol_deposit_authority_info)?;
         stake_pool.check_mint(pool_mint_info)?;
+        // minting pool tokens doesn't invoke the transfer hook, but holders
+        // would be stuck with tokens that can't be withdrawn
+        check_transfer_hook_program(&config, pool_mint_info)?;
+        check_depositor_allowlist(
+            program_id,
+            stake_pool_info.key,
+            &stake_pool,
+            &config,
+            dest_user_pool_info,
+            accounts.get(DEPOSIT_SOL_DEPOSITOR_ACCOUNTS_INDEX),
+        )?;
+        let depositor_cap = check_depositor_proof(
+            &stake_pool,
+            &config,
+            dest_user_pool_info,
+            depositor_proof.as_ref(),
+        )?;
         stake_pool.check_reserve_stake(reserve_stake_account_info)?;
 
         if stake_pool.token_program_id != *token_program_info.key {
@@ -2698,14 +3287,32 @@ impl Processor {
         // cannot deposit in the pool.  Let it fail here!
 
         // We want this to hold to ensure that deposit_sol mints pool tokens
-        // at the right price
-        if stake_pool.last_update_epoch < clock.epoch {
-            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
-        }
+        // at the right price, unless the manager allows deposits at the last
+        // known price, less a haircut for the rewards not yet accounted for
+        let partial_update_haircut = if u64::from(stake_pool.last_update_epoch) < clock.epoch {
+            Some(
+                config
+                    .partial_update_haircut
+                    .ok_or(StakePoolError::StakeListAndPoolOutOfDate)?,
+            )
+        } else {
+            None
+        };
 
-        let new_pool_tokens = stake_pool
+        let mut new_pool_tokens = stake_pool
             .calc_pool_tokens_for_deposit(deposit_lamports)
             .ok_or(StakePoolError::CalculationFailure)?;
+        if let Some(partial_update_haircut) = partial_update_haircut {
+            // the withheld pool tokens are never minted, so their value goes
+            // to the existing pool token holders
+            let pool_tokens_withheld = partial_update_haircut
+                .apply(new_pool_tokens)
+                .and_then(|pool_tokens| u64::try_from(pool_tokens).ok())
+                .ok_or(StakePoolError::CalculationFailure)?;
+            new_pool_tokens = new_pool_tokens
+                .checked_sub(pool_tokens_withheld)
+                .ok_or(StakePoolError::CalculationFailure)?;
+        }
 
         let pool_tokens_sol_deposit_fee = stake_pool
             .calc_pool_tokens_sol_deposit_fee(new_pool_tokens)
@@ -2782,15 +3389,27 @@ impl Processor {
             )?;
         }
 
-        stake_pool.pool_token_supply = stake_pool
-            .pool_token_supply
+        stake_pool.pool_token_supply = u64::from(stake_pool.pool_token_supply)
             .checked_add(new_pool_tokens)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        stake_pool.total_lamports = stake_pool
-            .total_lamports
+            .ok_or(StakePoolError::CalculationFailure)?
+            .into();
+        stake_pool.total_lamports = u64::from(stake_pool.total_lamports)
             .checked_add(deposit_lamports)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+            .ok_or(StakePoolError::CalculationFailure)?
+            .into();
+        check_depositor_cap(
+            program_id,
+            stake_pool_info.key,
+            accounts.get(DEPOSIT_SOL_DEPOSITOR_ACCOUNTS_INDEX.saturating_add(1)),
+            depositor_cap,
+            deposit_lamports,
+        )?;
+
+        set_return_data(&borsh::to_vec(&DepositWithdrawReturnData {
+            amount_out: pool_tokens_user,
+            fee: pool_tokens_manager_deposit_fee,
+            referral_fee: pool_tokens_referral_fee,
+        })?);
 
         Ok(())
     }
@@ -2818,17 +3437,21 @@ impl Processor {
         let clock = &Clock::from_account_info(clock_info)?;
         let token_program_info = next_account_info(account_info_iter)?;
         let stake_program_info = next_account_info(account_info_iter)?;
+        let additional_accounts = account_info_iter.as_slice();
 
         let rent = Rent::get()?;
 
         check_stake_program(stake_program_info.key)?;
         check_account_owner(stake_pool_info, program_id)?;
-        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        let mut stake_pool_data = stake_pool_info.data.borrow_mut();
+        let mut stake_pool = StakePoolView::new(&mut stake_pool_data)?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
+        let config = stake_pool.config()?;
 
         let decimals = stake_pool.check_mint(pool_mint_info)?;
+        let transfer_hook_program_id = check_transfer_hook_program(&config, pool_mint_info)?;
         stake_pool.check_validator_list(validator_list_info)?;
         stake_pool.check_authority_withdraw(
             withdraw_authority_info.key,
@@ -2843,7 +3466,7 @@ impl Processor {
             return Err(ProgramError::IncorrectProgramId);
         }
 
-        if stake_pool.last_update_epoch < clock.epoch {
+        if u64::from(stake_pool.last_update_epoch) < clock.epoch {
             return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
         }
 
@@ -2949,7 +3572,7 @@ impl Processor {
             let vote_account_address = delegation.voter_pubkey;
 
             if let Some(preferred_withdraw_validator) =
-                stake_pool.preferred_withdraw_validator_vote_address
+                stake_pool.preferred_withdraw_validator_vote_address()
             {
                 // Defensive check, in case the preferred validator was somehow
                 // removed.
@@ -3046,16 +3669,7 @@ impl Processor {
                     withdraw_lamports = split_from_lamports;
 
                     // reset the preferred validator if needed
-                    if stake_pool.preferred_deposit_validator_vote_address
-                        == Some(vote_account_address)
-                    {
-                        stake_pool.preferred_deposit_validator_vote_address = None;
-                    }
-                    if stake_pool.preferred_withdraw_validator_vote_address
-                        == Some(vote_account_address)
-                    {
-                        stake_pool.preferred_withdraw_validator_vote_address = None;
-                    }
+                    stake_pool.reset_preferred_validator(&vote_account_address)?;
                 }
             }
             Some((validator_stake_info, withdraw_source))
@@ -3089,27 +3703,14 @@ impl Processor {
             clock_info.clone(),
         )?;
 
-        if pool_tokens_fee > 0 {
-            Self::token_transfer(
-                token_program_info.clone(),
-                burn_from_pool_info.clone(),
-                pool_mint_info.clone(),
-                manager_fee_info.clone(),
-                user_transfer_authority_info.clone(),
-                pool_tokens_fee,
-                decimals,
-            )?;
-        }
-
-        stake_pool.pool_token_supply = stake_pool
-            .pool_token_supply
+        stake_pool.pool_token_supply = u64::from(stake_pool.pool_token_supply)
             .checked_sub(pool_tokens_burnt)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        stake_pool.total_lamports = stake_pool
-            .total_lamports
+            .ok_or(StakePoolError::CalculationFailure)?
+            .into();
+        stake_pool.total_lamports = u64::from(stake_pool.total_lamports)
             .checked_sub(withdraw_lamports)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+            .ok_or(StakePoolError::CalculationFailure)?
+            .into();
 
         if let Some((validator_list_item, withdraw_source)) = validator_list_item_info {
             match withdraw_source {
@@ -3145,6 +3746,30 @@ impl Processor {
             }
         }
 
+        // the transfer hook may need the stake pool or validator list, so
+        // release them once all of their changes are written
+        drop(stake_pool_data);
+        drop(validator_list_data);
+        if pool_tokens_fee > 0 {
+            Self::token_transfer(
+                token_program_info.clone(),
+                burn_from_pool_info.clone(),
+                pool_mint_info.clone(),
+                manager_fee_info.clone(),
+                user_transfer_authority_info.clone(),
+                pool_tokens_fee,
+                decimals,
+                transfer_hook_program_id,
+                additional_accounts,
+            )?;
+        }
+
+        set_return_data(&borsh::to_vec(&DepositWithdrawReturnData {
+            amount_out: withdraw_lamports,
+            fee: pool_tokens_fee,
+            referral_fee: 0,
+        })?);
+
         Ok(())
     }
 
@@ -3169,15 +3794,20 @@ impl Processor {
         let stake_history_info = next_account_info(account_info_iter)?;
         let stake_program_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        // the optional SOL withdraw authority is included, which is harmless
+        // since transfer hook accounts are resolved by address
+        let additional_accounts = account_info_iter.as_slice();
         let sol_withdraw_authority_info = next_account_info(account_info_iter);
 
         let rent = Rent::get()?;
 
         check_account_owner(stake_pool_info, program_id)?;
-        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        let mut stake_pool_data = stake_pool_info.data.borrow_mut();
+        let mut stake_pool = StakePoolView::new(&mut stake_pool_data)?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
+        let config = stake_pool.config()?;
 
         stake_pool.check_authority_withdraw(
             withdraw_authority_info.key,
@@ -3186,6 +3816,7 @@ impl Processor {
         )?;
         stake_pool.check_sol_withdraw_authority(sol_withdraw_authority_info)?;
         let decimals = stake_pool.check_mint(pool_mint_info)?;
+        let transfer_hook_program_id = check_transfer_hook_program(&config, pool_mint_info)?;
         stake_pool.check_reserve_stake(reserve_stake_info)?;
 
         if stake_pool.token_program_id != *token_program_info.key {
@@ -3198,8 +3829,12 @@ impl Processor {
         }
 
         // We want this to hold to ensure that withdraw_sol burns pool tokens
-        // at the right price
-        if stake_pool.last_update_epoch < Clock::get()?.epoch {
+        // at the right price. If the manager allows it, withdrawals go ahead
+        // at the last known price, which can only be lower than the updated
+        // one, since the update adds the epoch's rewards.
+        if u64::from(stake_pool.last_update_epoch) < Clock::get()?.epoch
+            && config.partial_update_haircut.is_none()
+        {
             return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
         }
 
@@ -3255,18 +3890,6 @@ impl Processor {
             pool_tokens_burnt,
         )?;
 
-        if pool_tokens_fee > 0 {
-            Self::token_transfer(
-                token_program_info.clone(),
-                burn_from_pool_info.clone(),
-                pool_mint_info.clone(),
-                manager_fee_info.clone(),
-                user_transfer_authority_info.clone(),
-                pool_tokens_fee,
-                decimals,
-            )?;
-        }
-
         Self::stake_withdraw(
             stake_pool_info.key,
             reserve_stake_info.clone(),
@@ -3279,15 +3902,37 @@ impl Processor {
             withdraw_lamports,
         )?;
 
-        stake_pool.pool_token_supply = stake_pool
-            .pool_token_supply
+        stake_pool.pool_token_supply = u64::from(stake_pool.pool_token_supply)
             .checked_sub(pool_tokens_burnt)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        stake_pool.total_lamports = stake_pool
-            .total_lamports
+            .ok_or(StakePoolError::CalculationFailure)?
+            .into();
+        stake_pool.total_lamports = u64::from(stake_pool.total_lamports)
             .checked_sub(withdraw_lamports)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+            .ok_or(StakePoolError::CalculationFailure)?
+            .into();
+
+        // the transfer hook may need the stake pool, so release it once all
+        // of its changes are written
+        drop(stake_pool_data);
+        if pool_tokens_fee > 0 {
+            Self::token_transfer(
+                token_program_info.clone(),
+                burn_from_pool_info.clone(),
+                pool_mint_info.clone(),
+                manager_fee_info.clone(),
+                user_transfer_authority_info.clone(),
+                pool_tokens_fee,
+                decimals,
+                transfer_hook_program_id,
+                additional_accounts,
+            )?;
+        }
+
+        set_return_data(&borsh::to_vec(&DepositWithdrawReturnData {
+            amount_out: withdraw_lamports,
+            fee: pool_tokens_fee,
+            referral_fee: 0,
+        })?);
 
         Ok(())
     }
@@ -3318,7 +3963,11 @@ impl Processor {
         check_system_program(system_program_info.key)?;
         check_account_owner(payer_info, &system_program::id())?;
         check_account_owner(stake_pool_info, program_id)?;
-        check_mpl_metadata_program(mpl_token_metadata_program_info.key)?;
+        let token_2022_metadata =
+            *mpl_token_metadata_program_info.key == spl_token_2022_interface::id();
+        if !token_2022_metadata {
+            check_mpl_metadata_program(mpl_token_metadata_program_info.key)?;
+        }
 
         let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
@@ -3332,16 +3981,64 @@ impl Processor {
             stake_pool_info.key,
         )?;
         stake_pool.check_mint(pool_mint_info)?;
-        check_mpl_metadata_account_address(metadata_info.key, &stake_pool.pool_mint)?;
 
-        // Token mint authority for stake-pool token is stake-pool withdraw authority
-        let token_mint_authority = withdraw_authority_info;
+        let (_, stake_withdraw_bump_seed) =
+            crate::find_withdraw_authority_program_address(program_id, stake_pool_info.key);
 
-        let new_metadata_instruction = create_metadata_accounts_v3(
-            *mpl_token_metadata_program_info.key,
-            *metadata_info.key,
-            *pool_mint_info.key,
-            *token_mint_authority.key,
+        let token_mint_authority_signer_seeds: &[&[_]] = &[
+            stake_pool_info.key.as_ref(),
+            AUTHORITY_WITHDRAW,
+            &[stake_withdraw_bump_seed],
+        ];
+
+        if token_2022_metadata {
+            // Token-2022 mints hold their own metadata, so the pool mint must
+            // be the metadata account
+            check_token_2022_metadata_account(metadata_info, &stake_pool)?;
+            let token_metadata = TokenMetadata {
+                update_authority: (*withdraw_authority_info.key).into(),
+                mint: *pool_mint_info.key,
+                name,
+                symbol,
+                uri,
+                additional_metadata: vec![],
+            };
+            Self::fund_token_2022_metadata(
+                payer_info,
+                pool_mint_info,
+                token_metadata.tlv_size_of()?,
+            )?;
+
+            // The withdraw authority is both the mint authority and the update
+            // authority
+            let initialize_instruction = token_metadata_instruction::initialize(
+                mpl_token_metadata_program_info.key,
+                pool_mint_info.key,
+                withdraw_authority_info.key,
+                pool_mint_info.key,
+                withdraw_authority_info.key,
+                token_metadata.name,
+                token_metadata.symbol,
+                token_metadata.uri,
+            );
+            invoke_signed(
+                &initialize_instruction,
+                &[pool_mint_info.clone(), withdraw_authority_info.clone()],
+                &[token_mint_authority_signer_seeds],
+            )?;
+            return Ok(());
+        }
+
+        check_mpl_metadata_account_address(metadata_info.key, &stake_pool.pool_mint)?;
+
+        // Token mint authority for stake-pool token is stake-pool withdraw authority
+        let token_mint_authority = withdraw_authority_info;
+
+        let new_metadata_instruction = create_metadata_accounts_v3(
+            *mpl_token_metadata_program_info.key,
+            *metadata_info.key,
+            *pool_mint_info.key,
+            *token_mint_authority.key,
             *payer_info.key,
             *token_mint_authority.key,
             name,
@@ -3349,15 +4046,6 @@ impl Processor {
             uri,
         );
 
-        let (_, stake_withdraw_bump_seed) =
-            crate::find_withdraw_authority_program_address(program_id, stake_pool_info.key);
-
-        let token_mint_authority_signer_seeds: &[&[_]] = &[
-            stake_pool_info.key.as_ref(),
-            AUTHORITY_WITHDRAW,
-            &[stake_withdraw_bump_seed],
-        ];
-
         invoke_signed(
             &new_metadata_instruction,
             &[
@@ -3392,7 +4080,11 @@ impl Processor {
 
         check_account_owner(stake_pool_info, program_id)?;
 
-        check_mpl_metadata_program(mpl_token_metadata_program_info.key)?;
+        let token_2022_metadata =
+            *mpl_token_metadata_program_info.key == spl_token_2022_interface::id();
+        if !token_2022_metadata {
+            check_mpl_metadata_program(mpl_token_metadata_program_info.key)?;
+        }
 
         let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
@@ -3405,6 +4097,75 @@ impl Processor {
             program_id,
             stake_pool_info.key,
         )?;
+
+        let (_, stake_withdraw_bump_seed) =
+            crate::find_withdraw_authority_program_address(program_id, stake_pool_info.key);
+
+        let token_mint_authority_signer_seeds: &[&[_]] = &[
+            stake_pool_info.key.as_ref(),
+            AUTHORITY_WITHDRAW,
+            &[stake_withdraw_bump_seed],
+        ];
+
+        if token_2022_metadata {
+            check_token_2022_metadata_account(metadata_info, &stake_pool)?;
+            let mut token_metadata = {
+                let mint_data = metadata_info.data.borrow();
+                let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+                mint.get_variable_len_extension::<TokenMetadata>()?
+            };
+            let previous_len = token_metadata.tlv_size_of()?;
+            let updates = [
+                (Field::Name, name),
+                (Field::Symbol, symbol),
+                (Field::Uri, uri),
+            ]
+            .into_iter()
+            .filter(|(field, value)| match field {
+                Field::Name => token_metadata.name != *value,
+                Field::Symbol => token_metadata.symbol != *value,
+                _ => token_metadata.uri != *value,
+            })
+            .collect::<Vec<_>>();
+            for (field, value) in &updates {
+                token_metadata.update(field.clone(), value.clone());
+            }
+
+            // Token-2022 reallocates the mint for longer fields, which must
+            // then be funded by the optional payer
+            let new_len = token_metadata.tlv_size_of()?;
+            if new_len > previous_len {
+                let payer_info = next_account_info(account_info_iter)?;
+                let system_program_info = next_account_info(account_info_iter)?;
+                if !payer_info.is_signer {
+                    msg!("Payer did not sign metadata update");
+                    return Err(StakePoolError::SignatureMissing.into());
+                }
+                check_system_program(system_program_info.key)?;
+                Self::fund_token_2022_metadata(
+                    payer_info,
+                    metadata_info,
+                    new_len.saturating_sub(previous_len),
+                )?;
+            }
+
+            for (field, value) in updates {
+                let update_field_instruction = token_metadata_instruction::update_field(
+                    mpl_token_metadata_program_info.key,
+                    metadata_info.key,
+                    withdraw_authority_info.key,
+                    field,
+                    value,
+                );
+                invoke_signed(
+                    &update_field_instruction,
+                    &[metadata_info.clone(), withdraw_authority_info.clone()],
+                    &[token_mint_authority_signer_seeds],
+                )?;
+            }
+            return Ok(());
+        }
+
         check_mpl_metadata_account_address(metadata_info.key, &stake_pool.pool_mint)?;
 
         // Token mint authority for stake-pool token is withdraw authority only
@@ -3428,15 +4189,6 @@ impl Processor {
             Some(true),
         );
 
-        let (_, stake_withdraw_bump_seed) =
-            crate::find_withdraw_authority_program_address(program_id, stake_pool_info.key);
-
-        let token_mint_authority_signer_seeds: &[&[_]] = &[
-            stake_pool_info.key.as_ref(),
-            AUTHORITY_WITHDRAW,
-            &[stake_withdraw_bump_seed],
-        ];
-
         invoke_signed(
             &update_metadata_accounts_instruction,
             &[metadata_info.clone(), withdraw_authority_info.clone()],
@@ -3563,9 +4315,621 @@ impl Processor {
         Ok(())
     }
 
+    /// Processes [`ResizeValidatorList`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_resize_validator_list(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_validators: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let rent = Rent::get()?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        stake_pool.check_validator_list(validator_list_info)?;
+        check_account_owner(validator_list_info, program_id)?;
+        check_system_program(system_program_info.key)?;
+
+        if max_validators > MAX_VALIDATORS_IN_POOL {
+            return Err(StakePoolError::TooManyValidatorsInPool.into());
+        }
+
+        let mut header = {
+            let mut validator_list_data = validator_list_info.data.borrow_mut();
+            let (header, _) = ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+            header
+        };
+        if !header.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        if max_validators < header.max_validators {
+            msg!(
+                "Validator list can only grow, current maximum {}, requested {}",
+                header.max_validators,
+                max_validators
+            );
+            return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
+        }
+
+        let current_len = validator_list_info.data_len();
+        let new_len = ValidatorList::calculate_len(max_validators)
+            .min(current_len.saturating_add(MAX_PERMITTED_DATA_INCREASE))
+            .max(current_len);
+
+        let required_lamports = rent.minimum_balance(new_len);
+        let additional_lamports = required_lamports.saturating_sub(validator_list_info.lamports());
+        if additional_lamports > 0 {
+            Self::sol_transfer(
+                payer_info.clone(),
+                validator_list_info.clone(),
+                additional_lamports,
+            )?;
+        }
+        validator_list_info.resize(new_len)?;
+
+        header.max_validators = ValidatorList::calculate_max_validators(new_len) as u32;
+        borsh::to_writer(&mut validator_list_info.data.borrow_mut()[..], &header)?;
+
+        Ok(())
+    }
+
+    /// Processes [`ShrinkValidatorList`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_shrink_validator_list(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_validators: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+
+        let rent = Rent::get()?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        stake_pool.check_validator_list(validator_list_info)?;
+        check_account_owner(validator_list_info, program_id)?;
+        if destination_info.key == validator_list_info.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut header = {
+            let mut validator_list_data = validator_list_info.data.borrow_mut();
+            let (header, mut validator_list) =
+                ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+            if !header.is_valid() {
+                return Err(StakePoolError::InvalidState.into());
+            }
+
+            let kept_update_epoch =
+                untrack_removed_validators(stake_pool_info, &stake_pool, &validator_list)?;
+            validator_list.retain::<ValidatorStakeInfo, _>(|x| {
+                !can_clean_up_validator(x, kept_update_epoch)
+            })?;
+
+            if max_validators < validator_list.len()
+                || max_validators > header.max_validators
+                || max_validators == 0
+            {
+                msg!(
+                    "Incorrect validator list size provided, list contains {} validators with a maximum of {}, provided {}",
+                    validator_list.len(),
+                    header.max_validators,
+                    max_validators
+                );
+                return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
+            }
+
+            reset_inactive_preferred_validators(&mut stake_pool, &validator_list);
+            header
+        };
+        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+
+        let new_len =
+            ValidatorList::calculate_len(max_validators).min(validator_list_info.data_len());
+        validator_list_info.resize(new_len)?;
+
+        header.max_validators = ValidatorList::calculate_max_validators(new_len) as u32;
+        borsh::to_writer(&mut validator_list_info.data.borrow_mut()[..], &header)?;
+
+        let excess_lamports = validator_list_info
+            .lamports()
+            .saturating_sub(rent.minimum_balance(new_len));
+        if excess_lamports > 0 {
+            let validator_list_lamports = validator_list_info
+                .lamports()
+                .checked_sub(excess_lamports)
+                .ok_or(StakePoolError::CalculationFailure)?;
+            let destination_lamports = destination_info
+                .lamports()
+                .checked_add(excess_lamports)
+                .ok_or(StakePoolError::CalculationFailure)?;
+            **validator_list_info.try_borrow_mut_lamports()? = validator_list_lamports;
+            **destination_info.try_borrow_mut_lamports()? = destination_lamports;
+        }
+
+        Ok(())
+    }
+
+    /// Processes [`ClosePool`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_close_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let withdraw_authority_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let new_mint_authority_info = next_account_info(account_info_iter).ok();
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        stake_pool.check_authority_withdraw(
+            withdraw_authority_info.key,
+            program_id,
+            stake_pool_info.key,
+        )?;
+        stake_pool.check_validator_list(validator_list_info)?;
+        check_account_owner(validator_list_info, program_id)?;
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
+        stake_pool.check_mint(pool_mint_info)?;
+        check_stake_program(stake_program_info.key)?;
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if destination_info.key == stake_pool_info.key
+            || destination_info.key == validator_list_info.key
+            || destination_info.key == reserve_stake_info.key
+        {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if stake_pool.pool_token_supply != 0 {
+            msg!(
+                "Pool still has {} pool tokens in circulation",
+                stake_pool.pool_token_supply
+            );
+            return Err(StakePoolError::NonZeroPoolTokenSupply.into());
+        }
+
+        {
+            let mut validator_list_data = validator_list_info.data.borrow_mut();
+            let (header, validator_list) =
+                ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+            if !header.is_valid() {
+                return Err(StakePoolError::InvalidState.into());
+            }
+            if validator_list
+                .find::<ValidatorStakeInfo, _>(|x| !ValidatorStakeInfo::is_removed(x))
+                .is_some()
+            {
+                return Err(StakePoolError::ValidatorListNotEmpty.into());
+            }
+        }
+
+        Self::stake_withdraw(
+            stake_pool_info.key,
+            reserve_stake_info.clone(),
+            withdraw_authority_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.stake_withdraw_bump_seed,
+            destination_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            reserve_stake_info.lamports(),
+        )?;
+
+        let authority_signature_seeds = [
+            stake_pool_info.key.as_ref(),
+            AUTHORITY_WITHDRAW,
+            &[stake_pool.stake_withdraw_bump_seed],
+        ];
+        let set_authority_instruction = spl_token_2022_interface::instruction::set_authority(
+            token_program_info.key,
+            pool_mint_info.key,
+            new_mint_authority_info.map(|info| info.key),
+            spl_token_2022_interface::instruction::AuthorityType::MintTokens,
+            withdraw_authority_info.key,
+            &[],
+        )?;
+        invoke_signed(
+            &set_authority_instruction,
+            &[pool_mint_info.clone(), withdraw_authority_info.clone()],
+            &[&authority_signature_seeds],
+        )?;
+
+        close_program_account(validator_list_info, destination_info)?;
+        close_program_account(stake_pool_info, destination_info)?;
+
+        Ok(())
+    }
+
+    /// Processes [`MigratePool`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_migrate_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let rent = Rent::get()?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        check_system_program(system_program_info.key)?;
+
+        let current_len = stake_pool_info.data_len();
+        let new_len = StakePool::account_len().max(current_len);
+        if stake_pool.account_type == AccountType::StakePoolV2 && new_len == current_len {
+            msg!("Stake pool already uses the current layout");
+            return Ok(());
+        }
+
+        // read the existing configuration before the account type changes
+        let config = StakePoolConfig::load(&stake_pool, &stake_pool_info.data.borrow())?;
+
+        let required_lamports = rent.minimum_balance(new_len);
+        let additional_lamports = required_lamports.saturating_sub(stake_pool_info.lamports());
+        if additional_lamports > 0 {
+            Self::sol_transfer(
+                payer_info.clone(),
+                stake_pool_info.clone(),
+                additional_lamports,
+            )?;
+        }
+        stake_pool_info.resize(new_len)?;
+
+        stake_pool.account_type = AccountType::StakePoolV2;
+        let mut stake_pool_data = stake_pool_info.data.borrow_mut();
+        borsh::to_writer(&mut stake_pool_data[..], &stake_pool)?;
+        config.store(&mut stake_pool_data)?;
+
+        Ok(())
+    }
+
+    /// Processes [`SetPartialUpdateHaircut`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_partial_update_haircut(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        haircut: Option<Fee>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        if stake_pool.account_type != AccountType::StakePoolV2 {
+            return Err(StakePoolError::StakePoolNotMigrated.into());
+        }
+
+        if let Some(haircut) = haircut {
+            if haircut.numerator > haircut.denominator {
+                msg!("Haircut greater than 100%: {:?}", haircut);
+                return Err(StakePoolError::FeeTooHigh.into());
+            }
+        }
+
+        let mut config = StakePoolConfig::load(&stake_pool, &stake_pool_info.data.borrow())?;
+        config.partial_update_haircut = haircut;
+        config.store(&mut stake_pool_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes [`SetTransferHookProgramIds`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_transfer_hook_program_ids(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        program_ids: Vec<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        if stake_pool.account_type != AccountType::StakePoolV2 {
+            return Err(StakePoolError::StakePoolNotMigrated.into());
+        }
+
+        if program_ids.len() > MAX_TRANSFER_HOOK_PROGRAMS {
+            msg!(
+                "Provided {} transfer hook programs, maximum is {}",
+                program_ids.len(),
+                MAX_TRANSFER_HOOK_PROGRAMS
+            );
+            return Err(StakePoolError::TooManyTransferHookPrograms.into());
+        }
+
+        let mut config = StakePoolConfig::load(&stake_pool, &stake_pool_info.data.borrow())?;
+        config.transfer_hook_program_ids = [None; MAX_TRANSFER_HOOK_PROGRAMS];
+        for (allowed, program_id) in config.transfer_hook_program_ids.iter_mut().zip(program_ids) {
+            *allowed = Some(program_id);
+        }
+        config.store(&mut stake_pool_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes [`SetDepositorAllowlist`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_depositor_allowlist(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        enabled: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        if stake_pool.account_type != AccountType::StakePoolV2 {
+            return Err(StakePoolError::StakePoolNotMigrated.into());
+        }
+
+        let mut config = StakePoolConfig::load(&stake_pool, &stake_pool_info.data.borrow())?;
+        config.depositor_allowlist_enabled = enabled;
+        config.store(&mut stake_pool_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes [`AddDepositor`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_add_depositor(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let entry_info = next_account_info(account_info_iter)?;
+        let depositor_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        check_system_program(system_program_info.key)?;
+
+        let (entry_address, bump_seed) = find_depositor_allowlist_entry_address(
+            program_id,
+            stake_pool_info.key,
+            depositor_info.key,
+        );
+        if entry_address != *entry_info.key {
+            msg!(
+                "Incorrect depositor allowlist entry, expected {}, received {}",
+                entry_address,
+                entry_info.key
+            );
+            return Err(StakePoolError::InvalidProgramAddress.into());
+        }
+        if !entry_info.data_is_empty() {
+            return Err(StakePoolError::AlreadyInUse.into());
+        }
+
+        let space = get_packed_len::<DepositorAllowlistEntry>();
+        let required_lamports = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(entry_info.lamports());
+        if required_lamports > 0 {
+            Self::sol_transfer(payer_info.clone(), entry_info.clone(), required_lamports)?;
+        }
+        let entry_signer_seeds: &[&[_]] = &[
+            DEPOSITOR_ALLOWLIST_SEED_PREFIX,
+            stake_pool_info.key.as_ref(),
+            depositor_info.key.as_ref(),
+            &[bump_seed],
+        ];
+        invoke_signed(
+            &system_instruction::allocate(entry_info.key, space as u64),
+            core::slice::from_ref(entry_info),
+            &[entry_signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(entry_info.key, program_id),
+            core::slice::from_ref(entry_info),
+            &[entry_signer_seeds],
+        )?;
+
+        let entry = DepositorAllowlistEntry {
+            account_type: AccountType::DepositorAllowlistEntry,
+            stake_pool: *stake_pool_info.key,
+            depositor: *depositor_info.key,
+        };
+        borsh::to_writer(&mut entry_info.data.borrow_mut()[..], &entry)?;
+        Ok(())
+    }
+
+    /// Processes [`RemoveDepositor`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_remove_depositor(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let entry_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+
+        check_account_owner(entry_info, program_id)?;
+        let entry = try_from_slice_unchecked::<DepositorAllowlistEntry>(&entry_info.data.borrow())?;
+        if !entry.allows(stake_pool_info.key, &entry.depositor) {
+            msg!(
+                "Account {} is not a depositor allowlist entry of the stake pool",
+                entry_info.key
+            );
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        close_program_account(entry_info, destination_info)
+    }
+
+    /// Processes [`SetDepositorMerkleRoot`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_depositor_merkle_root(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        root: Option<[u8; 32]>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        if stake_pool.account_type != AccountType::StakePoolV2 {
+            return Err(StakePoolError::StakePoolNotMigrated.into());
+        }
+
+        let mut config = StakePoolConfig::load(&stake_pool, &stake_pool_info.data.borrow())?;
+        config.depositor_merkle_root = root;
+        config.store(&mut stake_pool_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes [`CreateDepositorRecord`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_create_depositor_record(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let record_info = next_account_info(account_info_iter)?;
+        let depositor_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        check_system_program(system_program_info.key)?;
+
+        let (record_address, bump_seed) =
+            find_depositor_record_address(program_id, stake_pool_info.key, depositor_info.key);
+        if record_address != *record_info.key {
+            msg!(
+                "Incorrect depositor record, expected {}, received {}",
+                record_address,
+                record_info.key
+            );
+            return Err(StakePoolError::InvalidProgramAddress.into());
+        }
+        if !record_info.data_is_empty() {
+            return Err(StakePoolError::AlreadyInUse.into());
+        }
+
+        let space = get_packed_len::<DepositorRecord>();
+        let required_lamports = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(record_info.lamports());
+        if required_lamports > 0 {
+            Self::sol_transfer(payer_info.clone(), record_info.clone(), required_lamports)?;
+        }
+        let record_signer_seeds: &[&[_]] = &[
+            DEPOSITOR_RECORD_SEED_PREFIX,
+            stake_pool_info.key.as_ref(),
+            depositor_info.key.as_ref(),
+            &[bump_seed],
+        ];
+        invoke_signed(
+            &system_instruction::allocate(record_info.key, space as u64),
+            core::slice::from_ref(record_info),
+            &[record_signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(record_info.key, program_id),
+            core::slice::from_ref(record_info),
+            &[record_signer_seeds],
+        )?;
+
+        let record = DepositorRecord {
+            account_type: AccountType::DepositorRecord,
+            stake_pool: *stake_pool_info.key,
+            depositor: *depositor_info.key,
+            deposited: 0,
+        };
+        borsh::to_writer(&mut record_info.data.borrow_mut()[..], &record)?;
+        Ok(())
+    }
+
     /// Processes [`Instruction`](enum.Instruction.html).
     pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
-        let instruction = StakePoolInstruction::try_from_slice(input)?;
+        let mut data = input;
+        let instruction = StakePoolInstruction::deserialize(&mut data)?;
+        // deposits may be followed by a depositor proof, nothing else may have
+        // trailing data
+        let depositor_proof = if data.is_empty() {
+            None
+        } else if matches!(
+            instruction,
+            StakePoolInstruction::DepositStake
+                | StakePoolInstruction::DepositSol(_)
+                | StakePoolInstruction::DepositStakeWithSlippage { .. }
+                | StakePoolInstruction::DepositSolWithSlippage { .. }
+        ) {
+            Some(DepositorProof::try_from_slice(data)?)
+        } else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
         match instruction {
             StakePoolInstruction::Initialize {
                 fee,
@@ -3698,7 +5062,7 @@ impl Processor {
             }
             StakePoolInstruction::DepositStake => {
                 msg!("Instruction: DepositStake");
-                Self::process_deposit_stake(program_id, accounts, None)
+                Self::process_deposit_stake(program_id, accounts, None, depositor_proof)
             }
             StakePoolInstruction::WithdrawStake(amount) => {
                 msg!("Instruction: WithdrawStake");
@@ -3722,7 +5086,7 @@ impl Processor {
             }
             StakePoolInstruction::DepositSol(lamports) => {
                 msg!("Instruction: DepositSol");
-                Self::process_deposit_sol(program_id, accounts, lamports, None)
+                Self::process_deposit_sol(program_id, accounts, lamports, None, depositor_proof)
             }
             StakePoolInstruction::WithdrawSol(pool_tokens) => {
                 msg!("Instruction: WithdrawSol");
@@ -3745,7 +5109,12 @@ impl Processor {
                 minimum_pool_tokens_out,
             } => {
                 msg!("Instruction: DepositStakeWithSlippage");
-                Self::process_deposit_stake(program_id, accounts, Some(minimum_pool_tokens_out))
+                Self::process_deposit_stake(
+                    program_id,
+                    accounts,
+                    Some(minimum_pool_tokens_out),
+                    depositor_proof,
+                )
             }
             StakePoolInstruction::WithdrawStakeWithSlippage {
                 pool_tokens_in,
@@ -3769,6 +5138,7 @@ impl Processor {
                     accounts,
                     lamports_in,
                     Some(minimum_pool_tokens_out),
+                    depositor_proof,
                 )
             }
             StakePoolInstruction::WithdrawSolWithSlippage {
@@ -3783,6 +5153,62 @@ impl Processor {
                     Some(minimum_lamports_out),
                 )
             }
+            StakePoolInstruction::ResizeValidatorList { max_validators } => {
+                msg!("Instruction: ResizeValidatorList");
+                Self::process_resize_validator_list(program_id, accounts, max_validators)
+            }
+            StakePoolInstruction::ShrinkValidatorList { max_validators } => {
+                msg!("Instruction: ShrinkValidatorList");
+                Self::process_shrink_validator_list(program_id, accounts, max_validators)
+            }
+            StakePoolInstruction::ClosePool => {
+                msg!("Instruction: ClosePool");
+                Self::process_close_pool(program_id, accounts)
+            }
+            StakePoolInstruction::MigratePool => {
+                msg!("Instruction: MigratePool");
+                Self::process_migrate_pool(program_id, accounts)
+            }
+            StakePoolInstruction::UpdateValidatorBalance {
+                validator_index,
+                no_merge,
+            } => {
+                msg!("Instruction: UpdateValidatorBalance");
+                Self::process_update_validator_balance(
+                    program_id,
+                    accounts,
+                    validator_index,
+                    no_merge,
+                )
+            }
+            StakePoolInstruction::SetPartialUpdateHaircut { haircut } => {
+                msg!("Instruction: SetPartialUpdateHaircut");
+                Self::process_set_partial_update_haircut(program_id, accounts, haircut)
+            }
+            StakePoolInstruction::SetTransferHookProgramIds { program_ids } => {
+                msg!("Instruction: SetTransferHookProgramIds");
+                Self::process_set_transfer_hook_program_ids(program_id, accounts, program_ids)
+            }
+            StakePoolInstruction::SetDepositorAllowlist { enabled } => {
+                msg!("Instruction: SetDepositorAllowlist");
+                Self::process_set_depositor_allowlist(program_id, accounts, enabled)
+            }
+            StakePoolInstruction::AddDepositor => {
+                msg!("Instruction: AddDepositor");
+                Self::process_add_depositor(program_id, accounts)
+            }
+            StakePoolInstruction::RemoveDepositor => {
+                msg!("Instruction: RemoveDepositor");
+                Self::process_remove_depositor(program_id, accounts)
+            }
+            StakePoolInstruction::SetDepositorMerkleRoot { root } => {
+                msg!("Instruction: SetDepositorMerkleRoot");
+                Self::process_set_depositor_merkle_root(program_id, accounts, root)
+            }
+            StakePoolInstruction::CreateDepositorRecord => {
+                msg!("Instruction: CreateDepositorRecord");
+                Self::process_create_depositor_record(program_id, accounts)
+            }
         }
     }
 }
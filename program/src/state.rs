@@ -2,15 +2,16 @@
 
 use {
     crate::{
-        big_vec::BigVec, error::StakePoolError, MAX_WITHDRAWAL_FEE_INCREASE,
-        MAX_WITHDRAWAL_FEE_INCREASE_FACTOR, WITHDRAWAL_BASELINE_FEE,
+        big_vec::BigVec, error::StakePoolError, MAX_TRANSFER_HOOK_PROGRAMS,
+        MAX_WITHDRAWAL_FEE_INCREASE, MAX_WITHDRAWAL_FEE_INCREASE_FACTOR, WITHDRAWAL_BASELINE_FEE,
     },
+    arrayref::array_ref,
     borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
     bytemuck::{Pod, Zeroable},
     num_derive::{FromPrimitive, ToPrimitive},
     num_traits::{FromPrimitive, ToPrimitive},
     solana_account_info::AccountInfo,
-    solana_borsh::v1::get_instance_packed_len,
+    solana_borsh::v1::{get_instance_packed_len, get_packed_len, try_from_slice_unchecked},
     solana_msg::msg,
     solana_program_error::ProgramError,
     solana_program_memory::sol_memcmp,
@@ -20,9 +21,15 @@ use {
     spl_pod::primitives::{PodU32, PodU64},
     spl_token_2022_interface::{
         extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+        native_mint,
         state::{Account, AccountState, Mint},
     },
-    std::{borrow::Borrow, convert::TryFrom, fmt, matches},
+    std::{
+        borrow::Borrow,
+        convert::TryFrom,
+        fmt, matches, mem,
+        ops::{Deref, DerefMut},
+    },
 };
 
 /// Enum representing the account type managed by the program
@@ -35,13 +42,20 @@ pub enum AccountType {
     StakePool,
     /// Validator stake list
     ValidatorList,
+    /// Stake pool followed by a `StakePoolConfig`
+    StakePoolV2,
+    /// Depositor allowlist entry
+    DepositorAllowlistEntry,
+    /// Record of a depositor's deposits, to enforce their cap
+    DepositorRecord,
 }
 
 /// Initialized program details.
 #[repr(C)]
 #[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
 pub struct StakePool {
-    /// Account type, must be `StakePool` currently
+    /// Account type, either `StakePool`, or `StakePoolV2` if the account also
+    /// contains a `StakePoolConfig`
     pub account_type: AccountType,
 
     /// Manager authority, allows for updating the staker, manager, and fee
@@ -160,30 +174,13 @@ impl StakePool {
     /// `stake_lamports`
     #[inline]
     pub fn calc_pool_tokens_for_deposit(&self, stake_lamports: u64) -> Option<u64> {
-        if self.total_lamports == 0 || self.pool_token_supply == 0 {
-            return Some(stake_lamports);
-        }
-        u64::try_from(
-            (stake_lamports as u128)
-                .checked_mul(self.pool_token_supply as u128)?
-                .checked_div(self.total_lamports as u128)?,
-        )
-        .ok()
+        calc_pool_tokens_for_deposit(self.total_lamports, self.pool_token_supply, stake_lamports)
     }
 
     /// calculate lamports amount on withdrawal
     #[inline]
     pub fn calc_lamports_withdraw_amount(&self, pool_tokens: u64) -> Option<u64> {
-        // `checked_div` returns `None` for a 0 quotient result, but in this
-        // case, a return of 0 is valid for small amounts of pool tokens. So
-        // we check for that separately
-        let numerator = (pool_tokens as u128).checked_mul(self.total_lamports as u128)?;
-        let denominator = self.pool_token_supply as u128;
-        if numerator < denominator || denominator == 0 {
-            Some(0)
-        } else {
-            u64::try_from(numerator.checked_div(denominator)?).ok()
-        }
+        calc_lamports_withdraw_amount(self.total_lamports, self.pool_token_supply, pool_tokens)
     }
 
     /// calculate pool tokens to be deducted as withdrawal fees
@@ -207,12 +204,7 @@ impl StakePool {
     /// calculate pool tokens to be deducted from deposit fees as referral fees
     #[inline]
     pub fn calc_pool_tokens_stake_referral_fee(&self, stake_deposit_fee: u64) -> Option<u64> {
-        u64::try_from(
-            (stake_deposit_fee as u128)
-                .checked_mul(self.stake_referral_fee as u128)?
-                .checked_div(100u128)?,
-        )
-        .ok()
+        calc_referral_fee(stake_deposit_fee, self.stake_referral_fee)
     }
 
     /// calculate pool tokens to be deducted as SOL deposit fees
@@ -225,12 +217,7 @@ impl StakePool {
     /// fees
     #[inline]
     pub fn calc_pool_tokens_sol_referral_fee(&self, sol_deposit_fee: u64) -> Option<u64> {
-        u64::try_from(
-            (sol_deposit_fee as u128)
-                .checked_mul(self.sol_referral_fee as u128)?
-                .checked_div(100u128)?,
-        )
-        .ok()
+        calc_referral_fee(sol_deposit_fee, self.sol_referral_fee)
     }
 
     /// Calculate the fee in pool tokens that goes to the manager
@@ -256,6 +243,22 @@ impl StakePool {
         }
     }
 
+    /// Calculate the multiplier for the pool mint's `ScaledUiAmount`
+    /// extension, so that the UI amount of pool tokens is their value in SOL,
+    /// or `None` if the pool holds no lamports or has no pool tokens
+    pub fn calc_scaled_ui_multiplier(&self, pool_mint_decimals: u8) -> Option<f64> {
+        if self.total_lamports == 0 || self.pool_token_supply == 0 {
+            return None;
+        }
+        // UI amounts are scaled by the mint's decimals, so adjust for any
+        // difference with SOL's decimals
+        let decimals_difference =
+            i32::from(pool_mint_decimals).saturating_sub(i32::from(native_mint::DECIMALS));
+        let multiplier = self.total_lamports as f64 / self.pool_token_supply as f64
+            * 10f64.powi(decimals_difference);
+        multiplier.is_normal().then_some(multiplier)
+    }
+
     /// Get the current value of pool tokens, rounded up
     #[inline]
     pub fn get_lamports_per_pool_token(&self) -> Option<u64> {
@@ -296,23 +299,7 @@ impl StakePool {
         &self,
         manager_fee_info: &AccountInfo,
     ) -> Result<(), ProgramError> {
-        let account_data = manager_fee_info.try_borrow_data()?;
-        let token_account = StateWithExtensions::<Account>::unpack(&account_data)?;
-        if manager_fee_info.owner != &self.token_program_id
-            || token_account.base.state != AccountState::Initialized
-            || token_account.base.mint != self.pool_mint
-        {
-            msg!("Manager fee account is not owned by token program, is not initialized, or does not match stake pool's mint");
-            return Err(StakePoolError::InvalidFeeAccount.into());
-        }
-        let extensions = token_account.get_extension_types()?;
-        if extensions
-            .iter()
-            .any(|x| !is_extension_supported_for_fee_account(x))
-        {
-            return Err(StakePoolError::UnsupportedFeeAccountExtension.into());
-        }
-        Ok(())
+        check_manager_fee_info(manager_fee_info, &self.token_program_id, &self.pool_mint)
     }
 
     /// Checks that the withdraw authority is valid
@@ -331,70 +318,11 @@ impl StakePool {
             self.stake_withdraw_bump_seed,
         )
     }
-    /// Checks that the deposit authority is valid
-    #[inline]
-    pub(crate) fn check_stake_deposit_authority(
-        &self,
-        stake_deposit_authority: &Pubkey,
-    ) -> Result<(), ProgramError> {
-        if self.stake_deposit_authority == *stake_deposit_authority {
-            Ok(())
-        } else {
-            Err(StakePoolError::InvalidStakeDepositAuthority.into())
-        }
-    }
-
-    /// Checks that the deposit authority is valid
-    /// Does nothing if `sol_deposit_authority` is currently not set
-    #[inline]
-    pub(crate) fn check_sol_deposit_authority(
-        &self,
-        maybe_sol_deposit_authority: Result<&AccountInfo, ProgramError>,
-    ) -> Result<(), ProgramError> {
-        if let Some(auth) = self.sol_deposit_authority {
-            let sol_deposit_authority = maybe_sol_deposit_authority?;
-            if auth != *sol_deposit_authority.key {
-                msg!("Expected {}, received {}", auth, sol_deposit_authority.key);
-                return Err(StakePoolError::InvalidSolDepositAuthority.into());
-            }
-            if !sol_deposit_authority.is_signer {
-                msg!("SOL Deposit authority signature missing");
-                return Err(StakePoolError::SignatureMissing.into());
-            }
-        }
-        Ok(())
-    }
-
-    /// Checks that the sol withdraw authority is valid
-    /// Does nothing if `sol_withdraw_authority` is currently not set
-    #[inline]
-    pub(crate) fn check_sol_withdraw_authority(
-        &self,
-        maybe_sol_withdraw_authority: Result<&AccountInfo, ProgramError>,
-    ) -> Result<(), ProgramError> {
-        if let Some(auth) = self.sol_withdraw_authority {
-            let sol_withdraw_authority = maybe_sol_withdraw_authority?;
-            if auth != *sol_withdraw_authority.key {
-                return Err(StakePoolError::InvalidSolWithdrawAuthority.into());
-            }
-            if !sol_withdraw_authority.is_signer {
-                msg!("SOL withdraw authority signature missing");
-                return Err(StakePoolError::SignatureMissing.into());
-            }
-        }
-        Ok(())
-    }
 
     /// Check mint is correct
     #[inline]
     pub(crate) fn check_mint(&self, mint_info: &AccountInfo) -> Result<u8, ProgramError> {
-        if *mint_info.key != self.pool_mint {
-            Err(StakePoolError::WrongPoolMint.into())
-        } else {
-            let mint_data = mint_info.try_borrow_data()?;
-            let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
-            Ok(mint.base.decimals)
-        }
+        check_mint(&self.pool_mint, mint_info)
     }
 
     /// Check manager validity and signature
@@ -436,16 +364,7 @@ impl StakePool {
         &self,
         validator_list_info: &AccountInfo,
     ) -> Result<(), ProgramError> {
-        if *validator_list_info.key != self.validator_list {
-            msg!(
-                "Invalid validator list provided, expected {}, received {}",
-                self.validator_list,
-                validator_list_info.key
-            );
-            Err(StakePoolError::InvalidValidatorStakeList.into())
-        } else {
-            Ok(())
-        }
+        check_validator_list(&self.validator_list, validator_list_info)
     }
 
     /// Check the reserve stake is valid
@@ -453,21 +372,21 @@ impl StakePool {
         &self,
         reserve_stake_info: &AccountInfo,
     ) -> Result<(), ProgramError> {
-        if *reserve_stake_info.key != self.reserve_stake {
-            msg!(
-                "Invalid reserve stake provided, expected {}, received {}",
-                self.reserve_stake,
-                reserve_stake_info.key
-            );
-            Err(StakePoolError::InvalidProgramAddress.into())
-        } else {
-            Ok(())
-        }
+        check_reserve_stake(&self.reserve_stake, reserve_stake_info)
     }
 
     /// Check if `StakePool` is actually initialized as a stake pool
     pub fn is_valid(&self) -> bool {
-        self.account_type == AccountType::StakePool
+        matches!(
+            self.account_type,
+            AccountType::StakePool | AccountType::StakePoolV2
+        )
+    }
+
+    /// Size of a stake pool account holding the current `StakePoolConfig`
+    /// layout
+    pub fn account_len() -> usize {
+        StakePoolConfig::offset().saturating_add(get_packed_len::<StakePoolConfig>())
     }
 
     /// Check if `StakePool` is currently uninitialized
@@ -498,7 +417,7 @@ impl StakePool {
 
 /// Checks if the given extension is supported for the stake pool mint
 pub fn is_extension_supported_for_mint(extension_type: &ExtensionType) -> bool {
-    const SUPPORTED_EXTENSIONS: [ExtensionType; 8] = [
+    const SUPPORTED_EXTENSIONS: [ExtensionType; 10] = [
         ExtensionType::Uninitialized,
         ExtensionType::TransferFeeConfig,
         ExtensionType::ConfidentialTransferMint,
@@ -507,6 +426,8 @@ pub fn is_extension_supported_for_mint(extension_type: &ExtensionType) -> bool {
         ExtensionType::InterestBearingConfig,
         ExtensionType::MetadataPointer,
         ExtensionType::TokenMetadata,
+        ExtensionType::ScaledUiAmount,
+        ExtensionType::TransferHook, // ok, if the manager allows the program
     ];
     if !SUPPORTED_EXTENSIONS.contains(extension_type) {
         msg!(
@@ -524,11 +445,12 @@ pub fn is_extension_supported_for_fee_account(extension_type: &ExtensionType) ->
     // Note: this does not include the `ConfidentialTransferAccount` extension
     // because it is possible to block non-confidential transfers with the
     // extension enabled.
-    const SUPPORTED_EXTENSIONS: [ExtensionType; 4] = [
+    const SUPPORTED_EXTENSIONS: [ExtensionType; 5] = [
         ExtensionType::Uninitialized,
         ExtensionType::TransferFeeAmount,
         ExtensionType::ImmutableOwner,
         ExtensionType::CpiGuard,
+        ExtensionType::TransferHookAccount,
     ];
     if !SUPPORTED_EXTENSIONS.contains(extension_type) {
         msg!("Fee account cannot have the {:?} extension", extension_type);
@@ -538,6 +460,761 @@ pub fn is_extension_supported_for_fee_account(extension_type: &ExtensionType) ->
     }
 }
 
+/// Calculate the pool tokens that should be minted for a deposit of
+/// `stake_lamports`
+fn calc_pool_tokens_for_deposit(
+    total_lamports: u64,
+    pool_token_supply: u64,
+    stake_lamports: u64,
+) -> Option<u64> {
+    if total_lamports == 0 || pool_token_supply == 0 {
+        return Some(stake_lamports);
+    }
+    u64::try_from(
+        (stake_lamports as u128)
+            .checked_mul(pool_token_supply as u128)?
+            .checked_div(total_lamports as u128)?,
+    )
+    .ok()
+}
+
+/// Calculate the lamports amount on withdrawal of `pool_tokens`
+fn calc_lamports_withdraw_amount(
+    total_lamports: u64,
+    pool_token_supply: u64,
+    pool_tokens: u64,
+) -> Option<u64> {
+    // `checked_div` returns `None` for a 0 quotient result, but in this
+    // case, a return of 0 is valid for small amounts of pool tokens. So
+    // we check for that separately
+    let numerator = (pool_tokens as u128).checked_mul(total_lamports as u128)?;
+    let denominator = pool_token_supply as u128;
+    if numerator < denominator || denominator == 0 {
+        Some(0)
+    } else {
+        u64::try_from(numerator.checked_div(denominator)?).ok()
+    }
+}
+
+/// Calculate the part of the deposit fee paid out to the referrer, with
+/// `referral_fee` as a percentage
+fn calc_referral_fee(deposit_fee: u64, referral_fee: u8) -> Option<u64> {
+    u64::try_from(
+        (deposit_fee as u128)
+            .checked_mul(referral_fee as u128)?
+            .checked_div(100u128)?,
+    )
+    .ok()
+}
+
+fn check_manager_fee_info(
+    manager_fee_info: &AccountInfo,
+    token_program_id: &Pubkey,
+    pool_mint: &Pubkey,
+) -> Result<(), ProgramError> {
+    let account_data = manager_fee_info.try_borrow_data()?;
+    let token_account = StateWithExtensions::<Account>::unpack(&account_data)?;
+    if manager_fee_info.owner != token_program_id
+        || token_account.base.state != AccountState::Initialized
+        || token_account.base.mint != *pool_mint
+    {
+        msg!("Manager fee account is not owned by token program, is not initialized, or does not match stake pool's mint");
+        return Err(StakePoolError::InvalidFeeAccount.into());
+    }
+    let extensions = token_account.get_extension_types()?;
+    if extensions
+        .iter()
+        .any(|x| !is_extension_supported_for_fee_account(x))
+    {
+        return Err(StakePoolError::UnsupportedFeeAccountExtension.into());
+    }
+    Ok(())
+}
+
+fn check_mint(pool_mint: &Pubkey, mint_info: &AccountInfo) -> Result<u8, ProgramError> {
+    if mint_info.key != pool_mint {
+        Err(StakePoolError::WrongPoolMint.into())
+    } else {
+        let mint_data = mint_info.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+        Ok(mint.base.decimals)
+    }
+}
+
+fn check_validator_list(
+    validator_list: &Pubkey,
+    validator_list_info: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if validator_list_info.key != validator_list {
+        msg!(
+            "Invalid validator list provided, expected {}, received {}",
+            validator_list,
+            validator_list_info.key
+        );
+        Err(StakePoolError::InvalidValidatorStakeList.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn check_reserve_stake(
+    reserve_stake: &Pubkey,
+    reserve_stake_info: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if reserve_stake_info.key != reserve_stake {
+        msg!(
+            "Invalid reserve stake provided, expected {}, received {}",
+            reserve_stake,
+            reserve_stake_info.key
+        );
+        Err(StakePoolError::InvalidProgramAddress.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Helper type to read and write the fixed-size start of a `StakePool` in
+/// place, up to and including `last_update_epoch`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct StakePoolHeader {
+    /// Account type, as the `AccountType` discriminant
+    pub account_type: u8,
+    /// Manager authority
+    pub manager: Pubkey,
+    /// Staker authority
+    pub staker: Pubkey,
+    /// Stake deposit authority
+    pub stake_deposit_authority: Pubkey,
+    /// Stake withdrawal authority bump seed
+    pub stake_withdraw_bump_seed: u8,
+    /// Validator stake list storage account
+    pub validator_list: Pubkey,
+    /// Reserve stake account
+    pub reserve_stake: Pubkey,
+    /// Pool Mint
+    pub pool_mint: Pubkey,
+    /// Manager fee account
+    pub manager_fee_account: Pubkey,
+    /// Pool token program id
+    pub token_program_id: Pubkey,
+    /// Total stake under management
+    pub total_lamports: PodU64,
+    /// Total supply of pool tokens
+    pub pool_token_supply: PodU64,
+    /// Last epoch the `total_lamports` field was updated
+    pub last_update_epoch: PodU64,
+}
+
+/// Offsets of the fields of a `StakePool` that come after `Option` or
+/// `FutureEpoch` fields, and so can move around
+#[derive(Clone, Copy, Debug)]
+struct StakePoolOffsets {
+    preferred_deposit_validator_vote_address: usize,
+    preferred_withdraw_validator_vote_address: usize,
+    stake_deposit_fee: usize,
+    stake_referral_fee: usize,
+    sol_deposit_authority: usize,
+    sol_deposit_fee: usize,
+    sol_withdraw_authority: usize,
+    sol_withdrawal_fee: usize,
+}
+impl StakePoolOffsets {
+    const PUBKEY_LEN: usize = PUBKEY_BYTES;
+    const FEE_LEN: usize = 16;
+    /// Length of the lockup and epoch fee, between the header and the first
+    /// variable-length field
+    const LOCKUP_AND_EPOCH_FEE_LEN: usize = 8 + 8 + PUBKEY_BYTES + Self::FEE_LEN;
+
+    /// Walk through the variable-length fields of the encoded `StakePool`.
+    ///
+    /// Offsets saturate instead of overflowing, since running past the end of
+    /// the data fails the final length check anyway.
+    fn find(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut offset =
+            mem::size_of::<StakePoolHeader>().saturating_add(Self::LOCKUP_AND_EPOCH_FEE_LEN);
+        // next_epoch_fee
+        offset = offset.saturating_add(Self::tagged_len(data, offset, Self::FEE_LEN)?);
+        let preferred_deposit_validator_vote_address = offset;
+        offset = offset.saturating_add(Self::tagged_len(data, offset, Self::PUBKEY_LEN)?);
+        let preferred_withdraw_validator_vote_address = offset;
+        offset = offset.saturating_add(Self::tagged_len(data, offset, Self::PUBKEY_LEN)?);
+        let stake_deposit_fee = offset;
+        // stake_deposit_fee and stake_withdrawal_fee
+        offset = offset.saturating_add(Self::FEE_LEN.saturating_mul(2));
+        // next_stake_withdrawal_fee
+        offset = offset.saturating_add(Self::tagged_len(data, offset, Self::FEE_LEN)?);
+        let stake_referral_fee = offset;
+        offset = offset.saturating_add(1);
+        let sol_deposit_authority = offset;
+        offset = offset.saturating_add(Self::tagged_len(data, offset, Self::PUBKEY_LEN)?);
+        let sol_deposit_fee = offset;
+        // sol_deposit_fee and sol_referral_fee
+        offset = offset.saturating_add(Self::FEE_LEN.saturating_add(1));
+        let sol_withdraw_authority = offset;
+        offset = offset.saturating_add(Self::tagged_len(data, offset, Self::PUBKEY_LEN)?);
+        let sol_withdrawal_fee = offset;
+        offset = offset.saturating_add(Self::FEE_LEN);
+        // next_sol_withdrawal_fee
+        offset = offset.saturating_add(Self::tagged_len(data, offset, Self::FEE_LEN)?);
+        // last_epoch_pool_token_supply and last_epoch_total_lamports
+        offset = offset.saturating_add(8 + 8);
+        if offset > data.len() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        Ok(Self {
+            preferred_deposit_validator_vote_address,
+            preferred_withdraw_validator_vote_address,
+            stake_deposit_fee,
+            stake_referral_fee,
+            sol_deposit_authority,
+            sol_deposit_fee,
+            sol_withdraw_authority,
+            sol_withdrawal_fee,
+        })
+    }
+
+    /// Length of an `Option` or `FutureEpoch` field, which is only followed by
+    /// its value if the tag is non-zero
+    fn tagged_len(data: &[u8], offset: usize, value_len: usize) -> Result<usize, ProgramError> {
+        match data.get(offset) {
+            Some(0) => Ok(1),
+            Some(1) | Some(2) => Ok(value_len.saturating_add(1)),
+            Some(_) => Err(ProgramError::InvalidAccountData),
+            None => Err(ProgramError::AccountDataTooSmall),
+        }
+    }
+}
+
+/// Zero-copy view of a `StakePool` account, for instructions that only need a
+/// few of its fields, such as deposits and withdrawals.
+///
+/// The header is read and written in place, and the remaining fields are
+/// only decoded when requested, avoiding the (de)serialization of the whole
+/// `StakePool`.
+pub struct StakePoolView<'data> {
+    data: &'data mut [u8],
+    offsets: StakePoolOffsets,
+}
+impl<'data> StakePoolView<'data> {
+    /// Create a view over the stake pool account data
+    pub fn new(data: &'data mut [u8]) -> Result<Self, ProgramError> {
+        if data.len() < mem::size_of::<StakePoolHeader>() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        let offsets = StakePoolOffsets::find(data)?;
+        Ok(Self { data, offsets })
+    }
+
+    /// Check if the view is over an initialized stake pool
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::StakePool as u8
+            || self.account_type == AccountType::StakePoolV2 as u8
+    }
+
+    /// Read the additional configuration, as in `StakePoolConfig::load`.
+    ///
+    /// The whole configuration is decoded on every call, so handlers should
+    /// read it once and pass it to their checks.
+    pub fn config(&self) -> Result<StakePoolConfig, ProgramError> {
+        if self.account_type != AccountType::StakePoolV2 as u8 {
+            return Ok(StakePoolConfig::default());
+        }
+        StakePoolConfig::unpack_from(self.data)
+    }
+
+    fn read_pubkey_option(&self, offset: usize) -> Option<Pubkey> {
+        if self.data[offset] == 0 {
+            None
+        } else {
+            Some(Pubkey::new_from_array(*array_ref![
+                self.data,
+                offset.saturating_add(1),
+                PUBKEY_BYTES
+            ]))
+        }
+    }
+
+    fn read_fee(&self, offset: usize) -> Fee {
+        Fee {
+            denominator: u64::from_le_bytes(*array_ref![self.data, offset, 8]),
+            numerator: u64::from_le_bytes(*array_ref![self.data, offset.saturating_add(8), 8]),
+        }
+    }
+
+    /// Preferred deposit validator vote account pubkey
+    pub fn preferred_deposit_validator_vote_address(&self) -> Option<Pubkey> {
+        self.read_pubkey_option(self.offsets.preferred_deposit_validator_vote_address)
+    }
+
+    /// Preferred withdraw validator vote account pubkey
+    pub fn preferred_withdraw_validator_vote_address(&self) -> Option<Pubkey> {
+        self.read_pubkey_option(self.offsets.preferred_withdraw_validator_vote_address)
+    }
+
+    /// Fee assessed on stake deposits
+    pub fn stake_deposit_fee(&self) -> Fee {
+        self.read_fee(self.offsets.stake_deposit_fee)
+    }
+
+    /// Fee assessed on stake withdrawals
+    pub fn stake_withdrawal_fee(&self) -> Fee {
+        self.read_fee(
+            self.offsets
+                .stake_deposit_fee
+                .saturating_add(StakePoolOffsets::FEE_LEN),
+        )
+    }
+
+    /// Fees paid out to referrers on referred stake deposits
+    pub fn stake_referral_fee(&self) -> u8 {
+        self.data[self.offsets.stake_referral_fee]
+    }
+
+    /// Authority required to sign `DepositSol`, if any
+    pub fn sol_deposit_authority(&self) -> Option<Pubkey> {
+        self.read_pubkey_option(self.offsets.sol_deposit_authority)
+    }
+
+    /// Fee assessed on SOL deposits
+    pub fn sol_deposit_fee(&self) -> Fee {
+        self.read_fee(self.offsets.sol_deposit_fee)
+    }
+
+    /// Fees paid out to referrers on referred SOL deposits
+    pub fn sol_referral_fee(&self) -> u8 {
+        self.data[self
+            .offsets
+            .sol_deposit_fee
+            .saturating_add(StakePoolOffsets::FEE_LEN)]
+    }
+
+    /// Authority required to sign `WithdrawSol`, if any
+    pub fn sol_withdraw_authority(&self) -> Option<Pubkey> {
+        self.read_pubkey_option(self.offsets.sol_withdraw_authority)
+    }
+
+    /// Fee assessed on SOL withdrawals
+    pub fn sol_withdrawal_fee(&self) -> Fee {
+        self.read_fee(self.offsets.sol_withdrawal_fee)
+    }
+
+    /// Clear the preferred deposit and withdraw validators if they are set to
+    /// `vote_account_address`.
+    ///
+    /// Since this changes the length of the encoding, the whole `StakePool` is
+    /// rewritten.
+    pub fn reset_preferred_validator(
+        &mut self,
+        vote_account_address: &Pubkey,
+    ) -> Result<(), ProgramError> {
+        let vote_account_address = Some(*vote_account_address);
+        if self.preferred_deposit_validator_vote_address() != vote_account_address
+            && self.preferred_withdraw_validator_vote_address() != vote_account_address
+        {
+            return Ok(());
+        }
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(self.data)?;
+        if stake_pool.preferred_deposit_validator_vote_address == vote_account_address {
+            stake_pool.preferred_deposit_validator_vote_address = None;
+        }
+        if stake_pool.preferred_withdraw_validator_vote_address == vote_account_address {
+            stake_pool.preferred_withdraw_validator_vote_address = None;
+        }
+        borsh::to_writer(&mut self.data[..], &stake_pool)?;
+        self.offsets = StakePoolOffsets::find(self.data)?;
+        Ok(())
+    }
+
+    /// calculate the pool tokens that should be minted for a deposit of
+    /// `stake_lamports`
+    #[inline]
+    pub fn calc_pool_tokens_for_deposit(&self, stake_lamports: u64) -> Option<u64> {
+        calc_pool_tokens_for_deposit(
+            self.total_lamports.into(),
+            self.pool_token_supply.into(),
+            stake_lamports,
+        )
+    }
+
+    /// calculate lamports amount on withdrawal
+    #[inline]
+    pub fn calc_lamports_withdraw_amount(&self, pool_tokens: u64) -> Option<u64> {
+        calc_lamports_withdraw_amount(
+            self.total_lamports.into(),
+            self.pool_token_supply.into(),
+            pool_tokens,
+        )
+    }
+
+    /// calculate pool tokens to be deducted as withdrawal fees
+    #[inline]
+    pub fn calc_pool_tokens_stake_withdrawal_fee(&self, pool_tokens: u64) -> Option<u64> {
+        u64::try_from(self.stake_withdrawal_fee().apply(pool_tokens)?).ok()
+    }
+
+    /// calculate pool tokens to be deducted as withdrawal fees
+    #[inline]
+    pub fn calc_pool_tokens_sol_withdrawal_fee(&self, pool_tokens: u64) -> Option<u64> {
+        u64::try_from(self.sol_withdrawal_fee().apply(pool_tokens)?).ok()
+    }
+
+    /// calculate pool tokens to be deducted as stake deposit fees
+    #[inline]
+    pub fn calc_pool_tokens_stake_deposit_fee(&self, pool_tokens_minted: u64) -> Option<u64> {
+        u64::try_from(self.stake_deposit_fee().apply(pool_tokens_minted)?).ok()
+    }
+
+    /// calculate pool tokens to be deducted from deposit fees as referral fees
+    #[inline]
+    pub fn calc_pool_tokens_stake_referral_fee(&self, stake_deposit_fee: u64) -> Option<u64> {
+        calc_referral_fee(stake_deposit_fee, self.stake_referral_fee())
+    }
+
+    /// calculate pool tokens to be deducted as SOL deposit fees
+    #[inline]
+    pub fn calc_pool_tokens_sol_deposit_fee(&self, pool_tokens_minted: u64) -> Option<u64> {
+        u64::try_from(self.sol_deposit_fee().apply(pool_tokens_minted)?).ok()
+    }
+
+    /// calculate pool tokens to be deducted from SOL deposit fees as referral
+    /// fees
+    #[inline]
+    pub fn calc_pool_tokens_sol_referral_fee(&self, sol_deposit_fee: u64) -> Option<u64> {
+        calc_referral_fee(sol_deposit_fee, self.sol_referral_fee())
+    }
+
+    /// Get the current value of pool tokens, rounded up
+    #[inline]
+    pub fn get_lamports_per_pool_token(&self) -> Option<u64> {
+        let pool_token_supply = u64::from(self.pool_token_supply);
+        u64::from(self.total_lamports)
+            .checked_add(pool_token_supply)?
+            .checked_sub(1)?
+            .checked_div(pool_token_supply)
+    }
+
+    /// Check if the manager fee info is a valid token program account
+    /// capable of receiving tokens from the mint.
+    pub fn check_manager_fee_info(
+        &self,
+        manager_fee_info: &AccountInfo,
+    ) -> Result<(), ProgramError> {
+        check_manager_fee_info(manager_fee_info, &self.token_program_id, &self.pool_mint)
+    }
+
+    /// Checks that the withdraw authority is valid
+    #[inline]
+    pub(crate) fn check_authority_withdraw(
+        &self,
+        withdraw_authority: &Pubkey,
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+    ) -> Result<(), ProgramError> {
+        StakePool::check_program_derived_authority(
+            withdraw_authority,
+            program_id,
+            stake_pool_address,
+            crate::AUTHORITY_WITHDRAW,
+            self.stake_withdraw_bump_seed,
+        )
+    }
+
+    /// Checks that the deposit authority is valid
+    #[inline]
+    pub(crate) fn check_stake_deposit_authority(
+        &self,
+        stake_deposit_authority: &Pubkey,
+    ) -> Result<(), ProgramError> {
+        if self.stake_deposit_authority == *stake_deposit_authority {
+            Ok(())
+        } else {
+            Err(StakePoolError::InvalidStakeDepositAuthority.into())
+        }
+    }
+
+    /// Checks that the deposit authority is valid
+    /// Does nothing if `sol_deposit_authority` is currently not set
+    #[inline]
+    pub(crate) fn check_sol_deposit_authority(
+        &self,
+        maybe_sol_deposit_authority: Result<&AccountInfo, ProgramError>,
+    ) -> Result<(), ProgramError> {
+        if let Some(auth) = self.sol_deposit_authority() {
+            let sol_deposit_authority = maybe_sol_deposit_authority?;
+            if auth != *sol_deposit_authority.key {
+                msg!("Expected {}, received {}", auth, sol_deposit_authority.key);
+                return Err(StakePoolError::InvalidSolDepositAuthority.into());
+            }
+            if !sol_deposit_authority.is_signer {
+                msg!("SOL Deposit authority signature missing");
+                return Err(StakePoolError::SignatureMissing.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the sol withdraw authority is valid
+    /// Does nothing if `sol_withdraw_authority` is currently not set
+    #[inline]
+    pub(crate) fn check_sol_withdraw_authority(
+        &self,
+        maybe_sol_withdraw_authority: Result<&AccountInfo, ProgramError>,
+    ) -> Result<(), ProgramError> {
+        if let Some(auth) = self.sol_withdraw_authority() {
+            let sol_withdraw_authority = maybe_sol_withdraw_authority?;
+            if auth != *sol_withdraw_authority.key {
+                return Err(StakePoolError::InvalidSolWithdrawAuthority.into());
+            }
+            if !sol_withdraw_authority.is_signer {
+                msg!("SOL withdraw authority signature missing");
+                return Err(StakePoolError::SignatureMissing.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check mint is correct
+    #[inline]
+    pub(crate) fn check_mint(&self, mint_info: &AccountInfo) -> Result<u8, ProgramError> {
+        check_mint(&self.pool_mint, mint_info)
+    }
+
+    /// Check the validator list is valid
+    pub fn check_validator_list(
+        &self,
+        validator_list_info: &AccountInfo,
+    ) -> Result<(), ProgramError> {
+        check_validator_list(&self.validator_list, validator_list_info)
+    }
+
+    /// Check the reserve stake is valid
+    pub fn check_reserve_stake(
+        &self,
+        reserve_stake_info: &AccountInfo,
+    ) -> Result<(), ProgramError> {
+        check_reserve_stake(&self.reserve_stake, reserve_stake_info)
+    }
+}
+impl Deref for StakePoolView<'_> {
+    type Target = StakePoolHeader;
+    fn deref(&self) -> &StakePoolHeader {
+        bytemuck::from_bytes(&self.data[..mem::size_of::<StakePoolHeader>()])
+    }
+}
+impl DerefMut for StakePoolView<'_> {
+    fn deref_mut(&mut self) -> &mut StakePoolHeader {
+        bytemuck::from_bytes_mut(&mut self.data[..mem::size_of::<StakePoolHeader>()])
+    }
+}
+
+/// Additional stake pool configuration, stored at a fixed offset after the
+/// `StakePool` in `StakePoolV2` accounts.
+///
+/// New fields are only ever appended, and must deserialize to their default
+/// value from zeroed bytes, so that pools created with an older layout pick
+/// them up after being grown by `MigratePool`. Their default value must also
+/// encode to zeroed bytes, so that `store` still works on those pools until
+/// the new fields are set.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct StakePoolConfig {
+    /// Epoch in which `updated_validator_count` and `updated_lamports` were
+    /// tallied
+    pub update_epoch: u64,
+
+    /// Number of validator list entries updated during `update_epoch`
+    pub updated_validator_count: u32,
+
+    /// Sum of the stake lamports of every validator list entry updated during
+    /// `update_epoch`
+    pub updated_lamports: u64,
+
+    /// If set, SOL deposits and withdrawals are allowed before the pool is
+    /// updated for the epoch, with this share of the pool tokens of each
+    /// deposit withheld to cover the rewards not yet accounted for
+    pub partial_update_haircut: Option<Fee>,
+
+    /// Transfer hook programs allowed on the pool mint. Deposits and
+    /// withdrawals fail if the mint's transfer hook program is not one of
+    /// these.
+    pub transfer_hook_program_ids: [Option<Pubkey>; MAX_TRANSFER_HOOK_PROGRAMS],
+
+    /// If true, deposits only succeed if the owner of the account receiving
+    /// the pool tokens has a `DepositorAllowlistEntry`, whoever funds them
+    pub depositor_allowlist_enabled: bool,
+
+    /// If set, deposits only succeed with a proof that the owner of the
+    /// account receiving the pool tokens is in the Merkle tree with this root,
+    /// built with `merkle::leaf_hash`
+    pub depositor_merkle_root: Option<[u8; 32]>,
+}
+impl StakePoolConfig {
+    /// Offset of the configuration in a `StakePoolV2` account, after the
+    /// largest possible `StakePool`
+    pub fn offset() -> usize {
+        get_packed_len::<StakePool>()
+    }
+
+    /// Read the configuration from the stake pool account data.
+    ///
+    /// Pools that have not been migrated to `StakePoolV2` get the default
+    /// configuration, as do any fields missing from an older layout.
+    pub fn load(stake_pool: &StakePool, data: &[u8]) -> Result<Self, ProgramError> {
+        if stake_pool.account_type != AccountType::StakePoolV2 {
+            return Ok(Self::default());
+        }
+        Self::unpack_from(data)
+    }
+
+    fn unpack_from(data: &[u8]) -> Result<Self, ProgramError> {
+        let config_data = data
+            .get(Self::offset()..)
+            .ok_or(ProgramError::AccountDataTooSmall)?;
+        let mut buffer = vec![0; get_packed_len::<Self>()];
+        let len = config_data.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&config_data[..len]);
+        Ok(try_from_slice_unchecked::<Self>(&buffer)?)
+    }
+
+    /// Write the configuration into the stake pool account data.
+    ///
+    /// Accounts migrated with an older, shorter layout only get the part of
+    /// the encoding that fits, as long as the rest is zeroed, which `load`
+    /// reads back as the default values of the newer fields. Setting any of
+    /// those fields requires `MigratePool` first.
+    pub fn store(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        let encoded = borsh::to_vec(self)?;
+        let config_data = data
+            .get_mut(Self::offset()..)
+            .ok_or(StakePoolError::StakePoolNotMigrated)?;
+        let (stored, truncated) = encoded.split_at(config_data.len().min(encoded.len()));
+        if truncated.iter().any(|byte| *byte != 0) {
+            return Err(StakePoolError::StakePoolNotMigrated.into());
+        }
+        // zero out the region first, since borsh leaves stale bytes after
+        // shorter encodings, which newer fields would read
+        config_data.fill(0);
+        config_data[..stored.len()].copy_from_slice(stored);
+        Ok(())
+    }
+
+    /// Check if the given transfer hook program is allowed on the pool mint
+    pub fn is_transfer_hook_program_allowed(&self, program_id: &Pubkey) -> bool {
+        self.transfer_hook_program_ids
+            .iter()
+            .flatten()
+            .any(|allowed| allowed == program_id)
+    }
+
+    /// Tally a validator list entry updated in `epoch`, given its last update
+    /// epoch and stake lamports before the update, and its stake lamports
+    /// after.
+    ///
+    /// An entry already updated in `epoch` is only counted once, so validators
+    /// may be updated any number of times and in any order.
+    pub fn track_validator_update(
+        &mut self,
+        epoch: u64,
+        previous_update_epoch: u64,
+        previous_lamports: u64,
+        lamports: u64,
+    ) -> Result<(), StakePoolError> {
+        if self.update_epoch != epoch {
+            self.update_epoch = epoch;
+            self.updated_validator_count = 0;
+            self.updated_lamports = 0;
+        }
+        if previous_update_epoch == epoch {
+            // the entry may have been updated without being tallied, before
+            // the pool was migrated or with a read-only stake pool, in which
+            // case the count never reaches the list length and this sum is
+            // ignored, so saturating is fine
+            self.updated_lamports = self.updated_lamports.saturating_sub(previous_lamports);
+        } else {
+            self.updated_validator_count = self
+                .updated_validator_count
+                .checked_add(1)
+                .ok_or(StakePoolError::CalculationFailure)?;
+        }
+        self.updated_lamports = self
+            .updated_lamports
+            .checked_add(lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// Forget validator list entries tallied in `update_epoch` that are about
+    /// to be removed from the list. Removed entries hold no lamports, so only
+    /// the count changes.
+    pub fn untrack_removed_validators(&mut self, removed_validator_count: u32) {
+        self.updated_validator_count = self
+            .updated_validator_count
+            .saturating_sub(removed_validator_count);
+    }
+
+    /// Total stake lamports of all validators, if all `validator_count`
+    /// entries of the validator list were tallied in `epoch`
+    pub fn updated_validator_lamports(&self, epoch: u64, validator_count: u32) -> Option<u64> {
+        (self.update_epoch == epoch && self.updated_validator_count == validator_count)
+            .then_some(self.updated_lamports)
+    }
+}
+
+/// Entry of a stake pool's depositor allowlist, at the address given by
+/// `find_depositor_allowlist_entry_address`
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct DepositorAllowlistEntry {
+    /// Account type, must be `DepositorAllowlistEntry` currently
+    pub account_type: AccountType,
+
+    /// Stake pool the entry belongs to
+    pub stake_pool: Pubkey,
+
+    /// Owner of pool token accounts allowed to receive pool tokens from
+    /// deposits
+    pub depositor: Pubkey,
+}
+impl DepositorAllowlistEntry {
+    /// Check if the entry is initialized and allows `depositor` into
+    /// `stake_pool`
+    pub fn allows(&self, stake_pool: &Pubkey, depositor: &Pubkey) -> bool {
+        self.account_type == AccountType::DepositorAllowlistEntry
+            && self.stake_pool == *stake_pool
+            && self.depositor == *depositor
+    }
+}
+
+/// Record of the deposits made into a pool for a depositor, to enforce the cap
+/// of their `DepositorProof`, at the address given by
+/// `find_depositor_record_address`
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct DepositorRecord {
+    /// Account type, must be `DepositorRecord` currently
+    pub account_type: AccountType,
+
+    /// Stake pool the deposits were made into
+    pub stake_pool: Pubkey,
+
+    /// Owner of the pool token accounts that received the deposits
+    pub depositor: Pubkey,
+
+    /// Total lamports deposited, whatever pool token account received them.
+    /// Withdrawals don't free up room under the cap.
+    pub deposited: u64,
+}
+impl DepositorRecord {
+    /// Check if the record is initialized and belongs to `depositor` in
+    /// `stake_pool`
+    pub fn is_for(&self, stake_pool: &Pubkey, depositor: &Pubkey) -> bool {
+        self.account_type == AccountType::DepositorRecord
+            && self.stake_pool == *stake_pool
+            && self.depositor == *depositor
+    }
+}
+
 /// Storage list for all validator stake accounts in the pool.
 #[repr(C)]
 #[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
@@ -757,6 +1434,11 @@ impl ValidatorStakeInfo {
             && data[0..16] == [0; 16] // active and transient stake lamports are 0
     }
 
+    /// Check that the validator stake info was last updated in `epoch`
+    pub fn is_updated_in(data: &[u8], epoch: u64) -> bool {
+        data[16..24] == epoch.to_le_bytes()
+    }
+
     /// Check that the validator stake info is active
     pub fn is_active(data: &[u8]) -> bool {
         FromPrimitive::from_u8(data[40]) == Some(StakeStatus::Active)
@@ -800,6 +1482,14 @@ impl ValidatorList {
             .saturating_div(ValidatorStakeInfo::LEN)
     }
 
+    /// Calculate the account length required to hold `max_validators`
+    /// entries
+    pub fn calculate_len(max_validators: u32) -> usize {
+        let header_size = ValidatorListHeader::LEN.saturating_add(4);
+        header_size
+            .saturating_add((max_validators as usize).saturating_mul(ValidatorStakeInfo::LEN))
+    }
+
     /// Check if contains validator with particular pubkey
     pub fn contains(&self, vote_account_address: &Pubkey) -> bool {
         self.validators
@@ -1245,6 +1935,7 @@ mod test {
         fn stake_list_size_calculation(test_amount in 0..=100_000_u32) {
             let validators = ValidatorList::new(test_amount);
             let size = get_instance_packed_len(&validators).unwrap();
+            assert_eq!(ValidatorList::calculate_len(test_amount), size);
             assert_eq!(ValidatorList::calculate_max_validators(size), test_amount as usize);
             assert_eq!(ValidatorList::calculate_max_validators(size.saturating_add(1)), test_amount as usize);
             assert_eq!(ValidatorList::calculate_max_validators(size.saturating_add(get_packed_len::<ValidatorStakeInfo>())), (test_amount + 1)as usize);
@@ -1516,4 +2207,194 @@ mod test {
             new_fee.check_withdrawal(&old_fee).unwrap_err()
         );
     }
+
+    fn test_fee(numerator: u64, denominator: u64) -> Fee {
+        Fee {
+            numerator,
+            denominator,
+        }
+    }
+
+    #[test]
+    fn stake_pool_view() {
+        let vote_account_address = Pubkey::new_unique();
+        let base = StakePool {
+            account_type: AccountType::StakePoolV2,
+            manager: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            manager_fee_account: Pubkey::new_unique(),
+            total_lamports: 1_000,
+            pool_token_supply: 900,
+            last_update_epoch: 5,
+            stake_deposit_fee: test_fee(1, 10),
+            stake_withdrawal_fee: test_fee(2, 10),
+            stake_referral_fee: 25,
+            sol_deposit_fee: test_fee(3, 10),
+            sol_referral_fee: 50,
+            sol_withdrawal_fee: test_fee(4, 10),
+            last_epoch_pool_token_supply: 800,
+            last_epoch_total_lamports: 850,
+            ..StakePool::default()
+        };
+        let with_options = StakePool {
+            next_epoch_fee: FutureEpoch::One(test_fee(5, 10)),
+            preferred_deposit_validator_vote_address: Some(vote_account_address),
+            preferred_withdraw_validator_vote_address: Some(vote_account_address),
+            next_stake_withdrawal_fee: FutureEpoch::Two(test_fee(6, 10)),
+            sol_deposit_authority: Some(Pubkey::new_unique()),
+            sol_withdraw_authority: Some(Pubkey::new_unique()),
+            next_sol_withdrawal_fee: FutureEpoch::One(test_fee(7, 10)),
+            ..base.clone()
+        };
+
+        for stake_pool in [base, with_options] {
+            let mut data = vec![0; get_packed_len::<StakePool>()];
+            borsh::to_writer(&mut data[..], &stake_pool).unwrap();
+            let mut view = StakePoolView::new(&mut data).unwrap();
+
+            assert!(view.is_valid());
+            assert_eq!(view.manager, stake_pool.manager);
+            assert_eq!(view.pool_mint, stake_pool.pool_mint);
+            assert_eq!(view.manager_fee_account, stake_pool.manager_fee_account);
+            assert_eq!(u64::from(view.total_lamports), stake_pool.total_lamports);
+            assert_eq!(
+                u64::from(view.pool_token_supply),
+                stake_pool.pool_token_supply
+            );
+            assert_eq!(
+                u64::from(view.last_update_epoch),
+                stake_pool.last_update_epoch
+            );
+            assert_eq!(
+                view.preferred_deposit_validator_vote_address(),
+                stake_pool.preferred_deposit_validator_vote_address
+            );
+            assert_eq!(
+                view.preferred_withdraw_validator_vote_address(),
+                stake_pool.preferred_withdraw_validator_vote_address
+            );
+            assert_eq!(view.stake_deposit_fee(), stake_pool.stake_deposit_fee);
+            assert_eq!(view.stake_withdrawal_fee(), stake_pool.stake_withdrawal_fee);
+            assert_eq!(view.stake_referral_fee(), stake_pool.stake_referral_fee);
+            assert_eq!(
+                view.sol_deposit_authority(),
+                stake_pool.sol_deposit_authority
+            );
+            assert_eq!(view.sol_deposit_fee(), stake_pool.sol_deposit_fee);
+            assert_eq!(view.sol_referral_fee(), stake_pool.sol_referral_fee);
+            assert_eq!(
+                view.sol_withdraw_authority(),
+                stake_pool.sol_withdraw_authority
+            );
+            assert_eq!(view.sol_withdrawal_fee(), stake_pool.sol_withdrawal_fee);
+            assert_eq!(
+                view.calc_pool_tokens_for_deposit(100),
+                stake_pool.calc_pool_tokens_for_deposit(100)
+            );
+            assert_eq!(
+                view.calc_lamports_withdraw_amount(100),
+                stake_pool.calc_lamports_withdraw_amount(100)
+            );
+
+            view.total_lamports = 2_000.into();
+            view.pool_token_supply = 1_800.into();
+            view.reset_preferred_validator(&vote_account_address)
+                .unwrap();
+            assert_eq!(view.preferred_deposit_validator_vote_address(), None);
+            assert_eq!(view.sol_withdrawal_fee(), stake_pool.sol_withdrawal_fee);
+
+            let updated = try_from_slice_unchecked::<StakePool>(&data).unwrap();
+            assert_eq!(
+                updated,
+                StakePool {
+                    total_lamports: 2_000,
+                    pool_token_supply: 1_800,
+                    preferred_deposit_validator_vote_address: None,
+                    preferred_withdraw_validator_vote_address: None,
+                    ..stake_pool
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn stake_pool_config_tracks_validator_updates() {
+        let mut config = StakePoolConfig {
+            update_epoch: 4,
+            updated_validator_count: 3,
+            updated_lamports: 300,
+            ..StakePoolConfig::default()
+        };
+
+        // a new epoch starts a new tally
+        config.track_validator_update(5, 4, 100, 110).unwrap();
+        assert_eq!(config.updated_validator_lamports(5, 1), Some(110));
+        config.track_validator_update(5, 4, 200, 220).unwrap();
+        assert_eq!(config.updated_validator_lamports(5, 1), None);
+        assert_eq!(config.updated_validator_lamports(5, 2), Some(330));
+
+        // updating again only replaces the lamports
+        config.track_validator_update(5, 5, 110, 120).unwrap();
+        assert_eq!(config.updated_validator_lamports(5, 2), Some(340));
+        assert_eq!(config.updated_validator_lamports(6, 2), None);
+
+        config.track_validator_update(5, 4, 0, 0).unwrap();
+        config.untrack_removed_validators(1);
+        assert_eq!(config.updated_validator_lamports(5, 2), Some(340));
+    }
+
+    #[test]
+    fn stake_pool_config_store_older_layout() {
+        // room for the tally fields only, as migrated by an older version
+        let older_len = StakePoolConfig::offset() + 8 + 4 + 8;
+        let mut data = vec![0; older_len];
+        let config = StakePoolConfig {
+            update_epoch: 4,
+            updated_validator_count: 3,
+            updated_lamports: 300,
+            ..StakePoolConfig::default()
+        };
+        config.store(&mut data).unwrap();
+        assert_eq!(StakePoolConfig::unpack_from(&data).unwrap(), config);
+
+        let config = StakePoolConfig {
+            depositor_allowlist_enabled: true,
+            ..config
+        };
+        assert_eq!(
+            config.store(&mut data),
+            Err(StakePoolError::StakePoolNotMigrated.into())
+        );
+
+        let mut data = vec![0; StakePool::account_len()];
+        config.store(&mut data).unwrap();
+        assert_eq!(StakePoolConfig::unpack_from(&data).unwrap(), config);
+        assert_eq!(
+            config.store(&mut data[..StakePoolConfig::offset() - 1]),
+            Err(StakePoolError::StakePoolNotMigrated.into())
+        );
+    }
+
+    #[test]
+    fn scaled_ui_multiplier() {
+        let stake_pool = StakePool {
+            total_lamports: 1_100,
+            pool_token_supply: 1_000,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.calc_scaled_ui_multiplier(9), Some(1.1));
+        assert_eq!(stake_pool.calc_scaled_ui_multiplier(6), Some(1.1 / 1_000.0));
+        assert_eq!(stake_pool.calc_scaled_ui_multiplier(10), Some(11.0));
+
+        let empty_pool = StakePool {
+            pool_token_supply: 0,
+            ..stake_pool.clone()
+        };
+        assert_eq!(empty_pool.calc_scaled_ui_multiplier(9), None);
+        let no_lamports_pool = StakePool {
+            total_lamports: 0,
+            ..stake_pool
+        };
+        assert_eq!(no_lamports_pool.calc_scaled_ui_multiplier(9), None);
+    }
 }
@@ -0,0 +1,247 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{instruction::InstructionError, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, MINIMUM_RESERVE_LAMPORTS},
+    spl_token_2022_interface::{extension::StateWithExtensionsOwned, state::Mint},
+    test_case::test_case,
+};
+
+async fn setup(token_program_id: Pubkey) -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new_with_token_program(token_program_id);
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    (context, stake_pool_accounts)
+}
+
+fn close_pool_transaction(
+    context: &ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    manager: &Keypair,
+    destination: &Pubkey,
+    new_mint_authority: Option<&Pubkey>,
+) -> Transaction {
+    Transaction::new_signed_with_payer(
+        &[instruction::close_pool(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            destination,
+            &stake_pool_accounts.token_program_id,
+            new_mint_authority,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, manager],
+        context.last_blockhash,
+    )
+}
+
+#[test_case(spl_token_interface::id(); "token")]
+#[test_case(spl_token_2022_interface::id(); "token-2022")]
+#[tokio::test]
+async fn success(token_program_id: Pubkey) {
+    let (mut context, stake_pool_accounts) = setup(token_program_id).await;
+    let destination = Keypair::new();
+
+    let mut recovered_lamports = 0;
+    for address in [
+        stake_pool_accounts.stake_pool.pubkey(),
+        stake_pool_accounts.validator_list.pubkey(),
+        stake_pool_accounts.reserve_stake.pubkey(),
+    ] {
+        recovered_lamports += get_account(&mut context.banks_client, &address)
+            .await
+            .lamports;
+    }
+
+    let transaction = close_pool_transaction(
+        &context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &destination.pubkey(),
+        None,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    for address in [
+        stake_pool_accounts.stake_pool.pubkey(),
+        stake_pool_accounts.validator_list.pubkey(),
+        stake_pool_accounts.reserve_stake.pubkey(),
+    ] {
+        assert!(context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    let destination_lamports = get_account(&mut context.banks_client, &destination.pubkey())
+        .await
+        .lamports;
+    assert_eq!(destination_lamports, recovered_lamports);
+
+    let pool_mint = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+    let pool_mint = StateWithExtensionsOwned::<Mint>::unpack(pool_mint.data).unwrap();
+    assert!(pool_mint.base.mint_authority.is_none());
+}
+
+#[tokio::test]
+async fn success_with_new_mint_authority() {
+    let (mut context, stake_pool_accounts) = setup(spl_token_interface::id()).await;
+    let destination = Keypair::new();
+    let new_mint_authority = Pubkey::new_unique();
+
+    let transaction = close_pool_transaction(
+        &context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &destination.pubkey(),
+        Some(&new_mint_authority),
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let pool_mint = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+    let pool_mint = StateWithExtensionsOwned::<Mint>::unpack(pool_mint.data).unwrap();
+    assert_eq!(
+        pool_mint.base.mint_authority,
+        Some(new_mint_authority).into()
+    );
+}
+
+#[tokio::test]
+async fn fail_non_zero_pool_token_supply() {
+    let (mut context, stake_pool_accounts) = setup(spl_token_interface::id()).await;
+    let destination = Keypair::new();
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let transaction = close_pool_transaction(
+        &context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &destination.pubkey(),
+        None,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::NonZeroPoolTokenSupply as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_validator_in_pool() {
+    let (mut context, stake_pool_accounts) = setup(spl_token_interface::id()).await;
+    let destination = Keypair::new();
+
+    simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        None,
+    )
+    .await;
+
+    let transaction = close_pool_transaction(
+        &context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &destination.pubkey(),
+        None,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::ValidatorListNotEmpty as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (context, stake_pool_accounts) = setup(spl_token_interface::id()).await;
+    let wrong_manager = Keypair::new();
+
+    let transaction = close_pool_transaction(
+        &context,
+        &stake_pool_accounts,
+        &wrong_manager,
+        &wrong_manager.pubkey(),
+        None,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::WrongManager as u32)
+        )
+    );
+}
@@ -0,0 +1,1407 @@
+#![allow(clippy::arithmetic_side_effects)]
+//! Compute unit benchmarks for every stake pool instruction, run against
+//! pools of increasing size.
+//!
+//! Each pool size writes a report to
+//! `$CARGO_TARGET_TMPDIR/compute_units/pool_<validators>.csv` and compares it
+//! against the baseline stored in `tests/fixtures/compute_units`. The test
+//! fails if any instruction uses more than `REGRESSION_TOLERANCE_PERCENT`
+//! above its baseline, or starts failing where it used to succeed.
+//!
+//! Compute units are only metered for BPF programs, so this must be run with
+//! `cargo test-sbf --test compute_units`, and is skipped under the native
+//! processor. A missing baseline fails the test. To record new baselines, run
+//! it with `UPDATE_COMPUTE_UNITS_BASELINE=1`.
+//!
+//! Every `StakePoolInstruction` must be benchmarked, which is checked against
+//! the variants of the enum itself.
+//!
+//! Only the units consumed by the stake pool program itself are counted, which
+//! includes any CPIs it makes, but not other instructions in the transaction,
+//! such as the stake authorizations that precede `DepositStake`.
+//!
+//! `Redelegate` is not covered since it is permanently disabled.
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_compute_budget_interface::ComputeBudgetInstruction,
+    solana_program::{
+        borsh1::try_from_slice_unchecked, clock::Clock, instruction::Instruction, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        account::AccountSharedData,
+        native_token::LAMPORTS_PER_SOL,
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    },
+    solana_stake_interface as stake,
+    solana_system_interface::instruction as system_instruction,
+    spl_stake_pool::{
+        find_ephemeral_stake_program_address, find_stake_program_address,
+        find_transient_stake_program_address, id,
+        instruction::{self, FundingType, PreferredValidatorType, StakePoolInstruction},
+        state::{Fee, FeeType, StakePool, StakeStatus, ValidatorList},
+        MAX_VALIDATORS_TO_UPDATE, MINIMUM_RESERVE_LAMPORTS,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+        num::NonZeroU32,
+        path::{Path, PathBuf},
+    },
+    test_case::test_case,
+};
+
+const STAKE_AMOUNT: u64 = 200_000_000_000;
+const COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+const REGRESSION_TOLERANCE_PERCENT: u64 = 5;
+const UPDATE_BASELINE_ENV: &str = "UPDATE_COMPUTE_UNITS_BASELINE";
+const REPORT_HEADER: &str = "instruction,validators,compute_units";
+/// Recorded instead of a number when the instruction failed, usually by
+/// running out of compute units
+const FAILED: &str = "failed";
+/// Instructions left out of the benchmarks
+const NOT_BENCHMARKED: &[&str] = &["Redelegate"];
+
+/// Compute units used by each instruction for a single pool size, in the
+/// order they were run
+struct Report {
+    validators: u32,
+    entries: Vec<(&'static str, Option<u64>)>,
+    /// Whether compute units were reported, which only happens for BPF
+    /// programs
+    metered: bool,
+}
+
+impl Report {
+    fn new(validators: u32) -> Self {
+        Self {
+            validators,
+            entries: vec![],
+            metered: true,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        format!("pool_{}.csv", self.validators)
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = format!("{}\n", REPORT_HEADER);
+        for (name, units) in &self.entries {
+            let units = units.map_or_else(|| FAILED.to_string(), |units| units.to_string());
+            csv.push_str(&format!("{},{},{}\n", name, self.validators, units));
+        }
+        csv
+    }
+
+    /// Compares the report against the baseline, returning a description of
+    /// every regression. Instructions missing from the baseline are skipped.
+    fn regressions(&self, baseline: &str) -> Vec<String> {
+        let baseline = parse_csv(baseline);
+        self.entries
+            .iter()
+            .filter_map(|(name, units)| {
+                let baseline_units = (*baseline.get(*name)?)?;
+                match units {
+                    None => Some(format!(
+                        "{}: failed, baseline {} compute units",
+                        name, baseline_units
+                    )),
+                    Some(units)
+                        if *units * 100 > baseline_units * (100 + REGRESSION_TOLERANCE_PERCENT) =>
+                    {
+                        Some(format!(
+                            "{}: {} compute units, baseline {}",
+                            name, units, baseline_units
+                        ))
+                    }
+                    Some(_) => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns every instruction that was not benchmarked
+    fn missing_instructions(&self) -> Vec<&'static str> {
+        let benchmarked: BTreeSet<&str> = self.entries.iter().map(|(name, _)| *name).collect();
+        instruction_names()
+            .into_iter()
+            .filter(|name| !benchmarked.contains(name) && !NOT_BENCHMARKED.contains(name))
+            .collect()
+    }
+
+    fn write_and_check(&self) {
+        let missing_instructions = self.missing_instructions();
+        assert!(
+            missing_instructions.is_empty(),
+            "Instructions not benchmarked: {}",
+            missing_instructions.join(", ")
+        );
+
+        let report_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compute_units");
+        fs::create_dir_all(&report_dir).unwrap();
+        let report_path = report_dir.join(self.file_name());
+        fs::write(&report_path, self.to_csv()).unwrap();
+        println!("Compute unit report written to {}", report_path.display());
+
+        let baseline_path = baseline_dir().join(self.file_name());
+        if std::env::var_os(UPDATE_BASELINE_ENV).is_some() {
+            fs::create_dir_all(baseline_dir()).unwrap();
+            fs::write(&baseline_path, self.to_csv()).unwrap();
+            println!(
+                "Compute unit baseline written to {}",
+                baseline_path.display()
+            );
+            return;
+        }
+        let Ok(baseline) = fs::read_to_string(&baseline_path) else {
+            panic!(
+                "No compute unit baseline at {}, run with {}=1 to create it",
+                baseline_path.display(),
+                UPDATE_BASELINE_ENV
+            );
+        };
+        let regressions = self.regressions(&baseline);
+        assert!(
+            regressions.is_empty(),
+            "Compute unit regressions with {} validators:\n{}",
+            self.validators,
+            regressions.join("\n")
+        );
+    }
+}
+
+fn baseline_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/compute_units")
+}
+
+/// Parses a report into the compute units used by each instruction, or `None`
+/// if it failed
+fn parse_csv(csv: &str) -> BTreeMap<String, Option<u64>> {
+    csv.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split(',');
+            let name = columns.next()?.trim();
+            let units = columns.nth(1)?.trim();
+            Some((name.to_string(), units.parse::<u64>().ok()))
+        })
+        .collect()
+}
+
+/// Names an instruction as in the reports. The match is exhaustive, so a new
+/// instruction does not compile until it is named here, and then fails the
+/// test until it is benchmarked.
+#[allow(deprecated)]
+fn instruction_name(instruction: &StakePoolInstruction) -> &'static str {
+    match instruction {
+        StakePoolInstruction::Initialize { .. } => "Initialize",
+        StakePoolInstruction::AddValidatorToPool(_) => "AddValidatorToPool",
+        StakePoolInstruction::RemoveValidatorFromPool => "RemoveValidatorFromPool",
+        StakePoolInstruction::DecreaseValidatorStake { .. } => "DecreaseValidatorStake",
+        StakePoolInstruction::IncreaseValidatorStake { .. } => "IncreaseValidatorStake",
+        StakePoolInstruction::SetPreferredValidator { .. } => "SetPreferredValidator",
+        StakePoolInstruction::UpdateValidatorListBalance { .. } => "UpdateValidatorListBalance",
+        StakePoolInstruction::UpdateStakePoolBalance => "UpdateStakePoolBalance",
+        StakePoolInstruction::CleanupRemovedValidatorEntries => "CleanupRemovedValidatorEntries",
+        StakePoolInstruction::DepositStake => "DepositStake",
+        StakePoolInstruction::WithdrawStake(_) => "WithdrawStake",
+        StakePoolInstruction::SetManager => "SetManager",
+        StakePoolInstruction::SetFee { .. } => "SetFee",
+        StakePoolInstruction::SetStaker => "SetStaker",
+        StakePoolInstruction::DepositSol(_) => "DepositSol",
+        StakePoolInstruction::SetFundingAuthority(_) => "SetFundingAuthority",
+        StakePoolInstruction::WithdrawSol(_) => "WithdrawSol",
+        StakePoolInstruction::CreateTokenMetadata { .. } => "CreateTokenMetadata",
+        StakePoolInstruction::UpdateTokenMetadata { .. } => "UpdateTokenMetadata",
+        StakePoolInstruction::IncreaseAdditionalValidatorStake { .. } => {
+            "IncreaseAdditionalValidatorStake"
+        }
+        StakePoolInstruction::DecreaseAdditionalValidatorStake { .. } => {
+            "DecreaseAdditionalValidatorStake"
+        }
+        StakePoolInstruction::DecreaseValidatorStakeWithReserve { .. } => {
+            "DecreaseValidatorStakeWithReserve"
+        }
+        StakePoolInstruction::Redelegate { .. } => "Redelegate",
+        StakePoolInstruction::DepositStakeWithSlippage { .. } => "DepositStakeWithSlippage",
+        StakePoolInstruction::WithdrawStakeWithSlippage { .. } => "WithdrawStakeWithSlippage",
+        StakePoolInstruction::DepositSolWithSlippage { .. } => "DepositSolWithSlippage",
+        StakePoolInstruction::WithdrawSolWithSlippage { .. } => "WithdrawSolWithSlippage",
+        StakePoolInstruction::ResizeValidatorList { .. } => "ResizeValidatorList",
+        StakePoolInstruction::ShrinkValidatorList { .. } => "ShrinkValidatorList",
+        StakePoolInstruction::ClosePool => "ClosePool",
+        StakePoolInstruction::MigratePool => "MigratePool",
+        StakePoolInstruction::UpdateValidatorBalance { .. } => "UpdateValidatorBalance",
+        StakePoolInstruction::SetPartialUpdateHaircut { .. } => "SetPartialUpdateHaircut",
+        StakePoolInstruction::SetTransferHookProgramIds { .. } => "SetTransferHookProgramIds",
+        StakePoolInstruction::SetDepositorAllowlist { .. } => "SetDepositorAllowlist",
+        StakePoolInstruction::AddDepositor => "AddDepositor",
+        StakePoolInstruction::RemoveDepositor => "RemoveDepositor",
+        StakePoolInstruction::SetDepositorMerkleRoot { .. } => "SetDepositorMerkleRoot",
+        StakePoolInstruction::CreateDepositorRecord => "CreateDepositorRecord",
+    }
+}
+
+/// Names every variant of `StakePoolInstruction`, found by decoding each
+/// variant index followed by zeroed fields
+fn instruction_names() -> Vec<&'static str> {
+    (0..=u8::MAX)
+        .map_while(|index| {
+            let mut data = vec![0; 1024];
+            data[0] = index;
+            StakePoolInstruction::deserialize(&mut data.as_slice()).ok()
+        })
+        .map(|instruction| instruction_name(&instruction))
+        .collect()
+}
+
+/// Sums the compute units consumed by the stake pool program out of the
+/// transaction logs. The program never invokes itself, so every
+/// "consumed" line for it is a top-level instruction.
+fn stake_pool_compute_units(log_messages: &[String]) -> Option<u64> {
+    let prefix = format!("Program {} consumed ", id());
+    log_messages
+        .iter()
+        .filter_map(|message| message.strip_prefix(&prefix))
+        .filter_map(|rest| rest.split(' ').next()?.parse::<u64>().ok())
+        .reduce(|total, units| total + units)
+}
+
+/// Runs the instructions in a single transaction, recording the compute units
+/// used by the stake pool program under `name`. Returns whether the
+/// transaction succeeded.
+async fn measure(
+    context: &mut ProgramTestContext,
+    report: &mut Report,
+    name: &'static str,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> bool {
+    for instruction in instructions.iter().filter(|ix| ix.program_id == id()) {
+        let decoded = StakePoolInstruction::deserialize(&mut instruction.data.as_slice()).unwrap();
+        assert_eq!(instruction_name(&decoded), name);
+    }
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let mut all_instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+        COMPUTE_UNIT_LIMIT,
+    )];
+    all_instructions.extend_from_slice(instructions);
+    let transaction = Transaction::new_signed_with_payer(
+        &all_instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    if let Err(error) = result.result {
+        println!(
+            "{} failed with {} validators: {:?}",
+            name, report.validators, error
+        );
+        report.entries.push((name, None));
+        return false;
+    }
+    match stake_pool_compute_units(&result.metadata.unwrap().log_messages) {
+        Some(units) => report.entries.push((name, Some(units))),
+        None => report.metered = false,
+    }
+    true
+}
+
+async fn warp_to_next_epoch(context: &mut ProgramTestContext) {
+    let clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let epoch_schedule = &context.genesis_config().epoch_schedule;
+    let slot = epoch_schedule.get_first_slot_in_epoch(clock.epoch + 1) + 1;
+    context.warp_to_slot(slot).unwrap();
+    context.last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+}
+
+/// Builds a pool holding `num_validators` active validators, with room for
+/// one more, which is added and removed during the benchmark
+async fn setup(num_validators: u32) -> (ProgramTestContext, StakePoolAccounts, Vec<Pubkey>) {
+    let max_validators = num_validators + 1;
+    let mut program_test = program_test();
+    let stake_pool_accounts = StakePoolAccounts {
+        max_validators,
+        compute_unit_limit: Some(COMPUTE_UNIT_LIMIT),
+        ..Default::default()
+    };
+
+    let stake_pool_pubkey = stake_pool_accounts.stake_pool.pubkey();
+    let (mut stake_pool, mut validator_list) = stake_pool_accounts.state();
+    stake_pool.last_update_epoch = FIRST_NORMAL_EPOCH;
+
+    let vote_account_pubkeys: Vec<Pubkey> = (0..max_validators)
+        .map(|_| add_vote_account(&mut program_test))
+        .collect();
+    for vote_account_address in vote_account_pubkeys.iter().take(num_validators as usize) {
+        add_validator_stake_account(
+            &mut program_test,
+            &mut stake_pool,
+            &mut validator_list,
+            &stake_pool_pubkey,
+            &stake_pool_accounts.withdraw_authority,
+            vote_account_address,
+            STAKE_AMOUNT,
+            StakeStatus::Active,
+        );
+    }
+
+    add_reserve_stake_account(
+        &mut program_test,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        STAKE_AMOUNT,
+    );
+    add_stake_pool_account(&mut program_test, &stake_pool_pubkey, &stake_pool);
+    add_validator_list_account(
+        &mut program_test,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &validator_list,
+        max_validators,
+    );
+    add_mint_account(
+        &mut program_test,
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        stake_pool.pool_token_supply,
+    );
+    add_token_account(
+        &mut program_test,
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let epoch_schedule = &context.genesis_config().epoch_schedule;
+    let slot = epoch_schedule.first_normal_slot + epoch_schedule.slots_per_epoch + 1;
+    context.warp_to_slot(slot).unwrap();
+
+    (context, stake_pool_accounts, vote_account_pubkeys)
+}
+
+/// Marks every validator as updated for the current epoch, so that the pool
+/// balance can be updated without sending an update for every chunk
+async fn mark_validator_list_updated(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) {
+    let clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let mut account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let mut validator_list = try_from_slice_unchecked::<ValidatorList>(&account.data).unwrap();
+    for validator in validator_list.validators.iter_mut() {
+        validator.last_update_epoch = clock.epoch.into();
+    }
+    let data = borsh::to_vec(&validator_list).unwrap();
+    account.data[..data.len()].copy_from_slice(&data);
+    context.set_account(
+        &stake_pool_accounts.validator_list.pubkey(),
+        &AccountSharedData::from(account),
+    );
+}
+
+/// Benchmarks the instructions that only work on a pool without validators or
+/// pool tokens, using a validator list sized for `max_validators`
+async fn benchmark_empty_pool(report: &mut Report, max_validators: u32) {
+    let mut context = program_test_with_metadata_program()
+        .start_with_context()
+        .await;
+    let stake_pool_accounts = StakePoolAccounts {
+        max_validators,
+        ..Default::default()
+    };
+    create_mint(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_mint,
+        &stake_pool_accounts.withdraw_authority,
+        stake_pool_accounts.pool_decimals,
+        &[],
+    )
+    .await
+    .unwrap();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_fee_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.manager,
+        &[],
+    )
+    .await
+    .unwrap();
+    create_independent_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.reserve_stake,
+        &stake::state::Authorized {
+            staker: stake_pool_accounts.withdraw_authority,
+            withdrawer: stake_pool_accounts.withdraw_authority,
+        },
+        &stake::state::Lockup::default(),
+        MINIMUM_RESERVE_LAMPORTS,
+    )
+    .await;
+
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let validator_list_len = ValidatorList::calculate_len(max_validators);
+    let stake_pool_pubkey = stake_pool_accounts.stake_pool.pubkey();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &stake_pool_pubkey,
+            rent.minimum_balance(StakePool::account_len()),
+            StakePool::account_len() as u64,
+            &id(),
+        ),
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            rent.minimum_balance(validator_list_len),
+            validator_list_len as u64,
+            &id(),
+        ),
+        instruction::initialize(
+            &id(),
+            &stake_pool_pubkey,
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.staker.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.token_program_id,
+            None,
+            stake_pool_accounts.epoch_fee,
+            stake_pool_accounts.withdrawal_fee,
+            stake_pool_accounts.deposit_fee,
+            stake_pool_accounts.referral_fee,
+            max_validators,
+        ),
+    ];
+    let initialized = measure(
+        &mut context,
+        report,
+        "Initialize",
+        &instructions,
+        &[
+            &stake_pool_accounts.stake_pool,
+            &stake_pool_accounts.validator_list,
+            &stake_pool_accounts.manager,
+        ],
+    )
+    .await;
+    if !initialized {
+        return;
+    }
+
+    let payer = context.payer.pubkey();
+    measure(
+        &mut context,
+        report,
+        "CreateTokenMetadata",
+        &[instruction::create_token_metadata(
+            &id(),
+            &stake_pool_pubkey,
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &payer,
+            "name".to_string(),
+            "SYM".to_string(),
+            "uri".to_string(),
+        )],
+        &[&stake_pool_accounts.manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        report,
+        "UpdateTokenMetadata",
+        &[instruction::update_token_metadata(
+            &id(),
+            &stake_pool_pubkey,
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            "new_name".to_string(),
+            "NEW".to_string(),
+            "new_uri".to_string(),
+        )],
+        &[&stake_pool_accounts.manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        report,
+        "ClosePool",
+        &[instruction::close_pool(
+            &id(),
+            &stake_pool_pubkey,
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &payer,
+            &stake_pool_accounts.token_program_id,
+            None,
+        )],
+        &[&stake_pool_accounts.manager],
+    )
+    .await;
+}
+
+/// Creates a stake account owned by `user`, delegated to `vote_account`
+async fn create_deposit_stake(
+    context: &mut ProgramTestContext,
+    user: &Keypair,
+    vote_account: &Pubkey,
+) -> Pubkey {
+    let deposit_stake = Keypair::new();
+    create_independent_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &deposit_stake,
+        &stake::state::Authorized {
+            staker: user.pubkey(),
+            withdrawer: user.pubkey(),
+        },
+        &stake::state::Lockup::default(),
+        TEST_STAKE_AMOUNT,
+    )
+    .await;
+    delegate_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &deposit_stake.pubkey(),
+        user,
+        vote_account,
+    )
+    .await;
+    deposit_stake.pubkey()
+}
+
+async fn create_blank_stake(context: &mut ProgramTestContext) -> Pubkey {
+    let stake = Keypair::new();
+    create_blank_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake,
+    )
+    .await;
+    stake.pubkey()
+}
+
+#[test_case(1; "1")]
+#[test_case(100; "100")]
+#[test_case(1_000; "1000")]
+#[test_case(20_000; "20000")]
+#[tokio::test]
+async fn compute_units(num_validators: u32) {
+    let mut report = Report::new(num_validators);
+    benchmark_empty_pool(&mut report, num_validators).await;
+    if !report.metered {
+        println!("Compute units are only metered for BPF programs, run with `cargo test-sbf`");
+        return;
+    }
+
+    let (mut context, stake_pool_accounts, vote_account_pubkeys) = setup(num_validators).await;
+    let stake_pool_pubkey = stake_pool_accounts.stake_pool.pubkey();
+    let validator_list_pubkey = stake_pool_accounts.validator_list.pubkey();
+    let reserve_stake_pubkey = stake_pool_accounts.reserve_stake.pubkey();
+    let pool_mint_pubkey = stake_pool_accounts.pool_mint.pubkey();
+    let pool_fee_pubkey = stake_pool_accounts.pool_fee_account.pubkey();
+    let withdraw_authority = stake_pool_accounts.withdraw_authority;
+    let token_program_id = stake_pool_accounts.token_program_id;
+    let manager = &stake_pool_accounts.manager;
+    let staker = &stake_pool_accounts.staker;
+    let minimum_delegation = stake_pool_get_minimum_delegation(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+    )
+    .await;
+
+    let first_vote = vote_account_pubkeys[0];
+    let (first_stake, _) = find_stake_program_address(&id(), &first_vote, &stake_pool_pubkey, None);
+
+    // Configuration
+    measure(
+        &mut context,
+        &mut report,
+        "SetPreferredValidator",
+        &[instruction::set_preferred_validator(
+            &id(),
+            &stake_pool_pubkey,
+            &staker.pubkey(),
+            &validator_list_pubkey,
+            PreferredValidatorType::Deposit,
+            Some(first_vote),
+        )],
+        &[staker],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "SetFee",
+        &[instruction::set_fee(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            FeeType::SolDeposit(stake_pool_accounts.sol_deposit_fee),
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "SetStaker",
+        &[instruction::set_staker(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            &staker.pubkey(),
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "SetFundingAuthority",
+        &[instruction::set_funding_authority(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            None,
+            FundingType::SolDeposit,
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "SetManager",
+        &[instruction::set_manager(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            &manager.pubkey(),
+            &pool_fee_pubkey,
+        )],
+        &[manager],
+    )
+    .await;
+
+    // Deposits and withdrawals
+    let user = Keypair::new();
+    let pool_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &token_program_id,
+        &pool_token_account,
+        &pool_mint_pubkey,
+        &user,
+        &[],
+    )
+    .await
+    .unwrap();
+    let pool_token_pubkey = pool_token_account.pubkey();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        LAMPORTS_PER_SOL,
+    )
+    .await;
+
+    let payer = context.payer.pubkey();
+    measure(
+        &mut context,
+        &mut report,
+        "DepositSol",
+        &[instruction::deposit_sol(
+            &id(),
+            &stake_pool_pubkey,
+            &withdraw_authority,
+            &reserve_stake_pubkey,
+            &payer,
+            &pool_token_pubkey,
+            &pool_fee_pubkey,
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+            TEST_STAKE_AMOUNT,
+        )],
+        &[],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "DepositSolWithSlippage",
+        &[instruction::deposit_sol_with_slippage(
+            &id(),
+            &stake_pool_pubkey,
+            &withdraw_authority,
+            &reserve_stake_pubkey,
+            &payer,
+            &pool_token_pubkey,
+            &pool_fee_pubkey,
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+            TEST_STAKE_AMOUNT,
+            0,
+        )],
+        &[],
+    )
+    .await;
+
+    let deposit_stake = create_deposit_stake(&mut context, &user, &first_vote).await;
+    measure(
+        &mut context,
+        &mut report,
+        "DepositStake",
+        &instruction::deposit_stake(
+            &id(),
+            &stake_pool_pubkey,
+            &validator_list_pubkey,
+            &withdraw_authority,
+            &deposit_stake,
+            &user.pubkey(),
+            &first_stake,
+            &reserve_stake_pubkey,
+            &pool_token_pubkey,
+            &pool_fee_pubkey,
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+        ),
+        &[&user],
+    )
+    .await;
+    let deposit_stake = create_deposit_stake(&mut context, &user, &first_vote).await;
+    measure(
+        &mut context,
+        &mut report,
+        "DepositStakeWithSlippage",
+        &instruction::deposit_stake_with_slippage(
+            &id(),
+            &stake_pool_pubkey,
+            &validator_list_pubkey,
+            &withdraw_authority,
+            &deposit_stake,
+            &user.pubkey(),
+            &first_stake,
+            &reserve_stake_pubkey,
+            &pool_token_pubkey,
+            &pool_fee_pubkey,
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+            0,
+        ),
+        &[&user],
+    )
+    .await;
+
+    let pool_tokens_to_withdraw = TEST_STAKE_AMOUNT / 2;
+    measure(
+        &mut context,
+        &mut report,
+        "WithdrawSol",
+        &[instruction::withdraw_sol(
+            &id(),
+            &stake_pool_pubkey,
+            &withdraw_authority,
+            &user.pubkey(),
+            &pool_token_pubkey,
+            &reserve_stake_pubkey,
+            &user.pubkey(),
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+            pool_tokens_to_withdraw,
+        )],
+        &[&user],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "WithdrawSolWithSlippage",
+        &[instruction::withdraw_sol_with_slippage(
+            &id(),
+            &stake_pool_pubkey,
+            &withdraw_authority,
+            &user.pubkey(),
+            &pool_token_pubkey,
+            &reserve_stake_pubkey,
+            &user.pubkey(),
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+            pool_tokens_to_withdraw,
+            0,
+        )],
+        &[&user],
+    )
+    .await;
+
+    let pool_tokens_to_withdraw = TEST_STAKE_AMOUNT.max(minimum_delegation * 2);
+    let stake_recipient = create_blank_stake(&mut context).await;
+    measure(
+        &mut context,
+        &mut report,
+        "WithdrawStake",
+        &[instruction::withdraw_stake(
+            &id(),
+            &stake_pool_pubkey,
+            &validator_list_pubkey,
+            &withdraw_authority,
+            &first_stake,
+            &stake_recipient,
+            &user.pubkey(),
+            &user.pubkey(),
+            &pool_token_pubkey,
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+            pool_tokens_to_withdraw,
+        )],
+        &[&user],
+    )
+    .await;
+    let stake_recipient = create_blank_stake(&mut context).await;
+    measure(
+        &mut context,
+        &mut report,
+        "WithdrawStakeWithSlippage",
+        &[instruction::withdraw_stake_with_slippage(
+            &id(),
+            &stake_pool_pubkey,
+            &validator_list_pubkey,
+            &withdraw_authority,
+            &first_stake,
+            &stake_recipient,
+            &user.pubkey(),
+            &user.pubkey(),
+            &pool_token_pubkey,
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+            pool_tokens_to_withdraw,
+            0,
+        )],
+        &[&user],
+    )
+    .await;
+
+    // Validator management, on the spare vote account at the end of the list
+    let new_vote = vote_account_pubkeys[num_validators as usize];
+    let (new_stake, _) = find_stake_program_address(&id(), &new_vote, &stake_pool_pubkey, None);
+    let (new_transient_stake, _) =
+        find_transient_stake_program_address(&id(), &new_vote, &stake_pool_pubkey, 0);
+    let added = measure(
+        &mut context,
+        &mut report,
+        "AddValidatorToPool",
+        &[instruction::add_validator_to_pool(
+            &id(),
+            &stake_pool_pubkey,
+            &staker.pubkey(),
+            &reserve_stake_pubkey,
+            &withdraw_authority,
+            &validator_list_pubkey,
+            &new_stake,
+            &new_vote,
+            None,
+        )],
+        &[staker],
+    )
+    .await;
+    if added {
+        measure(
+            &mut context,
+            &mut report,
+            "RemoveValidatorFromPool",
+            &[instruction::remove_validator_from_pool(
+                &id(),
+                &stake_pool_pubkey,
+                &staker.pubkey(),
+                &withdraw_authority,
+                &validator_list_pubkey,
+                &new_stake,
+                &new_transient_stake,
+            )],
+            &[staker],
+        )
+        .await;
+    }
+
+    // Epoch update
+    warp_to_next_epoch(&mut context).await;
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    let update_len = validator_list
+        .validators
+        .len()
+        .min(MAX_VALIDATORS_TO_UPDATE);
+    measure(
+        &mut context,
+        &mut report,
+        "UpdateValidatorListBalance",
+        &[instruction::update_validator_list_balance_chunk(
+            &id(),
+            &stake_pool_pubkey,
+            &withdraw_authority,
+            &validator_list_pubkey,
+            &reserve_stake_pubkey,
+            &validator_list,
+            update_len,
+            0,
+            /* no_merge = */ false,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await;
+    // the removed validator, at the end of the list, needs a real update to be
+    // cleaned up
+    let last_index = validator_list.validators.len() - 1;
+    if added && last_index >= update_len {
+        let mut instructions = vec![instruction::update_validator_list_balance_chunk(
+            &id(),
+            &stake_pool_pubkey,
+            &withdraw_authority,
+            &validator_list_pubkey,
+            &reserve_stake_pubkey,
+            &validator_list,
+            1,
+            last_index,
+            /* no_merge = */ false,
+        )
+        .unwrap()];
+        stake_pool_accounts.maybe_add_compute_budget_instruction(&mut instructions);
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&context.payer.pubkey()),
+            &[&context.payer],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+    mark_validator_list_updated(&mut context, &stake_pool_accounts).await;
+    measure(
+        &mut context,
+        &mut report,
+        "UpdateStakePoolBalance",
+        &[instruction::update_stake_pool_balance(
+            &id(),
+            &stake_pool_pubkey,
+            &withdraw_authority,
+            &validator_list_pubkey,
+            &reserve_stake_pubkey,
+            &pool_fee_pubkey,
+            &pool_mint_pubkey,
+            &token_program_id,
+        )],
+        &[],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "CleanupRemovedValidatorEntries",
+        &[instruction::cleanup_removed_validator_entries(
+            &id(),
+            &stake_pool_pubkey,
+            &validator_list_pubkey,
+        )],
+        &[],
+    )
+    .await;
+
+    // Rebalancing, with each direction on a different validator
+    let rebalance_lamports = minimum_delegation + LAMPORTS_PER_SOL;
+    let transient_stake_seed = 0;
+    let ephemeral_stake_seed = 0;
+    let (ephemeral_stake, _) =
+        find_ephemeral_stake_program_address(&id(), &stake_pool_pubkey, ephemeral_stake_seed);
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    let validator_accounts: Vec<(Pubkey, Pubkey, Pubkey)> = validator_list
+        .validators
+        .iter()
+        .take(3)
+        .map(|validator| {
+            let (stake, _) = find_stake_program_address(
+                &id(),
+                &validator.vote_account_address,
+                &stake_pool_pubkey,
+                NonZeroU32::new(validator.validator_seed_suffix.into()),
+            );
+            let (transient_stake, _) = find_transient_stake_program_address(
+                &id(),
+                &validator.vote_account_address,
+                &stake_pool_pubkey,
+                transient_stake_seed,
+            );
+            (validator.vote_account_address, stake, transient_stake)
+        })
+        .collect();
+
+    if let Some((vote, stake, transient_stake)) = validator_accounts.first() {
+        measure(
+            &mut context,
+            &mut report,
+            "IncreaseValidatorStake",
+            &[instruction::increase_validator_stake(
+                &id(),
+                &stake_pool_pubkey,
+                &staker.pubkey(),
+                &withdraw_authority,
+                &validator_list_pubkey,
+                &reserve_stake_pubkey,
+                transient_stake,
+                stake,
+                vote,
+                rebalance_lamports,
+                transient_stake_seed,
+            )],
+            &[staker],
+        )
+        .await;
+        measure(
+            &mut context,
+            &mut report,
+            "IncreaseAdditionalValidatorStake",
+            &[instruction::increase_additional_validator_stake(
+                &id(),
+                &stake_pool_pubkey,
+                &staker.pubkey(),
+                &withdraw_authority,
+                &validator_list_pubkey,
+                &reserve_stake_pubkey,
+                &ephemeral_stake,
+                transient_stake,
+                stake,
+                vote,
+                rebalance_lamports,
+                transient_stake_seed,
+                ephemeral_stake_seed,
+            )],
+            &[staker],
+        )
+        .await;
+    }
+    if let Some((_, stake, transient_stake)) = validator_accounts.get(1) {
+        measure(
+            &mut context,
+            &mut report,
+            "DecreaseValidatorStakeWithReserve",
+            &[instruction::decrease_validator_stake_with_reserve(
+                &id(),
+                &stake_pool_pubkey,
+                &staker.pubkey(),
+                &withdraw_authority,
+                &validator_list_pubkey,
+                &reserve_stake_pubkey,
+                stake,
+                transient_stake,
+                rebalance_lamports,
+                transient_stake_seed,
+            )],
+            &[staker],
+        )
+        .await;
+        measure(
+            &mut context,
+            &mut report,
+            "DecreaseAdditionalValidatorStake",
+            &[instruction::decrease_additional_validator_stake(
+                &id(),
+                &stake_pool_pubkey,
+                &staker.pubkey(),
+                &withdraw_authority,
+                &validator_list_pubkey,
+                &reserve_stake_pubkey,
+                stake,
+                &ephemeral_stake,
+                transient_stake,
+                rebalance_lamports,
+                transient_stake_seed,
+                ephemeral_stake_seed,
+            )],
+            &[staker],
+        )
+        .await;
+    }
+    if let Some((_, stake, transient_stake)) = validator_accounts.get(2) {
+        #[allow(deprecated)]
+        let decrease = instruction::decrease_validator_stake(
+            &id(),
+            &stake_pool_pubkey,
+            &staker.pubkey(),
+            &withdraw_authority,
+            &validator_list_pubkey,
+            stake,
+            transient_stake,
+            rebalance_lamports,
+            transient_stake_seed,
+        );
+        measure(
+            &mut context,
+            &mut report,
+            "DecreaseValidatorStake",
+            &[decrease],
+            &[staker],
+        )
+        .await;
+    }
+
+    // Account maintenance
+    let max_validators = stake_pool_accounts.max_validators;
+    measure(
+        &mut context,
+        &mut report,
+        "ResizeValidatorList",
+        &[instruction::resize_validator_list(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            &validator_list_pubkey,
+            &payer,
+            max_validators + 1,
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "ShrinkValidatorList",
+        &[instruction::shrink_validator_list(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            &validator_list_pubkey,
+            &payer,
+            max_validators,
+        )],
+        &[manager],
+    )
+    .await;
+    // the pool was written by the test helpers without room for its config,
+    // so this performs a full migration
+    measure(
+        &mut context,
+        &mut report,
+        "MigratePool",
+        &[instruction::migrate_pool(&id(), &stake_pool_pubkey, &payer)],
+        &[],
+    )
+    .await;
+
+    // Instructions that require the migrated pool
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    measure(
+        &mut context,
+        &mut report,
+        "UpdateValidatorBalance",
+        &[instruction::update_validator_balance(
+            &id(),
+            &stake_pool_pubkey,
+            &withdraw_authority,
+            &validator_list_pubkey,
+            &reserve_stake_pubkey,
+            &validator_list,
+            0,
+            /* no_merge = */ false,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "SetPartialUpdateHaircut",
+        &[instruction::set_partial_update_haircut(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            Some(Fee {
+                numerator: 1,
+                denominator: 100,
+            }),
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "SetTransferHookProgramIds",
+        &[instruction::set_transfer_hook_program_ids(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            vec![Pubkey::new_unique()],
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "SetDepositorAllowlist",
+        &[instruction::set_depositor_allowlist(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            true,
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "AddDepositor",
+        &[instruction::add_depositor(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            &payer,
+            &user.pubkey(),
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "RemoveDepositor",
+        &[instruction::remove_depositor(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            &user.pubkey(),
+            &payer,
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "SetDepositorMerkleRoot",
+        &[instruction::set_depositor_merkle_root(
+            &id(),
+            &stake_pool_pubkey,
+            &manager.pubkey(),
+            Some([1; 32]),
+        )],
+        &[manager],
+    )
+    .await;
+    measure(
+        &mut context,
+        &mut report,
+        "CreateDepositorRecord",
+        &[instruction::create_depositor_record(
+            &id(),
+            &stake_pool_pubkey,
+            &payer,
+            &user.pubkey(),
+        )],
+        &[],
+    )
+    .await;
+
+    report.write_and_check();
+}
+
+#[test]
+fn regressions() {
+    let mut report = Report::new(100);
+    report.entries = vec![
+        ("DepositSol", Some(105)),
+        ("WithdrawSol", Some(106)),
+        ("DepositStake", None),
+        ("SetFee", Some(1)),
+        ("SetStaker", None),
+    ];
+    let baseline = format!(
+        "{}\nDepositSol,100,100\nWithdrawSol,100,100\nDepositStake,100,100\nSetStaker,100,{}\n",
+        REPORT_HEADER, FAILED
+    );
+    assert_eq!(
+        report.regressions(&baseline),
+        vec![
+            "WithdrawSol: 106 compute units, baseline 100".to_string(),
+            "DepositStake: failed, baseline 100 compute units".to_string(),
+        ]
+    );
+    assert_eq!(parse_csv(&report.to_csv()).len(), report.entries.len());
+}
+
+#[test]
+fn instruction_names_cover_every_variant() {
+    let names = instruction_names();
+    assert_eq!(names.first(), Some(&"Initialize"));
+    assert_eq!(names.last(), Some(&"CreateDepositorRecord"));
+    assert_eq!(names.iter().collect::<BTreeSet<_>>().len(), names.len());
+
+    let mut report = Report::new(1);
+    report.entries = vec![("Initialize", Some(1)), ("SetFee", None)];
+    let missing = report.missing_instructions();
+    assert!(!missing.contains(&"Initialize"));
+    assert!(!missing.contains(&"SetFee"));
+    assert!(!missing.contains(&"Redelegate"));
+    assert_eq!(missing.len(), names.len() - 3);
+}
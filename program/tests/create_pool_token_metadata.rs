@@ -11,9 +11,12 @@ use {
         transaction::{Transaction, TransactionError},
     },
     spl_stake_pool::{
-        error::StakePoolError::{AlreadyInUse, SignatureMissing, WrongManager},
+        error::StakePoolError::{
+            AlreadyInUse, InvalidMetadataAccount, SignatureMissing, WrongManager,
+        },
         instruction, MINIMUM_RESERVE_LAMPORTS,
     },
+    spl_token_2022_interface::extension::ExtensionType,
     test_case::test_case,
 };
 
@@ -270,3 +273,109 @@ async fn fail_create_metadata_twice() {
         _ => panic!("Wrong error occurs while trying to create pool token metadata twice"),
     }
 }
+
+async fn setup_token_2022() -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts =
+        StakePoolAccounts::new_with_token_program(spl_token_2022_interface::id());
+    stake_pool_accounts
+        .initialize_stake_pool_with_mint_extensions(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+            &[ExtensionType::MetadataPointer],
+        )
+        .await
+        .unwrap();
+
+    (context, stake_pool_accounts)
+}
+
+#[tokio::test]
+async fn success_token_2022_metadata() {
+    let (mut context, stake_pool_accounts) = setup_token_2022().await;
+
+    let name = "test_name";
+    let symbol = "SYM";
+    let uri = "test_uri";
+
+    let ix = instruction::create_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &context.payer.pubkey(),
+        name.to_string(),
+        symbol.to_string(),
+        uri.to_string(),
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let metadata = get_token_2022_metadata(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+
+    assert_eq!(metadata.name, name);
+    assert_eq!(metadata.symbol, symbol);
+    assert_eq!(metadata.uri, uri);
+    assert_eq!(metadata.mint, stake_pool_accounts.pool_mint.pubkey());
+    assert_eq!(
+        Option::<Pubkey>::from(metadata.update_authority),
+        Some(stake_pool_accounts.withdraw_authority)
+    );
+}
+
+#[tokio::test]
+async fn fail_token_2022_wrong_metadata_account() {
+    let (context, stake_pool_accounts) = setup_token_2022().await;
+
+    let mut ix = instruction::create_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &context.payer.pubkey(),
+        "test_name".to_string(),
+        "SYM".to_string(),
+        "test_uri".to_string(),
+    );
+    ix.accounts[5].pubkey = Pubkey::new_unique();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(InvalidMetadataAccount as u32)
+        )
+    );
+}
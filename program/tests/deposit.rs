@@ -2,6 +2,7 @@
 mod helpers;
 
 use {
+    borsh::BorshDeserialize,
     helpers::*,
     solana_program::{
         borsh1::try_from_slice_unchecked,
@@ -462,6 +463,113 @@ async fn success_with_extra_stake_lamports() {
     );
 }
 
+#[tokio::test]
+async fn success_return_data() {
+    let (
+        mut context,
+        stake_pool_accounts,
+        validator_stake_account,
+        user,
+        deposit_stake,
+        pool_token_account,
+        stake_lamports,
+    ) = setup(spl_token_interface::id()).await;
+
+    let referrer = Keypair::new();
+    let referrer_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &referrer_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &referrer,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let manager_pool_balance_pre = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &instruction::deposit_stake(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &deposit_stake,
+            &user.pubkey(),
+            &validator_stake_account.stake_account,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &pool_token_account,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &referrer_token_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &stake_pool_accounts.token_program_id,
+        ),
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user],
+        context.last_blockhash,
+    );
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok(), "{:?}", result.result);
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, id());
+    let return_data =
+        instruction::DepositWithdrawReturnData::try_from_slice(&return_data.data).unwrap();
+
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake::state::StakeStateV2>());
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool =
+        try_from_slice_unchecked::<state::StakePool>(stake_pool.data.as_slice()).unwrap();
+    let fee_tokens = stake_pool
+        .calc_pool_tokens_sol_deposit_fee(stake_rent)
+        .unwrap()
+        + stake_pool
+            .calc_pool_tokens_stake_deposit_fee(stake_lamports - stake_rent)
+            .unwrap();
+    let referral_fee = stake_pool_accounts.calculate_referral_fee(fee_tokens);
+    assert!(referral_fee > 0);
+    assert_eq!(
+        return_data,
+        instruction::DepositWithdrawReturnData {
+            amount_out: stake_lamports - fee_tokens,
+            fee: fee_tokens - referral_fee,
+            referral_fee,
+        }
+    );
+
+    let user_token_balance =
+        get_token_balance(&mut context.banks_client, &pool_token_account).await;
+    assert_eq!(user_token_balance, return_data.amount_out);
+    let referrer_balance =
+        get_token_balance(&mut context.banks_client, &referrer_token_account.pubkey()).await;
+    assert_eq!(referrer_balance, return_data.referral_fee);
+    let manager_pool_balance_post = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(
+        manager_pool_balance_post - manager_pool_balance_pre,
+        return_data.fee
+    );
+}
+
 #[tokio::test]
 async fn fail_with_wrong_stake_program_id() {
     let (
@@ -2,6 +2,7 @@
 mod helpers;
 
 use {
+    borsh::BorshDeserialize,
     helpers::*,
     solana_program::{
         borsh1::try_from_slice_unchecked, instruction::InstructionError, pubkey::Pubkey,
@@ -463,6 +464,74 @@ async fn success_with_referral_fee() {
     assert_eq!(referrer_balance_pre + referral_fee, referrer_balance_post);
 }
 
+#[tokio::test]
+async fn success_return_data() {
+    let (mut context, stake_pool_accounts, _user, pool_token_account) =
+        setup(spl_token_interface::id()).await;
+
+    let referrer = Keypair::new();
+    let referrer_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &referrer_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &referrer,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::deposit_sol(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &context.payer.pubkey(),
+            &pool_token_account,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &referrer_token_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token_interface::id(),
+            TEST_STAKE_AMOUNT,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok(), "{:?}", result.result);
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, id());
+    let return_data =
+        instruction::DepositWithdrawReturnData::try_from_slice(&return_data.data).unwrap();
+
+    let deposit_fee = stake_pool_accounts.calculate_sol_deposit_fee(TEST_STAKE_AMOUNT);
+    let referral_fee = stake_pool_accounts.calculate_sol_referral_fee(deposit_fee);
+    assert!(referral_fee > 0);
+    assert_eq!(
+        return_data,
+        instruction::DepositWithdrawReturnData {
+            amount_out: TEST_STAKE_AMOUNT - deposit_fee,
+            fee: deposit_fee - referral_fee,
+            referral_fee,
+        }
+    );
+    let user_token_balance =
+        get_token_balance(&mut context.banks_client, &pool_token_account).await;
+    assert_eq!(user_token_balance, return_data.amount_out);
+    let referrer_balance =
+        get_token_balance(&mut context.banks_client, &referrer_token_account.pubkey()).await;
+    assert_eq!(referrer_balance, return_data.referral_fee);
+}
+
 #[tokio::test]
 async fn fail_with_invalid_referrer() {
     let (context, stake_pool_accounts, _user, pool_token_account) =
@@ -0,0 +1,518 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        borsh1::try_from_slice_unchecked,
+        hash::Hash,
+        instruction::{AccountMeta, InstructionError},
+        pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        find_depositor_allowlist_entry_address, id, instruction,
+        state::{AccountType, DepositorAllowlistEntry},
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Keypair, Keypair) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    let pool_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &pool_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_depositor_allowlist(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            true,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    (context, stake_pool_accounts, user, pool_token_account)
+}
+
+async fn add_depositor(
+    context: &mut ProgramTestContext,
+    last_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    manager: &Keypair,
+    depositor: &Pubkey,
+) -> Result<(), TransactionError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::add_depositor(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &manager.pubkey(),
+            &context.payer.pubkey(),
+            depositor,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, manager],
+        *last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+async fn deposit_sol(
+    context: &mut ProgramTestContext,
+    last_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    pool_token_account: &Pubkey,
+    entry_depositor: Option<&Pubkey>,
+) -> Result<(), TransactionError> {
+    let mut deposit_instruction = instruction::deposit_sol(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &context.payer.pubkey(),
+        pool_token_account,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.token_program_id,
+        TEST_STAKE_AMOUNT,
+    );
+    if let Some(entry_depositor) = entry_depositor {
+        instruction::add_depositor_allowlist_entry(
+            &mut deposit_instruction,
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            entry_depositor,
+        );
+    }
+    let transaction = Transaction::new_signed_with_payer(
+        &[deposit_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        *last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+#[tokio::test]
+async fn success_add_and_deposit_sol() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let last_blockhash = context.last_blockhash;
+    add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let (entry_address, _) = find_depositor_allowlist_entry_address(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &user.pubkey(),
+    );
+    let entry_account = get_account(&mut context.banks_client, &entry_address).await;
+    assert_eq!(entry_account.owner, id());
+    let entry = try_from_slice_unchecked::<DepositorAllowlistEntry>(&entry_account.data).unwrap();
+    assert_eq!(
+        entry,
+        DepositorAllowlistEntry {
+            account_type: AccountType::DepositorAllowlistEntry,
+            stake_pool: stake_pool_accounts.stake_pool.pubkey(),
+            depositor: user.pubkey(),
+        }
+    );
+
+    deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&user.pubkey()),
+    )
+    .await
+    .unwrap();
+    let pool_tokens =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+    assert_eq!(
+        pool_tokens,
+        TEST_STAKE_AMOUNT - stake_pool_accounts.calculate_sol_deposit_fee(TEST_STAKE_AMOUNT)
+    );
+}
+
+#[tokio::test]
+async fn success_remove() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let last_blockhash = context.last_blockhash;
+    add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let destination = Pubkey::new_unique();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::remove_depositor(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &user.pubkey(),
+            &destination,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (entry_address, _) = find_depositor_allowlist_entry_address(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &user.pubkey(),
+    );
+    assert!(context
+        .banks_client
+        .get_account(entry_address)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(
+        get_account(&mut context.banks_client, &destination)
+            .await
+            .lamports
+            > 0
+    );
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&user.pubkey()),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::DepositorNotAllowed as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_without_entry() {
+    let (mut context, stake_pool_accounts, _, pool_token_account) = setup().await;
+
+    let last_blockhash = context.last_blockhash;
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::DepositorNotAllowed as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_with_other_entry() {
+    let (mut context, stake_pool_accounts, _, pool_token_account) = setup().await;
+
+    let other_depositor = Pubkey::new_unique();
+    let last_blockhash = context.last_blockhash;
+    add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &other_depositor,
+    )
+    .await
+    .unwrap();
+
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&other_depositor),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::DepositorNotAllowed as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_with_entry_out_of_place() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let last_blockhash = context.last_blockhash;
+    add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    // the entry is only looked for at its fixed index
+    let mut deposit_instruction = instruction::deposit_sol(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &context.payer.pubkey(),
+        &pool_token_account.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.token_program_id,
+        TEST_STAKE_AMOUNT,
+    );
+    let (entry_address, _) = find_depositor_allowlist_entry_address(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &user.pubkey(),
+    );
+    deposit_instruction.accounts.extend([
+        AccountMeta::new_readonly(id(), false),
+        AccountMeta::new_readonly(id(), false),
+        AccountMeta::new_readonly(entry_address, false),
+    ]);
+    let transaction = Transaction::new_signed_with_payer(
+        &[deposit_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::DepositorNotAllowed as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn success_gate_applies_to_token_recipient() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    // the payer funding the deposits is allowed, the token recipient is not
+    let last_blockhash = context.last_blockhash;
+    let payer = context.payer.pubkey();
+    add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &payer,
+    )
+    .await
+    .unwrap();
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&payer),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::DepositorNotAllowed as u32)
+        )
+    );
+
+    // once the recipient is allowed, anyone may fund deposits on its behalf
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::remove_depositor(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &payer,
+            &payer,
+        )],
+        Some(&payer),
+        &[&context.payer, &stake_pool_accounts.manager],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&user.pubkey()),
+    )
+    .await
+    .unwrap();
+    assert!(get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await > 0);
+}
+
+#[tokio::test]
+async fn fail_add_twice() {
+    let (mut context, stake_pool_accounts, user, _) = setup().await;
+
+    let last_blockhash = context.last_blockhash;
+    add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    let error = add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::AlreadyInUse as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_add_wrong_manager() {
+    let (mut context, stake_pool_accounts, user, _) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let last_blockhash = context.last_blockhash;
+    let error = add_depositor(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &wrong_manager,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::WrongManager as u32)
+        )
+    );
+}
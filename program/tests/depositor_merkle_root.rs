@@ -0,0 +1,620 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        borsh1::try_from_slice_unchecked, hash::Hash, instruction::InstructionError, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        find_depositor_record_address, id,
+        instruction::{self, DepositorProof},
+        merkle::{self, MerkleTree},
+        state::{DepositorRecord, StakePool, StakePoolConfig},
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Keypair, Keypair) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    let pool_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &pool_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    (context, stake_pool_accounts, user, pool_token_account)
+}
+
+/// Tree of `depositor` with `cap` among a few other depositors, and the proof
+/// for `depositor`
+fn depositor_tree(depositor: &Pubkey, cap: u64) -> ([u8; 32], DepositorProof) {
+    let leaves = vec![
+        merkle::leaf_hash(&Pubkey::new_unique(), merkle::NO_CAP),
+        merkle::leaf_hash(&Pubkey::new_unique(), TEST_STAKE_AMOUNT),
+        merkle::leaf_hash(depositor, cap),
+        merkle::leaf_hash(&Pubkey::new_unique(), merkle::NO_CAP),
+        merkle::leaf_hash(&Pubkey::new_unique(), 1),
+    ];
+    let tree = MerkleTree::new(leaves);
+    (
+        tree.root().unwrap(),
+        DepositorProof {
+            cap,
+            proof: tree.proof(2).unwrap(),
+        },
+    )
+}
+
+async fn set_depositor_merkle_root(
+    context: &mut ProgramTestContext,
+    last_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    manager: &Keypair,
+    root: Option<[u8; 32]>,
+) -> Result<(), TransactionError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_depositor_merkle_root(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &manager.pubkey(),
+            root,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, manager],
+        *last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+async fn create_depositor_record(
+    context: &mut ProgramTestContext,
+    last_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    depositor: &Pubkey,
+) -> Result<(), TransactionError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::create_depositor_record(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &context.payer.pubkey(),
+            depositor,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        *last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+async fn get_deposited(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    depositor: &Pubkey,
+) -> u64 {
+    let (record_address, _) =
+        find_depositor_record_address(&id(), &stake_pool_accounts.stake_pool.pubkey(), depositor);
+    let record_account = get_account(&mut context.banks_client, &record_address).await;
+    try_from_slice_unchecked::<DepositorRecord>(&record_account.data)
+        .unwrap()
+        .deposited
+}
+
+/// Deposit `TEST_STAKE_AMOUNT` lamports, passing the depositor record of
+/// `depositor`, if any
+async fn deposit_sol(
+    context: &mut ProgramTestContext,
+    last_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    pool_token_account: &Pubkey,
+    depositor_proof: Option<&DepositorProof>,
+    depositor: Option<&Pubkey>,
+) -> Result<(), TransactionError> {
+    let mut deposit_instruction = instruction::deposit_sol(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &context.payer.pubkey(),
+        pool_token_account,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.token_program_id,
+        TEST_STAKE_AMOUNT,
+    );
+    if let Some(depositor_proof) = depositor_proof {
+        instruction::add_depositor_proof(&mut deposit_instruction, depositor_proof);
+    }
+    if let Some(depositor) = depositor {
+        instruction::add_depositor_record(
+            &mut deposit_instruction,
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            depositor,
+        );
+    }
+    let transaction = Transaction::new_signed_with_payer(
+        &[deposit_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        *last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+#[tokio::test]
+async fn success_deposit_sol_with_proof() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let (root, depositor_proof) = depositor_tree(&user.pubkey(), merkle::NO_CAP);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+
+    let stake_pool_account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_account.data).unwrap();
+    let config = StakePoolConfig::load(&stake_pool, &stake_pool_account.data).unwrap();
+    assert_eq!(config.depositor_merkle_root, Some(root));
+
+    deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&depositor_proof),
+        None,
+    )
+    .await
+    .unwrap();
+    let pool_tokens =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+    assert_eq!(
+        pool_tokens,
+        TEST_STAKE_AMOUNT - stake_pool_accounts.calculate_sol_deposit_fee(TEST_STAKE_AMOUNT)
+    );
+}
+
+#[tokio::test]
+async fn success_deposit_sol_under_cap() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let (root, depositor_proof) = depositor_tree(&user.pubkey(), TEST_STAKE_AMOUNT);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+    create_depositor_record(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&depositor_proof),
+        Some(&user.pubkey()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_deposited(&mut context, &stake_pool_accounts, &user.pubkey()).await,
+        TEST_STAKE_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn success_unset() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let (root, _) = depositor_tree(&user.pubkey(), merkle::NO_CAP);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        None,
+    )
+    .await
+    .unwrap();
+
+    deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_without_proof() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let (root, _) = depositor_tree(&user.pubkey(), merkle::NO_CAP);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        None,
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidDepositorProof as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_wrong_cap() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let (root, mut depositor_proof) = depositor_tree(&user.pubkey(), TEST_STAKE_AMOUNT);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+
+    depositor_proof.cap = merkle::NO_CAP;
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&depositor_proof),
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidDepositorProof as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_other_depositor() {
+    let (mut context, stake_pool_accounts, _, pool_token_account) = setup().await;
+
+    let (root, depositor_proof) = depositor_tree(&Pubkey::new_unique(), merkle::NO_CAP);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&depositor_proof),
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidDepositorProof as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_over_cap() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let (root, depositor_proof) = depositor_tree(&user.pubkey(), TEST_STAKE_AMOUNT / 2);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+    create_depositor_record(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&depositor_proof),
+        Some(&user.pubkey()),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::DepositorCapExceeded as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_over_cap_across_token_accounts() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let (root, depositor_proof) = depositor_tree(&user.pubkey(), TEST_STAKE_AMOUNT * 3 / 2);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+    create_depositor_record(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&depositor_proof),
+        Some(&user.pubkey()),
+    )
+    .await
+    .unwrap();
+
+    // the first account holding the pool tokens doesn't matter, the cap is on
+    // everything the depositor deposited
+    let other_pool_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &other_pool_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user,
+        &[],
+    )
+    .await
+    .unwrap();
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &other_pool_token_account.pubkey(),
+        Some(&depositor_proof),
+        Some(&user.pubkey()),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::DepositorCapExceeded as u32)
+        )
+    );
+    assert_eq!(
+        get_deposited(&mut context, &stake_pool_accounts, &user.pubkey()).await,
+        TEST_STAKE_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_without_record() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let (root, depositor_proof) = depositor_tree(&user.pubkey(), TEST_STAKE_AMOUNT);
+    let last_blockhash = context.last_blockhash;
+    set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(root),
+    )
+    .await
+    .unwrap();
+
+    let error = deposit_sol(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &pool_token_account.pubkey(),
+        Some(&depositor_proof),
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidDepositorRecord as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_proof_on_other_instruction() {
+    let (context, stake_pool_accounts, user, _) = setup().await;
+
+    let (root, depositor_proof) = depositor_tree(&user.pubkey(), merkle::NO_CAP);
+    let mut set_instruction = instruction::set_depositor_merkle_root(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        Some(root),
+    );
+    instruction::add_depositor_proof(&mut set_instruction, &depositor_proof);
+    let transaction = Transaction::new_signed_with_payer(
+        &[set_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (mut context, stake_pool_accounts, user, _) = setup().await;
+
+    let (root, _) = depositor_tree(&user.pubkey(), merkle::NO_CAP);
+    let wrong_manager = Keypair::new();
+    let last_blockhash = context.last_blockhash;
+    let error = set_depositor_merkle_root(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &wrong_manager,
+        Some(root),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::WrongManager as u32)
+        )
+    );
+}
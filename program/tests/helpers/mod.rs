@@ -4,7 +4,7 @@ use {
     borsh::BorshDeserialize,
     solana_compute_budget_interface::ComputeBudgetInstruction,
     solana_program::{
-        borsh1::{get_instance_packed_len, get_packed_len, try_from_slice_unchecked},
+        borsh1::{get_instance_packed_len, try_from_slice_unchecked},
         hash::Hash,
         instruction::Instruction,
         program_option::COption,
@@ -37,10 +37,11 @@ use {
         MAX_VALIDATORS_TO_UPDATE, MINIMUM_RESERVE_LAMPORTS,
     },
     spl_token_2022_interface::{
-        extension::{ExtensionType, StateWithExtensionsOwned},
+        extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensionsOwned},
         native_mint,
         state::{Account, Mint},
     },
+    spl_token_metadata_interface::state::TokenMetadata,
     std::{convert::TryInto, num::NonZeroU32},
 };
 
@@ -50,6 +51,7 @@ pub const MAX_TEST_VALIDATORS: u32 = 10_000;
 pub const DEFAULT_VALIDATOR_STAKE_SEED: Option<NonZeroU32> = NonZeroU32::new(1_010);
 pub const DEFAULT_TRANSIENT_STAKE_SEED: u64 = 42;
 pub const STAKE_ACCOUNT_RENT_EXEMPTION: u64 = 2_282_880;
+pub const TEST_TRANSFER_HOOK_PROGRAM_ID: Pubkey = Pubkey::new_from_array([7; 32]);
 const ACCOUNT_RENT_EXEMPTION: u64 = 1_000_000_000; // go with something big to be safe
 
 pub fn program_test() -> ProgramTest {
@@ -158,6 +160,24 @@ pub async fn create_mint(
             ),
             ExtensionType::NonTransferable =>
                 spl_token_2022_interface::instruction::initialize_non_transferable_mint(program_id, &mint_pubkey),
+            ExtensionType::ScaledUiAmount => spl_token_2022_interface::extension::scaled_ui_amount::instruction::initialize(
+                program_id,
+                &mint_pubkey,
+                Some(*manager),
+                1.0,
+            ),
+            ExtensionType::TransferHook => spl_token_2022_interface::extension::transfer_hook::instruction::initialize(
+                program_id,
+                &mint_pubkey,
+                Some(*manager),
+                Some(TEST_TRANSFER_HOOK_PROGRAM_ID),
+            ),
+            ExtensionType::MetadataPointer => spl_token_2022_interface::extension::metadata_pointer::instruction::initialize(
+                program_id,
+                &mint_pubkey,
+                Some(*manager),
+                Some(mint_pubkey),
+            ),
             _ => unimplemented!(),
         };
         instructions.push(instruction.unwrap());
@@ -271,7 +291,8 @@ pub async fn create_token_account(
             ExtensionType::TransferFeeAmount
             | ExtensionType::MemoTransfer
             | ExtensionType::CpiGuard
-            | ExtensionType::NonTransferableAccount => (),
+            | ExtensionType::NonTransferableAccount
+            | ExtensionType::TransferHookAccount => (),
             _ => unimplemented!(),
         };
     }
@@ -448,6 +469,15 @@ pub async fn burn_tokens(
         .map_err(|e| e.into())
 }
 
+pub async fn get_token_2022_metadata(
+    banks_client: &mut BanksClient,
+    pool_mint: &Pubkey,
+) -> TokenMetadata {
+    let mint_account = banks_client.get_account(*pool_mint).await.unwrap().unwrap();
+    let mint = StateWithExtensionsOwned::<Mint>::unpack(mint_account.data).unwrap();
+    mint.get_variable_len_extension::<TokenMetadata>().unwrap()
+}
+
 pub async fn get_token_balance(banks_client: &mut BanksClient, token: &Pubkey) -> u64 {
     let token_account = banks_client.get_account(*token).await.unwrap().unwrap();
     let account_info = StateWithExtensionsOwned::<Account>::unpack(token_account.data).unwrap();
@@ -559,7 +589,7 @@ pub async fn create_stake_pool(
     max_validators: u32,
 ) -> Result<(), TransportError> {
     let rent = banks_client.get_rent().await.unwrap();
-    let rent_stake_pool = rent.minimum_balance(get_packed_len::<state::StakePool>());
+    let rent_stake_pool = rent.minimum_balance(state::StakePool::account_len());
     let validator_list_size =
         get_instance_packed_len(&state::ValidatorList::new(max_validators)).unwrap();
     let rent_validator_list = rent.minimum_balance(validator_list_size);
@@ -570,7 +600,7 @@ pub async fn create_stake_pool(
                 &payer.pubkey(),
                 &stake_pool.pubkey(),
                 rent_stake_pool,
-                get_packed_len::<state::StakePool>() as u64,
+                state::StakePool::account_len() as u64,
                 &id(),
             ),
             system_instruction::create_account(
@@ -962,6 +992,24 @@ impl StakePoolAccounts {
         payer: &Keypair,
         recent_blockhash: &Hash,
         reserve_lamports: u64,
+    ) -> Result<(), TransportError> {
+        self.initialize_stake_pool_with_mint_extensions(
+            banks_client,
+            payer,
+            recent_blockhash,
+            reserve_lamports,
+            &[],
+        )
+        .await
+    }
+
+    pub async fn initialize_stake_pool_with_mint_extensions(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        reserve_lamports: u64,
+        mint_extensions: &[ExtensionType],
     ) -> Result<(), TransportError> {
         create_mint(
             banks_client,
@@ -971,7 +1019,7 @@ impl StakePoolAccounts {
             &self.pool_mint,
             &self.withdraw_authority,
             self.pool_decimals,
-            &[],
+            mint_extensions,
         )
         .await?;
         create_token_account(
@@ -982,7 +1030,7 @@ impl StakePoolAccounts {
             &self.pool_fee_account,
             &self.pool_mint.pubkey(),
             &self.manager,
-            &[],
+            &ExtensionType::get_required_init_account_extensions(mint_extensions),
         )
         .await?;
         create_independent_stake_account(
@@ -2008,7 +2056,7 @@ impl StakePoolAccounts {
         let (_, stake_withdraw_bump_seed) =
             find_withdraw_authority_program_address(&id(), &self.stake_pool.pubkey());
         let stake_pool = state::StakePool {
-            account_type: state::AccountType::StakePool,
+            account_type: state::AccountType::StakePoolV2,
             manager: self.manager.pubkey(),
             staker: self.staker.pubkey(),
             stake_deposit_authority: self.stake_deposit_authority,
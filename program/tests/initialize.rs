@@ -95,7 +95,7 @@ async fn success(token_program_id: Pubkey) {
 
     // Stake pool now exists
     let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
-    assert_eq!(stake_pool.data.len(), get_packed_len::<state::StakePool>());
+    assert_eq!(stake_pool.data.len(), state::StakePool::account_len());
     assert_eq!(stake_pool.owner, id());
 
     // Validator stake list storage initialized
@@ -0,0 +1,133 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::borsh1::try_from_slice_unchecked,
+    solana_program_test::*,
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        signature::Signer,
+        transaction::Transaction,
+    },
+    spl_stake_pool::{
+        id, instruction,
+        state::{AccountType, StakePool},
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    (context, stake_pool_accounts)
+}
+
+/// Rewrite the stake pool as an unmigrated pool, only large enough to hold its
+/// current serialization
+async fn set_v1_stake_pool(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> StakePool {
+    let mut stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    stake_pool.account_type = AccountType::StakePool;
+    let data = borsh::to_vec(&stake_pool).unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let account = AccountSharedData::from(Account {
+        lamports: rent.minimum_balance(data.len()),
+        data,
+        owner: id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    context.set_account(&stake_pool_accounts.stake_pool.pubkey(), &account);
+    stake_pool
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    let pre_stake_pool = set_v1_stake_pool(&mut context, &stake_pool_accounts).await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::migrate_pool(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &context.payer.pubkey(),
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let stake_pool_account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    assert_eq!(stake_pool_account.data.len(), StakePool::account_len());
+    let rent = context.banks_client.get_rent().await.unwrap();
+    assert!(rent.is_exempt(stake_pool_account.lamports, stake_pool_account.data.len()));
+
+    let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_account.data).unwrap();
+    assert_eq!(
+        stake_pool,
+        StakePool {
+            account_type: AccountType::StakePoolV2,
+            ..pre_stake_pool
+        }
+    );
+}
+
+#[tokio::test]
+async fn success_already_migrated() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    let pre_stake_pool_account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let pre_stake_pool =
+        try_from_slice_unchecked::<StakePool>(&pre_stake_pool_account.data).unwrap();
+    assert_eq!(pre_stake_pool.account_type, AccountType::StakePoolV2);
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::migrate_pool(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &context.payer.pubkey(),
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let stake_pool_account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    assert_eq!(stake_pool_account, pre_stake_pool_account);
+}
@@ -0,0 +1,211 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{borsh1::try_from_slice_unchecked, hash::Hash},
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError, id, instruction, state::ValidatorList, MAX_VALIDATORS_IN_POOL,
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    (banks_client, payer, recent_blockhash, stake_pool_accounts)
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+    let new_max_validators = stake_pool_accounts.max_validators + 1_000;
+
+    let instructions = instruction::resize_validator_list_instructions(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+        &payer.pubkey(),
+        stake_pool_accounts.max_validators,
+        new_max_validators,
+    );
+    assert!(instructions.len() > 1);
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[&payer, &stake_pool_accounts.manager],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let validator_list = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    assert_eq!(
+        validator_list.data.len(),
+        ValidatorList::calculate_len(new_max_validators)
+    );
+    let rent = banks_client.get_rent().await.unwrap();
+    assert!(rent.is_exempt(validator_list.lamports, validator_list.data.len()));
+    let validator_list =
+        try_from_slice_unchecked::<ValidatorList>(validator_list.data.as_slice()).unwrap();
+    assert_eq!(validator_list.header.max_validators, new_max_validators);
+    assert!(validator_list.validators.is_empty());
+}
+
+#[tokio::test]
+async fn success_partial() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+    let pre_len = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await
+    .data
+    .len();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::resize_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &payer.pubkey(),
+            MAX_VALIDATORS_IN_POOL,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &stake_pool_accounts.manager],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let validator_list = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let post_len = validator_list.data.len();
+    assert_eq!(
+        post_len,
+        pre_len + solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE
+    );
+    let validator_list =
+        try_from_slice_unchecked::<ValidatorList>(validator_list.data.as_slice()).unwrap();
+    assert_eq!(
+        validator_list.header.max_validators as usize,
+        ValidatorList::calculate_max_validators(post_len)
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+    let wrong_manager = Keypair::new();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::resize_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &payer.pubkey(),
+            stake_pool_accounts.max_validators + 1,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &wrong_manager],
+        recent_blockhash,
+    );
+    let error = banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::WrongManager as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_shrink() {
+    let (banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::resize_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &payer.pubkey(),
+            stake_pool_accounts.max_validators - 1,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &stake_pool_accounts.manager],
+        recent_blockhash,
+    );
+    let error = banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::UnexpectedValidatorListAccountSize as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_too_many_validators() {
+    let (banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::resize_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &payer.pubkey(),
+            MAX_VALIDATORS_IN_POOL + 1,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &stake_pool_accounts.manager],
+        recent_blockhash,
+    );
+    let error = banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::TooManyValidatorsInPool as u32)
+        )
+    );
+}
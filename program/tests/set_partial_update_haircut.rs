@@ -0,0 +1,394 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{borsh1::try_from_slice_unchecked, hash::Hash, instruction::InstructionError},
+    solana_program_test::*,
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        id, instruction,
+        state::{AccountType, Fee, StakePool, StakePoolConfig},
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+const HAIRCUT: Fee = Fee {
+    numerator: 1,
+    denominator: 10,
+};
+
+async fn setup() -> (
+    ProgramTestContext,
+    Hash,
+    StakePoolAccounts,
+    Keypair,
+    Keypair,
+) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    let pool_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &pool_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    (
+        context,
+        last_blockhash,
+        stake_pool_accounts,
+        user,
+        pool_token_account,
+    )
+}
+
+async fn set_partial_update_haircut(
+    context: &mut ProgramTestContext,
+    last_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    manager: &Keypair,
+    haircut: Option<Fee>,
+) -> Result<(), TransactionError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_partial_update_haircut(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &manager.pubkey(),
+            haircut,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, manager],
+        *last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+async fn get_stake_pool_config(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> StakePoolConfig {
+    let stake_pool_account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_account.data).unwrap();
+    StakePoolConfig::load(&stake_pool, &stake_pool_account.data).unwrap()
+}
+
+#[tokio::test]
+async fn success_set_and_unset() {
+    let (mut context, last_blockhash, stake_pool_accounts, _, _) = setup().await;
+
+    set_partial_update_haircut(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(HAIRCUT),
+    )
+    .await
+    .unwrap();
+    let config = get_stake_pool_config(&mut context, &stake_pool_accounts).await;
+    assert_eq!(config.partial_update_haircut, Some(HAIRCUT));
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    set_partial_update_haircut(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        None,
+    )
+    .await
+    .unwrap();
+    let config = get_stake_pool_config(&mut context, &stake_pool_accounts).await;
+    assert_eq!(config.partial_update_haircut, None);
+}
+
+#[tokio::test]
+async fn success_deposit_sol_before_update() {
+    let (mut context, last_blockhash, stake_pool_accounts, _, pool_token_account) = setup().await;
+
+    set_partial_update_haircut(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(HAIRCUT),
+    )
+    .await
+    .unwrap();
+
+    let pre_stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    let pre_pool_tokens =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    // the pool was empty before the first deposit, so tokens are 1:1
+    let pool_tokens =
+        TEST_STAKE_AMOUNT - (TEST_STAKE_AMOUNT * HAIRCUT.numerator).div_ceil(HAIRCUT.denominator);
+    let user_pool_tokens = pool_tokens - stake_pool_accounts.calculate_sol_deposit_fee(pool_tokens);
+    let post_pool_tokens =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+    assert_eq!(post_pool_tokens, pre_pool_tokens + user_pool_tokens);
+
+    let post_stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    assert_eq!(
+        post_stake_pool.total_lamports,
+        pre_stake_pool.total_lamports + TEST_STAKE_AMOUNT
+    );
+    assert_eq!(
+        post_stake_pool.pool_token_supply,
+        pre_stake_pool.pool_token_supply + pool_tokens
+    );
+    let clock = context
+        .banks_client
+        .get_sysvar::<solana_program::clock::Clock>()
+        .await
+        .unwrap();
+    assert!(post_stake_pool.last_update_epoch < clock.epoch);
+}
+
+#[tokio::test]
+async fn success_withdraw_sol_before_update() {
+    let (mut context, last_blockhash, stake_pool_accounts, user, pool_token_account) =
+        setup().await;
+
+    set_partial_update_haircut(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(HAIRCUT),
+    )
+    .await
+    .unwrap();
+
+    let pre_pool_tokens =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+    let error = stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &user,
+            &pool_token_account.pubkey(),
+            pre_pool_tokens / 2,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let post_pool_tokens =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+    assert_eq!(post_pool_tokens, pre_pool_tokens - pre_pool_tokens / 2);
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_before_update_without_haircut() {
+    let (mut context, last_blockhash, stake_pool_accounts, _, pool_token_account) = setup().await;
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::StakeListAndPoolOutOfDate as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_withdraw_sol_before_update_without_haircut() {
+    let (mut context, last_blockhash, stake_pool_accounts, user, pool_token_account) =
+        setup().await;
+
+    let error = stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &user,
+            &pool_token_account.pubkey(),
+            1_000_000,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::StakeListAndPoolOutOfDate as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (mut context, last_blockhash, stake_pool_accounts, _, _) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let error = set_partial_update_haircut(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &wrong_manager,
+        Some(HAIRCUT),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::WrongManager as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_haircut_too_high() {
+    let (mut context, last_blockhash, stake_pool_accounts, _, _) = setup().await;
+
+    let error = set_partial_update_haircut(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(Fee {
+            numerator: 11,
+            denominator: 10,
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::FeeTooHigh as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_not_migrated() {
+    let (mut context, last_blockhash, stake_pool_accounts, _, _) = setup().await;
+
+    let mut stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    stake_pool.account_type = AccountType::StakePool;
+    let data = borsh::to_vec(&stake_pool).unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    context.set_account(
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &AccountSharedData::from(Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: id(),
+            executable: false,
+            rent_epoch: 0,
+        }),
+    );
+
+    let error = set_partial_update_haircut(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(HAIRCUT),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::StakePoolNotMigrated as u32)
+        )
+    );
+}
@@ -0,0 +1,352 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        borsh1::try_from_slice_unchecked, hash::Hash, instruction::InstructionError, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        id, instruction,
+        state::{AccountType, StakePool, StakePoolConfig},
+        MAX_TRANSFER_HOOK_PROGRAMS, MINIMUM_RESERVE_LAMPORTS,
+    },
+    spl_token_2022_interface::extension::ExtensionType,
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Keypair, Keypair) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts =
+        StakePoolAccounts::new_with_token_program(spl_token_2022_interface::id());
+    stake_pool_accounts
+        .initialize_stake_pool_with_mint_extensions(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+            &[ExtensionType::TransferHook],
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    let pool_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &pool_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user,
+        &[ExtensionType::TransferHookAccount],
+    )
+    .await
+    .unwrap();
+
+    (context, stake_pool_accounts, user, pool_token_account)
+}
+
+async fn set_transfer_hook_program_ids(
+    context: &mut ProgramTestContext,
+    last_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    manager: &Keypair,
+    program_ids: Vec<Pubkey>,
+) -> Result<(), TransactionError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_transfer_hook_program_ids(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &manager.pubkey(),
+            program_ids,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, manager],
+        *last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+async fn get_stake_pool_config(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> StakePoolConfig {
+    let stake_pool_account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_account.data).unwrap();
+    StakePoolConfig::load(&stake_pool, &stake_pool_account.data).unwrap()
+}
+
+#[tokio::test]
+async fn success_set_and_clear() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let other_program_id = Pubkey::new_unique();
+    let last_blockhash = context.last_blockhash;
+    set_transfer_hook_program_ids(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        vec![other_program_id, TEST_TRANSFER_HOOK_PROGRAM_ID],
+    )
+    .await
+    .unwrap();
+    let config = get_stake_pool_config(&mut context, &stake_pool_accounts).await;
+    assert_eq!(
+        config.transfer_hook_program_ids,
+        [
+            Some(other_program_id),
+            Some(TEST_TRANSFER_HOOK_PROGRAM_ID),
+            None,
+            None
+        ]
+    );
+    assert!(config.is_transfer_hook_program_allowed(&TEST_TRANSFER_HOOK_PROGRAM_ID));
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    set_transfer_hook_program_ids(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        vec![],
+    )
+    .await
+    .unwrap();
+    let config = get_stake_pool_config(&mut context, &stake_pool_accounts).await;
+    assert_eq!(
+        config.transfer_hook_program_ids,
+        [None; MAX_TRANSFER_HOOK_PROGRAMS]
+    );
+}
+
+#[tokio::test]
+async fn success_deposit_sol_allowed_hook() {
+    let (mut context, stake_pool_accounts, _, pool_token_account) = setup().await;
+
+    let last_blockhash = context.last_blockhash;
+    set_transfer_hook_program_ids(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        vec![TEST_TRANSFER_HOOK_PROGRAM_ID],
+    )
+    .await
+    .unwrap();
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+    let pool_tokens =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+    assert_eq!(
+        pool_tokens,
+        TEST_STAKE_AMOUNT - stake_pool_accounts.calculate_sol_deposit_fee(TEST_STAKE_AMOUNT)
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_hook_not_allowed() {
+    let (mut context, stake_pool_accounts, _, pool_token_account) = setup().await;
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::TransferHookProgramNotAllowed as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_withdraw_sol_hook_no_longer_allowed() {
+    let (mut context, stake_pool_accounts, user, pool_token_account) = setup().await;
+
+    let last_blockhash = context.last_blockhash;
+    set_transfer_hook_program_ids(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        vec![TEST_TRANSFER_HOOK_PROGRAM_ID],
+    )
+    .await
+    .unwrap();
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    set_transfer_hook_program_ids(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        vec![Pubkey::new_unique()],
+    )
+    .await
+    .unwrap();
+
+    let error = stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &user,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT / 2,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::TransferHookProgramNotAllowed as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_too_many_programs() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let last_blockhash = context.last_blockhash;
+    let error = set_transfer_hook_program_ids(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        (0..=MAX_TRANSFER_HOOK_PROGRAMS)
+            .map(|_| Pubkey::new_unique())
+            .collect(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::TooManyTransferHookPrograms as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let last_blockhash = context.last_blockhash;
+    let error = set_transfer_hook_program_ids(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &wrong_manager,
+        vec![TEST_TRANSFER_HOOK_PROGRAM_ID],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::WrongManager as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_not_migrated() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let mut stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    stake_pool.account_type = AccountType::StakePool;
+    let data = borsh::to_vec(&stake_pool).unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    context.set_account(
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &AccountSharedData::from(Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: id(),
+            executable: false,
+            rent_epoch: 0,
+        }),
+    );
+
+    let last_blockhash = context.last_blockhash;
+    let error = set_transfer_hook_program_ids(
+        &mut context,
+        &last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        vec![TEST_TRANSFER_HOOK_PROGRAM_ID],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::StakePoolNotMigrated as u32)
+        )
+    );
+}
@@ -0,0 +1,271 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{borsh1::try_from_slice_unchecked, instruction::InstructionError},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        id, instruction,
+        state::{AccountType, ValidatorList, ValidatorListHeader},
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, ValidatorStakeAccount) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    let validator_stake = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        None,
+    )
+    .await;
+
+    (context, stake_pool_accounts, validator_stake)
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts, _) = setup().await;
+    let destination = Keypair::new();
+    let new_max_validators = 10;
+
+    let pre_lamports = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await
+    .lamports;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::shrink_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &destination.pubkey(),
+            new_max_validators,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let rent = context.banks_client.get_rent().await.unwrap();
+    assert_eq!(
+        validator_list.data.len(),
+        ValidatorList::calculate_len(new_max_validators)
+    );
+    assert_eq!(
+        validator_list.lamports,
+        rent.minimum_balance(validator_list.data.len())
+    );
+    let destination_lamports = get_account(&mut context.banks_client, &destination.pubkey())
+        .await
+        .lamports;
+    assert_eq!(destination_lamports, pre_lamports - validator_list.lamports);
+
+    let validator_list =
+        try_from_slice_unchecked::<ValidatorList>(validator_list.data.as_slice()).unwrap();
+    assert_eq!(validator_list.header.max_validators, new_max_validators);
+    assert_eq!(validator_list.validators.len(), 1);
+}
+
+#[tokio::test]
+async fn success_compacts_removed_validators() {
+    let (mut context, stake_pool_accounts, validator_stake) = setup().await;
+    let destination = Keypair::new();
+
+    let error = stake_pool_accounts
+        .remove_validator_from_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake.stake_account,
+            &validator_stake.transient_stake_account,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            1,
+            false,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    // the validator is still in the list, ready for removal, so the list can
+    // only be shrunk to 1 by compacting it
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    assert_eq!(validator_list.validators.len(), 1);
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::shrink_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &destination.pubkey(),
+            1,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        try_from_slice_unchecked::<ValidatorList>(validator_list.data.as_slice()).unwrap();
+    assert_eq!(
+        validator_list,
+        ValidatorList {
+            header: ValidatorListHeader {
+                account_type: AccountType::ValidatorList,
+                max_validators: 1,
+            },
+            validators: vec![],
+        }
+    );
+}
+
+#[tokio::test]
+async fn fail_below_current_length() {
+    let (context, stake_pool_accounts, _) = setup().await;
+    let destination = Keypair::new();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::shrink_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &destination.pubkey(),
+            0,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::UnexpectedValidatorListAccountSize as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_grow() {
+    let (context, stake_pool_accounts, _) = setup().await;
+    let destination = Keypair::new();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::shrink_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &destination.pubkey(),
+            stake_pool_accounts.max_validators + 1,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::UnexpectedValidatorListAccountSize as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (context, stake_pool_accounts, _) = setup().await;
+    let wrong_manager = Keypair::new();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::shrink_validator_list(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &wrong_manager.pubkey(),
+            10,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_manager],
+        context.last_blockhash,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::WrongManager as u32)
+        )
+    );
+}
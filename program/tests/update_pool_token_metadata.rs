@@ -13,6 +13,7 @@ use {
         error::StakePoolError::{SignatureMissing, WrongManager},
         instruction, MINIMUM_RESERVE_LAMPORTS,
     },
+    spl_token_2022_interface::extension::ExtensionType,
 };
 
 async fn setup() -> (ProgramTestContext, StakePoolAccounts) {
@@ -188,3 +189,163 @@ async fn fail_wrong_manager_signed() {
         _ => panic!("Wrong error occurs while signing with the wrong manager"),
     }
 }
+
+async fn setup_token_2022() -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts =
+        StakePoolAccounts::new_with_token_program(spl_token_2022_interface::id());
+    stake_pool_accounts
+        .initialize_stake_pool_with_mint_extensions(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+            &[ExtensionType::MetadataPointer],
+        )
+        .await
+        .unwrap();
+
+    let ix = instruction::create_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &context.payer.pubkey(),
+        "test_name".to_string(),
+        "SYM".to_string(),
+        "test_uri".to_string(),
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    (context, stake_pool_accounts)
+}
+
+#[tokio::test]
+async fn success_update_token_2022_metadata() {
+    let (mut context, stake_pool_accounts) = setup_token_2022().await;
+
+    let updated_name = "updated_name";
+    let updated_symbol = "USYM";
+    let updated_uri = "updated_uri";
+
+    let ix = instruction::update_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        Some(&context.payer.pubkey()),
+        updated_name.to_string(),
+        updated_symbol.to_string(),
+        updated_uri.to_string(),
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let metadata = get_token_2022_metadata(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+
+    assert_eq!(metadata.name, updated_name);
+    assert_eq!(metadata.symbol, updated_symbol);
+    assert_eq!(metadata.uri, updated_uri);
+}
+
+#[tokio::test]
+async fn success_update_token_2022_metadata_shorter_without_payer() {
+    let (mut context, stake_pool_accounts) = setup_token_2022().await;
+
+    let ix = instruction::update_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        None,
+        "name".to_string(),
+        "SYM".to_string(),
+        "uri".to_string(),
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let metadata = get_token_2022_metadata(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+
+    assert_eq!(metadata.name, "name");
+    assert_eq!(metadata.uri, "uri");
+}
+
+#[allow(deprecated)] // `next_account_info` still reports `NotEnoughAccountKeys`
+#[tokio::test]
+async fn fail_update_token_2022_metadata_longer_without_payer() {
+    let (context, stake_pool_accounts) = setup_token_2022().await;
+
+    let ix = instruction::update_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        None,
+        "a much longer updated name".to_string(),
+        "SYM".to_string(),
+        "test_uri".to_string(),
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+    );
+}
@@ -12,6 +12,13 @@ use {
     },
     solana_stake_interface::state::StakeStateV2,
     spl_stake_pool::{error::StakePoolError, state::StakePool, MINIMUM_RESERVE_LAMPORTS},
+    spl_token_2022_interface::{
+        extension::{
+            scaled_ui_amount::ScaledUiAmountConfig, BaseStateWithExtensions, ExtensionType,
+            StateWithExtensionsOwned,
+        },
+        state::Mint,
+    },
     std::num::NonZeroU32,
 };
 
@@ -305,6 +312,82 @@ async fn success_absorbing_extra_lamports() {
     assert_eq!(pool_token_supply, pre_token_supply + expected_fee);
 }
 
+#[tokio::test]
+async fn success_scaled_ui_amount() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts =
+        StakePoolAccounts::new_with_token_program(spl_token_2022_interface::id());
+    stake_pool_accounts
+        .initialize_stake_pool_with_mint_extensions(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+            &[ExtensionType::ScaledUiAmount],
+        )
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    // Rewards paid into the reserve raise the value of pool tokens
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        TEST_STAKE_AMOUNT / 10,
+    )
+    .await;
+
+    let slot = context.genesis_config().epoch_schedule.first_normal_slot + 1;
+    context.warp_to_slot(slot).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .update_all(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            false,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    assert!(stake_pool.total_lamports > stake_pool.pool_token_supply);
+
+    let pool_mint = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+    let pool_mint = StateWithExtensionsOwned::<Mint>::unpack(pool_mint.data).unwrap();
+    let scaled_ui_amount = pool_mint.get_extension::<ScaledUiAmountConfig>().unwrap();
+    assert_eq!(
+        f64::from(scaled_ui_amount.new_multiplier),
+        stake_pool
+            .calc_scaled_ui_multiplier(pool_mint.base.decimals)
+            .unwrap()
+    );
+}
+
 #[tokio::test]
 async fn fail_with_wrong_validator_list() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
@@ -0,0 +1,556 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        borsh1::try_from_slice_unchecked, clock::Clock, hash::Hash, instruction::InstructionError,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        id, instruction,
+        state::{AccountType, StakePool, StakePoolConfig, ValidatorList},
+        MAX_TRANSFER_HOOK_PROGRAMS, MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+const NUM_VALIDATORS: usize = 3;
+
+async fn setup() -> (ProgramTestContext, Hash, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    for _ in 0..NUM_VALIDATORS {
+        simple_add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts,
+            None,
+        )
+        .await;
+    }
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    (context, last_blockhash, stake_pool_accounts)
+}
+
+async fn update_validator_balance(
+    context: &mut ProgramTestContext,
+    last_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_index: usize,
+) -> Result<(), TransactionError> {
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::update_validator_balance(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &validator_list,
+            validator_index,
+            /* no_merge = */ false,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        *last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+async fn get_stake_pool_config(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> StakePoolConfig {
+    let stake_pool_account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_account.data).unwrap();
+    StakePoolConfig::load(&stake_pool, &stake_pool_account.data).unwrap()
+}
+
+#[tokio::test]
+async fn success_out_of_order() {
+    let (mut context, last_blockhash, stake_pool_accounts) = setup().await;
+    let epoch = context
+        .banks_client
+        .get_sysvar::<solana_program::clock::Clock>()
+        .await
+        .unwrap()
+        .epoch;
+
+    for validator_index in (0..NUM_VALIDATORS).rev() {
+        update_validator_balance(
+            &mut context,
+            &last_blockhash,
+            &stake_pool_accounts,
+            validator_index,
+        )
+        .await
+        .unwrap();
+    }
+
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    assert!(validator_list
+        .validators
+        .iter()
+        .all(|validator| u64::from(validator.last_update_epoch) == epoch));
+    let validator_lamports = validator_list
+        .validators
+        .iter()
+        .map(|validator| validator.stake_lamports().unwrap())
+        .sum::<u64>();
+    let config = get_stake_pool_config(&mut context, &stake_pool_accounts).await;
+    assert_eq!(
+        config,
+        StakePoolConfig {
+            update_epoch: epoch,
+            updated_validator_count: NUM_VALIDATORS as u32,
+            updated_lamports: validator_lamports,
+            partial_update_haircut: None,
+            transfer_hook_program_ids: [None; MAX_TRANSFER_HOOK_PROGRAMS],
+            depositor_allowlist_enabled: false,
+            depositor_merkle_root: None,
+        }
+    );
+
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(&mut context.banks_client, &context.payer, &last_blockhash)
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    assert_eq!(stake_pool.last_update_epoch, epoch);
+    let expected_lamports = get_validator_list_sum(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    assert_eq!(stake_pool.total_lamports, expected_lamports);
+}
+
+#[tokio::test]
+async fn success_update_twice() {
+    let (mut context, last_blockhash, stake_pool_accounts) = setup().await;
+
+    update_validator_balance(&mut context, &last_blockhash, &stake_pool_accounts, 1)
+        .await
+        .unwrap();
+    let config = get_stake_pool_config(&mut context, &stake_pool_accounts).await;
+    assert_eq!(config.updated_validator_count, 1);
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    update_validator_balance(&mut context, &last_blockhash, &stake_pool_accounts, 1)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_stake_pool_config(&mut context, &stake_pool_accounts).await,
+        config
+    );
+}
+
+#[tokio::test]
+async fn success_with_list_update() {
+    let (mut context, last_blockhash, stake_pool_accounts) = setup().await;
+
+    update_validator_balance(&mut context, &last_blockhash, &stake_pool_accounts, 2)
+        .await
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            NUM_VALIDATORS,
+            false,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let config = get_stake_pool_config(&mut context, &stake_pool_accounts).await;
+    assert_eq!(config.updated_validator_count, NUM_VALIDATORS as u32);
+}
+
+#[tokio::test]
+async fn success_finishes_from_tally() {
+    let (mut context, last_blockhash, stake_pool_accounts) = setup().await;
+
+    for validator_index in 0..NUM_VALIDATORS {
+        update_validator_balance(
+            &mut context,
+            &last_blockhash,
+            &stake_pool_accounts,
+            validator_index,
+        )
+        .await
+        .unwrap();
+    }
+
+    // make an entry look out of date, which would fail if the pool balance
+    // update summed the whole list
+    let mut validator_list_account = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let mut validator_list =
+        try_from_slice_unchecked::<ValidatorList>(&validator_list_account.data).unwrap();
+    validator_list.validators[0].last_update_epoch = 0.into();
+    let data = borsh::to_vec(&validator_list).unwrap();
+    validator_list_account.data[..data.len()].copy_from_slice(&data);
+    context.set_account(
+        &stake_pool_accounts.validator_list.pubkey(),
+        &AccountSharedData::from(validator_list_account),
+    );
+
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(&mut context.banks_client, &context.payer, &last_blockhash)
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+}
+
+#[tokio::test]
+async fn fail_not_all_updated() {
+    let (mut context, last_blockhash, stake_pool_accounts) = setup().await;
+
+    for validator_index in 1..NUM_VALIDATORS {
+        update_validator_balance(
+            &mut context,
+            &last_blockhash,
+            &stake_pool_accounts,
+            validator_index,
+        )
+        .await
+        .unwrap();
+    }
+
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(&mut context.banks_client, &context.payer, &last_blockhash)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::StakeListOutOfDate as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_validator_stake() {
+    let (mut context, last_blockhash, stake_pool_accounts) = setup().await;
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+
+    let mut ix = instruction::update_validator_balance(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &validator_list,
+        0,
+        /* no_merge = */ false,
+    )
+    .unwrap();
+    let other_ix = instruction::update_validator_balance(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &validator_list,
+        1,
+        /* no_merge = */ false,
+    )
+    .unwrap();
+    ix.accounts[7] = other_ix.accounts[7].clone();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidStakeAccountAddress as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_not_migrated() {
+    let (mut context, last_blockhash, stake_pool_accounts) = setup().await;
+
+    let mut stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    stake_pool.account_type = AccountType::StakePool;
+    let data = borsh::to_vec(&stake_pool).unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    context.set_account(
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &AccountSharedData::from(Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: id(),
+            executable: false,
+            rent_epoch: 0,
+        }),
+    );
+
+    let error = update_validator_balance(&mut context, &last_blockhash, &stake_pool_accounts, 0)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::StakePoolNotMigrated as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn read_only_update_skips_tallied_validators() {
+    let mut context = program_test().start_with_context().await;
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    let mut slot = first_normal_slot + 1;
+    context.warp_to_slot(slot).unwrap();
+
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            TEST_STAKE_AMOUNT * 2 + MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+    let mut validator_stakes = vec![];
+    for _ in 0..NUM_VALIDATORS {
+        validator_stakes.push(
+            simple_add_validator_to_pool(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                &stake_pool_accounts,
+                None,
+            )
+            .await,
+        );
+    }
+    let validator_stake = &validator_stakes[0];
+
+    // move stake from the reserve to the first validator, and back, leaving
+    // an inactive transient stake to be merged into the reserve
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake.transient_stake_account,
+            &validator_stake.stake_account,
+            &validator_stake.vote.pubkey(),
+            TEST_STAKE_AMOUNT,
+            validator_stake.transient_stake_seed,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    slot += slots_per_epoch;
+    context.warp_to_slot(slot).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+    stake_pool_accounts
+        .update_all(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            false,
+        )
+        .await;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake_either(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &validator_stake.stake_account,
+            &validator_stake.transient_stake_account,
+            TEST_STAKE_AMOUNT,
+            validator_stake.transient_stake_seed,
+            DecreaseInstruction::Reserve,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    slot += slots_per_epoch;
+    context.warp_to_slot(slot).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+
+    // tally the first validator with its transient stake
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::update_validator_balance(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &validator_list,
+            0,
+            /* no_merge = */ true,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    assert!(u64::from(validator_list.validators[0].transient_stake_lamports) > 0);
+
+    // merging the transient stake without tallying it would leave its lamports
+    // in the tally, counting them twice once they are in the reserve, so a
+    // read-only update skips the first validator, and only updates the second
+    // one, without tallying it
+    let mut ix = instruction::update_validator_list_balance_chunk(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &validator_list,
+        2,
+        0,
+        /* no_merge = */ false,
+    )
+    .unwrap();
+    ix.accounts[0].is_writable = false;
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    assert!(u64::from(validator_list.validators[0].transient_stake_lamports) > 0);
+    let clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    assert_eq!(
+        u64::from(validator_list.validators[1].last_update_epoch),
+        clock.epoch
+    );
+    let config = get_stake_pool_config(&mut context, &stake_pool_accounts).await;
+    assert_eq!(config.updated_validator_count, 1);
+
+    // a tallied update merges it, and since the second validator was left out
+    // of the tally, the pool balance is summed from the list
+    let error = stake_pool_accounts
+        .update_all(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            false,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    assert_eq!(
+        u64::from(validator_list.validators[0].transient_stake_lamports),
+        0
+    );
+    let stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    let expected_lamports = get_validator_list_sum(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    assert_eq!(stake_pool.total_lamports, expected_lamports);
+}
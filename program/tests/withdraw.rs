@@ -2,6 +2,7 @@
 mod helpers;
 
 use {
+    borsh::BorshDeserialize,
     helpers::*,
     solana_program::{
         borsh1::try_from_slice_unchecked,
@@ -259,6 +260,83 @@ async fn _success(token_program_id: Pubkey, test_type: SuccessTestType) {
     );
 }
 
+#[tokio::test]
+async fn success_return_data() {
+    let (
+        mut context,
+        stake_pool_accounts,
+        validator_stake_account,
+        deposit_info,
+        user_transfer_authority,
+        user_stake_recipient,
+        tokens_to_withdraw,
+    ) = setup_for_withdraw(spl_token_interface::id(), 0).await;
+
+    let pool_fee_balance_before = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    let initial_stake_lamports =
+        get_account(&mut context.banks_client, &user_stake_recipient.pubkey())
+            .await
+            .lamports;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::withdraw_stake(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &validator_stake_account.stake_account,
+            &user_stake_recipient.pubkey(),
+            &Pubkey::new_unique(),
+            &user_transfer_authority.pubkey(),
+            &deposit_info.pool_account.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &stake_pool_accounts.token_program_id,
+            tokens_to_withdraw,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user_transfer_authority],
+        context.last_blockhash,
+    );
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok(), "{:?}", result.result);
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, id());
+    let return_data =
+        instruction::DepositWithdrawReturnData::try_from_slice(&return_data.data).unwrap();
+
+    // first and only deposit, lamports:pool 1:1
+    let withdrawal_fee = stake_pool_accounts.calculate_withdrawal_fee(tokens_to_withdraw);
+    assert_eq!(
+        return_data,
+        instruction::DepositWithdrawReturnData {
+            amount_out: tokens_to_withdraw - withdrawal_fee,
+            fee: withdrawal_fee,
+            referral_fee: 0,
+        }
+    );
+    let user_stake_recipient_account =
+        get_account(&mut context.banks_client, &user_stake_recipient.pubkey()).await;
+    assert_eq!(
+        user_stake_recipient_account.lamports,
+        initial_stake_lamports + return_data.amount_out
+    );
+    let pool_fee_balance = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(pool_fee_balance, pool_fee_balance_before + return_data.fee);
+}
+
 #[tokio::test]
 async fn fail_with_wrong_stake_program() {
     let (
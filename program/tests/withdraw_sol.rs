@@ -2,6 +2,7 @@
 mod helpers;
 
 use {
+    borsh::BorshDeserialize,
     helpers::*,
     solana_program::{
         borsh1::try_from_slice_unchecked, instruction::InstructionError, pubkey::Pubkey,
@@ -391,3 +392,52 @@ async fn success_with_slippage(token_program_id: Pubkey) {
         pre_reserve_lamports - amount_received
     );
 }
+
+#[tokio::test]
+async fn success_return_data() {
+    let (mut context, stake_pool_accounts, user, pool_token_account, pool_tokens) =
+        setup(spl_token_interface::id()).await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::withdraw_sol(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &user.pubkey(),
+            &pool_token_account,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &user.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token_interface::id(),
+            pool_tokens,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user],
+        context.last_blockhash,
+    );
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok(), "{:?}", result.result);
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, id());
+    let return_data =
+        instruction::DepositWithdrawReturnData::try_from_slice(&return_data.data).unwrap();
+
+    let withdrawal_fee = stake_pool_accounts.calculate_withdrawal_fee(pool_tokens);
+    assert_eq!(
+        return_data,
+        instruction::DepositWithdrawReturnData {
+            amount_out: pool_tokens - withdrawal_fee,
+            fee: withdrawal_fee,
+            referral_fee: 0,
+        }
+    );
+    let user_lamports = get_account(&mut context.banks_client, &user.pubkey())
+        .await
+        .lamports;
+    assert_eq!(user_lamports, return_data.amount_out);
+}
@@ -0,0 +1,76 @@
+//! Error types
+
+use {
+    num_derive::FromPrimitive,
+    solana_program_error::{ProgramError, ToStr},
+    thiserror::Error,
+};
+
+/// Errors that may be returned by the deposit authority program.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum DepositAuthorityError {
+    // 0.
+    /// The account cannot be initialized because it is already being used.
+    #[error("AlreadyInUse")]
+    AlreadyInUse,
+    /// The program address provided doesn't match the value generated by the
+    /// program.
+    #[error("InvalidProgramAddress")]
+    InvalidProgramAddress,
+    /// The deposit limits or user deposit record are invalid.
+    #[error("InvalidState")]
+    InvalidState,
+    /// The calculation failed.
+    #[error("CalculationFailure")]
+    CalculationFailure,
+    /// The signer is not the admin of the deposit limits.
+    #[error("WrongAdmin")]
+    WrongAdmin,
+
+    // 5.
+    /// The signer is not the manager of the stake pool.
+    #[error("WrongManager")]
+    WrongManager,
+    /// The deposit is below the minimum.
+    #[error("DepositTooSmall")]
+    DepositTooSmall,
+    /// The deposit would take the user over their cap.
+    #[error("UserCapExceeded")]
+    UserCapExceeded,
+    /// The deposit would take the pool over its cap for the epoch.
+    #[error("EpochCapExceeded")]
+    EpochCapExceeded,
+    /// The referrer account is not the one required by the deposit limits.
+    #[error("WrongReferrer")]
+    WrongReferrer,
+}
+impl From<DepositAuthorityError> for ProgramError {
+    fn from(e: DepositAuthorityError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+impl TryFrom<u32> for DepositAuthorityError {
+    type Error = ProgramError;
+    fn try_from(code: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(code).ok_or(ProgramError::InvalidArgument)
+    }
+}
+impl ToStr for DepositAuthorityError {
+    fn to_str(&self) -> &'static str {
+        match self {
+            // 0.
+            Self::AlreadyInUse => "Error: The account cannot be initialized because it is already being used.",
+            Self::InvalidProgramAddress => "Error: The program address provided doesn't match the value generated by the program.",
+            Self::InvalidState => "Error: The deposit limits or user deposit record are invalid.",
+            Self::CalculationFailure => "Error: The calculation failed.",
+            Self::WrongAdmin => "Error: The signer is not the admin of the deposit limits.",
+
+            // 5.
+            Self::WrongManager => "Error: The signer is not the manager of the stake pool.",
+            Self::DepositTooSmall => "Error: The deposit is below the minimum.",
+            Self::UserCapExceeded => "Error: The deposit would take the user over their cap.",
+            Self::EpochCapExceeded => "Error: The deposit would take the pool over its cap for the epoch.",
+            Self::WrongReferrer => "Error: The referrer account is not the one required by the deposit limits.",
+        }
+    }
+}
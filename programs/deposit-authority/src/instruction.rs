@@ -0,0 +1,279 @@
+//! Instruction types
+
+#![allow(clippy::too_many_arguments)]
+
+use {
+    crate::{
+        find_config_address, find_deposit_authority_address, find_user_deposit_address,
+        state::DepositLimits,
+    },
+    borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+};
+
+/// Instructions supported by the deposit authority program.
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum DepositAuthorityInstruction {
+    ///   Initializes the deposit limits of a stake pool.
+    ///
+    ///   The stake pool manager must then set the deposit authority as both
+    ///   the stake and SOL deposit authority of the pool, with
+    ///   `SetFundingAuthority`.
+    ///
+    ///   0. `[w]` Deposit limits, derived from the stake pool
+    ///   1. `[]` Stake pool
+    ///   2. `[s]` Stake pool manager
+    ///   3. `[]` Admin allowed to change the limits
+    ///   4. `[ws]` Payer for the deposit limits account
+    ///   5. `[]` System program
+    Initialize {
+        /// Limits enforced on deposits
+        limits: DepositLimits,
+    },
+
+    ///   (Admin only) Replaces the deposit limits.
+    ///
+    ///   0. `[w]` Deposit limits
+    ///   1. `[s]` Admin
+    SetLimits {
+        /// Limits enforced on deposits
+        limits: DepositLimits,
+    },
+
+    ///   (Admin only) Transfers the right to change the deposit limits.
+    ///
+    ///   0. `[w]` Deposit limits
+    ///   1. `[s]` Admin
+    ///   2. `[]` New admin
+    SetAdmin,
+
+    ///   Deposits SOL into the stake pool through `DepositSolWithSlippage`,
+    ///   once the deposit passes the limits.
+    ///
+    ///   0. `[w]` Deposit limits
+    ///   1. `[w]` Deposit record of the user, created on their first deposit
+    ///   2. `[ws]` User providing the lamports, paying for the deposit record
+    ///   3. `[]` Deposit authority
+    ///   4. `[]` System program
+    ///   5. `[]` Stake pool program
+    ///   6. `[w]` Stake pool
+    ///   7. `[]` Stake pool withdraw authority
+    ///   8. `[w]` Reserve stake account
+    ///   9. `[w]` User account to receive pool tokens
+    ///  10. `[w]` Account to receive fee tokens
+    ///  11. `[w]` Account to receive a portion of fee as referral fees
+    ///  12. `[w]` Pool token mint account
+    ///  13. `[]` Token program id
+    ///  14. ..14+N `[]` N accounts passed on to the stake pool program, such
+    ///      as a depositor allowlist entry
+    DepositSol {
+        /// Lamports to deposit
+        lamports_in: u64,
+        /// Minimum amount of pool tokens that must be received
+        minimum_pool_tokens_out: u64,
+    },
+
+    ///   Deposits a stake account into the stake pool through
+    ///   `DepositStakeWithSlippage`, once the deposit passes the limits. The
+    ///   lamports in the stake account count as the deposit.
+    ///
+    ///   0. `[w]` Deposit limits
+    ///   1. `[w]` Deposit record of the user, created on their first deposit
+    ///   2. `[ws]` User, staker and withdrawer of the stake account, paying
+    ///      for the deposit record
+    ///   3. `[]` Deposit authority
+    ///   4. `[]` System program
+    ///   5. `[]` Stake pool program
+    ///   6. `[w]` Stake pool
+    ///   7. `[w]` Validator stake list storage account
+    ///   8. `[]` Stake pool withdraw authority
+    ///   9. `[w]` Stake account to join the pool
+    ///  10. `[w]` Validator stake account for the stake account to be merged
+    ///      with
+    ///  11. `[w]` Reserve stake account, to withdraw rent exempt reserve
+    ///  12. `[w]` User account to receive pool tokens
+    ///  13. `[w]` Account to receive pool fee tokens
+    ///  14. `[w]` Account to receive a portion of pool fee tokens as referral
+    ///      fees
+    ///  15. `[w]` Pool token mint account
+    ///  16. `[]` Sysvar clock account
+    ///  17. `[]` Sysvar stake history account
+    ///  18. `[]` Pool token program id
+    ///  19. `[]` Stake program id
+    ///  20. ..20+N `[]` N accounts passed on to the stake pool program, such
+    ///      as a depositor allowlist entry
+    DepositStake {
+        /// Minimum amount of pool tokens that must be received
+        minimum_pool_tokens_out: u64,
+    },
+}
+
+/// Creates an `Initialize` instruction.
+pub fn initialize(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    limits: DepositLimits,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(find_config_address(program_id, stake_pool).0, false),
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*admin, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(solana_system_interface::program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&DepositAuthorityInstruction::Initialize { limits }).unwrap(),
+    }
+}
+
+/// Creates a `SetLimits` instruction.
+pub fn set_limits(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    admin: &Pubkey,
+    limits: DepositLimits,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(find_config_address(program_id, stake_pool).0, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&DepositAuthorityInstruction::SetLimits { limits }).unwrap(),
+    }
+}
+
+/// Creates a `SetAdmin` instruction.
+pub fn set_admin(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(find_config_address(program_id, stake_pool).0, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(*new_admin, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&DepositAuthorityInstruction::SetAdmin).unwrap(),
+    }
+}
+
+/// Accounts of a deposit through the deposit authority, ahead of the stake
+/// pool's own
+fn deposit_accounts(
+    program_id: &Pubkey,
+    stake_pool_program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    user: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(find_config_address(program_id, stake_pool).0, false),
+        AccountMeta::new(
+            find_user_deposit_address(program_id, stake_pool, user).0,
+            false,
+        ),
+        AccountMeta::new(*user, true),
+        AccountMeta::new_readonly(
+            find_deposit_authority_address(program_id, stake_pool).0,
+            false,
+        ),
+        AccountMeta::new_readonly(solana_system_interface::program::id(), false),
+        AccountMeta::new_readonly(*stake_pool_program_id, false),
+    ]
+}
+
+/// Creates a `DepositSol` instruction.
+pub fn deposit_sol(
+    program_id: &Pubkey,
+    stake_pool_program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    reserve_stake_account: &Pubkey,
+    user: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    referrer_pool_tokens_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    lamports_in: u64,
+    minimum_pool_tokens_out: u64,
+) -> Instruction {
+    let mut accounts = deposit_accounts(program_id, stake_pool_program_id, stake_pool, user);
+    accounts.extend_from_slice(&[
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*reserve_stake_account, false),
+        AccountMeta::new(*pool_tokens_to, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*referrer_pool_tokens_account, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ]);
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&DepositAuthorityInstruction::DepositSol {
+            lamports_in,
+            minimum_pool_tokens_out,
+        })
+        .unwrap(),
+    }
+}
+
+/// Creates a `DepositStake` instruction.
+pub fn deposit_stake(
+    program_id: &Pubkey,
+    stake_pool_program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list_storage: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    deposit_stake_address: &Pubkey,
+    user: &Pubkey,
+    validator_stake_account: &Pubkey,
+    reserve_stake_account: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    referrer_pool_tokens_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    minimum_pool_tokens_out: u64,
+) -> Instruction {
+    let mut accounts = deposit_accounts(program_id, stake_pool_program_id, stake_pool, user);
+    accounts.extend_from_slice(&[
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new(*validator_list_storage, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*deposit_stake_address, false),
+        AccountMeta::new(*validator_stake_account, false),
+        AccountMeta::new(*reserve_stake_account, false),
+        AccountMeta::new(*pool_tokens_to, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*referrer_pool_tokens_account, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(solana_sdk_ids::sysvar::clock::id(), false),
+        AccountMeta::new_readonly(solana_sdk_ids::sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(solana_stake_interface::program::id(), false),
+    ]);
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&DepositAuthorityInstruction::DepositStake {
+            minimum_pool_tokens_out,
+        })
+        .unwrap(),
+    }
+}
@@ -0,0 +1,58 @@
+#![deny(missing_docs)]
+
+//! A reference deposit authority for stake pools, which enforces limits on
+//! deposits before passing them on to the stake pool program
+
+pub mod error;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+
+#[cfg(not(feature = "no-entrypoint"))]
+pub mod entrypoint;
+
+use solana_pubkey::Pubkey;
+
+/// Seed for the deposit limits of a stake pool
+const CONFIG_SEED: &[u8] = b"config";
+
+/// Seed for the deposit authority of a stake pool
+const AUTHORITY_DEPOSIT: &[u8] = b"deposit";
+
+/// Seed for the deposit record of a user
+const USER_DEPOSIT_SEED_PREFIX: &[u8] = b"user";
+
+/// Generates the address of the deposit limits of a stake pool
+pub fn find_config_address(program_id: &Pubkey, stake_pool_address: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[stake_pool_address.as_ref(), CONFIG_SEED], program_id)
+}
+
+/// Generates the deposit authority to set as both the stake and SOL deposit
+/// authority of the stake pool
+pub fn find_deposit_authority_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[stake_pool_address.as_ref(), AUTHORITY_DEPOSIT],
+        program_id,
+    )
+}
+
+/// Generates the address of the record of a user's deposits into a stake pool
+pub fn find_user_deposit_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    user: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            USER_DEPOSIT_SEED_PREFIX,
+            stake_pool_address.as_ref(),
+            user.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+solana_pubkey::declare_id!("GKFNJo7UrFXmUkGMwPF1gmU687QsSyJP1T6cmTUNAFE4");
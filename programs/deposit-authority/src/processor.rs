@@ -0,0 +1,518 @@
+//! Program state processor
+
+use {
+    crate::{
+        error::DepositAuthorityError,
+        find_config_address, find_user_deposit_address,
+        instruction::DepositAuthorityInstruction,
+        state::{AccountType, Config, DepositLimits, UserDeposit},
+        AUTHORITY_DEPOSIT, CONFIG_SEED, USER_DEPOSIT_SEED_PREFIX,
+    },
+    borsh::BorshDeserialize,
+    solana_account_info::{next_account_info, AccountInfo},
+    solana_borsh::v1::{get_packed_len, try_from_slice_unchecked},
+    solana_clock::Clock,
+    solana_cpi::{invoke, invoke_signed},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_msg::msg,
+    solana_program_error::{ProgramError, ProgramResult},
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_system_interface::{instruction as system_instruction, program as system_program},
+    solana_sysvar::Sysvar,
+    spl_stake_pool::state::StakePool,
+};
+
+/// Check account owner is the given program
+fn check_account_owner(
+    account_info: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<(), ProgramError> {
+    if *program_id != *account_info.owner {
+        msg!(
+            "Expected account to be owned by program {}, received {}",
+            program_id,
+            account_info.owner
+        );
+        Err(ProgramError::IncorrectProgramId)
+    } else {
+        Ok(())
+    }
+}
+
+/// Check system program address
+fn check_system_program(program_id: &Pubkey) -> Result<(), ProgramError> {
+    if *program_id != system_program::id() {
+        msg!(
+            "Expected system program {}, received {}",
+            system_program::id(),
+            program_id
+        );
+        Err(ProgramError::IncorrectProgramId)
+    } else {
+        Ok(())
+    }
+}
+
+/// Load the deposit limits, checking that the admin signed
+fn load_config_for_admin(
+    program_id: &Pubkey,
+    config_info: &AccountInfo,
+    admin_info: &AccountInfo,
+) -> Result<Config, ProgramError> {
+    check_account_owner(config_info, program_id)?;
+    let config = try_from_slice_unchecked::<Config>(&config_info.data.borrow())?;
+    if !config.is_valid() {
+        return Err(DepositAuthorityError::InvalidState.into());
+    }
+    if config.admin != *admin_info.key {
+        msg!(
+            "Incorrect admin provided, expected {}, received {}",
+            config.admin,
+            admin_info.key
+        );
+        return Err(DepositAuthorityError::WrongAdmin.into());
+    }
+    if !admin_info.is_signer {
+        msg!("Admin signature missing");
+        return Err(DepositAuthorityError::WrongAdmin.into());
+    }
+    Ok(config)
+}
+
+/// Create an account at a program address, owned by the program, even if
+/// someone already sent lamports to the address
+fn create_program_account<'a>(
+    program_id: &Pubkey,
+    payer_info: &AccountInfo<'a>,
+    account_info: &AccountInfo<'a>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let required_lamports = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account_info.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, account_info.key, required_lamports),
+            &[payer_info.clone(), account_info.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account_info.key, space as u64),
+        core::slice::from_ref(account_info),
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account_info.key, program_id),
+        core::slice::from_ref(account_info),
+        &[signer_seeds],
+    )
+}
+
+/// Accounts shared by both deposit instructions, ahead of the stake pool's own
+struct DepositAccounts<'a, 'b> {
+    config_info: &'b AccountInfo<'a>,
+    user_deposit_info: &'b AccountInfo<'a>,
+    user_info: &'b AccountInfo<'a>,
+    deposit_authority_info: &'b AccountInfo<'a>,
+    system_program_info: &'b AccountInfo<'a>,
+    stake_pool_program_info: &'b AccountInfo<'a>,
+    stake_pool_info: &'b AccountInfo<'a>,
+}
+
+impl<'a, 'b> DepositAccounts<'a, 'b> {
+    fn new(
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+    ) -> Result<Self, ProgramError> {
+        Ok(Self {
+            config_info: next_account_info(account_info_iter)?,
+            user_deposit_info: next_account_info(account_info_iter)?,
+            user_info: next_account_info(account_info_iter)?,
+            deposit_authority_info: next_account_info(account_info_iter)?,
+            system_program_info: next_account_info(account_info_iter)?,
+            stake_pool_program_info: next_account_info(account_info_iter)?,
+            stake_pool_info: next_account_info(account_info_iter)?,
+        })
+    }
+}
+
+/// Program state handler.
+pub struct Processor {}
+impl Processor {
+    /// Check a deposit of `lamports` against the limits and record it,
+    /// creating the user's deposit record if needed. Returns the signer seeds
+    /// bump of the deposit authority.
+    fn check_and_record_deposit(
+        program_id: &Pubkey,
+        deposit_accounts: &DepositAccounts,
+        referrer_info: &AccountInfo,
+        lamports: u64,
+    ) -> Result<u8, ProgramError> {
+        let DepositAccounts {
+            config_info,
+            user_deposit_info,
+            user_info,
+            deposit_authority_info,
+            system_program_info,
+            stake_pool_program_info,
+            stake_pool_info,
+        } = deposit_accounts;
+
+        check_system_program(system_program_info.key)?;
+        check_account_owner(stake_pool_info, stake_pool_program_info.key)?;
+        check_account_owner(config_info, program_id)?;
+        let mut config = try_from_slice_unchecked::<Config>(&config_info.data.borrow())?;
+        if !config.is_valid() || config.stake_pool != *stake_pool_info.key {
+            msg!(
+                "Deposit limits {} are not for stake pool {}",
+                config_info.key,
+                stake_pool_info.key
+            );
+            return Err(DepositAuthorityError::InvalidState.into());
+        }
+        let deposit_authority = Pubkey::create_program_address(
+            &[
+                stake_pool_info.key.as_ref(),
+                AUTHORITY_DEPOSIT,
+                &[config.deposit_authority_bump_seed],
+            ],
+            program_id,
+        )
+        .map_err(|_| DepositAuthorityError::InvalidProgramAddress)?;
+        if deposit_authority != *deposit_authority_info.key {
+            msg!(
+                "Incorrect deposit authority, expected {}, received {}",
+                deposit_authority,
+                deposit_authority_info.key
+            );
+            return Err(DepositAuthorityError::InvalidProgramAddress.into());
+        }
+        if !user_info.is_signer {
+            msg!("User signature missing");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (user_deposit_address, user_deposit_bump_seed) =
+            find_user_deposit_address(program_id, stake_pool_info.key, user_info.key);
+        if user_deposit_address != *user_deposit_info.key {
+            msg!(
+                "Incorrect user deposit record, expected {}, received {}",
+                user_deposit_address,
+                user_deposit_info.key
+            );
+            return Err(DepositAuthorityError::InvalidProgramAddress.into());
+        }
+        let mut user_deposit = if user_deposit_info.data_is_empty() {
+            create_program_account(
+                program_id,
+                user_info,
+                user_deposit_info,
+                get_packed_len::<UserDeposit>(),
+                &[
+                    USER_DEPOSIT_SEED_PREFIX,
+                    stake_pool_info.key.as_ref(),
+                    user_info.key.as_ref(),
+                    &[user_deposit_bump_seed],
+                ],
+            )?;
+            UserDeposit {
+                account_type: AccountType::UserDeposit,
+                stake_pool: *stake_pool_info.key,
+                user: *user_info.key,
+                deposited: 0,
+            }
+        } else {
+            check_account_owner(user_deposit_info, program_id)?;
+            let user_deposit =
+                try_from_slice_unchecked::<UserDeposit>(&user_deposit_info.data.borrow())?;
+            if user_deposit.account_type != AccountType::UserDeposit {
+                return Err(DepositAuthorityError::InvalidState.into());
+            }
+            user_deposit
+        };
+
+        let epoch = Clock::get()?.epoch;
+        let epoch_deposited = config.deposited_in_epoch(epoch);
+        config.limits.check(
+            lamports,
+            user_deposit.deposited,
+            epoch_deposited,
+            referrer_info.key,
+        )?;
+
+        // the checks above guarantee these don't overflow
+        user_deposit.deposited = user_deposit.deposited.saturating_add(lamports);
+        config.epoch = epoch;
+        config.epoch_deposited = epoch_deposited.saturating_add(lamports);
+        borsh::to_writer(&mut user_deposit_info.data.borrow_mut()[..], &user_deposit)?;
+        borsh::to_writer(&mut config_info.data.borrow_mut()[..], &config)?;
+
+        Ok(config.deposit_authority_bump_seed)
+    }
+
+    /// Append the accounts following the stake pool's own to a deposit
+    /// instruction
+    fn pass_through_accounts(instruction: &mut Instruction, remaining_accounts: &[AccountInfo]) {
+        instruction
+            .accounts
+            .extend(remaining_accounts.iter().map(|account_info| AccountMeta {
+                pubkey: *account_info.key,
+                is_signer: account_info.is_signer,
+                is_writable: account_info.is_writable,
+            }));
+    }
+
+    /// Processes [`Initialize`](enum.Instruction.html).
+    fn process_initialize(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        limits: DepositLimits,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        check_system_program(system_program_info.key)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            msg!("Account {} is not a stake pool", stake_pool_info.key);
+            return Err(DepositAuthorityError::InvalidState.into());
+        }
+        if stake_pool.manager != *manager_info.key || !manager_info.is_signer {
+            msg!("Stake pool manager signature missing");
+            return Err(DepositAuthorityError::WrongManager.into());
+        }
+
+        let (config_address, config_bump_seed) =
+            find_config_address(program_id, stake_pool_info.key);
+        if config_address != *config_info.key {
+            msg!(
+                "Incorrect deposit limits address, expected {}, received {}",
+                config_address,
+                config_info.key
+            );
+            return Err(DepositAuthorityError::InvalidProgramAddress.into());
+        }
+        if !config_info.data_is_empty() {
+            return Err(DepositAuthorityError::AlreadyInUse.into());
+        }
+        create_program_account(
+            program_id,
+            payer_info,
+            config_info,
+            get_packed_len::<Config>(),
+            &[
+                stake_pool_info.key.as_ref(),
+                CONFIG_SEED,
+                &[config_bump_seed],
+            ],
+        )?;
+
+        let (_, deposit_authority_bump_seed) =
+            crate::find_deposit_authority_address(program_id, stake_pool_info.key);
+        let config = Config {
+            account_type: AccountType::Config,
+            stake_pool: *stake_pool_info.key,
+            admin: *admin_info.key,
+            deposit_authority_bump_seed,
+            limits,
+            epoch: 0,
+            epoch_deposited: 0,
+        };
+        borsh::to_writer(&mut config_info.data.borrow_mut()[..], &config)?;
+        Ok(())
+    }
+
+    /// Processes [`SetLimits`](enum.Instruction.html).
+    fn process_set_limits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        limits: DepositLimits,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        let mut config = load_config_for_admin(program_id, config_info, admin_info)?;
+        config.limits = limits;
+        borsh::to_writer(&mut config_info.data.borrow_mut()[..], &config)?;
+        Ok(())
+    }
+
+    /// Processes [`SetAdmin`](enum.Instruction.html).
+    fn process_set_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let new_admin_info = next_account_info(account_info_iter)?;
+
+        let mut config = load_config_for_admin(program_id, config_info, admin_info)?;
+        config.admin = *new_admin_info.key;
+        borsh::to_writer(&mut config_info.data.borrow_mut()[..], &config)?;
+        Ok(())
+    }
+
+    /// Processes [`DepositSol`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_deposit_sol(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        lamports_in: u64,
+        minimum_pool_tokens_out: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let deposit_accounts = DepositAccounts::new(account_info_iter)?;
+        let withdraw_authority_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let dest_user_pool_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let referrer_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let deposit_authority_bump_seed = Self::check_and_record_deposit(
+            program_id,
+            &deposit_accounts,
+            referrer_fee_info,
+            lamports_in,
+        )?;
+
+        let stake_pool_info = deposit_accounts.stake_pool_info;
+        let mut instruction = spl_stake_pool::instruction::deposit_sol_with_authority_and_slippage(
+            deposit_accounts.stake_pool_program_info.key,
+            stake_pool_info.key,
+            deposit_accounts.deposit_authority_info.key,
+            withdraw_authority_info.key,
+            reserve_stake_info.key,
+            deposit_accounts.user_info.key,
+            dest_user_pool_info.key,
+            manager_fee_info.key,
+            referrer_fee_info.key,
+            pool_mint_info.key,
+            token_program_info.key,
+            lamports_in,
+            minimum_pool_tokens_out,
+        );
+        Self::pass_through_accounts(&mut instruction, account_info_iter.as_slice());
+        invoke_signed(
+            &instruction,
+            accounts,
+            &[&[
+                stake_pool_info.key.as_ref(),
+                AUTHORITY_DEPOSIT,
+                &[deposit_authority_bump_seed],
+            ]],
+        )
+    }
+
+    /// Processes [`DepositStake`](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_deposit_stake(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        minimum_pool_tokens_out: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let deposit_accounts = DepositAccounts::new(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let withdraw_authority_info = next_account_info(account_info_iter)?;
+        let stake_info = next_account_info(account_info_iter)?;
+        let validator_stake_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let dest_user_pool_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let referrer_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+        let _stake_history_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let _stake_program_info = next_account_info(account_info_iter)?;
+
+        let deposit_authority_bump_seed = Self::check_and_record_deposit(
+            program_id,
+            &deposit_accounts,
+            referrer_fee_info,
+            stake_info.lamports(),
+        )?;
+
+        let stake_pool_info = deposit_accounts.stake_pool_info;
+        let mut instructions =
+            spl_stake_pool::instruction::deposit_stake_with_authority_and_slippage(
+                deposit_accounts.stake_pool_program_info.key,
+                stake_pool_info.key,
+                validator_list_info.key,
+                deposit_accounts.deposit_authority_info.key,
+                withdraw_authority_info.key,
+                stake_info.key,
+                deposit_accounts.user_info.key,
+                validator_stake_info.key,
+                reserve_stake_info.key,
+                dest_user_pool_info.key,
+                manager_fee_info.key,
+                referrer_fee_info.key,
+                pool_mint_info.key,
+                token_program_info.key,
+                minimum_pool_tokens_out,
+            );
+        let mut deposit_instruction = instructions
+            .pop()
+            .ok_or(DepositAuthorityError::InvalidState)?;
+        // hand the stake account over to the deposit authority, with the
+        // user's signature
+        for authorize_instruction in &instructions {
+            invoke(authorize_instruction, accounts)?;
+        }
+        Self::pass_through_accounts(&mut deposit_instruction, account_info_iter.as_slice());
+        invoke_signed(
+            &deposit_instruction,
+            accounts,
+            &[&[
+                stake_pool_info.key.as_ref(),
+                AUTHORITY_DEPOSIT,
+                &[deposit_authority_bump_seed],
+            ]],
+        )
+    }
+
+    /// Processes [`Instruction`](enum.Instruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        let instruction = DepositAuthorityInstruction::try_from_slice(input)?;
+        match instruction {
+            DepositAuthorityInstruction::Initialize { limits } => {
+                msg!("Instruction: Initialize");
+                Self::process_initialize(program_id, accounts, limits)
+            }
+            DepositAuthorityInstruction::SetLimits { limits } => {
+                msg!("Instruction: SetLimits");
+                Self::process_set_limits(program_id, accounts, limits)
+            }
+            DepositAuthorityInstruction::SetAdmin => {
+                msg!("Instruction: SetAdmin");
+                Self::process_set_admin(program_id, accounts)
+            }
+            DepositAuthorityInstruction::DepositSol {
+                lamports_in,
+                minimum_pool_tokens_out,
+            } => {
+                msg!("Instruction: DepositSol");
+                Self::process_deposit_sol(
+                    program_id,
+                    accounts,
+                    lamports_in,
+                    minimum_pool_tokens_out,
+                )
+            }
+            DepositAuthorityInstruction::DepositStake {
+                minimum_pool_tokens_out,
+            } => {
+                msg!("Instruction: DepositStake");
+                Self::process_deposit_stake(program_id, accounts, minimum_pool_tokens_out)
+            }
+        }
+    }
+}
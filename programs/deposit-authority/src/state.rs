@@ -0,0 +1,197 @@
+//! State transition types
+
+use {
+    crate::error::DepositAuthorityError,
+    borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
+    solana_clock::Epoch,
+    solana_pubkey::Pubkey,
+};
+
+/// Enum representing the account type managed by the program
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub enum AccountType {
+    /// If the account has not been initialized, the enum will be 0
+    #[default]
+    Uninitialized,
+    /// Deposit limits of a stake pool
+    Config,
+    /// Record of a user's deposits into a stake pool
+    UserDeposit,
+}
+
+/// Limits enforced on deposits, set by the admin
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct DepositLimits {
+    /// Smallest deposit accepted, in lamports
+    pub minimum_deposit: u64,
+    /// Most lamports a single user may deposit over the life of the pool, if
+    /// any. Withdrawals don't free up room under the cap.
+    pub user_cap: Option<u64>,
+    /// Most lamports all users together may deposit during an epoch, if any
+    pub epoch_cap: Option<u64>,
+    /// Pool token account that must receive the referral fees of every
+    /// deposit, if any
+    pub referrer: Option<Pubkey>,
+}
+
+impl DepositLimits {
+    /// Check a deposit of `lamports` by a user who already deposited
+    /// `user_deposited`, while `epoch_deposited` were deposited this epoch
+    pub fn check(
+        &self,
+        lamports: u64,
+        user_deposited: u64,
+        epoch_deposited: u64,
+        referrer: &Pubkey,
+    ) -> Result<(), DepositAuthorityError> {
+        if lamports < self.minimum_deposit {
+            return Err(DepositAuthorityError::DepositTooSmall);
+        }
+        if let Some(user_cap) = self.user_cap {
+            let user_total = user_deposited
+                .checked_add(lamports)
+                .ok_or(DepositAuthorityError::CalculationFailure)?;
+            if user_total > user_cap {
+                return Err(DepositAuthorityError::UserCapExceeded);
+            }
+        }
+        if let Some(epoch_cap) = self.epoch_cap {
+            let epoch_total = epoch_deposited
+                .checked_add(lamports)
+                .ok_or(DepositAuthorityError::CalculationFailure)?;
+            if epoch_total > epoch_cap {
+                return Err(DepositAuthorityError::EpochCapExceeded);
+            }
+        }
+        if self
+            .referrer
+            .is_some_and(|required_referrer| required_referrer != *referrer)
+        {
+            return Err(DepositAuthorityError::WrongReferrer);
+        }
+        Ok(())
+    }
+}
+
+/// Deposit limits of a stake pool, and the deposits made this epoch
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct Config {
+    /// Account type, must be `Config` currently
+    pub account_type: AccountType,
+    /// Stake pool the limits apply to
+    pub stake_pool: Pubkey,
+    /// Authority allowed to change the limits
+    pub admin: Pubkey,
+    /// Bump seed of the deposit authority, to sign deposits
+    pub deposit_authority_bump_seed: u8,
+    /// Limits enforced on deposits
+    pub limits: DepositLimits,
+    /// Epoch of the last deposit
+    pub epoch: Epoch,
+    /// Lamports deposited during `epoch`
+    pub epoch_deposited: u64,
+}
+
+impl Config {
+    /// Check if the deposit limits are initialized
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::Config
+    }
+
+    /// Lamports deposited so far during `epoch`
+    pub fn deposited_in_epoch(&self, epoch: Epoch) -> u64 {
+        if self.epoch == epoch {
+            self.epoch_deposited
+        } else {
+            0
+        }
+    }
+}
+
+/// Record of a user's deposits into a stake pool, to enforce the user cap
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct UserDeposit {
+    /// Account type, must be `UserDeposit` currently
+    pub account_type: AccountType,
+    /// Stake pool the deposits were made into
+    pub stake_pool: Pubkey,
+    /// User who made the deposits
+    pub user: Pubkey,
+    /// Total lamports deposited
+    pub deposited: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_borsh::v1::get_packed_len};
+
+    #[test]
+    fn check_limits() {
+        let referrer = Pubkey::new_unique();
+        let limits = DepositLimits {
+            minimum_deposit: 100,
+            user_cap: Some(1_000),
+            epoch_cap: Some(5_000),
+            referrer: Some(referrer),
+        };
+        assert_eq!(limits.check(100, 900, 4_900, &referrer), Ok(()));
+        assert_eq!(
+            limits.check(99, 0, 0, &referrer),
+            Err(DepositAuthorityError::DepositTooSmall)
+        );
+        assert_eq!(
+            limits.check(100, 901, 0, &referrer),
+            Err(DepositAuthorityError::UserCapExceeded)
+        );
+        assert_eq!(
+            limits.check(100, 0, 4_901, &referrer),
+            Err(DepositAuthorityError::EpochCapExceeded)
+        );
+        assert_eq!(
+            limits.check(100, 0, 0, &Pubkey::new_unique()),
+            Err(DepositAuthorityError::WrongReferrer)
+        );
+        assert_eq!(
+            limits.check(100, u64::MAX, 0, &referrer),
+            Err(DepositAuthorityError::CalculationFailure)
+        );
+
+        let no_limits = DepositLimits::default();
+        assert_eq!(
+            no_limits.check(u64::MAX, u64::MAX, u64::MAX, &Pubkey::new_unique()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn deposited_in_epoch() {
+        let config = Config {
+            epoch: 10,
+            epoch_deposited: 1_000,
+            ..Config::default()
+        };
+        assert_eq!(config.deposited_in_epoch(10), 1_000);
+        assert_eq!(config.deposited_in_epoch(11), 0);
+    }
+
+    #[test]
+    fn config_packed_len_fits_all_limits() {
+        let config = Config {
+            account_type: AccountType::Config,
+            limits: DepositLimits {
+                minimum_deposit: 1,
+                user_cap: Some(2),
+                epoch_cap: Some(3),
+                referrer: Some(Pubkey::new_unique()),
+            },
+            ..Config::default()
+        };
+        assert_eq!(
+            borsh::to_vec(&config).unwrap().len(),
+            get_packed_len::<Config>()
+        );
+    }
+}
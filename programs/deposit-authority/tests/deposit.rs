@@ -0,0 +1,627 @@
+#![allow(clippy::arithmetic_side_effects)]
+#[path = "../../../program/tests/helpers/mod.rs"]
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        borsh1::try_from_slice_unchecked, instruction::InstructionError, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    solana_stake_interface as stake,
+    spl_stake_pool::{
+        error::StakePoolError,
+        instruction::{set_funding_authority, FundingType},
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+    spl_stake_pool_deposit_authority::{
+        self as deposit_authority,
+        error::DepositAuthorityError,
+        find_config_address, find_deposit_authority_address, find_user_deposit_address,
+        instruction,
+        processor::Processor,
+        state::{Config, DepositLimits, UserDeposit},
+    },
+};
+
+struct Setup {
+    context: ProgramTestContext,
+    stake_pool_accounts: StakePoolAccounts,
+    validator_stake: ValidatorStakeAccount,
+    admin: Keypair,
+}
+
+async fn setup(limits: DepositLimits) -> Setup {
+    let mut program_test = program_test();
+    program_test.add_program(
+        "spl_stake_pool_deposit_authority",
+        deposit_authority::id(),
+        processor!(Processor::process),
+    );
+    let mut context = program_test.start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+    // added before the deposit authority takes over, since it deposits SOL
+    let validator_stake = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        None,
+    )
+    .await;
+
+    let admin = Keypair::new();
+    let stake_pool_address = stake_pool_accounts.stake_pool.pubkey();
+    let (deposit_authority_address, _) =
+        find_deposit_authority_address(&deposit_authority::id(), &stake_pool_address);
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize(
+                &deposit_authority::id(),
+                &stake_pool_address,
+                &stake_pool_accounts.manager.pubkey(),
+                &admin.pubkey(),
+                &context.payer.pubkey(),
+                limits,
+            ),
+            set_funding_authority(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &stake_pool_accounts.manager.pubkey(),
+                Some(&deposit_authority_address),
+                FundingType::StakeDeposit,
+            ),
+            set_funding_authority(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &stake_pool_accounts.manager.pubkey(),
+                Some(&deposit_authority_address),
+                FundingType::SolDeposit,
+            ),
+        ],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    Setup {
+        context,
+        stake_pool_accounts,
+        validator_stake,
+        admin,
+    }
+}
+
+/// Create a funded user with a pool token account
+async fn create_user(setup: &mut Setup) -> (Keypair, Pubkey) {
+    let user = Keypair::new();
+    let last_blockhash = setup.context.get_new_latest_blockhash().await.unwrap();
+    transfer(
+        &mut setup.context.banks_client,
+        &setup.context.payer,
+        &last_blockhash,
+        &user.pubkey(),
+        TEST_STAKE_AMOUNT * 10,
+    )
+    .await;
+    let pool_token_account = Keypair::new();
+    create_token_account(
+        &mut setup.context.banks_client,
+        &setup.context.payer,
+        &last_blockhash,
+        &setup.stake_pool_accounts.token_program_id,
+        &pool_token_account,
+        &setup.stake_pool_accounts.pool_mint.pubkey(),
+        &user,
+        &[],
+    )
+    .await
+    .unwrap();
+    (user, pool_token_account.pubkey())
+}
+
+async fn deposit_sol(
+    setup: &mut Setup,
+    user: &Keypair,
+    pool_token_account: &Pubkey,
+    referrer: &Pubkey,
+    lamports: u64,
+) -> Result<(), TransactionError> {
+    let stake_pool_accounts = &setup.stake_pool_accounts;
+    let last_blockhash = setup.context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::deposit_sol(
+            &deposit_authority::id(),
+            &spl_stake_pool::id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &user.pubkey(),
+            pool_token_account,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            referrer,
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &stake_pool_accounts.token_program_id,
+            lamports,
+            0,
+        )],
+        Some(&setup.context.payer.pubkey()),
+        &[&setup.context.payer, user],
+        last_blockhash,
+    );
+    setup
+        .context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+async fn get_user_deposit(setup: &mut Setup, user: &Pubkey) -> UserDeposit {
+    let (address, _) = find_user_deposit_address(
+        &deposit_authority::id(),
+        &setup.stake_pool_accounts.stake_pool.pubkey(),
+        user,
+    );
+    let account = get_account(&mut setup.context.banks_client, &address).await;
+    try_from_slice_unchecked::<UserDeposit>(&account.data).unwrap()
+}
+
+async fn get_config(setup: &mut Setup) -> Config {
+    let (address, _) = find_config_address(
+        &deposit_authority::id(),
+        &setup.stake_pool_accounts.stake_pool.pubkey(),
+    );
+    let account = get_account(&mut setup.context.banks_client, &address).await;
+    try_from_slice_unchecked::<Config>(&account.data).unwrap()
+}
+
+fn custom_error(error: DepositAuthorityError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn success_deposit_sol() {
+    let mut setup = setup(DepositLimits {
+        minimum_deposit: TEST_STAKE_AMOUNT,
+        user_cap: Some(TEST_STAKE_AMOUNT * 2),
+        epoch_cap: Some(TEST_STAKE_AMOUNT * 4),
+        referrer: None,
+    })
+    .await;
+    let (user, pool_token_account) = create_user(&mut setup).await;
+
+    let referrer = setup.stake_pool_accounts.pool_fee_account.pubkey();
+    deposit_sol(
+        &mut setup,
+        &user,
+        &pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap();
+    deposit_sol(
+        &mut setup,
+        &user,
+        &pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    let pool_tokens = get_token_balance(&mut setup.context.banks_client, &pool_token_account).await;
+    assert!(pool_tokens > 0);
+    let user_deposit = get_user_deposit(&mut setup, &user.pubkey()).await;
+    assert_eq!(user_deposit.user, user.pubkey());
+    assert_eq!(user_deposit.deposited, TEST_STAKE_AMOUNT * 2);
+    let config = get_config(&mut setup).await;
+    assert_eq!(config.epoch_deposited, TEST_STAKE_AMOUNT * 2);
+}
+
+#[tokio::test]
+async fn success_deposit_stake() {
+    let mut setup = setup(DepositLimits::default()).await;
+    let (user, pool_token_account) = create_user(&mut setup).await;
+
+    let user_stake = Keypair::new();
+    let last_blockhash = setup.context.get_new_latest_blockhash().await.unwrap();
+    let stake_lamports = create_independent_stake_account(
+        &mut setup.context.banks_client,
+        &setup.context.payer,
+        &last_blockhash,
+        &user_stake,
+        &stake::state::Authorized {
+            staker: user.pubkey(),
+            withdrawer: user.pubkey(),
+        },
+        &stake::state::Lockup::default(),
+        TEST_STAKE_AMOUNT,
+    )
+    .await;
+    delegate_stake_account(
+        &mut setup.context.banks_client,
+        &setup.context.payer,
+        &last_blockhash,
+        &user_stake.pubkey(),
+        &user,
+        &setup.validator_stake.vote.pubkey(),
+    )
+    .await;
+
+    let stake_pool_accounts = &setup.stake_pool_accounts;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::deposit_stake(
+            &deposit_authority::id(),
+            &spl_stake_pool::id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &user_stake.pubkey(),
+            &user.pubkey(),
+            &setup.validator_stake.stake_account,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &pool_token_account,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &stake_pool_accounts.token_program_id,
+            1,
+        )],
+        Some(&setup.context.payer.pubkey()),
+        &[&setup.context.payer, &user],
+        last_blockhash,
+    );
+    setup
+        .context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let pool_tokens = get_token_balance(&mut setup.context.banks_client, &pool_token_account).await;
+    assert!(pool_tokens > 0);
+    let user_deposit = get_user_deposit(&mut setup, &user.pubkey()).await;
+    assert_eq!(user_deposit.deposited, stake_lamports);
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_too_small() {
+    let mut setup = setup(DepositLimits {
+        minimum_deposit: TEST_STAKE_AMOUNT,
+        ..DepositLimits::default()
+    })
+    .await;
+    let (user, pool_token_account) = create_user(&mut setup).await;
+
+    let referrer = setup.stake_pool_accounts.pool_fee_account.pubkey();
+    let error = deposit_sol(
+        &mut setup,
+        &user,
+        &pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT - 1,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error, custom_error(DepositAuthorityError::DepositTooSmall));
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_over_user_cap() {
+    let mut setup = setup(DepositLimits {
+        user_cap: Some(TEST_STAKE_AMOUNT * 3 / 2),
+        ..DepositLimits::default()
+    })
+    .await;
+    let (user, pool_token_account) = create_user(&mut setup).await;
+    let (other_user, other_pool_token_account) = create_user(&mut setup).await;
+
+    let referrer = setup.stake_pool_accounts.pool_fee_account.pubkey();
+    deposit_sol(
+        &mut setup,
+        &user,
+        &pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap();
+    let error = deposit_sol(
+        &mut setup,
+        &user,
+        &pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error, custom_error(DepositAuthorityError::UserCapExceeded));
+
+    // the cap is per user
+    deposit_sol(
+        &mut setup,
+        &other_user,
+        &other_pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_over_epoch_cap() {
+    let mut setup = setup(DepositLimits {
+        epoch_cap: Some(TEST_STAKE_AMOUNT * 3 / 2),
+        ..DepositLimits::default()
+    })
+    .await;
+    let (user, pool_token_account) = create_user(&mut setup).await;
+    let (other_user, other_pool_token_account) = create_user(&mut setup).await;
+
+    let referrer = setup.stake_pool_accounts.pool_fee_account.pubkey();
+    deposit_sol(
+        &mut setup,
+        &user,
+        &pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap();
+    let error = deposit_sol(
+        &mut setup,
+        &other_user,
+        &other_pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error, custom_error(DepositAuthorityError::EpochCapExceeded));
+
+    // room frees up in the next epoch
+    let first_normal_slot = setup
+        .context
+        .genesis_config()
+        .epoch_schedule
+        .first_normal_slot;
+    let slots_per_epoch = setup
+        .context
+        .genesis_config()
+        .epoch_schedule
+        .slots_per_epoch;
+    setup
+        .context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    let last_blockhash = setup.context.get_new_latest_blockhash().await.unwrap();
+    let error = setup
+        .stake_pool_accounts
+        .update_all(
+            &mut setup.context.banks_client,
+            &setup.context.payer,
+            &last_blockhash,
+            false,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+    deposit_sol(
+        &mut setup,
+        &other_user,
+        &other_pool_token_account,
+        &referrer,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_wrong_referrer() {
+    let required_referrer = Pubkey::new_unique();
+    let mut setup = setup(DepositLimits {
+        referrer: Some(required_referrer),
+        ..DepositLimits::default()
+    })
+    .await;
+    let (user, pool_token_account) = create_user(&mut setup).await;
+
+    let error = deposit_sol(
+        &mut setup,
+        &user,
+        &pool_token_account,
+        &pool_token_account,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error, custom_error(DepositAuthorityError::WrongReferrer));
+}
+
+#[tokio::test]
+async fn fail_deposit_sol_bypassing_authority() {
+    let mut setup = setup(DepositLimits::default()).await;
+    let (user, pool_token_account) = create_user(&mut setup).await;
+
+    let last_blockhash = setup.context.get_new_latest_blockhash().await.unwrap();
+    let error = setup
+        .stake_pool_accounts
+        .deposit_sol(
+            &mut setup.context.banks_client,
+            &setup.context.payer,
+            &last_blockhash,
+            &pool_token_account,
+            TEST_STAKE_AMOUNT,
+            Some(&user),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidSolDepositAuthority as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn success_set_limits_and_admin() {
+    let mut setup = setup(DepositLimits::default()).await;
+
+    let limits = DepositLimits {
+        minimum_deposit: 1,
+        user_cap: Some(2),
+        epoch_cap: Some(3),
+        referrer: Some(Pubkey::new_unique()),
+    };
+    let new_admin = Keypair::new();
+    let stake_pool_address = setup.stake_pool_accounts.stake_pool.pubkey();
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::set_limits(
+                &deposit_authority::id(),
+                &stake_pool_address,
+                &setup.admin.pubkey(),
+                limits.clone(),
+            ),
+            instruction::set_admin(
+                &deposit_authority::id(),
+                &stake_pool_address,
+                &setup.admin.pubkey(),
+                &new_admin.pubkey(),
+            ),
+        ],
+        Some(&setup.context.payer.pubkey()),
+        &[&setup.context.payer, &setup.admin],
+        setup.context.last_blockhash,
+    );
+    setup
+        .context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let config = get_config(&mut setup).await;
+    assert_eq!(config.limits, limits);
+    assert_eq!(config.admin, new_admin.pubkey());
+}
+
+#[tokio::test]
+async fn fail_set_limits_wrong_admin() {
+    let setup = setup(DepositLimits::default()).await;
+
+    let wrong_admin = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_limits(
+            &deposit_authority::id(),
+            &setup.stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_admin.pubkey(),
+            DepositLimits::default(),
+        )],
+        Some(&setup.context.payer.pubkey()),
+        &[&setup.context.payer, &wrong_admin],
+        setup.context.last_blockhash,
+    );
+    let error = setup
+        .context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(error, custom_error(DepositAuthorityError::WrongAdmin));
+}
+
+#[tokio::test]
+async fn fail_initialize_twice() {
+    let setup = setup(DepositLimits::default()).await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::initialize(
+            &deposit_authority::id(),
+            &setup.stake_pool_accounts.stake_pool.pubkey(),
+            &setup.stake_pool_accounts.manager.pubkey(),
+            &setup.context.payer.pubkey(),
+            &setup.context.payer.pubkey(),
+            DepositLimits::default(),
+        )],
+        Some(&setup.context.payer.pubkey()),
+        &[&setup.context.payer, &setup.stake_pool_accounts.manager],
+        setup.context.last_blockhash,
+    );
+    let error = setup
+        .context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(error, custom_error(DepositAuthorityError::AlreadyInUse));
+}
+
+#[tokio::test]
+async fn fail_initialize_wrong_manager() {
+    let mut program_test = program_test();
+    program_test.add_program(
+        "spl_stake_pool_deposit_authority",
+        deposit_authority::id(),
+        processor!(Processor::process),
+    );
+    let mut context = program_test.start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    let wrong_manager = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::initialize(
+            &deposit_authority::id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            &wrong_manager.pubkey(),
+            &context.payer.pubkey(),
+            DepositLimits::default(),
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_manager],
+        context.last_blockhash,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(error, custom_error(DepositAuthorityError::WrongManager));
+}